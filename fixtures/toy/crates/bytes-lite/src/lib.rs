@@ -0,0 +1,3 @@
+pub fn describe() -> String {
+    "bytes-lite".to_owned()
+}
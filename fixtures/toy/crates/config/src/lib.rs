@@ -0,0 +1,7 @@
+pub fn describe() -> String {
+    format!(
+        "config in {} ({})",
+        env!("TOY_BUILD_PROFILE"),
+        [text::describe(),].join(", ")
+    )
+}
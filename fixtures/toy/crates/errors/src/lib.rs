@@ -0,0 +1,7 @@
+pub fn describe() -> String {
+    format!(
+        "{} ({})",
+        "errors",
+        [ids::describe(), text::describe(),].join(", ")
+    )
+}
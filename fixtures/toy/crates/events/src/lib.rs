@@ -0,0 +1,7 @@
+pub fn describe() -> String {
+    format!(
+        "{} ({})",
+        "events",
+        [time::describe(), ids::describe(),].join(", ")
+    )
+}
@@ -0,0 +1,3 @@
+pub fn describe() -> String {
+    "ids".to_owned()
+}
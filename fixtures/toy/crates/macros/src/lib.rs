@@ -0,0 +1,16 @@
+use proc_macro::TokenStream;
+
+/// Adds a `NAME` constant with the name of the annotated type.
+#[proc_macro_derive(Named)]
+pub fn derive_named(input: TokenStream) -> TokenStream {
+    let input = input.to_string();
+    let name = input
+        .split_whitespace()
+        .skip_while(|word| *word != "struct" && *word != "enum")
+        .nth(1)
+        .unwrap()
+        .trim_end_matches(|c: char| !c.is_alphanumeric());
+    format!("impl {name} {{ pub const NAME: &'static str = \"{name}\"; }}")
+        .parse()
+        .unwrap()
+}
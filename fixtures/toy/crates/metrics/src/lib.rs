@@ -0,0 +1,7 @@
+pub fn describe() -> String {
+    format!(
+        "{} ({})",
+        "metrics",
+        [time::describe(), collections::describe(),].join(", ")
+    )
+}
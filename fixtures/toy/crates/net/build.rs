@@ -0,0 +1,6 @@
+fn main() {
+    println!(
+        "cargo:rustc-env=TOY_BUILD_PROFILE={}",
+        std::env::var("PROFILE").unwrap()
+    );
+}
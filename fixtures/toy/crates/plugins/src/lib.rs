@@ -0,0 +1,18 @@
+// Stands in for a crate that takes a while to compile.
+#[allow(long_running_const_eval)]
+const _: u64 = {
+    let mut i = 0;
+    let mut acc = 0u64;
+    while i < 100_000 {
+        acc = acc.wrapping_mul(31).wrapping_add(i);
+        i += 1;
+    }
+    acc
+};
+
+#[derive(macros::Named)]
+pub struct Plugins;
+
+pub fn describe() -> String {
+    format!("{} ({})", Plugins::NAME, [api::describe(),].join(", "))
+}
@@ -0,0 +1,3 @@
+pub fn describe() -> String {
+    format!("{} ({})", "time", [units::describe(),].join(", "))
+}
@@ -0,0 +1,4 @@
+fn main() {
+    println!("{}", server::describe());
+    println!("{}", client::describe());
+}
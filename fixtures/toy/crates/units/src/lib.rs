@@ -0,0 +1,3 @@
+pub fn describe() -> String {
+    "units".to_owned()
+}
@@ -0,0 +1,125 @@
+//! `cargo dicebox`: captures the inputs of dice_box for the current workspace and runs the default comparison on
+//! them, so that there are no files to assemble by hand.
+//!
+//! Captured timings, unit graph and a description of the environment they were captured in are kept in
+//! `<target-dir>/dice_box` and reused by subsequent runs with the same cargo arguments, until `--refresh` is passed.
+//! Timings are captured with a clean build in a separate target directory, so that existing build artifacts of the
+//! workspace are left alone.
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+
+#[derive(Parser)]
+#[clap(bin_name = "cargo")]
+enum Cargo {
+    Dicebox(Args),
+}
+
+/// Simulate builds of the current workspace with different Cargo schedulers.
+#[derive(clap::Args)]
+struct Args {
+    /// Number of threads in simulated build environment. Defaults to the number of cores of this machine.
+    #[clap(short, long)]
+    num_threads: Option<usize>,
+
+    /// Capture timings and unit graph again, even if there are ones from a previous run with the same cargo arguments.
+    #[clap(long)]
+    refresh: bool,
+
+    /// Extra arguments to pass to `cargo build` when capturing (e.g. `--release` or `--features`).
+    #[clap(last = true)]
+    cargo_args: Vec<String>,
+}
+
+/// Workspace root and target directory, as reported by `cargo metadata`.
+fn locate_workspace() -> Result<(PathBuf, PathBuf)> {
+    let output = Command::new("cargo")
+        .args(["metadata", "--format-version", "1", "--no-deps"])
+        .output()?;
+    if !output.status.success() {
+        bail!(
+            "cargo metadata failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    let metadata: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    let path = |key: &str| {
+        metadata[key]
+            .as_str()
+            .map(PathBuf::from)
+            .with_context(|| format!("cargo metadata did not report {key}"))
+    };
+    Ok((path("workspace_root")?, path("target_directory")?))
+}
+
+/// Whether inputs kept in `environment_file` were captured with the same extra `cargo_args`, so they can be reused.
+fn captured_with(environment_file: &Path, cargo_args: &[String]) -> bool {
+    std::fs::read_to_string(environment_file)
+        .ok()
+        .and_then(|contents| serde_json::from_str::<dice_box::Environment>(&contents).ok())
+        .is_some_and(|environment| environment.cargo_args == cargo_args)
+}
+
+fn main() -> Result<()> {
+    env_logger::init();
+    let Cargo::Dicebox(args) = Cargo::parse();
+    let num_threads = match args.num_threads {
+        Some(num_threads) => num_threads,
+        None => std::thread::available_parallelism()?.get(),
+    };
+    let (workspace_root, target_dir) = locate_workspace()?;
+    std::env::set_current_dir(&workspace_root)?;
+    let dir = target_dir.join("dice_box");
+    let timings_file = dir.join("timings.json");
+    let unit_graph_file = dir.join("unit-graph.json");
+    let environment_file = dir.join("environment.json");
+    if args.refresh
+        || !timings_file.exists()
+        || !unit_graph_file.exists()
+        || !captured_with(&environment_file, &args.cargo_args)
+    {
+        std::fs::create_dir_all(&dir)?;
+        let environment = dice_box::Environment::capture(&args.cargo_args)?;
+        std::fs::write(
+            &environment_file,
+            serde_json::to_string_pretty(&environment)?,
+        )?;
+        eprintln!("Capturing unit graph of {}", workspace_root.display());
+        let unit_graph = dice_box::capture_unit_graph(&args.cargo_args)?;
+        std::fs::write(&unit_graph_file, unit_graph)?;
+        eprintln!(
+            "Capturing timings of a clean build of {}",
+            workspace_root.display()
+        );
+        let build_dir = dir.join("build");
+        if build_dir.exists() {
+            std::fs::remove_dir_all(&build_dir)?;
+        }
+        let mut cargo_args = vec!["--target-dir".to_owned(), build_dir.display().to_string()];
+        cargo_args.extend(args.cargo_args.iter().cloned());
+        // Timings of a failed build must not be reused, so they only take their place once the build succeeds.
+        let partial = dir.join("timings.json.partial");
+        let mut output = BufWriter::new(File::create(&partial)?);
+        // The build runs with all cores of this machine; it's the simulation that models `num_threads`.
+        let cores = std::thread::available_parallelism()?.get();
+        dice_box::capture(cores, &cargo_args, &mut output)?;
+        output.flush()?;
+        std::fs::rename(&partial, &timings_file)?;
+    }
+    // Both binaries are installed side by side.
+    let dice_box = std::env::current_exe()?
+        .with_file_name(format!("dice_box{}", std::env::consts::EXE_SUFFIX));
+    let status = Command::new(&dice_box)
+        .arg(&timings_file)
+        .arg(&unit_graph_file)
+        .arg("--environment")
+        .arg(&environment_file)
+        .args(["--num-threads", &num_threads.to_string()])
+        .status()
+        .with_context(|| format!("failed to run {}", dice_box.display()))?;
+    std::process::exit(status.code().unwrap_or(1));
+}
@@ -0,0 +1,131 @@
+//! Advisor suggesting changes to a workspace that would make its build more parallel.
+//!
+//! Candidate changes are derived from the critical path of the build, and each of them is simulated to estimate how
+//! much it would save:
+//! - splitting a crate on the critical path (approximated by halving its metadata and codegen time),
+//! - removing a dependency between two consecutive crates of the critical path,
+//! - caching a build script run (making it free),
+//! - improving scheduling decisions, as measured by the slack left by the scheduler.
+use std::collections::{BTreeMap, BTreeSet};
+
+use tabled::Tabled;
+
+use crate::{
+    artifact::{Artifact, ArtifactType},
+    compaction::compact,
+    critical_path::critical_path,
+    dependency_queue::DependencyQueueBuilder,
+    error::InputError,
+    registry::HintProviderFactory,
+    runner::{Duration, ScheduledTask},
+    simulate,
+    timings::TimingInfo,
+    transforms::Rewrite,
+    Resources,
+};
+
+#[derive(Clone, Debug, PartialEq, Tabled)]
+pub struct Advice {
+    pub action: String,
+    /// Estimated makespan after applying the change.
+    pub makespan: Duration,
+    pub saving: Duration,
+}
+
+fn makespan(schedule: &[ScheduledTask]) -> u64 {
+    schedule
+        .iter()
+        .map(|task| task.end)
+        .max()
+        .unwrap_or_default()
+}
+
+/// Suggests changes that would shorten the build the most, ranked by estimated savings. At most `limit` pieces of
+/// advice are returned, and only the ones that actually save time.
+pub fn advise(
+    deps: &DependencyQueueBuilder,
+    timings: &BTreeMap<Artifact, TimingInfo>,
+    scheduler: &dyn HintProviderFactory,
+    num_threads: usize,
+    limit: usize,
+) -> Result<Vec<Advice>, InputError> {
+    let resources = Resources {
+        num_threads,
+        ..Default::default()
+    };
+    let schedule = simulate(deps, timings, scheduler, &resources)?;
+    let baseline = makespan(&schedule);
+    let mut advice = vec![];
+    let mut consider = |action: String, new_makespan: u64| {
+        if new_makespan < baseline {
+            advice.push(Advice {
+                action,
+                makespan: Duration::from_millis(new_makespan),
+                saving: Duration::from_millis(baseline - new_makespan),
+            });
+        }
+    };
+
+    consider(
+        "Improve scheduling decisions".into(),
+        makespan(&compact(&schedule, deps, num_threads)),
+    );
+    let chain = critical_path(deps, timings);
+    let packages: BTreeSet<&str> = chain
+        .iter()
+        .map(|artifact| artifact.package_id.as_str())
+        .collect();
+    // Rewrites that can't be applied to the build save nothing.
+    let what_if = |rewrite: Rewrite| -> Result<u64, InputError> {
+        let (mut deps, mut timings) = (deps.clone(), timings.clone());
+        if rewrite.apply(&mut deps, &mut timings).is_err() {
+            return Ok(baseline);
+        }
+        Ok(makespan(&simulate(&deps, &timings, scheduler, &resources)?))
+    };
+    for package in packages {
+        let name = chain
+            .iter()
+            .find(|artifact| artifact.package_id == package)
+            .unwrap()
+            .name_ver();
+        consider(
+            format!("Split crate {name}"),
+            what_if(Rewrite::Split {
+                package: package.to_owned(),
+            })?,
+        );
+    }
+    for pair in chain.windows(2) {
+        let (dependency, dependant) = (&pair[0], &pair[1]);
+        if dependency.package_id == dependant.package_id {
+            continue;
+        }
+        consider(
+            format!(
+                "Remove dependency {} -> {}",
+                dependant.name_ver(),
+                dependency.name_ver()
+            ),
+            what_if(Rewrite::RemoveDependency {
+                dependant: dependant.package_id.clone(),
+                dependency: dependency.package_id.clone(),
+            })?,
+        );
+    }
+    for build_script in timings
+        .keys()
+        .filter(|artifact| artifact.typ == ArtifactType::BuildScriptRun)
+    {
+        consider(
+            format!("Cache build script of {}", build_script.name_ver()),
+            what_if(Rewrite::CacheBuildScript {
+                package: build_script.package_id.clone(),
+            })?,
+        );
+    }
+
+    advice.sort_by(|a, b| b.saving.partial_cmp(&a.saving).unwrap());
+    advice.truncate(limit);
+    Ok(advice)
+}
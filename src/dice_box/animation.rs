@@ -0,0 +1,91 @@
+//! Time-compressed animation of a simulated build, as an SVG in which bars grow on thread lanes as units are being
+//! built. Browsers play it without any scripts, which makes it easy to embed in blog posts and slides.
+use std::io::Write;
+
+use anyhow::Result;
+
+use crate::{artifact::ArtifactType, runner::ScheduledTask};
+
+/// Assigns each task of `schedule` to a lane, so that no two tasks on the same lane overlap. Returns the lane of
+/// each task (in the order of `schedule`) and the number of lanes.
+fn lanes(schedule: &[ScheduledTask]) -> (Vec<usize>, usize) {
+    let mut order: Vec<usize> = (0..schedule.len()).collect();
+    order.sort_by_key(|index| (schedule[*index].start, schedule[*index].end));
+    let mut lane_ends: Vec<u64> = vec![];
+    let mut lanes = vec![0; schedule.len()];
+    for index in order {
+        let task = &schedule[index];
+        let lane = match lane_ends.iter().position(|end| *end <= task.start) {
+            Some(lane) => lane,
+            None => {
+                lane_ends.push(0);
+                lane_ends.len() - 1
+            }
+        };
+        lane_ends[lane] = task.end;
+        lanes[index] = lane;
+    }
+    (lanes, lane_ends.len())
+}
+
+/// Writes an animated SVG of `schedule` played back in `length` seconds.
+pub fn write_animation(
+    schedule: &[ScheduledTask],
+    title: &str,
+    length: f64,
+    f: &mut impl Write,
+) -> Result<()> {
+    const LANE_HEIGHT: u64 = 20;
+    const WIDTH: f64 = 1000.;
+    const HEADER_HEIGHT: u64 = 30;
+    let makespan = schedule
+        .iter()
+        .map(|task| task.end)
+        .max()
+        .unwrap_or(1)
+        .max(1) as f64;
+    let scale = WIDTH / makespan;
+    // Seconds of animation per millisecond of the build.
+    let speed = length / makespan;
+    let (lanes, lane_count) = lanes(schedule);
+    let height = HEADER_HEIGHT + LANE_HEIGHT * lane_count as u64;
+    writeln!(
+        f,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{WIDTH}" height="{height}" font-family="sans-serif" font-size="12">"#,
+    )?;
+    writeln!(
+        f,
+        r#"  <text x="0" y="15">{} ({:.1}s)</text>"#,
+        title.replace('&', "&amp;").replace('<', "&lt;"),
+        makespan / 1000.
+    )?;
+    for (task, lane) in schedule.iter().zip(lanes) {
+        let color = match task.artifact.typ {
+            ArtifactType::BuildScriptBuild | ArtifactType::BuildScriptRun => "#f0b165",
+            ArtifactType::Metadata => "#95cce8",
+            ArtifactType::Codegen => "#aa95e8",
+            ArtifactType::Link => "#e89595",
+        };
+        let width = ((task.end - task.start) as f64 * scale).max(1.);
+        writeln!(
+            f,
+            r#"  <rect x="{:.1}" y="{}" width="0" height="{}" fill="{color}"><title>{}</title><animate attributeName="width" from="0" to="{width:.1}" begin="{:.3}s" dur="{:.3}s" fill="freeze"/></rect>"#,
+            task.start as f64 * scale,
+            HEADER_HEIGHT + lane as u64 * LANE_HEIGHT + 2,
+            LANE_HEIGHT - 4,
+            task.artifact
+                .to_string()
+                .replace('&', "&amp;")
+                .replace('<', "&lt;"),
+            task.start as f64 * speed,
+            // Zero-length animations are not played at all.
+            ((task.end - task.start) as f64 * speed).max(0.001),
+        )?;
+    }
+    writeln!(
+        f,
+        r##"  <line x1="0" y1="{HEADER_HEIGHT}" x2="0" y2="{height}" stroke="#333"><animate attributeName="x1" from="0" to="{WIDTH}" dur="{length:.3}s" fill="freeze"/><animate attributeName="x2" from="0" to="{WIDTH}" dur="{length:.3}s" fill="freeze"/></line>"##,
+    )?;
+    writeln!(f, "</svg>")?;
+    Ok(())
+}
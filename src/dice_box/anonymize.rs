@@ -0,0 +1,262 @@
+//! Anonymization of inputs, producing datasets that can be shared for scheduler research when exact timings (or
+//! names of crates) are considered sensitive.
+//!
+//! Durations are rounded up to a multiple of a bucket size and package names can be replaced with symbolic ones.
+//! The structure of the unit graph is preserved as is.
+use std::collections::BTreeMap;
+
+use tabled::Tabled;
+
+use crate::{
+    artifact::{Artifact, ArtifactType},
+    critical_path::critical_path_lengths,
+    dependency_queue::DependencyQueueBuilder,
+    error::InputError,
+    registry::Registry,
+    runner::{Duration, Runner},
+    timings::TimingInfo,
+    unit_graph::UnitGraph,
+};
+
+pub struct Anonymizer {
+    /// Bucket size, in seconds.
+    bucket: f64,
+    /// Symbolic package ids by the original ones, if names are to be stripped.
+    symbols: Option<BTreeMap<String, String>>,
+}
+
+impl Anonymizer {
+    /// Creates an anonymizer for a given unit graph. With `symbolic` set, packages get renamed to `pkgN`.
+    pub fn new(graph: &UnitGraph, bucket: f64, symbolic: bool) -> Self {
+        let symbols = symbolic.then(|| {
+            let mut symbols = BTreeMap::new();
+            for unit in &graph.units {
+                let index = symbols.len();
+                symbols.entry(unit.pkg_id.clone()).or_insert_with(|| {
+                    // Workspace members are told apart by their source, so keep its kind.
+                    let source = if Artifact::is_workspace_member(&Artifact {
+                        typ: ArtifactType::Metadata,
+                        package_id: unit.pkg_id.clone(),
+                    }) {
+                        format!("path+file:///pkg{index}")
+                    } else {
+                        "registry+symbolic".to_owned()
+                    };
+                    format!("pkg{index} 0.0.0 ({source})")
+                });
+            }
+            symbols
+        });
+        Self { bucket, symbols }
+    }
+
+    fn bucketed(&self, seconds: f64) -> f64 {
+        (seconds / self.bucket).ceil().max(1.) * self.bucket
+    }
+
+    fn package_id(&self, package_id: &str) -> String {
+        self.symbols
+            .as_ref()
+            .and_then(|symbols| symbols.get(package_id))
+            .cloned()
+            .unwrap_or_else(|| package_id.to_owned())
+    }
+
+    fn target_name(&self, name: &str, package_id: &str) -> String {
+        match &self.symbols {
+            Some(_) if !["build-script-build", "build-script-main"].contains(&name) => self
+                .package_id(package_id)
+                .split(' ')
+                .next()
+                .unwrap()
+                .to_owned(),
+            _ => name.to_owned(),
+        }
+    }
+
+    /// Anonymizes contents of a timings file.
+    pub fn timings(&self, contents: &str) -> String {
+        let mut out = String::new();
+        for line in contents.lines().filter(|line| line.starts_with('{')) {
+            let mut timing: TimingInfo = serde_json::from_str(line).unwrap();
+            timing.duration = self.bucketed(timing.duration);
+            // Pipelined units keep only a coarse split of their time between metadata and codegen.
+            timing.rmeta_time = timing
+                .rmeta_time
+                .map(|rmeta_time| self.bucketed(rmeta_time).min(timing.duration));
+            timing.target.name = self.target_name(&timing.target.name, &timing.package_id);
+            timing.package_id = self.package_id(&timing.package_id);
+            out.push_str(&serde_json::to_string(&timing).unwrap());
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Anonymizes a unit graph. Names of features and dependencies are dropped with symbolic names.
+    pub fn unit_graph(&self, mut graph: UnitGraph) -> UnitGraph {
+        for unit in &mut graph.units {
+            unit.target.name = self.target_name(&unit.target.name, &unit.pkg_id);
+            unit.pkg_id = self.package_id(&unit.pkg_id);
+            if self.symbols.is_some() {
+                unit.features.clear();
+                for dependency in &mut unit.dependencies {
+                    dependency.extern_crate_name = None;
+                }
+            }
+        }
+        graph
+    }
+
+    /// Upper bound (in milliseconds) on how much bucketing can change the length of the critical path of the build.
+    /// Codegen units can be off by two buckets, as both the full duration and the metadata time are rounded.
+    pub fn critical_path_error(
+        &self,
+        deps: &DependencyQueueBuilder,
+        timings: &BTreeMap<Artifact, TimingInfo>,
+    ) -> u64 {
+        let errors = timings
+            .iter()
+            .map(|(artifact, timing)| {
+                let buckets = if artifact.typ == ArtifactType::Codegen {
+                    2.
+                } else {
+                    1.
+                };
+                let mut timing = timing.clone();
+                timing.duration = buckets * self.bucket;
+                (artifact.clone(), timing)
+            })
+            .collect();
+        critical_path_lengths(deps, &errors)
+            .into_values()
+            .max()
+            .unwrap_or_default()
+    }
+}
+
+/// How much bucketing changed the makespan under a given scheduler.
+#[derive(Clone, Debug, PartialEq, Tabled)]
+pub struct BucketingError {
+    pub label: String,
+    pub makespan: Duration,
+    pub bucketed: Duration,
+    pub difference: Duration,
+    /// Maximum change of the critical path length.
+    pub bound: Duration,
+    pub within_bound: bool,
+}
+
+/// Simulates all schedulers from `registry` on both original and anonymized inputs.
+pub fn verify_bucketing(
+    registry: &Registry,
+    original: (&DependencyQueueBuilder, &BTreeMap<Artifact, TimingInfo>),
+    anonymized: (&DependencyQueueBuilder, &BTreeMap<Artifact, TimingInfo>),
+    bound: u64,
+    num_threads: usize,
+) -> Result<Vec<BucketingError>, InputError> {
+    let simulate = |name: &str, (deps, timings): (&DependencyQueueBuilder, &_)| {
+        let hints = registry.create(name, deps, timings).unwrap();
+        Runner::new(deps.clone().finish(hints), timings.clone(), num_threads).calculate()
+    };
+    let bound = std::time::Duration::from_millis(bound);
+    registry
+        .names()
+        .map(|name| {
+            let (original, _) = simulate(name, original)?;
+            let (bucketed, _) = simulate(name, anonymized)?;
+            let difference = original.makespan.0.abs_diff(bucketed.makespan.0);
+            Ok(BucketingError {
+                label: original.label,
+                makespan: original.makespan,
+                bucketed: bucketed.makespan,
+                difference: Duration(difference),
+                bound: Duration(bound),
+                within_bound: difference <= bound,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph() -> UnitGraph {
+        let unit = |pkg_id: &str, name: &str, deps: &[usize]| {
+            serde_json::json!({
+                "pkg_id": pkg_id,
+                "target": { "name": name, "crate_types": ["lib"] },
+                "mode": "build",
+                "features": ["default"],
+                "dependencies": deps
+                    .iter()
+                    .map(|index| serde_json::json!({ "index": index, "extern_crate_name": "dep" }))
+                    .collect::<Vec<_>>(),
+            })
+        };
+        serde_json::from_value(serde_json::json!({
+            "units": [
+                unit("app 0.1.0 (path+file:///app)", "app", &[1]),
+                unit("serde 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)", "serde", &[]),
+            ],
+            "roots": [0],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn timings_are_rounded_up_to_buckets() {
+        let anonymizer = Anonymizer::new(&graph(), 0.5, false);
+        let line = serde_json::json!({
+            "reason": "timing-info",
+            "package_id": "app 0.1.0 (path+file:///app)",
+            "target": { "name": "app", "crate_types": ["lib"] },
+            "mode": "build",
+            "duration": 1.2,
+            "rmeta_time": 0.1,
+        });
+        let anonymized: TimingInfo =
+            serde_json::from_str(anonymizer.timings(&line.to_string()).trim()).unwrap();
+        assert_eq!(anonymized.duration, 1.5);
+        assert_eq!(anonymized.rmeta_time, Some(0.5));
+        assert_eq!(anonymized.package_id, "app 0.1.0 (path+file:///app)");
+        assert_eq!(anonymized.target.name, "app");
+    }
+
+    #[test]
+    fn symbolic_names_keep_the_structure() {
+        let graph = graph();
+        let anonymizer = Anonymizer::new(&graph, 0.5, true);
+        let anonymized = anonymizer.unit_graph(graph.clone());
+        let ids: Vec<_> = anonymized
+            .units
+            .iter()
+            .map(|unit| unit.pkg_id.as_str())
+            .collect();
+        assert_eq!(
+            ids,
+            [
+                "pkg0 0.0.0 (path+file:///pkg0)",
+                "pkg1 0.0.0 (registry+symbolic)"
+            ]
+        );
+        assert_eq!(anonymized.units[1].target.name, "pkg1");
+        assert_eq!(anonymized.roots, graph.roots);
+        for (unit, original) in anonymized.units.iter().zip(&graph.units) {
+            assert!(unit.features.is_empty());
+            let indices = |unit| {
+                serde_json::to_value(unit).unwrap()["dependencies"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|dependency| dependency["index"].clone())
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(indices(unit), indices(original));
+            assert!(unit
+                .dependencies
+                .iter()
+                .all(|dependency| dependency.extern_crate_name.is_none()));
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use serde::{Deserialize, Serialize};
+
 /// Possible artifacts that can be produced by compilations, used as edge values
 /// in the dependency graph.
 ///
@@ -5,7 +7,7 @@
 /// for example some units may only depend on the metadata for an rlib while
 /// others depend on the full rlib. This `Artifact` enum is used to distinguish
 /// this case and track the progress of compilations as they proceed.
-#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, PartialOrd, Ord)]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum ArtifactType {
     BuildScriptBuild,
     BuildScriptRun,
@@ -22,8 +24,141 @@ pub enum ArtifactType {
     Link,
 }
 
-#[derive(Clone, Eq, PartialEq, Hash, Debug, PartialOrd, Ord)]
+impl ArtifactType {
+    /// Name of the type in hint files, as accepted by [std::str::FromStr].
+    pub fn name(&self) -> &'static str {
+        match self {
+            ArtifactType::BuildScriptBuild => "build-script-build",
+            ArtifactType::BuildScriptRun => "build-script-run",
+            ArtifactType::Metadata => "metadata",
+            ArtifactType::Codegen => "codegen",
+            ArtifactType::Link => "link",
+        }
+    }
+}
+
+impl std::str::FromStr for ArtifactType {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "build-script-build" | "BuildScriptBuild" => Ok(ArtifactType::BuildScriptBuild),
+            "build-script-run" | "BuildScriptRun" => Ok(ArtifactType::BuildScriptRun),
+            "metadata" | "Metadata" => Ok(ArtifactType::Metadata),
+            "codegen" | "Codegen" => Ok(ArtifactType::Codegen),
+            "link" | "Link" => Ok(ArtifactType::Link),
+            _ => Err(format!(
+                "unknown unit type `{s}`, expected one of: build-script-build, build-script-run, metadata, codegen, link"
+            )),
+        }
+    }
+}
+
+/// Kinds of work that can be given separate pools of threads, modeling setups that bound heavy link or
+/// network-bound steps independently of compilation.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, PartialOrd, Ord)]
+pub enum ThreadPool {
+    Compile,
+    Link,
+    BuildScript,
+}
+
+impl std::str::FromStr for ThreadPool {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "compile" => Ok(ThreadPool::Compile),
+            "link" => Ok(ThreadPool::Link),
+            "build-script" => Ok(ThreadPool::BuildScript),
+            _ => Err(format!(
+                "unknown thread pool `{s}`, expected one of: compile, link, build-script"
+            )),
+        }
+    }
+}
+
+impl ArtifactType {
+    pub fn pool(self) -> ThreadPool {
+        match self {
+            ArtifactType::BuildScriptBuild | ArtifactType::BuildScriptRun => {
+                ThreadPool::BuildScript
+            }
+            ArtifactType::Metadata | ArtifactType::Codegen => ThreadPool::Compile,
+            ArtifactType::Link => ThreadPool::Link,
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Hash, Debug, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct Artifact {
     pub typ: ArtifactType,
     pub package_id: String,
 }
+
+impl Artifact {
+    /// Package id without the source, which is usually just noise.
+    pub fn name_ver(&self) -> String {
+        let (name, version, _) = split_package_id(&self.package_id);
+        if version.is_empty() {
+            return name.to_owned();
+        }
+        format!("{name} {version}")
+    }
+
+    /// Whether the artifact belongs to a workspace member (or any other local package), as opposed to a package
+    /// pulled from a registry or a git repository.
+    pub fn is_workspace_member(&self) -> bool {
+        split_package_id(&self.package_id).2.starts_with("path+")
+    }
+}
+
+impl std::fmt::Display for Artifact {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({:?})", self.name_ver(), self.typ)
+    }
+}
+
+/// Splits `package_id` into the name, version and source of the package. Cargo 1.77 switched from ids like
+/// "serde 1.0.188 (registry+https://github.com/rust-lang/crates.io-index)" to package id specs like
+/// "registry+https://github.com/rust-lang/crates.io-index#serde@1.0.188", and both are understood. Specs of packages
+/// named after their directory leave the name out (e.g. "path+file:///ws/crates/api#0.1.0").
+pub(crate) fn split_package_id(package_id: &str) -> (&str, &str, &str) {
+    if let Some((source, fragment)) = package_id
+        .rsplit_once('#')
+        .filter(|(source, _)| source.contains("://"))
+    {
+        return match fragment.split_once('@') {
+            Some((name, version)) => (name, version, source),
+            None => {
+                let path = source.split(['?', '#']).next().unwrap_or(source);
+                let name = path
+                    .trim_end_matches('/')
+                    .rsplit('/')
+                    .next()
+                    .unwrap_or(path);
+                (name, fragment, source)
+            }
+        };
+    }
+    let (name, rest) = package_id.split_once(' ').unwrap_or((package_id, ""));
+    let (version, source) = rest.split_once(" (").unwrap_or((rest, ""));
+    (name, version, source.trim_end_matches(')'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constraints::matches;
+
+    #[test]
+    fn package_id_specs_are_understood() {
+        let spec = "registry+https://github.com/rust-lang/crates.io-index#serde@1.0.188";
+        assert!(matches(spec, "serde"));
+        assert!(!matches(spec, "serde_json"));
+        let local = Artifact {
+            typ: ArtifactType::Metadata,
+            package_id: "path+file:///ws/crates/api#0.1.0".to_owned(),
+        };
+        assert_eq!(local.name_ver(), "api 0.1.0");
+        assert!(local.is_workspace_member());
+    }
+}
@@ -0,0 +1,92 @@
+//! Attribution of the makespan difference between two scenarios to the order in which units were dequeued, and to
+//! the configuration the build ran in (number of threads, constraints, pool limits, external events).
+//!
+//! Each scenario is characterized by its start order and its configuration. Both orders are replayed as priority
+//! lists in both configurations, which gives the makespan of every combination. The effect of the order is then the
+//! average change of makespan from swapping orders within either configuration, and the effect of placement is the
+//! average change from swapping configurations under either order; together they add up to the difference between
+//! the replays of the two scenarios. Whatever a fixed order cannot reproduce (deliberately idle threads, picks
+//! adapting to the core that freed up, charged scheduling overhead) is reported separately.
+use tabled::Tabled;
+
+use crate::{
+    artifact::{Artifact, ArtifactType},
+    dependency_queue::{CargoHints, DependencyQueueBuilder},
+    error::InputError,
+    hints::PriorityListHints,
+    runner::{start_order, Runner},
+};
+
+#[derive(Clone, Debug, PartialEq, Tabled)]
+pub struct Attribution {
+    pub left: String,
+    pub right: String,
+    /// Makespan of the right scenario minus the one of the left scenario, in milliseconds.
+    #[tabled(display_with = "display_delta")]
+    pub difference: i64,
+    /// Part of the difference due to different dequeue orders.
+    #[tabled(display_with = "display_delta")]
+    pub order: i64,
+    /// Part of the difference due to the same orders being placed differently in the two configurations.
+    #[tabled(display_with = "display_delta")]
+    pub placement: i64,
+    /// Part of the difference that replaying the orders does not reproduce.
+    #[tabled(display_with = "display_delta")]
+    pub other: i64,
+}
+
+fn display_delta(millis: &i64) -> String {
+    format!("{:+.3}s", *millis as f64 / 1000.)
+}
+
+fn makespan(runner: &Runner) -> i64 {
+    runner
+        .schedule()
+        .iter()
+        .map(|task| task.end)
+        .max()
+        .unwrap_or_default() as i64
+}
+
+/// Makespan of the build in the configuration of `runner`, picking units in `order` whenever they're ready.
+fn repeat(
+    runner: &Runner,
+    order: &[Artifact],
+    deps: &DependencyQueueBuilder,
+) -> Result<i64, InputError> {
+    let hints = PriorityListHints::new(order, CargoHints::new(deps));
+    let mut repeated = runner.reconfigured(deps.clone().finish(hints));
+    repeated.calculate()?;
+    Ok(makespan(&repeated))
+}
+
+/// Attributes the makespan difference between two simulated scenarios.
+pub fn attribute(
+    left: &Runner,
+    right: &Runner,
+    deps: &DependencyQueueBuilder,
+) -> Result<Attribution, InputError> {
+    // Codegen units are always started as soon as they're ready, so they don't take part in the order.
+    let order = |runner: &Runner| -> Vec<Artifact> {
+        start_order(runner.schedule())
+            .into_iter()
+            .filter(|artifact| artifact.typ != ArtifactType::Codegen)
+            .collect()
+    };
+    let (left_order, right_order) = (order(left), order(right));
+    let left_left = repeat(left, &left_order, deps)?;
+    let left_right = repeat(left, &right_order, deps)?;
+    let right_left = repeat(right, &left_order, deps)?;
+    let right_right = repeat(right, &right_order, deps)?;
+    let difference = makespan(right) - makespan(left);
+    let order = ((left_right - left_left) + (right_right - right_left)) / 2;
+    let placement = right_right - left_left - order;
+    Ok(Attribution {
+        left: left.label().to_owned(),
+        right: right.label().to_owned(),
+        difference,
+        order,
+        placement,
+        other: difference - order - placement,
+    })
+}
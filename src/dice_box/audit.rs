@@ -0,0 +1,102 @@
+//! Audit of the decisions a hint provider made, pointing authors of heuristics at the picks that cost the most.
+//!
+//! Every logged decision is judged on its own: the simulation is replayed up to the decision, the decision is made
+//! (either as the hint provider made it, or in favour of one of the other candidates) and the rest of the build is
+//! scheduled greedily with full knowledge of unit durations (longest critical path first). A decision is as bad as
+//! the makespan its best alternative saves under that continuation.
+use std::collections::BTreeMap;
+
+use tabled::Tabled;
+
+use crate::{
+    artifact::Artifact,
+    decision_log::DecisionReason,
+    dependency_queue::DependencyQueueBuilder,
+    error::InputError,
+    hints::CriticalPathHints,
+    runner::{Duration, Runner},
+    timings::TimingInfo,
+    trace::Decision,
+};
+
+/// A decision of the hint provider along with the candidate it should have picked instead.
+#[derive(Clone, Debug, PartialEq, Tabled)]
+pub struct DecisionAudit {
+    pub scenario: String,
+    /// Time of the decision since the start of the build.
+    pub time: Duration,
+    pub thread: usize,
+    /// Unit that was started, or `(wait)` if the hint provider left the thread idle.
+    pub choice: String,
+    pub alternative: String,
+    /// Makespan with the original decision, followed by greedy decisions.
+    pub makespan: Duration,
+    /// Makespan with the alternative, followed by greedy decisions.
+    pub alternative_makespan: Duration,
+    pub improvement: Duration,
+}
+
+/// Finds the `top` decisions of the hint provider of `runner` whose replacement would have shortened the build the
+/// most. Only available for runners that logged their decisions, after [Runner::calculate] is called.
+pub fn audit_decisions(
+    runner: &Runner,
+    deps: &DependencyQueueBuilder,
+    timings: &BTreeMap<Artifact, TimingInfo>,
+    top: usize,
+) -> Result<Vec<DecisionAudit>, InputError> {
+    let trace = runner.trace();
+    let continue_greedily = |prefix: &[Decision], decision: Decision| {
+        let decisions = prefix.iter().cloned().chain([decision]).collect();
+        let mut counterfactual = runner
+            .reconfigured(deps.clone().finish(CriticalPathHints::new(deps, timings)))
+            .with_replayed_prefix(decisions);
+        Ok::<_, InputError>(counterfactual.calculate()?.0.makespan)
+    };
+    let mut audits: Vec<DecisionAudit> = runner
+        .decision_log()
+        .iter()
+        // Codegen units and affinity picks are not up to the hint provider.
+        .filter(|logged| logged.reason == DecisionReason::HintProvider)
+        .map(|logged| {
+            let (prefix, rest) = trace.decisions.split_at(logged.index);
+            let original = &rest[0];
+            let Some((alternative, alternative_makespan)) = logged
+                .candidates
+                .iter()
+                .filter(|candidate| Some(&candidate.artifact) != logged.choice.as_ref())
+                .map(|candidate| {
+                    let decision = Decision {
+                        artifact: Some(candidate.artifact.clone()),
+                        overhead: original.overhead,
+                        wait: false,
+                        threads: None,
+                    };
+                    Ok((&candidate.artifact, continue_greedily(prefix, decision)?))
+                })
+                .collect::<Result<Vec<_>, InputError>>()?
+                .into_iter()
+                .min_by_key(|(_, makespan)| *makespan)
+            else {
+                return Ok(None);
+            };
+            let makespan = continue_greedily(prefix, original.clone())?;
+            Ok((alternative_makespan < makespan).then(|| DecisionAudit {
+                scenario: runner.label().to_owned(),
+                time: Duration::from_millis(logged.time),
+                thread: logged.thread,
+                choice: logged
+                    .choice
+                    .as_ref()
+                    .map_or_else(|| "(wait)".to_owned(), ToString::to_string),
+                alternative: alternative.to_string(),
+                makespan,
+                alternative_makespan,
+                improvement: makespan - alternative_makespan,
+            }))
+        })
+        .filter_map(Result::transpose)
+        .collect::<Result<_, InputError>>()?;
+    audits.sort_by(|a, b| b.improvement.cmp(&a.improvement).then(a.time.cmp(&b.time)));
+    audits.truncate(top);
+    Ok(audits)
+}
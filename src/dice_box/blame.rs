@@ -0,0 +1,83 @@
+//! Attribution of idle threads to the units that kept other units from starting. Whenever threads of a simulated
+//! build sit idle while units are still waiting for their dependencies, the idle thread time is split between the
+//! running units that the waiting ones (transitively) depend on. Units with a lot of blame are the ones to make
+//! faster or to start earlier, even if they don't take that long themselves.
+use std::collections::BTreeMap;
+
+use tabled::Tabled;
+
+use crate::{
+    artifact::Artifact,
+    dependency_queue::{reverse_dependencies, DependencyQueueBuilder},
+    runner::{Duration, ScheduledTask},
+};
+
+#[derive(Clone, Debug, PartialEq, Tabled)]
+pub struct Blame {
+    pub unit: String,
+    pub duration: Duration,
+    /// Thread time spent idle while units depending on this one were waiting for it.
+    pub blocked: Duration,
+}
+
+/// Blames idle thread time of `schedule` on `num_threads` threads on the units that were holding up the rest of the
+/// build, returning at most `limit` units with the most blame first.
+pub fn blame(
+    deps: &DependencyQueueBuilder,
+    schedule: &[ScheduledTask],
+    num_threads: usize,
+    limit: usize,
+) -> Vec<Blame> {
+    let dependants = reverse_dependencies(deps);
+    let starts: BTreeMap<&Artifact, u64> = schedule
+        .iter()
+        .map(|task| (&task.artifact, task.start))
+        .collect();
+    let mut times: Vec<u64> = schedule
+        .iter()
+        .flat_map(|task| [task.start, task.end])
+        .collect();
+    times.sort_unstable();
+    times.dedup();
+    let mut blocked = vec![0.; schedule.len()];
+    for window in times.windows(2) {
+        let (start, end) = (window[0], window[1]);
+        let running: Vec<usize> = (0..schedule.len())
+            .filter(|index| {
+                let task = &schedule[*index];
+                task.start <= start && task.end >= end && task.end > task.start
+            })
+            .collect();
+        let idle = num_threads.saturating_sub(running.len());
+        if idle == 0 {
+            continue;
+        }
+        let blockers: Vec<usize> = running
+            .into_iter()
+            .filter(|index| {
+                dependants
+                    .get(&schedule[*index].artifact)
+                    .into_iter()
+                    .flatten()
+                    .any(|dependant| starts.get(dependant).is_some_and(|at| *at >= end))
+            })
+            .collect();
+        let share = (idle as u64 * (end - start)) as f64 / blockers.len().max(1) as f64;
+        for index in blockers {
+            blocked[index] += share;
+        }
+    }
+    let mut blame: Vec<Blame> = schedule
+        .iter()
+        .zip(blocked)
+        .filter(|(_, blocked)| *blocked > 0.)
+        .map(|(task, blocked)| Blame {
+            unit: task.artifact.to_string(),
+            duration: Duration::from_millis(task.end - task.start),
+            blocked: Duration::from_millis(blocked.round() as u64),
+        })
+        .collect();
+    blame.sort_by(|a, b| b.blocked.cmp(&a.blocked).then(a.unit.cmp(&b.unit)));
+    blame.truncate(limit);
+    blame
+}
@@ -0,0 +1,187 @@
+//! On-disk cache of expensive preprocessing results.
+//!
+//! Computing transitive reverse dependencies and critical paths can take seconds on large graphs, which adds up
+//! when the same capture is simulated over and over again (e.g. during what-if exploration). Results are stored in
+//! a cache directory, keyed by a hash of all inputs that affect the shape of the dependency graph.
+//!
+//! Simulated scenarios are cached in the same directory: their decisions are replayed by subsequent runs on the same
+//! inputs, so that hint providers (some of which run simulations of their own) don't have to be consulted again.
+use std::collections::{BTreeMap, BTreeSet};
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    artifact::Artifact,
+    critical_path::{critical_path_lengths, duration_ms},
+    dependency_queue::{reverse_dependencies, DependencyQueueBuilder},
+    runner::Runner,
+    timings::TimingInfo,
+    trace::ScenarioTrace,
+};
+
+#[derive(Debug, Default)]
+pub struct Preprocessed {
+    pub(crate) reverse_dependencies: BTreeMap<Artifact, BTreeSet<Artifact>>,
+    pub(crate) critical_paths: BTreeMap<Artifact, u64>,
+    /// Fingerprint of timings that critical paths were computed with; they're stale if timings change.
+    pub(crate) timings_fingerprint: u64,
+}
+
+/// On-disk representation of [Preprocessed]; JSON does not support non-string map keys.
+#[derive(Serialize, Deserialize)]
+struct CacheFile {
+    reverse_dependencies: Vec<(Artifact, BTreeSet<Artifact>)>,
+    critical_paths: Vec<(Artifact, u64)>,
+    timings_fingerprint: u64,
+}
+
+pub(crate) fn timings_fingerprint(timings: &BTreeMap<Artifact, TimingInfo>) -> u64 {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    for artifact in timings.keys() {
+        artifact.hash(&mut hasher);
+        duration_ms(timings, artifact).hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+impl Preprocessed {
+    /// Computes preprocessing results from scratch. `deps` must not have any preprocessing results attached.
+    pub fn compute(
+        deps: &DependencyQueueBuilder,
+        timings: &BTreeMap<Artifact, TimingInfo>,
+    ) -> Self {
+        Self {
+            reverse_dependencies: reverse_dependencies(deps),
+            critical_paths: critical_path_lengths(deps, timings),
+            timings_fingerprint: timings_fingerprint(timings),
+        }
+    }
+
+    /// Loads preprocessing results for inputs identified by `key` from `cache_dir`, computing and storing them
+    /// there if they're not cached yet.
+    pub fn load_or_compute(
+        cache_dir: &Path,
+        key: u64,
+        deps: &DependencyQueueBuilder,
+        timings: &BTreeMap<Artifact, TimingInfo>,
+    ) -> Result<Self> {
+        let path = cache_dir.join(format!("{key:016x}.json"));
+        if let Ok(contents) = std::fs::read_to_string(&path) {
+            match serde_json::from_str::<CacheFile>(&contents) {
+                Ok(file) => return Ok(file.into()),
+                Err(e) => warn!("Ignoring corrupted cache file {}: {e}", path.display()),
+            }
+        }
+        let preprocessed = Self::compute(deps, timings);
+        std::fs::create_dir_all(cache_dir)?;
+        std::fs::write(
+            &path,
+            serde_json::to_string(&CacheFile::from(&preprocessed))?,
+        )?;
+        Ok(preprocessed)
+    }
+}
+
+impl From<CacheFile> for Preprocessed {
+    fn from(file: CacheFile) -> Self {
+        Self {
+            reverse_dependencies: file.reverse_dependencies.into_iter().collect(),
+            critical_paths: file.critical_paths.into_iter().collect(),
+            timings_fingerprint: file.timings_fingerprint,
+        }
+    }
+}
+
+impl From<&Preprocessed> for CacheFile {
+    fn from(preprocessed: &Preprocessed) -> Self {
+        Self {
+            reverse_dependencies: preprocessed
+                .reverse_dependencies
+                .iter()
+                .map(|(artifact, dependants)| (artifact.clone(), dependants.clone()))
+                .collect(),
+            critical_paths: preprocessed
+                .critical_paths
+                .iter()
+                .map(|(artifact, length)| (artifact.clone(), *length))
+                .collect(),
+            timings_fingerprint: preprocessed.timings_fingerprint,
+        }
+    }
+}
+
+/// Decisions of scenarios simulated by earlier runs, stored in a cache directory.
+pub struct ScenarioCache {
+    cache_dir: PathBuf,
+    /// Hash of inputs that affect hint providers without being reflected in their labels.
+    key: u64,
+}
+
+impl ScenarioCache {
+    pub fn new(cache_dir: &Path, key: u64) -> Self {
+        Self {
+            cache_dir: cache_dir.to_owned(),
+            key,
+        }
+    }
+
+    fn path(&self, key: u64) -> PathBuf {
+        self.cache_dir.join(format!("scenario-{key:016x}.json"))
+    }
+
+    fn load(&self, key: u64, runner: &Runner) -> Option<ScenarioTrace> {
+        let path = self.path(key);
+        let contents = std::fs::read_to_string(&path).ok()?;
+        match serde_json::from_str::<ScenarioTrace>(&contents) {
+            Ok(trace) if trace.label == runner.label() => Some(trace),
+            Ok(trace) => {
+                warn!(
+                    "Ignoring cache file {} of \"{}\" for \"{}\"",
+                    path.display(),
+                    trace.label,
+                    runner.label()
+                );
+                None
+            }
+            Err(e) => {
+                warn!("Ignoring corrupted cache file {}: {e}", path.display());
+                None
+            }
+        }
+    }
+
+    /// Makes scenarios that were simulated before replay their cached decisions. Returns the scenarios along with
+    /// cache keys of the ones that have to be simulated from scratch, to [store](ScenarioCache::store) them under.
+    pub fn restore(&self, scenarios: Vec<Runner>) -> (Vec<Runner>, Vec<Option<u64>>) {
+        scenarios
+            .into_iter()
+            .map(|runner| {
+                let mut hasher = std::collections::hash_map::DefaultHasher::new();
+                self.key.hash(&mut hasher);
+                runner.fingerprint().hash(&mut hasher);
+                let key = hasher.finish();
+                match self.load(key, &runner) {
+                    Some(trace) => (runner.with_replay(trace), None),
+                    None => (runner, Some(key)),
+                }
+            })
+            .unzip()
+    }
+
+    /// Stores decisions of simulated `scenarios` under the `keys` returned by [ScenarioCache::restore].
+    pub fn store(&self, scenarios: &[Runner], keys: &[Option<u64>]) -> Result<()> {
+        std::fs::create_dir_all(&self.cache_dir)?;
+        for (runner, key) in scenarios.iter().zip(keys) {
+            if let Some(key) = key {
+                let path = self.path(*key);
+                std::fs::write(&path, serde_json::to_string(&runner.trace())?)
+                    .with_context(|| format!("failed to write {}", path.display()))?;
+            }
+        }
+        Ok(())
+    }
+}
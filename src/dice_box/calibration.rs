@@ -0,0 +1,111 @@
+//! Calibration of the simulation against a recorded build, telling which costs that aren't simulated (spawning
+//! processes, waiting for jobserver tokens, Cargo's own bookkeeping) matter for a given project.
+//!
+//! Drift of a unit is how much later it started in reality than in the simulation. Drift accumulates along chains of
+//! dependent units, so the contribution of a unit itself is told apart as its extra wait: how much longer it waited
+//! after its last dependency had finished in reality than it did in the simulation.
+use std::collections::BTreeMap;
+
+use tabled::Tabled;
+
+use crate::{
+    artifact::Artifact,
+    dependency_queue::DependencyQueueBuilder,
+    runner::{Duration, ScheduledTask},
+};
+
+/// How far off a simulated schedule was from the recorded build as a whole.
+#[derive(Clone, Debug, PartialEq, Tabled)]
+pub struct Calibration {
+    pub label: String,
+    pub predicted_makespan: Duration,
+    pub actual_makespan: Duration,
+    /// How much longer the recorded build took, in seconds.
+    #[tabled(display_with = "display_seconds")]
+    pub drift: f64,
+    /// Extra waits of units that waited longer in reality, added up.
+    #[tabled(display_with = "display_seconds")]
+    pub extra_wait: f64,
+    /// Number of units built in both schedules.
+    pub units: usize,
+}
+
+/// How far off the simulation was for a single unit.
+#[derive(Clone, Debug, PartialEq, Tabled)]
+pub struct UnitCalibration {
+    pub unit: String,
+    pub predicted_start: Duration,
+    pub actual_start: Duration,
+    /// How much later the unit started in reality, in seconds; negative if it started earlier.
+    #[tabled(display_with = "display_seconds")]
+    pub drift: f64,
+    /// How much longer the unit waited after its last dependency had finished in reality, in seconds.
+    #[tabled(display_with = "display_seconds")]
+    pub extra_wait: f64,
+}
+
+pub(crate) fn display_seconds(seconds: &f64) -> String {
+    format!("{seconds:+.3}s")
+}
+
+/// Compares a `predicted` schedule of a scenario with a given `label` against the `actual` schedule of the recorded
+/// build. Units are reported in order of their extra wait, the longest first.
+pub fn calibrate(
+    label: String,
+    predicted: &[ScheduledTask],
+    actual: &[ScheduledTask],
+    deps: &DependencyQueueBuilder,
+) -> (Calibration, Vec<UnitCalibration>) {
+    let by_artifact = |schedule: &'_ [ScheduledTask]| -> BTreeMap<Artifact, (u64, u64)> {
+        schedule
+            .iter()
+            .map(|task| (task.artifact.clone(), (task.start, task.end)))
+            .collect()
+    };
+    let predicted_times = by_artifact(predicted);
+    let actual_times = by_artifact(actual);
+    // Time between the end of the last dependency of a unit and its start.
+    let wait = |times: &BTreeMap<Artifact, (u64, u64)>, artifact: &Artifact, start: u64| {
+        let ready_at = deps
+            .dep_map
+            .get(artifact)
+            .into_iter()
+            .flatten()
+            .filter_map(|dep| times.get(dep).map(|(_, end)| *end))
+            .max()
+            .unwrap_or_default();
+        start.saturating_sub(ready_at) as f64 / 1000.
+    };
+    let mut units: Vec<UnitCalibration> = predicted_times
+        .iter()
+        .filter_map(|(artifact, &(predicted_start, _))| {
+            let &(actual_start, _) = actual_times.get(artifact)?;
+            Some(UnitCalibration {
+                unit: artifact.to_string(),
+                predicted_start: Duration::from_millis(predicted_start),
+                actual_start: Duration::from_millis(actual_start),
+                drift: (actual_start as f64 - predicted_start as f64) / 1000.,
+                extra_wait: wait(&actual_times, artifact, actual_start)
+                    - wait(&predicted_times, artifact, predicted_start),
+            })
+        })
+        .collect();
+    units.sort_by(|a, b| b.extra_wait.total_cmp(&a.extra_wait));
+    let makespan = |schedule: &[ScheduledTask]| {
+        schedule
+            .iter()
+            .map(|task| task.end)
+            .max()
+            .unwrap_or_default()
+    };
+    let (predicted_makespan, actual_makespan) = (makespan(predicted), makespan(actual));
+    let calibration = Calibration {
+        label,
+        predicted_makespan: Duration::from_millis(predicted_makespan),
+        actual_makespan: Duration::from_millis(actual_makespan),
+        drift: (actual_makespan as f64 - predicted_makespan as f64) / 1000.,
+        extra_wait: units.iter().map(|unit| unit.extra_wait.max(0.)).sum(),
+        units: units.len(),
+    };
+    (calibration, units)
+}
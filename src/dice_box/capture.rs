@@ -0,0 +1,148 @@
+//! Capturing real builds, so that simulated schedules can be checked against what Cargo actually did.
+//!
+//! Cargo reports timings of a unit only once it's done, so the start of each unit is inferred from the time its
+//! timings were reported at and its duration. Inferred start times are added to the timings as `start` (in
+//! seconds), so that the real build order can be simulated later on.
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Command, Stdio};
+use std::time::Instant;
+
+use anyhow::{bail, Result};
+use tabled::Tabled;
+
+use crate::{
+    artifact::{Artifact, ArtifactType},
+    error::InputError,
+    package::group_by_package,
+    runner::{Duration, ScheduledTask},
+    timings::{node_type, parse_started, TimingInfo},
+};
+
+/// Runs `cargo +nightly build --timings=json` with `num_threads` jobs and extra `args`, returning the observed
+/// schedule. Output of Cargo is copied to `output` along with start times of units, so that it can be used as a
+/// timings file later on.
+pub fn capture(
+    num_threads: usize,
+    args: &[String],
+    output: &mut impl Write,
+) -> Result<Vec<ScheduledTask>> {
+    let mut child = Command::new("cargo")
+        .args(["+nightly", "build", "--timings=json", "-Zunstable-options"])
+        .args(["-j", &num_threads.to_string()])
+        .args(args)
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let start = Instant::now();
+    let mut schedule = vec![];
+    for line in BufReader::new(child.stdout.take().unwrap()).lines() {
+        let line = line?;
+        let timing = line
+            .starts_with('{')
+            .then(|| serde_json::from_str::<TimingInfo>(&line).ok())
+            .flatten();
+        let Some(timing) = timing else {
+            writeln!(output, "{line}")?;
+            continue;
+        };
+        let end = start.elapsed().as_millis() as u64;
+        let begin = end.saturating_sub((timing.duration * 1000.) as u64);
+        let mut recorded: serde_json::Map<String, serde_json::Value> = serde_json::from_str(&line)?;
+        recorded.insert("start".into(), (begin as f64 / 1000.).into());
+        writeln!(output, "{}", serde_json::to_string(&recorded)?)?;
+        push_tasks(&mut schedule, &timing, begin, end);
+    }
+    if !child.wait()?.success() {
+        bail!("cargo build failed");
+    }
+    schedule.sort_by_key(|task| task.start);
+    Ok(schedule)
+}
+
+/// Reads the schedule of a recorded build off a timings file with start times, as saved by `capture`. Empty for
+/// timings without start times.
+pub fn parse_observed_schedule(contents: &str) -> Result<Vec<ScheduledTask>, InputError> {
+    let mut schedule = vec![];
+    for (start, timing) in parse_started(contents)? {
+        let begin = (start * 1000.).round() as u64;
+        let end = begin + (timing.duration * 1000.).round() as u64;
+        push_tasks(&mut schedule, &timing, begin, end);
+    }
+    schedule.sort_by_key(|task| task.start);
+    Ok(schedule)
+}
+
+/// Adds units of a timing that ran from `begin` until `end` to `schedule`; a library with an rmeta time is split
+/// into its metadata and codegen.
+fn push_tasks(schedule: &mut Vec<ScheduledTask>, timing: &TimingInfo, begin: u64, end: u64) {
+    let typ = node_type(&timing.mode, &timing.target);
+    let artifact = |typ| Artifact {
+        typ,
+        package_id: timing.package_id.clone(),
+    };
+    match timing.rmeta_time {
+        Some(rmeta_time) if typ == ArtifactType::Metadata => {
+            let metadata_end = begin + (rmeta_time * 1000.) as u64;
+            schedule.push(ScheduledTask {
+                artifact: artifact(ArtifactType::Metadata),
+                start: begin,
+                end: metadata_end,
+            });
+            schedule.push(ScheduledTask {
+                artifact: artifact(ArtifactType::Codegen),
+                start: metadata_end,
+                end,
+            });
+        }
+        _ => schedule.push(ScheduledTask {
+            artifact: artifact(typ),
+            start: begin,
+            end,
+        }),
+    }
+}
+
+/// Runs `cargo +nightly build --unit-graph` with extra `args`, returning the unit graph. Nothing is built.
+pub fn capture_unit_graph(args: &[String]) -> Result<String> {
+    let output = Command::new("cargo")
+        .args(["+nightly", "build", "--unit-graph", "-Zunstable-options"])
+        .args(args)
+        .stderr(Stdio::inherit())
+        .output()?;
+    if !output.status.success() {
+        bail!("cargo build --unit-graph failed");
+    }
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+/// Predicted and actual start time of a package.
+#[derive(Clone, Debug, PartialEq, Tabled)]
+pub struct StartTimeError {
+    pub package: String,
+    pub predicted: Duration,
+    pub actual: Duration,
+    pub error: Duration,
+}
+
+/// Compares start times of packages built in both `predicted` and `actual` schedules, biggest errors first.
+pub fn start_time_errors(
+    predicted: &[ScheduledTask],
+    actual: &[ScheduledTask],
+) -> Vec<StartTimeError> {
+    let actual = group_by_package(actual);
+    let mut errors: Vec<StartTimeError> = group_by_package(predicted)
+        .into_iter()
+        .filter_map(|predicted| {
+            let actual = actual
+                .iter()
+                .find(|actual| actual.package == predicted.package)?;
+            Some(StartTimeError {
+                error: Duration(predicted.start.0.abs_diff(actual.start.0)),
+                package: predicted.package,
+                predicted: predicted.start,
+                actual: actual.start,
+            })
+        })
+        .collect();
+    errors.sort_by(|a, b| b.error.partial_cmp(&a.error).unwrap());
+    errors
+}
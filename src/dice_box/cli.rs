@@ -1,6 +1,11 @@
 use std::path::PathBuf;
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
+
+use crate::{
+    ArtifactType, MachineProfile, NHintsParams, NHintsTieBreak, OutputFormat, ThreadPool,
+    ValueMetric, Weights,
+};
 
 /// Dice_box - a testing ground for better Cargo scheduler.
 #[derive(Parser)]
@@ -18,4 +23,822 @@ pub struct Cli {
     /// Whether to output timings for builds.
     #[clap(short, long)]
     pub timings: bool,
+
+    /// Charge the real time taken by hint providers to make decisions as simulated scheduling overhead.
+    #[clap(short, long)]
+    pub budgeted: bool,
+
+    /// Simulate all scenarios a second time, one after another, and fail if any result differs from the parallel
+    /// run. Meant as a self-check in CI.
+    #[clap(long, conflicts_with = "budgeted")]
+    pub verify_determinism: bool,
+
+    /// Seed used to shuffle independent units in the FIFO baseline. Without it, units are picked in queue order.
+    #[clap(long)]
+    pub fifo_seed: Option<u64>,
+
+    /// Number of the longest units that the N-Hints scheduler orders up front.
+    #[clap(long, default_value_t = crate::hints::DEFAULT_N_HINTS)]
+    pub n_hints: usize,
+
+    /// How the N-Hints scheduler picks between ready units that are equally close to the front of its list.
+    #[clap(long, value_enum, default_value_t)]
+    pub n_hints_tie_break: NHintsTieBreak,
+
+    /// Make the N-Hints scheduler weigh metadata units without their codegen.
+    #[clap(long)]
+    pub n_hints_separate_codegen: bool,
+
+    /// Also simulate N-Hints with each of these values of N (e.g. `25,50,100`), and report the makespan for each.
+    #[clap(long, value_delimiter = ',')]
+    pub n_hints_sweep: Vec<usize>,
+
+    /// Also simulate a lookahead scheduler that, at each decision, simulates the rest of the build for this many of
+    /// the most promising candidates and picks the one that finishes it the earliest.
+    #[clap(long)]
+    pub rollout: Option<usize>,
+
+    /// Also simulate a scheduler scoring ready units by a weighted sum of signals, with a given weight as
+    /// `<duration|transitive-dependants|critical-path|unlocked>=W`. Weights that are not given are 0, or taken from
+    /// `--weights`.
+    #[clap(long, value_parser = parse_weight)]
+    pub weight: Vec<(String, f64)>,
+
+    /// JSON file with weights of the weighted scheduler, e.g. as printed by the `tune-weights` subcommand.
+    #[clap(long)]
+    pub weights: Option<PathBuf>,
+
+    /// Also simulate a critical path scheduler that may leave a thread idle for up to this many seconds, holding it
+    /// for a more critical unit that is about to become ready.
+    #[clap(long)]
+    pub reservation: Option<f64>,
+
+    /// Also simulate a schedule following an ordered list of units from a file: either JSON as written by
+    /// `--emit-hints`, or plain text with one `<package>:<type>` line per unit (e.g. `syn:metadata`). Whenever a
+    /// ready unit is not on the list, Cargo's current algorithm decides.
+    #[clap(long)]
+    pub hints_file: Option<PathBuf>,
+
+    /// Also simulate the order in which units started in the real build, to compare Cargo against the other
+    /// schedulers in the same table. Requires start times in the timings file, as saved by `verify --save`.
+    #[clap(long)]
+    pub real_build_order: bool,
+
+    /// Compare the simulation of Cargo's own scheduler against the recorded build, unit by unit, to tell which costs
+    /// that aren't simulated matter. Requires start times in the timings file, as saved by `verify --save`.
+    #[clap(long)]
+    pub calibrate: bool,
+
+    /// Also simulate a schedule computed by another tool or recorded from a real build system: a JSON file saying
+    /// when and on which thread each unit starts. It is checked against the unit graph first, and then simulated
+    /// with the same durations as every other scenario.
+    #[clap(long)]
+    pub external_schedule: Option<PathBuf>,
+
+    /// Also simulate a scheduler written as a Rhai script defining `suggest_next(candidates, context)`; see the
+    /// `script` module for what the script gets to see. Requires building with `--features scripting`.
+    #[cfg(feature = "scripting")]
+    #[clap(long)]
+    pub script_hints: Option<PathBuf>,
+
+    /// Also simulate a scheduler compiled to a WebAssembly plugin; see the `plugin` module for the interface it has
+    /// to implement. Requires building with `--features plugins`.
+    #[cfg(feature = "plugins")]
+    #[clap(long)]
+    pub plugin: Option<PathBuf>,
+
+    /// Also simulate an adversarial scheduler that always picks the least useful ready unit, and report the spread
+    /// between the best and the worst scenario. This bounds how much scheduling decisions can matter for a project.
+    #[clap(long)]
+    pub worst_case: bool,
+
+    /// Also simulate a scheduler picking ready units at random this many times, and report the spread of its
+    /// makespans. This shows how much scheduling decisions matter for a given graph at all.
+    #[clap(long)]
+    pub random_runs: Option<usize>,
+
+    /// Seed of the first random run; subsequent runs use consecutive seeds.
+    #[clap(long, default_value_t = 0, requires = "random_runs")]
+    pub random_seed: u64,
+
+    /// JSON file describing the environment the build was captured in (toolchain, flags, environment variables),
+    /// as written by `cargo dicebox`. It's included in reports and recorded traces.
+    #[clap(long)]
+    pub environment: Option<PathBuf>,
+
+    /// JSON file with constraints on when and where particular packages can be built.
+    #[clap(long)]
+    pub constraints: Option<PathBuf>,
+
+    /// JSON file with an ordered list of what-if rewrites (scaling durations, splitting crates, removing
+    /// dependencies, caching build scripts, disabling pipelining) to apply to the build before simulating it.
+    #[clap(long)]
+    pub transforms: Option<PathBuf>,
+
+    /// Treat units that took less than this many seconds as if they took this long (e.g. 0.05), to account for
+    /// process overhead. Applied after `--transforms`, as a `clamp` rewrite.
+    #[clap(long)]
+    pub min_duration: Option<f64>,
+
+    /// Treat units that took more than this many seconds as if they took this long, to study the structure of the
+    /// graph without a few outliers dominating it. Applied after `--transforms`, as a `clamp` rewrite.
+    #[clap(long)]
+    pub max_duration: Option<f64>,
+
+    /// Simulate an incremental rebuild after touching this package (can be given multiple times): only units of
+    /// dirty packages and their dependants are rebuilt, while the rest of the build is fresh. Applied after
+    /// `--transforms`, as a `dirty` rewrite.
+    #[clap(long)]
+    pub dirty: Vec<String>,
+
+    /// Report priority inversions, judging the value of each unit with a given metric.
+    #[clap(long, value_enum)]
+    pub inversions: Option<ValueMetric>,
+
+    /// Also simulate the dev loop of `cargo check` followed by `cargo build` under each scheduler.
+    #[clap(long)]
+    pub dev_loop: bool,
+
+    /// Target triple of the simulated build. Units built for other targets are dropped from the unit graph.
+    #[clap(long)]
+    pub platform: Option<String>,
+
+    /// Feature (as `package/feature`) that is not enabled in the simulated build, for unit graphs captured with
+    /// `--all-features`. Only features enabling optional dependencies can be pruned.
+    #[clap(long)]
+    pub disable_feature: Vec<String>,
+
+    /// Directory to cache expensive preprocessing results and decisions of simulated scenarios in, so that they can
+    /// be reused by subsequent runs on the same inputs.
+    #[clap(long)]
+    pub cache_dir: Option<PathBuf>,
+
+    /// Write a waterfall decomposition of the makespan of the best scenario to `<PATH>.csv` and `<PATH>.svg`
+    /// (`waterfall.csv` and `waterfall.svg` unless given).
+    #[clap(long, value_name = "PATH", num_args = 0..=1, default_missing_value = "waterfall")]
+    pub waterfall: Option<PathBuf>,
+
+    /// Write hints following the order of the best scenario to a given JSON file, for consumption by a build system
+    /// such as a patched Cargo. Each entry names a unit by its package id and type, with a priority (higher is picked
+    /// first) and a confidence estimated over jitter trials.
+    #[clap(long)]
+    pub emit_hints: Option<PathBuf>,
+
+    /// Number of simulations with perturbed durations to estimate confidences of emitted hints with. Each trial
+    /// simulates the build once more for every emitted hint, so large graphs take a while.
+    #[clap(long, default_value_t = 10, requires = "emit_hints")]
+    pub jitter_trials: usize,
+
+    /// Largest relative change of a duration in jitter trials (e.g. 0.2 for ±20%), below 1 so that durations stay
+    /// positive.
+    #[clap(long, default_value_t = 0.2, requires = "emit_hints", value_parser = parse_jitter)]
+    pub jitter: f64,
+
+    /// Write a time-compressed animation of a scenario (by its index in the results table) to `animation.svg`.
+    #[clap(long)]
+    pub animate: Option<usize>,
+
+    /// Write an interactive editor of a scenario (by its index in the results table) to `da-vinci.html`, in which
+    /// units can be dragged around to see how that changes the rest of the build.
+    #[clap(long)]
+    pub da_vinci: Option<usize>,
+
+    /// Graphs with more units than this are reported in summarized mode: HTML timings collapse external packages
+    /// and keep only the longest units, animations keep only the longest units and no editor is written.
+    #[clap(long, default_value_t = 5000)]
+    pub report_max_units: usize,
+
+    /// Number of units (longest first) drawn in summarized reports.
+    #[clap(long, default_value_t = 500)]
+    pub report_top_units: usize,
+
+    /// Number of points graphs over time are down-sampled to in summarized reports.
+    #[clap(long, default_value_t = 2000)]
+    pub report_max_points: usize,
+
+    /// Length of the animation written with `--animate`, in seconds.
+    #[clap(long, default_value_t = 10., requires = "animate")]
+    pub animation_length: f64,
+
+    /// Limit on the number of concurrently running units of a given kind, as `<compile|link|build-script>=N`.
+    #[clap(long, value_parser = parse_pool_limit)]
+    pub pool: Vec<(ThreadPool, usize)>,
+
+    /// Limit on the number of link units running at once, as linkers are memory- and IO-bound; same as
+    /// `--pool link=N`. The results table shows how long the limit was the bottleneck.
+    #[clap(long)]
+    pub max_concurrent_links: Option<usize>,
+
+    /// Delay between a unit becoming ready and it being started, in milliseconds, modeling Cargo's main loop
+    /// draining messages and checking fingerprints. Also reports how much of each makespan is due to it.
+    #[clap(long)]
+    pub dispatch_latency_ms: Option<u64>,
+
+    /// Class of cores of the machine, as `<count>x<speed>` with the speed relative to the machine timings were
+    /// recorded on (e.g. `--cores 8x1.0 --cores 4x0.6` for 8 performance and 4 efficiency cores). Counts must add
+    /// up to the number of threads.
+    #[clap(long, value_parser = parse_core_class)]
+    pub cores: Vec<(usize, f64)>,
+
+    /// Largest number of threads rustc can run the frontend of a unit on (`-Zthreads`). The scheduler decides how
+    /// many each unit gets.
+    #[clap(long)]
+    pub rustc_threads: Option<usize>,
+
+    /// Fraction of the frontend work that parallelizes with `--rustc-threads`, for Amdahl's law.
+    #[clap(
+        long,
+        default_value_t = 0.5,
+        requires = "rustc_threads",
+        value_parser = parse_parallel_fraction
+    )]
+    pub parallel_fraction: f64,
+
+    /// Run codegen of each unit on as many idle threads as it has codegen units, rather than on one.
+    #[clap(long)]
+    pub parallel_codegen: bool,
+
+    /// Number of codegen units of units built with a given profile, as `<profile>=N`, overriding the unit graph
+    /// and Cargo's defaults.
+    #[clap(long, value_parser = parse_codegen_units, requires = "parallel_codegen")]
+    pub codegen_units: Vec<(String, usize)>,
+
+    /// Disk IO bandwidth of the machine, in links running at once; other units take up less of it (build scripts
+    /// half as much as a link, codegen a quarter, metadata a tenth). Also reports how much time is lost to IO
+    /// contention.
+    #[clap(long)]
+    pub io_capacity: Option<f64>,
+
+    /// IO bandwidth taken up by units of a given type, as `<type>=<share of a link>`, e.g. `codegen=0.5`.
+    #[clap(long, value_parser = parse_io_consumption, requires = "io_capacity")]
+    pub io_consumption: Vec<(ArtifactType, f64)>,
+
+    /// Thermal throttling curve, as comma-separated `<sustained load>:<speed>` points (e.g. `4:1,8:0.7` for a machine
+    /// that keeps full speed with up to 4 busy threads and slows down to 70% with 8). Sustained load is the number of
+    /// busy threads averaged over `--throttling-window`.
+    #[clap(long, value_parser = parse_throttling_curve)]
+    pub throttling: Option<ThrottlingCurve>,
+
+    /// Time over which load is averaged for `--throttling`, in seconds.
+    #[clap(long, default_value_t = 30., requires = "throttling")]
+    pub throttling_window: f64,
+
+    /// Fixed cost of spawning the process of each unit, in milliseconds or as a preset for a platform (`linux`,
+    /// `macos` or `windows`), modeling process creation, rustc startup and wrapper scripts.
+    #[clap(long, value_parser = parse_spawn_overhead)]
+    pub spawn_overhead: Option<u64>,
+
+    /// Number of jobserver tokens shared by Cargo and rustc. Units need a token to run, on top of a thread, and
+    /// hold it until they finish; libraries keep theirs from metadata through codegen.
+    #[clap(long)]
+    pub jobserver_tokens: Option<usize>,
+
+    /// Time it takes to acquire a jobserver token other than Cargo's implicit one, in milliseconds.
+    #[clap(long, default_value_t = 0, requires = "jobserver_tokens")]
+    pub token_latency: u64,
+
+    /// JSON file with external events (e.g. cores becoming available or being taken up) to inject into the
+    /// simulation timeline.
+    #[clap(long)]
+    pub events: Option<PathBuf>,
+
+    /// Number of cores available over time, as comma-separated `<seconds>:<cores>` points (e.g. `0:4,60:16` for a
+    /// CI runner that scales up after a minute). Cores are only taken away from units that have yet to start.
+    #[clap(long, value_parser = parse_core_timeline)]
+    pub core_timeline: Option<CoreTimeline>,
+
+    /// Print a per-package summary of the build for each scenario.
+    #[clap(long)]
+    pub packages: bool,
+
+    /// Timings file of a `cargo test` run following the build, to simulate a build & test pipeline with.
+    #[clap(long, requires = "test_unit_graph")]
+    pub test_timings: Option<PathBuf>,
+
+    /// Unit graph file of a `cargo test` run following the build, obtained with e.g.
+    /// `cargo +nightly test --no-run --unit-graph`.
+    #[clap(long, requires = "test_timings")]
+    pub test_unit_graph: Option<PathBuf>,
+
+    /// Additional scheduler to simulate, by its name in the hint provider registry (e.g. `cargo`, `n-hints` or
+    /// `fifo`).
+    #[clap(long)]
+    pub scheduler: Vec<String>,
+
+    /// Report how much slack each scheduler left, by compacting its schedule without changing the order of units.
+    #[clap(long)]
+    pub compaction: bool,
+
+    /// Rank scenarios by the progress they make in the first this many seconds of the build (workspace members
+    /// built, share of work done), rather than by makespan.
+    #[clap(long)]
+    pub horizon: Option<f64>,
+
+    /// Also simulate each scenario on each of these numbers of threads (e.g. `2,4,8,16`), and fit Amdahl's law and
+    /// the Universal Scalability Law to the speedups over a single thread, which is always simulated too.
+    #[clap(long, value_delimiter = ',')]
+    pub thread_sweep: Vec<usize>,
+
+    /// Find the smallest number of threads on which each scenario's makespan is within this many percent (e.g. 5) of
+    /// its makespan with unlimited threads, to size CI runners. Found by binary search, which assumes that adding
+    /// threads never makes a build slower.
+    #[clap(long)]
+    pub min_cores: Option<f64>,
+
+    /// Machine to estimate the cost of each scenario on, as `<cores>=<$ per core-hour>`. Scenarios are simulated
+    /// with as many threads as the machine has cores, and the cost-vs-time Pareto frontier is reported. Optionally
+    /// followed by `,<µs per running unit>` of bookkeeping that Cargo's main loop does before each decision (e.g.
+    /// `64=0.05,40`), which keeps simulations on many cores from scaling linearly.
+    #[clap(long)]
+    pub machine: Vec<MachineProfile>,
+
+    /// Index of the scenario, as listed in the results table, that other scenarios are compared against in the
+    /// `vs_baseline` columns. Cargo Hints by default.
+    #[clap(long, default_value_t = 0)]
+    pub baseline: usize,
+
+    /// Format of the results of the comparison. With `json`, the results table is replaced by a JSON object with all
+    /// of its columns, along with the `--worst-case`, `--thread-sweep` and `--min-cores` reports. Other requested
+    /// reports are still printed as text, to stderr if the JSON goes to stdout.
+    #[clap(long, value_enum, default_value_t)]
+    pub output: OutputFormat,
+
+    /// Write the results of the comparison in the format of `--output` to a given file instead of stdout.
+    #[clap(long)]
+    pub output_file: Option<PathBuf>,
+
+    /// Report how many of the allotted threads each scenario used. For scenarios that never used more than 60% of
+    /// them, also find the smallest number of threads that achieves the same makespan.
+    #[clap(long)]
+    pub utilization: bool,
+
+    /// Report which units were running during the last part of each build (10% unless given, e.g. 0.2 for 20%) and
+    /// how many threads sat idle meanwhile. That's where scheduling and restructuring the workspace help differently.
+    #[clap(long, num_args = 0..=1, default_missing_value = "0.1")]
+    pub tail: Option<f64>,
+
+    /// Report stretches of each build during which at most this many units were running (1 unless given), the
+    /// units that ran during them and how much thread time was left idle meanwhile. These are the tall poles that
+    /// serialize a build.
+    #[clap(long, num_args = 0..=1, default_missing_value = "1")]
+    pub serial_stretches: Option<usize>,
+
+    /// Report the units (10 unless given) that held up the rest of the build the most under Cargo's current
+    /// algorithm, by the thread time that sat idle while units depending on them were waiting.
+    #[clap(long, num_args = 0..=1, default_missing_value = "10")]
+    pub blame: Option<usize>,
+
+    /// Also simulate each scheduler with aging: units that were ready for this many decisions are picked first.
+    /// Reports the longest time a unit had to wait for a core in each scenario.
+    #[clap(long)]
+    pub aging: Option<usize>,
+
+    /// Log every decision each scenario consults its scheduler for to a given JSON Lines file: the units that could
+    /// have been started, how the scheduler scored them and the unit that was started.
+    #[clap(long)]
+    pub decision_log: Option<PathBuf>,
+
+    /// Record all scheduling decisions of the simulated builds to a given file, as JSON.
+    #[clap(long)]
+    pub record: Option<PathBuf>,
+
+    /// Replay scheduling decisions recorded with `--record`, instead of consulting the schedulers again. The rest
+    /// of the options have to match the ones used for recording, or the trace is rejected.
+    #[clap(long, conflicts_with = "record")]
+    pub replay: Option<PathBuf>,
+
+    /// Experimental: fraction of time saved by a codegen unit running on the same core right after its metadata
+    /// (e.g. 0.1), modeling warm caches. Also adds a scenario with a scheduler that tries to exploit it.
+    #[clap(long, value_parser = parse_affinity)]
+    pub affinity: Option<f64>,
+
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Print a machine-readable explanation of when units of a given package were built in each scenario.
+    Explain {
+        /// Name or full package id of the package to explain.
+        package: String,
+    },
+    /// Query the dependency graph: list units a package transitively depends on, or those depending on it, or
+    /// the ones it shares with another package, or the shortest dependency chain between two packages.
+    Deps {
+        /// Name or full package id of the package to query.
+        package: String,
+        /// List units that transitively depend on the package instead.
+        #[clap(long, conflicts_with_all = ["shared_with", "chain_to"])]
+        reverse: bool,
+        /// List dependencies the package shares with this one.
+        #[clap(long, conflicts_with = "chain_to")]
+        shared_with: Option<String>,
+        /// Show the shortest chain of dependencies between the package and this one, in either direction.
+        #[clap(long)]
+        chain_to: Option<String>,
+    },
+    /// Compare the order in which two scenarios complete transitive dependencies of a given package.
+    Compare {
+        /// Name or full package id of the target package.
+        package: String,
+        /// Index of the first scenario to compare, as listed in the results table.
+        #[clap(long, default_value_t = 0)]
+        left: usize,
+        /// Index of the second scenario to compare, as listed in the results table.
+        #[clap(long, default_value_t = 1)]
+        right: usize,
+    },
+    /// Split the makespan difference between two scenarios into the part due to different dequeue orders and the
+    /// part due to the same orders being placed differently (e.g. on a different number of threads), by replaying
+    /// both orders in both configurations.
+    Attribute {
+        /// Index of the first scenario, as listed in the results table.
+        #[clap(long, default_value_t = 0)]
+        left: usize,
+        /// Index of the second scenario, as listed in the results table.
+        #[clap(long, default_value_t = 1)]
+        right: usize,
+    },
+    /// Find the decisions of a scenario's hint provider that hurt the makespan the most: each decision is replayed
+    /// with every other candidate in its place, and the rest of the build scheduled by longest critical path first.
+    Audit {
+        /// Index of the scenario, as listed in the results table.
+        #[clap(long, default_value_t = 0)]
+        scenario: usize,
+        /// Number of decisions to report.
+        #[clap(long, default_value_t = 5)]
+        top: usize,
+    },
+    /// Predict the schedule of Cargo's current algorithm, then run the real build (in the current directory) and
+    /// compare the prediction with what actually happened. The build should start from a clean state.
+    Verify {
+        /// File to save timings of the real build to, so that they can be used for subsequent predictions.
+        #[clap(long)]
+        save: Option<PathBuf>,
+        /// Extra arguments to pass to `cargo build`.
+        #[clap(last = true)]
+        cargo_args: Vec<String>,
+    },
+    /// Suggest changes to the workspace (splitting crates, removing dependencies, caching build scripts) that would
+    /// shorten the build under Cargo's current algorithm, ranked by estimated savings.
+    Advise {
+        /// Maximum number of suggestions to print.
+        #[clap(long, default_value_t = 10)]
+        limit: usize,
+    },
+    /// Print the critical path of the build: the longest chain of dependent units, which no schedule can beat, along
+    /// with the share of the makespan under Cargo's current algorithm that each of its units takes.
+    CriticalPath,
+    /// Rank units by how much faster the build under Cargo's current algorithm would be if they compiled faster, to
+    /// find the crates worth optimizing first.
+    Optimize {
+        /// How much faster each unit gets, in percent of its duration; 100 makes it free.
+        #[clap(long, default_value_t = 100.)]
+        reduction: f64,
+        /// Maximum number of units to print.
+        #[clap(long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// Search for a better schedule with a genetic algorithm, and report how far each scheduler is from it.
+    Search {
+        /// Number of schedules in each generation.
+        #[clap(long, default_value_t = 50)]
+        population: usize,
+        #[clap(long, default_value_t = 100)]
+        generations: usize,
+        #[clap(long, default_value_t = 0)]
+        seed: u64,
+        /// File to write the best schedule to, in the format of `--record`, so that it can be replayed.
+        #[clap(long, default_value = "search.json")]
+        output: PathBuf,
+    },
+    /// Search for a better schedule by simulated annealing over priorities of units, and report how far each
+    /// scheduler is from it.
+    Anneal {
+        #[clap(long, default_value_t = 1000)]
+        iterations: usize,
+        /// Factor the temperature is multiplied by after each iteration.
+        #[clap(long, default_value_t = 0.995)]
+        cooling: f64,
+        #[clap(long, default_value_t = 0)]
+        seed: u64,
+        /// File to write the best schedule to, in the format of `--record`, so that it can be replayed.
+        #[clap(long, default_value = "anneal.json")]
+        output: PathBuf,
+    },
+    /// Search for the weights of the weighted scheduler that result in the shortest build of this project.
+    TuneWeights {
+        /// Number of steps between 0 and 1 to try for each weight, in a grid search.
+        #[clap(long, default_value_t = 4)]
+        steps: usize,
+        /// Try this many random weights instead of a grid.
+        #[clap(long)]
+        samples: Option<usize>,
+        #[clap(long, default_value_t = 0, requires = "samples")]
+        seed: u64,
+        /// Maximum number of weights to print.
+        #[clap(long, default_value_t = 10)]
+        limit: usize,
+    },
+    /// Simulate N-Hints with every combination of list sizes, tie-break policies and codegen bundling, and print the
+    /// best configurations.
+    TuneNHints {
+        /// List sizes to try.
+        #[clap(long, value_delimiter = ',', default_value = "25,50,75,100,150")]
+        n: Vec<usize>,
+        /// Maximum number of configurations to print.
+        #[clap(long, default_value_t = 10)]
+        limit: usize,
+    },
+    /// Search for the best way to share the cores of one machine between this build and other, concurrent ones
+    /// (e.g. CI jobs), minimizing the makespan of the slowest one.
+    Colocate {
+        /// Timings and unit graph files of another build running at the same time.
+        #[clap(long = "job", num_args = 2, value_names = ["TIMINGS_FILE", "UNIT_GRAPH_FILE"])]
+        jobs: Vec<PathBuf>,
+        /// Number of steps of simulated annealing.
+        #[clap(long, default_value_t = 200)]
+        iterations: usize,
+        #[clap(long, default_value_t = 0)]
+        seed: u64,
+    },
+    /// Compare the build against one with a different unit graph (e.g. after removing a dependency): list units and
+    /// dependencies that were added or removed, and simulate both graphs with the same schedulers (`--scheduler`,
+    /// or all registered ones). Constraints and transforms only apply to this build.
+    Diff {
+        /// Unit graph of the changed build.
+        unit_graph: PathBuf,
+        /// Timings of the changed build, needed if it has units that this build doesn't.
+        #[clap(long)]
+        timings: Option<PathBuf>,
+    },
+    /// Write anonymized copies of the inputs, with durations rounded up to buckets, and report how much that changes
+    /// the simulated makespans.
+    Anonymize {
+        /// Bucket size, in seconds.
+        #[clap(long, default_value_t = 1.0, value_parser = parse_bucket)]
+        bucket: f64,
+        /// Also replace names of packages with symbolic ones.
+        #[clap(long)]
+        symbolic: bool,
+        /// Directory to write anonymized `timings.json` and `unit-graph.json` to.
+        #[clap(long, default_value = "anonymized")]
+        output: PathBuf,
+    },
+    /// Apply a hypothetical change to the build, and simulate the build before and after it with the same
+    /// schedulers (`--scheduler`, or all registered ones) to predict its payoff.
+    WhatIf {
+        #[clap(subcommand)]
+        change: WhatIf,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum WhatIf {
+    /// Remove dependencies of one package on another.
+    RemoveDep {
+        /// Name or full package id of the dependant package.
+        from: String,
+        /// Name or full package id of the package it should no longer depend on.
+        to: String,
+    },
+    /// Split the library of a crate into smaller crates that can be built in parallel. Dependants of the crate
+    /// depend on all of the parts, and each part has all the dependencies of the crate.
+    Split {
+        /// Name or full package id of the crate to split.
+        package: String,
+        /// Number of equally sized parts.
+        #[clap(long, default_value_t = 2)]
+        parts: usize,
+        /// Shares of the crate's build time that go to each part, e.g. `2:1:1` for three parts, the first of which
+        /// is as big as the other two together.
+        #[clap(long, value_parser = parse_split_ratio, conflicts_with = "parts")]
+        ratio: Option<SplitRatio>,
+    },
+    /// Merge two crates into one, which takes as long as both of them together and has the dependencies and
+    /// dependants of both.
+    Merge {
+        /// Name or full package id of the crate that the other one is merged into, and whose name the merged crate
+        /// keeps.
+        into: String,
+        /// Name or full package id of the crate to merge.
+        package: String,
+    },
+    /// Make metadata and codegen of a crate faster by a factor, e.g. 2 for a crate that compiles twice as fast.
+    Speedup {
+        /// Name or full package id of the crate to speed up.
+        package: String,
+        factor: f64,
+    },
+}
+
+fn parse_bucket(s: &str) -> Result<f64, String> {
+    let bucket: f64 = s
+        .parse()
+        .map_err(|e| format!("invalid bucket size `{s}`: {e}"))?;
+    if !bucket.is_finite() || bucket <= 0. {
+        return Err(format!("bucket size must be positive, got `{s}`"));
+    }
+    Ok(bucket)
+}
+
+fn parse_jitter(s: &str) -> Result<f64, String> {
+    let jitter: f64 = s
+        .parse()
+        .map_err(|e| format!("invalid jitter `{s}`: {e}"))?;
+    if !(0. ..1.).contains(&jitter) {
+        return Err(format!("jitter must be at least 0 and below 1, got `{s}`"));
+    }
+    Ok(jitter)
+}
+
+fn parse_affinity(s: &str) -> Result<f64, String> {
+    let affinity: f64 = s
+        .parse()
+        .map_err(|e| format!("invalid affinity `{s}`: {e}"))?;
+    if !(0. ..1.).contains(&affinity) {
+        return Err(format!(
+            "affinity must be at least 0 and below 1, got `{s}`"
+        ));
+    }
+    Ok(affinity)
+}
+
+fn parse_parallel_fraction(s: &str) -> Result<f64, String> {
+    let fraction: f64 = s
+        .parse()
+        .map_err(|e| format!("invalid fraction `{s}`: {e}"))?;
+    if !(0. ..=1.).contains(&fraction) {
+        return Err(format!("fraction must be between 0 and 1, got `{s}`"));
+    }
+    Ok(fraction)
+}
+
+fn parse_pool_limit(s: &str) -> Result<(ThreadPool, usize), String> {
+    let (pool, limit) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected `<pool>=<limit>`, got `{s}`"))?;
+    let limit: usize = limit
+        .parse()
+        .map_err(|e| format!("invalid limit `{limit}`: {e}"))?;
+    if limit == 0 {
+        return Err("limit must be at least 1, or units of the pool could never run".into());
+    }
+    Ok((pool.parse()?, limit))
+}
+
+fn parse_core_class(s: &str) -> Result<(usize, f64), String> {
+    let (count, speed) = s
+        .split_once('x')
+        .ok_or_else(|| format!("expected `<count>x<speed>`, got `{s}`"))?;
+    let count = count
+        .parse()
+        .map_err(|e| format!("invalid count `{count}`: {e}"))?;
+    let speed: f64 = speed
+        .parse()
+        .map_err(|e| format!("invalid speed `{speed}`: {e}"))?;
+    if speed <= 0. {
+        return Err(format!("speed must be positive, got `{speed}`"));
+    }
+    Ok((count, speed))
+}
+
+fn parse_codegen_units(s: &str) -> Result<(String, usize), String> {
+    let (profile, count) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected `<profile>=<count>`, got `{s}`"))?;
+    let count: usize = count
+        .parse()
+        .map_err(|e| format!("invalid count `{count}`: {e}"))?;
+    if count == 0 {
+        return Err(
+            "count must be at least 1, or codegen of the profile would have nothing to run".into(),
+        );
+    }
+    Ok((profile.to_owned(), count))
+}
+
+fn parse_io_consumption(s: &str) -> Result<(ArtifactType, f64), String> {
+    let (typ, consumption) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected `<type>=<consumption>`, got `{s}`"))?;
+    let consumption: f64 = consumption
+        .parse()
+        .map_err(|e| format!("invalid consumption `{consumption}`: {e}"))?;
+    if !consumption.is_finite() || consumption < 0. {
+        return Err(format!(
+            "consumption must be a finite, non-negative number, got `{consumption}`"
+        ));
+    }
+    Ok((typ.parse()?, consumption))
+}
+
+/// Points of a core availability timeline, wrapped so that clap doesn't take them for a list of values.
+#[derive(Clone, Debug)]
+pub struct CoreTimeline(pub Vec<(f64, usize)>);
+
+fn parse_core_timeline(s: &str) -> Result<CoreTimeline, String> {
+    s.split(',')
+        .map(|point| {
+            let (at, cores) = point
+                .split_once(':')
+                .ok_or_else(|| format!("expected `<seconds>:<cores>`, got `{point}`"))?;
+            let at = at
+                .parse()
+                .map_err(|e| format!("invalid time `{at}`: {e}"))?;
+            let cores = cores
+                .parse()
+                .map_err(|e| format!("invalid number of cores `{cores}`: {e}"))?;
+            Ok((at, cores))
+        })
+        .collect::<Result<_, String>>()
+        .map(CoreTimeline)
+}
+
+/// Shares of parts of a split crate, wrapped so that clap doesn't take them for a list of values.
+#[derive(Clone, Debug)]
+pub struct SplitRatio(pub Vec<f64>);
+
+/// Shares are checked when the split is applied, as they are for splits in a transforms file.
+fn parse_split_ratio(s: &str) -> Result<SplitRatio, String> {
+    s.split(':')
+        .map(|share| {
+            share
+                .parse::<f64>()
+                .map_err(|e| format!("invalid share `{share}`: {e}"))
+        })
+        .collect::<Result<_, String>>()
+        .map(SplitRatio)
+}
+
+/// Points of a throttling curve, wrapped so that clap doesn't take them for a list of values.
+#[derive(Clone, Debug)]
+pub struct ThrottlingCurve(pub Vec<(f64, f64)>);
+
+fn parse_throttling_curve(s: &str) -> Result<ThrottlingCurve, String> {
+    let mut curve = s
+        .split(',')
+        .map(|point| {
+            let (load, speed) = point
+                .split_once(':')
+                .ok_or_else(|| format!("expected `<load>:<speed>`, got `{point}`"))?;
+            let load: f64 = load
+                .parse()
+                .map_err(|e| format!("invalid load `{load}`: {e}"))?;
+            let speed: f64 = speed
+                .parse()
+                .map_err(|e| format!("invalid speed `{speed}`: {e}"))?;
+            if speed <= 0. {
+                return Err(format!("speed must be positive, got `{speed}`"));
+            }
+            Ok((load, speed))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    curve.sort_by(|a, b| a.0.total_cmp(&b.0));
+    Ok(ThrottlingCurve(curve))
+}
+
+/// Longest spawn overhead accepted, in milliseconds: an hour, far past anything a real platform takes.
+const MAX_SPAWN_OVERHEAD: u64 = 60 * 60 * 1000;
+
+fn parse_spawn_overhead(s: &str) -> Result<u64, String> {
+    let overhead = match s {
+        "linux" => 5,
+        "macos" => 15,
+        "windows" => 40,
+        _ => s.parse().map_err(|e| {
+            format!(
+                "expected milliseconds or one of `linux`, `macos` and `windows`, got `{s}`: {e}"
+            )
+        })?,
+    };
+    if overhead > MAX_SPAWN_OVERHEAD {
+        return Err(format!(
+            "overhead must be at most {MAX_SPAWN_OVERHEAD} ms (an hour), got `{s}`"
+        ));
+    }
+    Ok(overhead)
+}
+
+fn parse_weight(s: &str) -> Result<(String, f64), String> {
+    let (signal, weight) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected `<signal>=<weight>`, got `{s}`"))?;
+    let weight = weight
+        .parse()
+        .map_err(|e| format!("invalid weight `{weight}`: {e}"))?;
+    Weights::default().set(signal, weight)?;
+    Ok((signal.to_owned(), weight))
+}
+
+impl Cli {
+    /// Parameters of the N-Hints scheduler.
+    pub fn n_hints_params(&self) -> NHintsParams {
+        NHintsParams {
+            n: self.n_hints,
+            tie_break: self.n_hints_tie_break,
+            bundle_codegen: !self.n_hints_separate_codegen,
+        }
+    }
 }
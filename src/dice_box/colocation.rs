@@ -0,0 +1,220 @@
+//! Several builds sharing the cores of one machine, e.g. concurrent CI jobs.
+//!
+//! Each build starts with a fixed share of the cores. Once a build is done, its cores are handed over to the first
+//! build (in a given order of heirs) that is still running. Simulated annealing searches for the split of cores and
+//! the order of heirs that minimizes the makespan of the slowest build.
+use std::collections::BTreeMap;
+
+use tabled::Tabled;
+
+use crate::{
+    artifact::Artifact,
+    dependency_queue::DependencyQueueBuilder,
+    error::InputError,
+    events::ExternalEvent,
+    registry::HintProviderFactory,
+    rng::Rng,
+    runner::{Duration, Runner},
+    timings::TimingInfo,
+};
+
+pub struct Job {
+    pub label: String,
+    pub deps: DependencyQueueBuilder,
+    pub timings: BTreeMap<Artifact, TimingInfo>,
+}
+
+/// Division of cores between jobs.
+#[derive(Clone, Debug, PartialEq)]
+struct Allocation {
+    /// Number of cores each job starts with.
+    cores: Vec<usize>,
+    /// Order in which jobs inherit cores of the jobs that are done.
+    heirs: Vec<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq, Tabled)]
+pub struct JobAllocation {
+    pub label: String,
+    /// Number of cores the job starts with.
+    pub cores: usize,
+    /// Number of cores the job has at its end, after inheriting cores of other jobs.
+    pub final_cores: usize,
+    pub makespan: Duration,
+}
+
+/// Simulates all jobs under `allocation`, returning the makespans (in milliseconds) and final core counts of jobs.
+fn evaluate(
+    jobs: &[Job],
+    allocation: &Allocation,
+    scheduler: &dyn HintProviderFactory,
+) -> Result<(Vec<u64>, Vec<usize>), InputError> {
+    let mut events: Vec<Vec<ExternalEvent>> = vec![vec![]; jobs.len()];
+    let mut final_cores = allocation.cores.clone();
+    let mut done = vec![false; jobs.len()];
+    loop {
+        let makespans: Vec<u64> = jobs
+            .iter()
+            .zip(&allocation.cores)
+            .zip(&events)
+            .map(|((job, cores), events)| {
+                let hints = scheduler.create(&job.deps, &job.timings);
+                let mut runner =
+                    Runner::new(job.deps.clone().finish(hints), job.timings.clone(), *cores)
+                        .with_external_events(events.clone());
+                Ok(runner.calculate()?.0.makespan.as_millis())
+            })
+            .collect::<Result<_, InputError>>()?;
+        let Some(finished) = (0..jobs.len())
+            .filter(|job| !done[*job])
+            .min_by_key(|job| makespans[*job])
+        else {
+            return Ok((makespans, final_cores));
+        };
+        done[finished] = true;
+        let finished_at = makespans[finished];
+        let Some(heir) = allocation
+            .heirs
+            .iter()
+            .copied()
+            .find(|heir| !done[*heir] && makespans[*heir] > finished_at)
+        else {
+            continue;
+        };
+        events[heir].push(ExternalEvent {
+            label: format!("{} is done", jobs[finished].label),
+            at: finished_at as f64 / 1000.,
+            cores: final_cores[finished] as i64,
+            duration: None,
+        });
+        final_cores[heir] += final_cores[finished];
+    }
+}
+
+/// Searches for the best way to share `num_threads` cores between `jobs` with simulated annealing. Each job needs at
+/// least one core of its own.
+pub fn colocate(
+    jobs: &[Job],
+    num_threads: usize,
+    scheduler: &dyn HintProviderFactory,
+    iterations: usize,
+    seed: u64,
+) -> Result<Vec<JobAllocation>, InputError> {
+    if num_threads < jobs.len() {
+        return Err(InputError::invalid(format!(
+            "each of the {} jobs needs at least one core, but there are only {num_threads}",
+            jobs.len()
+        )));
+    }
+    let mut rng = Rng::new(seed);
+    // Start with an even split.
+    let mut current = Allocation {
+        cores: (0..jobs.len())
+            .map(|job| num_threads / jobs.len() + usize::from(job < num_threads % jobs.len()))
+            .collect(),
+        heirs: (0..jobs.len()).collect(),
+    };
+    let cost = |allocation: &Allocation| -> Result<u64, InputError> {
+        let (makespans, _) = evaluate(jobs, allocation, scheduler)?;
+        Ok(makespans.into_iter().max().unwrap_or_default())
+    };
+    let mut current_cost = cost(&current)?;
+    let mut best = (current.clone(), current_cost);
+    let mut temperature = current_cost as f64 * 0.1;
+    for _ in 0..iterations {
+        let mut candidate = current.clone();
+        let (a, b) = (rng.below(jobs.len()), rng.below(jobs.len()));
+        if rng.below(2) == 0 {
+            if a == b || candidate.cores[a] == 1 {
+                continue;
+            }
+            candidate.cores[a] -= 1;
+            candidate.cores[b] += 1;
+        } else {
+            candidate.heirs.swap(a, b);
+        }
+        let candidate_cost = cost(&candidate)?;
+        let accept = candidate_cost <= current_cost || {
+            let probability =
+                (-((candidate_cost - current_cost) as f64) / temperature.max(1.)).exp();
+            (rng.next_u64() as f64 / u64::MAX as f64) < probability
+        };
+        if accept {
+            current = candidate;
+            current_cost = candidate_cost;
+            if current_cost < best.1 {
+                best = (current.clone(), current_cost);
+            }
+        }
+        temperature *= 0.95;
+    }
+    let (best, _) = best;
+    let (makespans, final_cores) = evaluate(jobs, &best, scheduler)?;
+    Ok(jobs
+        .iter()
+        .enumerate()
+        .map(|(index, job)| JobAllocation {
+            label: job.label.clone(),
+            cores: best.cores[index],
+            final_cores: final_cores[index],
+            makespan: Duration::from_millis(makespans[index]),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{artifact::ArtifactType, CargoHints};
+
+    /// A job of units taking a second each, queued in order, with each depending on the previous one if `chained`.
+    fn job(label: &str, units: usize, chained: bool) -> Job {
+        let mut deps = DependencyQueueBuilder::new();
+        let mut timings = BTreeMap::new();
+        let mut previous = None;
+        for unit in 0..units {
+            let package_id = format!("{label}{unit}");
+            let artifact = Artifact {
+                typ: ArtifactType::Link,
+                package_id: package_id.clone(),
+            };
+            let timing: TimingInfo = serde_json::from_value(serde_json::json!({
+                "package_id": package_id,
+                "target": { "name": package_id, "crate_types": ["bin"] },
+                "mode": "build",
+                "duration": 1.,
+                "rmeta_time": null,
+            }))
+            .unwrap();
+            deps.queue(artifact.clone(), previous.filter(|_| chained));
+            timings.insert(artifact.clone(), timing);
+            previous = Some(artifact);
+        }
+        Job {
+            label: label.into(),
+            deps,
+            timings,
+        }
+    }
+
+    #[test]
+    fn cores_are_shared_and_handed_over() {
+        let jobs = [job("chain", 4, true), job("wide", 4, false)];
+        let scheduler = |deps: &DependencyQueueBuilder, _: &BTreeMap<Artifact, TimingInfo>| {
+            CargoHints::new(deps)
+        };
+        let allocations = colocate(&jobs, 4, &scheduler, 50, 0).unwrap();
+        assert_eq!(
+            allocations
+                .iter()
+                .map(|allocation| allocation.cores)
+                .sum::<usize>(),
+            4
+        );
+        assert!(allocations.iter().all(|allocation| allocation.cores >= 1));
+        // The chain can't go faster than a unit at a time, and gets the cores of the wide job once it's done.
+        assert_eq!(allocations[0].makespan, Duration::from_millis(4000));
+        assert_eq!(allocations[0].final_cores, 4);
+        assert!(allocations[1].makespan < allocations[0].makespan);
+    }
+}
@@ -0,0 +1,135 @@
+//! Post-processing of simulated schedules, telling apart the time lost to scheduling decisions from the time
+//! dictated by the shape of the dependency graph.
+//!
+//! Compaction keeps the order in which units were started, but shifts each of them as early as its dependencies
+//! and the number of cores allow. Whatever that saves is idle time the online scheduler left on the table. The
+//! remaining gap to the critical path is due to the graph itself (and the number of cores). Constraints, thread pool
+//! limits and external events are not taken into account.
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap};
+
+use tabled::Tabled;
+
+use crate::{
+    artifact::Artifact,
+    dependency_queue::DependencyQueueBuilder,
+    runner::{Duration, ScheduledTask},
+};
+
+/// How much slack a scheduler left in its schedule.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Tabled)]
+pub struct Compaction {
+    pub label: String,
+    pub makespan: Duration,
+    /// Makespan after shifting all units as early as possible, without changing their order.
+    pub compacted: Duration,
+    pub slack: Duration,
+    /// Length of the longest chain of dependent units, i.e. the makespan with unlimited cores.
+    pub critical_path: Duration,
+}
+
+/// Shifts tasks of `schedule` as early as their dependencies allow, keeping the order they were started in and
+/// using at most `num_threads` cores at a time.
+pub fn compact(
+    schedule: &[ScheduledTask],
+    deps: &DependencyQueueBuilder,
+    num_threads: usize,
+) -> Vec<ScheduledTask> {
+    let mut tasks: Vec<&ScheduledTask> = schedule.iter().collect();
+    tasks.sort_by_key(|task| task.start);
+    let mut free_cores: BinaryHeap<Reverse<u64>> =
+        std::iter::repeat_n(Reverse(0), num_threads.min(tasks.len())).collect();
+    let mut end_times: BTreeMap<&Artifact, u64> = BTreeMap::new();
+    let mut compacted = Vec::with_capacity(tasks.len());
+    for task in tasks {
+        let ready_at = deps
+            .dep_map
+            .get(&task.artifact)
+            .into_iter()
+            .flatten()
+            .filter_map(|dep| end_times.get(dep))
+            .max()
+            .copied()
+            .unwrap_or_default();
+        let Reverse(core_free_at) = free_cores.pop().unwrap();
+        let start = ready_at.max(core_free_at);
+        let end = start + (task.end - task.start);
+        free_cores.push(Reverse(end));
+        end_times.insert(&task.artifact, end);
+        compacted.push(ScheduledTask {
+            artifact: task.artifact.clone(),
+            start,
+            end,
+        });
+    }
+    compacted
+}
+
+/// Measures how much of the makespan of `schedule` is slack that compaction can get rid of.
+pub fn compaction(
+    label: String,
+    schedule: &[ScheduledTask],
+    deps: &DependencyQueueBuilder,
+    num_threads: usize,
+) -> Compaction {
+    let makespan = |schedule: &[ScheduledTask]| {
+        std::time::Duration::from_millis(
+            schedule
+                .iter()
+                .map(|task| task.end)
+                .max()
+                .unwrap_or_default(),
+        )
+    };
+    let original = makespan(schedule);
+    let compacted = makespan(&compact(schedule, deps, num_threads));
+    Compaction {
+        label,
+        makespan: Duration(original),
+        compacted: Duration(compacted),
+        slack: Duration(original.saturating_sub(compacted)),
+        critical_path: Duration(makespan(&compact(schedule, deps, schedule.len()))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::artifact::ArtifactType;
+
+    fn artifact(package_id: &str) -> Artifact {
+        Artifact {
+            typ: ArtifactType::Link,
+            package_id: package_id.into(),
+        }
+    }
+
+    #[test]
+    fn compaction_fills_idle_gaps() {
+        let mut deps = DependencyQueueBuilder::new();
+        deps.queue(artifact("a"), []);
+        deps.queue(artifact("b"), [artifact("a")]);
+        deps.queue(artifact("c"), []);
+        let schedule = [
+            ScheduledTask {
+                artifact: artifact("a"),
+                start: 0,
+                end: 10,
+            },
+            ScheduledTask {
+                artifact: artifact("c"),
+                start: 10,
+                end: 15,
+            },
+            ScheduledTask {
+                artifact: artifact("b"),
+                start: 20,
+                end: 30,
+            },
+        ];
+        let result = compaction("test".into(), &schedule, &deps, 2);
+        assert_eq!(result.compacted, Duration::from_millis(20));
+        assert_eq!(result.slack, Duration::from_millis(10));
+        assert_eq!(result.critical_path, Duration::from_millis(20));
+    }
+}
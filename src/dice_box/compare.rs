@@ -0,0 +1,79 @@
+//! Comparison of how two schedulers get to a chosen target.
+//!
+//! For a target package, we collect completion times of its transitive dependencies under each scheduler and put
+//! them side by side in the order of completion, marking the point where the orders diverge first. This makes
+//! behavioral differences between schedulers legible beyond a single makespan number.
+use tabled::Tabled;
+
+use crate::{
+    dependency_queue::DependencyQueueBuilder,
+    queries::{package_units, transitive_dependencies},
+    runner::ScheduledTask,
+};
+
+#[derive(Clone, Debug, PartialEq, Tabled)]
+pub struct ComparisonRow {
+    pub index: usize,
+    pub left: String,
+    pub left_finished: String,
+    pub right: String,
+    pub right_finished: String,
+    pub note: &'static str,
+}
+
+/// Tasks that `package` transitively depends on (including units of the package itself), in order of completion.
+fn unlock_timeline<'a>(
+    package: &str,
+    schedule: &'a [ScheduledTask],
+    deps: &DependencyQueueBuilder,
+) -> Vec<&'a ScheduledTask> {
+    let units = package_units(deps, package);
+    let closure = transitive_dependencies(deps, &units);
+    let mut timeline: Vec<_> = schedule
+        .iter()
+        .filter(|task| closure.contains(&task.artifact) || units.contains(&task.artifact))
+        .collect();
+    timeline.sort_by(|a, b| (a.end, &a.artifact).cmp(&(b.end, &b.artifact)));
+    timeline
+}
+
+pub fn compare(
+    package: &str,
+    left: &[ScheduledTask],
+    right: &[ScheduledTask],
+    deps: &DependencyQueueBuilder,
+) -> Vec<ComparisonRow> {
+    let left = unlock_timeline(package, left, deps);
+    let right = unlock_timeline(package, right, deps);
+    let first_divergence = left
+        .iter()
+        .zip(right.iter())
+        .position(|(left, right)| left.artifact != right.artifact);
+    let describe = |task: Option<&&ScheduledTask>| {
+        task.map(|task| {
+            (
+                task.artifact.to_string(),
+                format!("{:.3}s", task.end as f64 / 1000.),
+            )
+        })
+        .unwrap_or_default()
+    };
+    (0..left.len().max(right.len()))
+        .map(|index| {
+            let (left, left_finished) = describe(left.get(index));
+            let (right, right_finished) = describe(right.get(index));
+            ComparisonRow {
+                index,
+                left,
+                left_finished,
+                right,
+                right_finished,
+                note: if Some(index) == first_divergence {
+                    "<- first divergence"
+                } else {
+                    ""
+                },
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,236 @@
+//! Parser for the constraints file.
+//!
+//! Constraints model external factors that a scheduler has to work around (license servers, flaky build scripts,
+//! manual orderings and such). They are enforced by the [Runner](crate::Runner), so every hint provider is subject
+//! to them. An example constraints file:
+//! ```json
+//! {
+//!     "not-before": { "openssl-sys": 12.5 },
+//!     "pinned": { "ring": 0 },
+//!     "precedes": [["syn", "serde"]],
+//!     "exclusive": ["openssl-sys", "libz-sys"],
+//!     "memory": { "rustc_driver": 6.5, "polars-core": 4 },
+//!     "memory-budget": 16
+//! }
+//! ```
+//! Packages can be referred to either by their full package id or just by their name.
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+use tabled::Tabled;
+
+use crate::{
+    artifact::{split_package_id, Artifact, ArtifactType},
+    dependency_queue::DependencyQueueBuilder,
+    error::InputError,
+    runner::Duration,
+};
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Constraints {
+    /// Packages that must not start before a given time, in seconds.
+    not_before: BTreeMap<String, f64>,
+    /// Packages that must run on a given thread.
+    pinned: BTreeMap<String, usize>,
+    /// Pairs of packages where the first one has to be fully built before the second one can start, even if
+    /// there's no dependency edge between them.
+    precedes: Vec<(String, String)>,
+    /// Packages whose build scripts have global side effects (e.g. they write to a shared directory), so at most
+    /// one of them can run at a time.
+    exclusive: Vec<String>,
+    /// Peak memory (in GB) that rustc needs to build packages known to be memory hogs.
+    memory: BTreeMap<String, f64>,
+    /// Memory (in GB) available to the build. Memory hogs are not started if that would exceed it.
+    memory_budget: Option<f64>,
+}
+
+/// How much makespan is lost to serializing exclusive build scripts under a given scheduler.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Tabled)]
+pub struct ExclusivityCost {
+    pub label: String,
+    pub makespan: Duration,
+    pub without_exclusivity: Duration,
+    pub cost: Duration,
+}
+
+impl ExclusivityCost {
+    pub fn new(label: String, makespan: Duration, without_exclusivity: Duration) -> Self {
+        Self {
+            label,
+            cost: makespan - without_exclusivity,
+            makespan,
+            without_exclusivity,
+        }
+    }
+}
+
+/// Whether `package_id` (e.g. "serde 1.0.188 (registry+https://github.com/rust-lang/crates.io-index)")
+/// refers to `package`, which is either a full package id or just a package name.
+pub(crate) fn matches(package_id: &str, package: &str) -> bool {
+    package_id == package
+        || split_package_id(package_id).0 == package
+        || package_id
+            .strip_prefix(package)
+            .is_some_and(|rest| rest.starts_with(' '))
+}
+
+impl Constraints {
+    /// Time (in milliseconds) before which `artifact` must not start, if any.
+    pub(crate) fn release_time(&self, artifact: &Artifact) -> Option<u64> {
+        self.not_before
+            .iter()
+            .filter(|(package, _)| matches(&artifact.package_id, package))
+            .map(|(_, time)| (time * 1000.) as u64)
+            .max()
+    }
+
+    /// Thread that `artifact` has to run on, if any.
+    pub(crate) fn pinned_thread(&self, artifact: &Artifact) -> Option<usize> {
+        self.pinned
+            .iter()
+            .find(|(package, _)| matches(&artifact.package_id, package))
+            .map(|(_, thread)| *thread)
+    }
+
+    pub(crate) fn has_pins(&self) -> bool {
+        !self.pinned.is_empty()
+    }
+
+    /// Fewest threads that leave every pinned package the thread it's pinned to.
+    pub fn min_threads(&self) -> usize {
+        self.pinned.values().max().map_or(1, |thread| thread + 1)
+    }
+
+    /// Checks that the constraints can be met by a build on `num_threads` threads.
+    pub fn check(&self, num_threads: usize) -> Result<(), InputError> {
+        let problems: Vec<String> = self
+            .pinned
+            .iter()
+            .filter(|(_, thread)| **thread >= num_threads)
+            .map(|(package, thread)| {
+                format!("{package} is pinned to thread {thread}, but the build only has {num_threads} threads")
+            })
+            .collect();
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(InputError::Invalid(problems))
+        }
+    }
+
+    /// Whether `artifact` is a build script run that must not run alongside other exclusive ones.
+    pub(crate) fn is_exclusive(&self, artifact: &Artifact) -> bool {
+        artifact.typ == ArtifactType::BuildScriptRun
+            && self
+                .exclusive
+                .iter()
+                .any(|package| matches(&artifact.package_id, package))
+    }
+
+    pub fn has_exclusive(&self) -> bool {
+        !self.exclusive.is_empty()
+    }
+
+    /// Returns the same constraints, but with no build scripts marked as exclusive.
+    pub fn without_exclusive(&self) -> Constraints {
+        Constraints {
+            exclusive: vec![],
+            ..self.clone()
+        }
+    }
+
+    /// Peak memory (in GB) needed to build `artifact`, as far as known. Only rustc invocations are accounted for.
+    pub(crate) fn memory(&self, artifact: &Artifact) -> f64 {
+        if !matches!(
+            artifact.typ,
+            ArtifactType::Metadata | ArtifactType::Codegen | ArtifactType::Link
+        ) {
+            return 0.;
+        }
+        self.memory
+            .iter()
+            .find(|(package, _)| matches(&artifact.package_id, package))
+            .map_or(0., |(_, memory)| *memory)
+    }
+
+    pub fn memory_budget(&self) -> Option<f64> {
+        self.memory_budget
+    }
+
+    /// Returns the same constraints, but with unlimited memory.
+    pub fn without_memory_budget(&self) -> Constraints {
+        Constraints {
+            memory_budget: None,
+            ..self.clone()
+        }
+    }
+
+    /// Whether `artifact` can be started on thread `thread` at time `now` (in milliseconds).
+    /// `exclusive_running` should be set if an exclusive build script is running already, and `memory_in_use`
+    /// should be the memory taken up by running units.
+    pub(crate) fn allows(
+        &self,
+        artifact: &Artifact,
+        thread: usize,
+        now: u64,
+        exclusive_running: bool,
+        memory_in_use: f64,
+    ) -> bool {
+        self.release_time(artifact).is_none_or(|time| time <= now)
+            && self
+                .pinned_thread(artifact)
+                .is_none_or(|pinned| pinned == thread)
+            && !(exclusive_running && self.is_exclusive(artifact))
+            && self.memory_budget.is_none_or(|budget| {
+                let memory = self.memory(artifact);
+                // A memory hog that doesn't fit into the budget on its own can still run alone.
+                memory == 0. || memory_in_use == 0. || memory_in_use + memory <= budget
+            })
+    }
+
+    /// Adds artificial dependency edges for all of the `precedes` constraints. Fails on the first ordering that
+    /// would make units depend on each other, either through other orderings or against an existing dependency.
+    pub fn add_orderings(&self, builder: &mut DependencyQueueBuilder) -> Result<(), InputError> {
+        for (before, after) in &self.precedes {
+            let artifacts: Vec<Artifact> = builder.dep_map.keys().cloned().collect();
+            let befores: Vec<&Artifact> = artifacts
+                .iter()
+                .filter(|artifact| matches(&artifact.package_id, before))
+                .collect();
+            for after in artifacts
+                .iter()
+                .filter(|artifact| matches(&artifact.package_id, after))
+            {
+                for before in &befores {
+                    builder.add_dependency(after, (*before).clone());
+                }
+            }
+            if !builder.is_acyclic() {
+                return Err(InputError::invalid(format!(
+                    "ordering {before} before {after} makes them depend on each other"
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Deserialize constraints from contents of a constraints file.
+pub fn parse_constraints(contents: &str) -> serde_json::Result<Constraints> {
+    serde_json::from_str(contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn package_names_match_full_ids() {
+        let id = "serde 1.0.188 (registry+https://github.com/rust-lang/crates.io-index)";
+        assert!(matches(id, "serde"));
+        assert!(matches(id, id));
+        assert!(!matches(id, "serde_json"));
+        assert!(!matches("serde_json 1.0.107", "serde"));
+    }
+}
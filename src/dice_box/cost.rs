@@ -0,0 +1,78 @@
+//! Cost model for cloud machines, so that schedulers and machine sizes can be chosen together.
+use tabled::Tabled;
+
+use crate::runner::Duration;
+
+/// A machine that builds can be run on, billed per core-hour.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MachineProfile {
+    pub cores: usize,
+    /// Price of a core-hour, in dollars.
+    pub core_hour_rate: f64,
+    /// Bookkeeping time of Cargo's main loop per running unit, charged before each scheduling decision.
+    pub coordinator_cost: std::time::Duration,
+}
+
+impl std::str::FromStr for MachineProfile {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (cores, rest) = s.split_once('=').ok_or_else(|| {
+            format!("expected `<cores>=<$ per core-hour>[,<µs per running unit>]`, got `{s}`")
+        })?;
+        let (rate, coordinator_cost) = rest.split_once(',').unwrap_or((rest, "0"));
+        Ok(Self {
+            cores: cores
+                .parse()
+                .map_err(|e| format!("invalid number of cores `{cores}`: {e}"))?,
+            core_hour_rate: rate
+                .parse()
+                .map_err(|e| format!("invalid rate `{rate}`: {e}"))?,
+            coordinator_cost: coordinator_cost
+                .parse()
+                .map(std::time::Duration::from_micros)
+                .map_err(|e| format!("invalid coordinator cost `{coordinator_cost}`: {e}"))?,
+        })
+    }
+}
+
+/// Estimated cost of a scenario on a given machine.
+#[derive(Clone, Debug, PartialEq, Tabled)]
+pub struct CostEstimate {
+    pub label: String,
+    pub cores: usize,
+    pub makespan: Duration,
+    #[tabled(display_with = "display_dollars")]
+    pub cost: f64,
+    /// Whether no other estimate is both cheaper and faster.
+    pub pareto_optimal: bool,
+}
+
+fn display_dollars(cost: &f64) -> String {
+    format!("${cost:.4}")
+}
+
+impl CostEstimate {
+    pub fn new(label: String, machine: MachineProfile, makespan: Duration) -> Self {
+        let hours = makespan.as_secs_f64() / 3600.;
+        Self {
+            label,
+            cores: machine.cores,
+            cost: hours * machine.cores as f64 * machine.core_hour_rate,
+            makespan,
+            pareto_optimal: false,
+        }
+    }
+}
+
+/// Marks the estimates that are on the cost-vs-time Pareto frontier.
+pub fn mark_pareto_optimal(estimates: &mut [CostEstimate]) {
+    for index in 0..estimates.len() {
+        let estimate = &estimates[index];
+        let dominated = estimates.iter().any(|other| {
+            other.cost <= estimate.cost
+                && other.makespan <= estimate.makespan
+                && (other.cost < estimate.cost || other.makespan < estimate.makespan)
+        });
+        estimates[index].pareto_optimal = !dominated;
+    }
+}
@@ -0,0 +1,129 @@
+//! Critical path computations over the dependency graph.
+use std::collections::{BTreeMap, BTreeSet};
+
+use tabled::Tabled;
+
+use crate::{
+    artifact::Artifact, cache::timings_fingerprint, dependency_queue::DependencyQueueBuilder,
+    runner::Duration, timings::TimingInfo,
+};
+
+/// Duration of an artifact in milliseconds, the same way it's accounted for by the [Runner](crate::Runner).
+pub(crate) fn duration_ms(timings: &BTreeMap<Artifact, TimingInfo>, artifact: &Artifact) -> u64 {
+    timings
+        .get(artifact)
+        .map(|timing| (timing.duration * 1000.) as u64)
+        .unwrap_or_default()
+}
+
+/// For each artifact, computes the length (in milliseconds) of the longest chain of work that starts with
+/// building that artifact and ends with one of the final artifacts of the build. No schedule can finish the build
+/// earlier than that once the artifact is started.
+pub fn critical_path_lengths(
+    deps: &DependencyQueueBuilder,
+    timings: &BTreeMap<Artifact, TimingInfo>,
+) -> BTreeMap<Artifact, u64> {
+    if let Some(preprocessed) = deps.preprocessed.as_ref() {
+        if preprocessed.timings_fingerprint == timings_fingerprint(timings) {
+            return preprocessed.critical_paths.clone();
+        }
+    }
+    lengths(deps.dep_map.keys(), &deps.reverse_dep_map, timings)
+}
+
+/// The longest chain of dependent units, from one of the roots of the graph to one of its final units.
+pub fn critical_path(
+    deps: &DependencyQueueBuilder,
+    timings: &BTreeMap<Artifact, TimingInfo>,
+) -> Vec<Artifact> {
+    let lengths = critical_path_lengths(deps, timings);
+    let longest = |candidates: &mut dyn Iterator<Item = &Artifact>| {
+        candidates
+            .max_by_key(|artifact| lengths[*artifact])
+            .cloned()
+    };
+    let mut chain = vec![];
+    let mut next = longest(
+        &mut deps
+            .dep_map
+            .iter()
+            .filter(|(_, dependencies)| dependencies.is_empty())
+            .map(|(artifact, _)| artifact),
+    );
+    while let Some(artifact) = next {
+        next = longest(&mut deps.reverse_dep_map.get(&artifact).into_iter().flatten());
+        chain.push(artifact);
+    }
+    chain
+}
+
+/// A unit on the critical path of the build.
+#[derive(Clone, Debug, PartialEq, Tabled)]
+pub struct CriticalPathSegment {
+    pub unit: String,
+    /// Time at which the unit starts if every unit of the critical path starts as soon as the previous one is done.
+    pub start: Duration,
+    pub duration: Duration,
+    /// Duration of the unit as a percentage of the makespan.
+    #[tabled(display_with = "display_percentage")]
+    pub share: f64,
+}
+
+fn display_percentage(percentage: &f64) -> String {
+    format!("{percentage:.1}%")
+}
+
+/// Units of the critical path of the build that take any time, along with their share of `makespan`.
+pub fn critical_path_segments(
+    deps: &DependencyQueueBuilder,
+    timings: &BTreeMap<Artifact, TimingInfo>,
+    makespan: Duration,
+) -> Vec<CriticalPathSegment> {
+    let mut start = 0;
+    critical_path(deps, timings)
+        .into_iter()
+        .filter_map(|artifact| {
+            let duration = duration_ms(timings, &artifact);
+            start += duration;
+            (duration > 0).then(|| CriticalPathSegment {
+                unit: artifact.to_string(),
+                start: Duration::from_millis(start - duration),
+                duration: Duration::from_millis(duration),
+                share: duration as f64 / makespan.as_millis().max(1) as f64 * 100.,
+            })
+        })
+        .collect()
+}
+
+/// Computes critical path lengths of `keys` given a map from artifacts to their dependants.
+pub(crate) fn lengths<'a>(
+    keys: impl IntoIterator<Item = &'a Artifact>,
+    reverse_dep_map: &BTreeMap<Artifact, BTreeSet<Artifact>>,
+    timings: &BTreeMap<Artifact, TimingInfo>,
+) -> BTreeMap<Artifact, u64> {
+    fn visit(
+        key: &Artifact,
+        reverse_dep_map: &BTreeMap<Artifact, BTreeSet<Artifact>>,
+        timings: &BTreeMap<Artifact, TimingInfo>,
+        results: &mut BTreeMap<Artifact, u64>,
+    ) -> u64 {
+        if let Some(length) = results.get(key) {
+            return *length;
+        }
+        let longest_dependant = reverse_dep_map
+            .get(key)
+            .into_iter()
+            .flatten()
+            .map(|dependant| visit(dependant, reverse_dep_map, timings, results))
+            .max()
+            .unwrap_or_default();
+        let length = duration_ms(timings, key) + longest_dependant;
+        results.insert(key.clone(), length);
+        length
+    }
+    let mut results = BTreeMap::new();
+    for key in keys {
+        visit(key, reverse_dep_map, timings, &mut results);
+    }
+    results
+}
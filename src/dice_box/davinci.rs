@@ -0,0 +1,96 @@
+//! Da Vinci mode: an HTML page in which a simulated schedule can be edited by hand, to test intuitions about the
+//! build graph.
+//!
+//! Units can be dragged earlier or later. Downstream start times and the makespan are then recomputed the same way
+//! [compact](crate::compact) does it: units keep the order they were started in (with dragged units moved to their
+//! new position) and start as early as their dependencies and the number of cores allow, but never before the point
+//! they were dragged to. A unit can't be dragged before its dependencies are done.
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::{
+    artifact::{Artifact, ArtifactType},
+    compaction::compact,
+    dependency_queue::DependencyQueueBuilder,
+    runner::ScheduledTask,
+};
+
+#[derive(Serialize)]
+struct Unit {
+    name: String,
+    color: &'static str,
+    start: u64,
+    duration: u64,
+    /// Indices of units this one depends on.
+    deps: Vec<usize>,
+}
+
+/// Writes an editor of `schedule` (simulated with `num_threads` cores) as a standalone HTML page.
+pub fn write_editor(
+    schedule: &[ScheduledTask],
+    deps: &DependencyQueueBuilder,
+    num_threads: usize,
+    title: &str,
+    f: &mut impl Write,
+) -> Result<()> {
+    // The editor recomputes schedules under the compaction model, so start from a schedule that follows it too.
+    let schedule = compact(schedule, deps, num_threads);
+    let indices: BTreeMap<&Artifact, usize> = schedule
+        .iter()
+        .enumerate()
+        .map(|(index, task)| (&task.artifact, index))
+        .collect();
+    let units: Vec<Unit> = schedule
+        .iter()
+        .map(|task| Unit {
+            name: task.artifact.to_string(),
+            color: match task.artifact.typ {
+                ArtifactType::BuildScriptBuild | ArtifactType::BuildScriptRun => "#f0b165",
+                ArtifactType::Metadata => "#95cce8",
+                ArtifactType::Codegen => "#aa95e8",
+                ArtifactType::Link => "#e89595",
+            },
+            start: task.start,
+            duration: task.end - task.start,
+            deps: deps
+                .dep_map
+                .get(&task.artifact)
+                .into_iter()
+                .flatten()
+                .filter_map(|dep| indices.get(dep).copied())
+                .collect(),
+        })
+        .collect();
+    let title = title.replace('&', "&amp;").replace('<', "&lt;");
+    write!(
+        f,
+        r#"<html>
+<head>
+  <title>Da Vinci mode: {title}</title>
+  <meta charset="utf-8">
+  <style>
+    body {{ font-family: sans-serif; }}
+    rect {{ cursor: ew-resize; }}
+  </style>
+</head>
+<body>
+<h1>{title}</h1>
+<p>Drag units earlier or later to see how the rest of the build reacts. Dragged units are highlighted.</p>
+<p><span id="makespan"></span> <button id="reset">Reset</button></p>
+<svg id="schedule" xmlns="http://www.w3.org/2000/svg"></svg>
+<script>
+NUM_THREADS = {num_threads};
+UNITS = {units};
+{script}
+</script>
+</body>
+</html>
+"#,
+        units = serde_json::to_string(&units)?,
+        script = include_str!("davinci/davinci.js"),
+    )?;
+    Ok(())
+}
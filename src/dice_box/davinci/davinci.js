@@ -0,0 +1,95 @@
+// Interactive schedule editor. Units can be dragged earlier or later; the schedule is then rebuilt by a list
+// scheduler that orders units by their (desired) start times, and starts each as early as its dependencies, a free
+// core and the position it was dragged to allow.
+const LANE_HEIGHT = 20;
+const WIDTH = 1200;
+const makespanOf = (ends) => Math.max(1, ...ends);
+const ORIGINAL_MAKESPAN = makespanOf(UNITS.map(u => u.start + u.duration));
+const SCALE = WIDTH / ORIGINAL_MAKESPAN;
+const DEPENDANTS = UNITS.map(() => []);
+UNITS.forEach((unit, i) => unit.deps.forEach(dep => DEPENDANTS[dep].push(i)));
+
+let releases, starts, ends, lanes;
+
+function reset() {
+  releases = UNITS.map(() => 0);
+  starts = UNITS.map(u => u.start);
+  recompute();
+}
+
+function recompute() {
+  const remaining = UNITS.map(u => u.deps.length);
+  const cores = new Array(NUM_THREADS).fill(0);
+  const newStarts = new Array(UNITS.length);
+  ends = new Array(UNITS.length);
+  lanes = new Array(UNITS.length);
+  let ready = UNITS.map((_, i) => i).filter(i => remaining[i] === 0);
+  while (ready.length > 0) {
+    ready.sort((a, b) => starts[a] - starts[b] || a - b);
+    const i = ready.shift();
+    let lane = 0;
+    for (let core = 1; core < cores.length; core++) {
+      if (cores[core] < cores[lane]) {
+        lane = core;
+      }
+    }
+    const depsEnd = Math.max(0, ...UNITS[i].deps.map(dep => ends[dep]));
+    newStarts[i] = Math.max(depsEnd, cores[lane], releases[i]);
+    ends[i] = newStarts[i] + UNITS[i].duration;
+    cores[lane] = ends[i];
+    lanes[i] = lane;
+    for (const dependant of DEPENDANTS[i]) {
+      if (--remaining[dependant] === 0) {
+        ready.push(dependant);
+      }
+    }
+  }
+  starts = newStarts;
+  render();
+}
+
+function render() {
+  const svg = document.getElementById('schedule');
+  const makespan = makespanOf(ends);
+  svg.setAttribute('width', Math.max(WIDTH, makespan * SCALE) + 10);
+  svg.setAttribute('height', NUM_THREADS * LANE_HEIGHT);
+  svg.replaceChildren();
+  UNITS.forEach((unit, i) => {
+    const rect = document.createElementNS('http://www.w3.org/2000/svg', 'rect');
+    rect.setAttribute('x', starts[i] * SCALE);
+    rect.setAttribute('y', lanes[i] * LANE_HEIGHT + 2);
+    rect.setAttribute('width', Math.max(1, unit.duration * SCALE));
+    rect.setAttribute('height', LANE_HEIGHT - 4);
+    rect.setAttribute('fill', releases[i] > 0 ? '#e8c295' : unit.color);
+    rect.setAttribute('stroke', '#fff');
+    const title = document.createElementNS('http://www.w3.org/2000/svg', 'title');
+    title.textContent = `${unit.name}: ${(starts[i] / 1000).toFixed(2)}s - ${(ends[i] / 1000).toFixed(2)}s`;
+    rect.appendChild(title);
+    rect.addEventListener('mousedown', event => startDrag(event, i, rect));
+    svg.appendChild(rect);
+  });
+  const delta = (makespan - ORIGINAL_MAKESPAN) / 1000;
+  document.getElementById('makespan').textContent =
+    `Makespan: ${(makespan / 1000).toFixed(2)}s (${delta >= 0 ? '+' : ''}${delta.toFixed(2)}s)`;
+}
+
+function startDrag(event, i, rect) {
+  event.preventDefault();
+  const originX = event.clientX;
+  const originStart = starts[i];
+  const move = event => {
+    rect.setAttribute('x', Math.max(0, originStart * SCALE + event.clientX - originX));
+  };
+  const drop = event => {
+    document.removeEventListener('mousemove', move);
+    document.removeEventListener('mouseup', drop);
+    releases[i] = Math.max(0, Math.round(originStart + (event.clientX - originX) / SCALE));
+    starts[i] = releases[i];
+    recompute();
+  };
+  document.addEventListener('mousemove', move);
+  document.addEventListener('mouseup', drop);
+}
+
+document.getElementById('reset').addEventListener('click', reset);
+reset();
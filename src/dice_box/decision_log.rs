@@ -0,0 +1,80 @@
+//! Logs of scheduling decisions, for understanding why one scheduler beats another on a given project: each decision
+//! lists the ready units that could have been started, how the hint provider scored them (e.g. priorities of
+//! [CargoHints](crate::CargoHints)) and the unit that was started. Logs are written as JSON Lines, one decision per
+//! line:
+//! ```json
+//! {"scenario":"Cargo Hints","time":1250,"thread":2,"candidates":[{"artifact":{"typ":"Metadata","package_id":"path+file:///ws/crates/api#0.1.0"},"score":14.0}],"choice":{"typ":"Metadata","package_id":"path+file:///ws/crates/api#0.1.0"},"wait":false,"reason":"hint-provider"}
+//! ```
+//! Replayed decisions are not logged, as hint providers are not consulted for them.
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::{artifact::Artifact, runner::Runner};
+
+/// A unit that could have been started, along with its [score](crate::HintProvider::score), if the hint provider
+/// has one.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct LoggedCandidate {
+    pub artifact: Artifact,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score: Option<f64>,
+}
+
+/// What made the runner start the unit it did.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DecisionReason {
+    /// Codegen units are started as soon as they're ready, without consulting the hint provider.
+    Codegen,
+    /// The unit belongs to the package that last ran on the thread, which is preferred with affinity placement.
+    Affinity,
+    /// The hint provider picked the unit, or decided not to start any.
+    HintProvider,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct LoggedDecision {
+    /// Position of the decision among all decisions of the runner, as recorded in its [trace](Runner::trace).
+    #[serde(skip)]
+    pub index: usize,
+    /// In milliseconds since the start of the build.
+    pub time: u64,
+    pub thread: usize,
+    pub candidates: Vec<LoggedCandidate>,
+    /// Unit that was started, if any.
+    pub choice: Option<Artifact>,
+    /// Whether the hint provider deliberately left the thread idle.
+    pub wait: bool,
+    pub reason: DecisionReason,
+}
+
+#[derive(Serialize)]
+struct LogLine<'a> {
+    scenario: &'a str,
+    #[serde(flatten)]
+    decision: &'a LoggedDecision,
+}
+
+/// Writes decisions logged by `scenarios` to `path`, one per line.
+pub fn write_decision_log(path: &Path, scenarios: &[Runner]) -> Result<()> {
+    let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+    for runner in scenarios {
+        for decision in runner.decision_log() {
+            serde_json::to_writer(
+                &mut writer,
+                &LogLine {
+                    scenario: runner.label(),
+                    decision,
+                },
+            )?;
+            writeln!(writer)?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
@@ -12,10 +12,14 @@
 //! whole node is finished.
 
 use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
 
 use crate::{
     artifact::{Artifact, ArtifactType},
-    hints::HintProvider,
+    cache::Preprocessed,
+    critical_path::{duration_ms, lengths},
+    hints::{HintProvider, SchedulerContext, Suggestion},
+    timings::TimingInfo,
 };
 
 #[derive(Clone, Debug)]
@@ -33,6 +37,13 @@ pub struct DependencyQueueBuilder {
     /// This map is statically known and does not get updated throughout the
     /// lifecycle of the DependencyQueue.
     pub(super) reverse_dep_map: BTreeMap<Artifact, BTreeSet<Artifact>>,
+
+    /// All known keys, in the order they were queued in.
+    pub(super) insertion_order: Vec<Artifact>,
+
+    /// Results of expensive computations over this graph, possibly loaded from disk. Dropped whenever the graph
+    /// changes.
+    pub(super) preprocessed: Option<Arc<Preprocessed>>,
 }
 
 /// Analog of Cargo's DependencyQueue except of
@@ -62,11 +73,19 @@ pub struct DependencyQueue {
     hints: Box<dyn super::hints::HintProvider>,
 }
 
+impl Default for DependencyQueueBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl DependencyQueueBuilder {
     pub fn new() -> Self {
         Self {
             dep_map: BTreeMap::new(),
             reverse_dep_map: BTreeMap::new(),
+            insertion_order: Vec::new(),
+            preprocessed: None,
         }
     }
     /// Adds a new node and its dependencies to this queue.
@@ -81,6 +100,7 @@ impl DependencyQueueBuilder {
             return;
         }
 
+        self.preprocessed = None;
         let mut my_dependencies = BTreeSet::new();
         for dep in dependencies {
             my_dependencies.insert(dep.clone());
@@ -90,6 +110,83 @@ impl DependencyQueueBuilder {
                 .insert(key.clone());
         }
         self.dep_map.insert(key.clone(), my_dependencies);
+        self.insertion_order.push(key);
+    }
+
+    /// Returns `true` if `key` was queued.
+    pub fn contains(&self, key: &Artifact) -> bool {
+        self.dep_map.contains_key(key)
+    }
+
+    /// All queued units.
+    pub fn units(&self) -> impl Iterator<Item = &Artifact> {
+        self.dep_map.keys()
+    }
+
+    /// Attaches results of preprocessing, which are then used instead of recomputing them.
+    pub fn set_preprocessed(&mut self, preprocessed: Preprocessed) {
+        self.preprocessed = Some(Arc::new(preprocessed));
+    }
+
+    /// Adds an extra dependency edge to an already queued node.
+    pub(crate) fn add_dependency(&mut self, key: &Artifact, dependency: Artifact) {
+        let Some(dependencies) = self.dep_map.get_mut(key) else {
+            return;
+        };
+        dependencies.insert(dependency.clone());
+        self.preprocessed = None;
+        self.reverse_dep_map
+            .entry(dependency)
+            .or_default()
+            .insert(key.clone());
+    }
+
+    /// Removes a dependency edge from a queued node.
+    pub(crate) fn remove_dependency(&mut self, key: &Artifact, dependency: &Artifact) {
+        let Some(dependencies) = self.dep_map.get_mut(key) else {
+            return;
+        };
+        if !dependencies.remove(dependency) {
+            return;
+        }
+        self.preprocessed = None;
+        if let Some(dependants) = self.reverse_dep_map.get_mut(dependency) {
+            dependants.remove(key);
+        }
+    }
+
+    /// Returns `true` if no unit transitively depends on itself, i.e. the whole graph can be built.
+    pub fn is_acyclic(&self) -> bool {
+        let mut pending: BTreeMap<&Artifact, usize> = self
+            .dep_map
+            .iter()
+            .map(|(artifact, dependencies)| {
+                let queued = dependencies
+                    .iter()
+                    .filter(|dep| self.dep_map.contains_key(*dep))
+                    .count();
+                (artifact, queued)
+            })
+            .collect();
+        let mut ready: Vec<&Artifact> = pending
+            .iter()
+            .filter(|(_, count)| **count == 0)
+            .map(|(artifact, _)| *artifact)
+            .collect();
+        let mut built = 0;
+        while let Some(artifact) = ready.pop() {
+            built += 1;
+            for dependant in self.reverse_dep_map.get(artifact).into_iter().flatten() {
+                let Some(count) = pending.get_mut(dependant) else {
+                    continue;
+                };
+                *count -= 1;
+                if *count == 0 {
+                    ready.push(dependant);
+                }
+            }
+        }
+        built == self.dep_map.len()
     }
 
     pub fn finish(self, hints: Box<dyn HintProvider>) -> DependencyQueue {
@@ -107,10 +204,30 @@ impl DependencyQueue {
     /// A package is ready to be built when it has 0 un-built dependencies. If
     /// `None` is returned then no packages are ready to be built.
     pub fn dequeue(&mut self) -> Option<Artifact> {
+        // Nothing is running as far as the hint provider knows, so it can't decide to wait.
+        match self.dequeue_filtered(&SchedulerContext::default(), |_| true)? {
+            Suggestion::Start(artifact) => Some(artifact),
+            Suggestion::Wait => None,
+        }
+    }
+
+    /// Dequeues a package that is ready to be built and that is accepted by `allowed`, letting the hint provider
+    /// know about the state of the build in `context`. The hint provider may also decide to leave the thread idle
+    /// instead, as long as something is running.
+    pub fn dequeue_filtered(
+        &mut self,
+        context: &SchedulerContext,
+        allowed: impl Fn(&Artifact) -> bool,
+    ) -> Option<Suggestion<Artifact>> {
         let candidates: Vec<&Artifact> = self
             .dep_map
             .iter()
-            .filter_map(|(artifact, deps)| deps.is_empty().then_some(artifact))
+            .filter_map(|(artifact, deps)| {
+                (deps.is_empty()
+                    && allowed(artifact)
+                    && self.hints.accepts_core(artifact, context.core_speed))
+                .then_some(artifact)
+            })
             .collect();
 
         let key: Artifact = if let Some(codegen_unit) = candidates
@@ -119,11 +236,29 @@ impl DependencyQueue {
             .cloned()
         {
             codegen_unit.clone()
+        } else if context.running.is_empty() {
+            self.hints.suggest_next(&candidates, context)?.clone()
         } else {
-            self.hints.suggest_next(&candidates)?.clone()
+            match self.hints.decide(&candidates, context)? {
+                Suggestion::Start(artifact) => artifact.clone(),
+                Suggestion::Wait => return Some(Suggestion::Wait),
+            }
         };
         let _ = self.dep_map.remove(&key).unwrap();
-        Some(key.clone())
+        Some(Suggestion::Start(key.clone()))
+    }
+
+    /// Dequeues a given package, if it's ready to be built.
+    pub fn dequeue_exact(&mut self, key: &Artifact) -> Option<Artifact> {
+        if !self.dep_map.get(key).is_some_and(|deps| deps.is_empty()) {
+            return None;
+        }
+        self.dep_map.remove_entry(key).map(|(key, _)| key)
+    }
+
+    /// Returns the dependencies of a package which still need to be built, or `None` if the package is not queued.
+    pub(crate) fn pending_dependencies(&self, key: &Artifact) -> Option<&BTreeSet<Artifact>> {
+        self.dep_map.get(key)
     }
 
     /// Returns `true` if there are remaining packages to be built.
@@ -136,6 +271,18 @@ impl DependencyQueue {
         self.dep_map.len()
     }
 
+    /// Returns the remaining packages to be built.
+    pub fn remaining(&self) -> impl Iterator<Item = &Artifact> {
+        self.dep_map.keys()
+    }
+
+    /// Returns the packages that are ready to be built.
+    pub fn ready(&self) -> impl Iterator<Item = &Artifact> {
+        self.dep_map
+            .iter()
+            .filter_map(|(artifact, deps)| deps.is_empty().then_some(artifact))
+    }
+
     /// Indicate that something has finished.
     ///
     /// Calling this function indicates that the `node` has produced `edge`. All
@@ -165,6 +312,21 @@ impl DependencyQueue {
     pub fn hints(&self) -> &dyn HintProvider {
         &*self.hints
     }
+
+    /// Lower bounds (in milliseconds) on the makespan of building the remaining units: the length of the longest
+    /// chain of dependent units and the total amount of work.
+    pub(crate) fn lower_bounds(&self, timings: &BTreeMap<Artifact, TimingInfo>) -> (u64, u64) {
+        let critical_path = lengths(self.dep_map.keys(), &self.reverse_dep_map, timings)
+            .into_values()
+            .max()
+            .unwrap_or_default();
+        let total_work = self
+            .dep_map
+            .keys()
+            .map(|artifact| duration_ms(timings, artifact))
+            .sum();
+        (critical_path, total_work)
+    }
 }
 
 /// Scheduling implementation of Cargo as of 24.09.2023. It schedules dependencies based on potential parallelism
@@ -175,7 +337,11 @@ pub struct CargoHints {
 }
 
 impl HintProvider for CargoHints {
-    fn suggest_next<'a>(&mut self, timings: &[&'a Artifact]) -> Option<&'a Artifact> {
+    fn suggest_next<'a>(
+        &mut self,
+        timings: &[&'a Artifact],
+        _context: &SchedulerContext,
+    ) -> Option<&'a Artifact> {
         timings
             .iter()
             .max_by_key(|artifact| self.priority[artifact])
@@ -184,6 +350,10 @@ impl HintProvider for CargoHints {
     fn label(&self) -> String {
         "Cargo Hints".into()
     }
+
+    fn score(&self, artifact: &Artifact) -> Option<f64> {
+        self.priority.get(artifact).map(|priority| *priority as f64)
+    }
 }
 
 /// Creates a flattened reverse dependency list. For a given key, finds the
@@ -214,9 +384,12 @@ fn depth<'a>(
     &*slot
 }
 
-pub(super) fn reverse_dependencies(
+pub(crate) fn reverse_dependencies(
     deps: &DependencyQueueBuilder,
 ) -> BTreeMap<Artifact, BTreeSet<Artifact>> {
+    if let Some(preprocessed) = deps.preprocessed.as_ref() {
+        return preprocessed.reverse_dependencies.clone();
+    }
     let mut out = BTreeMap::new();
     for key in deps.dep_map.keys() {
         depth(key, &deps.reverse_dep_map, &mut out);
@@ -228,6 +401,7 @@ pub(super) fn reverse_dependencies(
 }
 
 impl CargoHints {
+    #[allow(clippy::new_ret_no_self)]
     pub fn new(deps: &DependencyQueueBuilder) -> Box<dyn HintProvider> {
         let out = reverse_dependencies(&deps);
         fn dependent_cost(typ: ArtifactType) -> usize {
@@ -0,0 +1,160 @@
+//! Simulation of the common development loop: `cargo check` of the workspace followed by `cargo build`.
+//!
+//! Check-mode units only produce metadata, so they're not reused by the subsequent build. Build scripts and
+//! proc-macros (along with everything they depend on) are built in full during the check pass though, so the build
+//! pass can reuse them as is, just like Cargo would.
+use std::collections::{BTreeMap, BTreeSet};
+
+use tabled::Tabled;
+
+use crate::{
+    artifact::{Artifact, ArtifactType},
+    dependency_queue::DependencyQueueBuilder,
+    error::InputError,
+    hints::HintProvider,
+    runner::{Duration, Runner},
+    timings::{TimingInfo, Timings},
+};
+
+/// Results of simulating the dev loop with a single scheduler.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Tabled)]
+pub struct DevLoopMakespan {
+    pub label: String,
+    pub num_threads: usize,
+    /// Time until `cargo check` finishes, i.e. until the user gets their first feedback.
+    pub check: Duration,
+    /// Time taken by `cargo build` after the check.
+    pub build: Duration,
+    pub total: Duration,
+}
+
+pub struct DevLoop {
+    check: DependencyQueueBuilder,
+    check_timings: BTreeMap<Artifact, TimingInfo>,
+    build: DependencyQueueBuilder,
+    build_timings: BTreeMap<Artifact, TimingInfo>,
+}
+
+fn is_shared(artifact: &Artifact, timings: &BTreeMap<Artifact, TimingInfo>) -> bool {
+    match artifact.typ {
+        ArtifactType::BuildScriptBuild | ArtifactType::BuildScriptRun => true,
+        ArtifactType::Link => timings
+            .get(artifact)
+            .is_some_and(|timing| timing.target.is_proc_macro()),
+        ArtifactType::Metadata | ArtifactType::Codegen => false,
+    }
+}
+
+impl DevLoop {
+    pub fn new(deps: &DependencyQueueBuilder, timings: &BTreeMap<Artifact, TimingInfo>) -> Self {
+        // Everything that shared units depend on has to be built in full during the check pass.
+        let mut fully_built: BTreeSet<&Artifact> = BTreeSet::new();
+        let mut stack: Vec<&Artifact> = deps
+            .dep_map
+            .keys()
+            .filter(|artifact| is_shared(artifact, timings))
+            .collect();
+        while let Some(artifact) = stack.pop() {
+            if fully_built.insert(artifact) {
+                stack.extend(deps.dep_map[artifact].iter());
+            }
+        }
+        // A lib is built in full if its codegen is needed, in which case its metadata can be reused too.
+        let reused_metadata: Vec<Artifact> = fully_built
+            .iter()
+            .filter(|artifact| artifact.typ == ArtifactType::Codegen)
+            .map(|artifact| Artifact {
+                typ: ArtifactType::Metadata,
+                ..(*artifact).clone()
+            })
+            .collect();
+        fully_built.extend(reused_metadata.iter());
+
+        let mut check = DependencyQueueBuilder::new();
+        let mut check_timings = BTreeMap::new();
+        let mut build = DependencyQueueBuilder::new();
+        let mut build_timings = BTreeMap::new();
+        for artifact in &deps.insertion_order {
+            let dependencies = &deps.dep_map[artifact];
+            let timing = timings.get(artifact);
+            if fully_built.contains(artifact) {
+                check.queue(artifact.clone(), dependencies.iter().cloned());
+                check_timings.extend(timing.map(|timing| (artifact.clone(), timing.clone())));
+                continue;
+            }
+            build.queue(
+                artifact.clone(),
+                dependencies
+                    .iter()
+                    .filter(|dep| !fully_built.contains(dep))
+                    .cloned(),
+            );
+            build_timings.extend(timing.map(|timing| (artifact.clone(), timing.clone())));
+            if artifact.typ == ArtifactType::Codegen {
+                continue;
+            }
+            // Check-mode units only need metadata of their dependencies and don't codegen anything themselves.
+            check.queue(
+                artifact.clone(),
+                dependencies.iter().map(|dep| match dep.typ {
+                    ArtifactType::Codegen if !fully_built.contains(dep) => Artifact {
+                        typ: ArtifactType::Metadata,
+                        ..dep.clone()
+                    },
+                    _ => dep.clone(),
+                }),
+            );
+            check_timings.extend(timing.map(|timing| {
+                let mut timing = timing.clone();
+                if let Some(rmeta_time) = timing.rmeta_time.take() {
+                    timing.duration = rmeta_time;
+                }
+                (artifact.clone(), timing)
+            }));
+        }
+        Self {
+            check,
+            check_timings,
+            build,
+            build_timings,
+        }
+    }
+
+    /// Simulates the dev loop, with scheduling decisions of both passes made by hint providers created with
+    /// `hints`. Returns the combined timeline of both passes.
+    pub fn run(
+        &self,
+        hints: impl Fn(
+            &DependencyQueueBuilder,
+            &BTreeMap<Artifact, TimingInfo>,
+        ) -> Box<dyn HintProvider>,
+        num_threads: usize,
+    ) -> Result<(DevLoopMakespan, Timings), InputError> {
+        let check_hints = hints(&self.check, &self.check_timings);
+        let label = check_hints.label();
+        let (check, check_timings) = Runner::new(
+            self.check.clone().finish(check_hints),
+            self.check_timings.clone(),
+            num_threads,
+        )
+        .calculate()?;
+        let (build, build_timings) = Runner::new(
+            self.build
+                .clone()
+                .finish(hints(&self.build, &self.build_timings)),
+            self.build_timings.clone(),
+            num_threads,
+        )
+        .calculate()?;
+        Ok((
+            DevLoopMakespan {
+                label,
+                num_threads,
+                total: check.makespan + build.makespan,
+                check: check.makespan,
+                build: build.makespan,
+            },
+            check_timings.followed_by(build_timings),
+        ))
+    }
+}
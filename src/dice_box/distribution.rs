@@ -0,0 +1,77 @@
+//! Spread of makespans, over repeated simulations with randomized scheduling decisions or across schedulers.
+use serde::Serialize;
+use tabled::Tabled;
+
+use crate::runner::Duration;
+
+#[derive(Clone, Debug, PartialEq, Tabled)]
+pub struct MakespanDistribution {
+    pub label: String,
+    pub runs: usize,
+    pub min: Duration,
+    pub median: Duration,
+    pub max: Duration,
+    pub stddev: Duration,
+}
+
+impl MakespanDistribution {
+    /// Summarizes `makespans`, which must not be empty.
+    pub fn new(label: String, mut makespans: Vec<Duration>) -> Self {
+        assert!(!makespans.is_empty(), "no makespans to summarize");
+        makespans.sort();
+        let runs = makespans.len();
+        let seconds: Vec<f64> = makespans.iter().map(Duration::as_secs_f64).collect();
+        let mean = seconds.iter().sum::<f64>() / runs as f64;
+        let variance = seconds
+            .iter()
+            .map(|seconds| (seconds - mean).powi(2))
+            .sum::<f64>()
+            / runs as f64;
+        let median = if runs % 2 == 1 {
+            makespans[runs / 2].0
+        } else {
+            (makespans[runs / 2 - 1].0 + makespans[runs / 2].0) / 2
+        };
+        Self {
+            label,
+            runs,
+            min: makespans[0],
+            median: Duration(median),
+            max: makespans[runs - 1],
+            stddev: Duration::from_secs_f64(variance.sqrt()),
+        }
+    }
+}
+
+/// Best and worst makespan across schedulers, bounding the potential impact of scheduling decisions.
+#[derive(Clone, Debug, PartialEq, Serialize, Tabled)]
+#[serde(rename_all = "kebab-case")]
+pub struct ScheduleSpread {
+    pub best: String,
+    pub best_makespan: Duration,
+    pub worst: String,
+    pub worst_makespan: Duration,
+    pub spread: Duration,
+}
+
+impl ScheduleSpread {
+    /// Finds the best and worst out of `makespans` (labelled), which must not be empty.
+    pub fn new<'a>(makespans: impl IntoIterator<Item = (&'a str, &'a Duration)>) -> Self {
+        let makespans: Vec<_> = makespans.into_iter().collect();
+        let (best, best_makespan) = *makespans
+            .iter()
+            .min_by_key(|(_, makespan)| *makespan)
+            .expect("no makespans to compare");
+        let (worst, worst_makespan) = *makespans
+            .iter()
+            .max_by_key(|(_, makespan)| *makespan)
+            .unwrap();
+        Self {
+            best: best.to_owned(),
+            best_makespan: *best_makespan,
+            worst: worst.to_owned(),
+            worst_makespan: *worst_makespan,
+            spread: *worst_makespan - *best_makespan,
+        }
+    }
+}
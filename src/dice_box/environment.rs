@@ -0,0 +1,137 @@
+//! Fingerprint of the environment a build was captured in, so that results from different environments (toolchains,
+//! targets, profiles, flags) aren't compared as if they were equivalent.
+//!
+//! Targets and profiles are read off the unit graph. The rest can only be observed while capturing a build, so it's
+//! stored in an environment file next to the captured timings:
+//! ```json
+//! {
+//!     "toolchain": "rustc 1.80.0-nightly (72fdf913c 2024-06-05)",
+//!     "host": "x86_64-unknown-linux-gnu",
+//!     "cargo-args": ["--release"],
+//!     "env": { "RUSTFLAGS": "-Ctarget-cpu=native" }
+//! }
+//! ```
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::process::Command;
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::unit_graph::UnitGraph;
+
+/// Environment variables that change what gets built, or how long it takes.
+const RELEVANT_VARIABLES: &[&str] = &[
+    "RUSTFLAGS",
+    "CARGO_ENCODED_RUSTFLAGS",
+    "RUSTC_WRAPPER",
+    "CARGO_INCREMENTAL",
+    "CARGO_BUILD_TARGET",
+];
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Environment {
+    /// Version of the compiler, as reported by `rustc -vV`.
+    pub toolchain: Option<String>,
+    /// Target triple of the machine the build ran on.
+    pub host: Option<String>,
+    /// Target triples units were built for; `host` stands for units built for the host.
+    pub targets: BTreeSet<String>,
+    /// Names of the profiles units were built with.
+    pub profiles: BTreeSet<String>,
+    /// Extra arguments passed to `cargo build`.
+    pub cargo_args: Vec<String>,
+    /// Values of relevant environment variables that were set.
+    pub env: BTreeMap<String, String>,
+}
+
+impl Environment {
+    /// Observes the environment of a build about to be captured with `cargo +nightly build` and extra `cargo_args`.
+    pub fn capture(cargo_args: &[String]) -> Result<Self> {
+        let output = Command::new("rustc").args(["+nightly", "-vV"]).output()?;
+        if !output.status.success() {
+            bail!("rustc -vV failed");
+        }
+        let version = String::from_utf8(output.stdout)?;
+        Ok(Self {
+            toolchain: version.lines().next().map(str::to_owned),
+            host: version
+                .lines()
+                .find_map(|line| line.strip_prefix("host: "))
+                .map(str::to_owned),
+            cargo_args: cargo_args.to_vec(),
+            env: std::env::vars()
+                .filter(|(name, _)| {
+                    RELEVANT_VARIABLES.contains(&name.as_str())
+                        || name.starts_with("CARGO_PROFILE_")
+                })
+                .collect(),
+            ..Default::default()
+        })
+    }
+
+    /// Fills in targets and profiles of units in `graph`.
+    pub fn with_unit_graph(mut self, graph: &UnitGraph) -> Self {
+        for unit in &graph.units {
+            self.targets
+                .insert(unit.platform.clone().unwrap_or_else(|| "host".to_owned()));
+            if let Some(profile) = &unit.profile {
+                self.profiles.insert(profile.name.clone());
+            }
+        }
+        self
+    }
+
+    /// Describes how `other` differs from this environment; empty if they're the same.
+    pub fn differences(&self, other: &Environment) -> Vec<String> {
+        let this = serde_json::to_value(self).unwrap();
+        let other = serde_json::to_value(other).unwrap();
+        this.as_object()
+            .unwrap()
+            .iter()
+            .filter(|(field, value)| other[field.as_str()] != **value)
+            .map(|(field, value)| format!("{field}: {value} vs {}", other[field.as_str()]))
+            .collect()
+    }
+}
+
+impl fmt::Display for Environment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn join<'a>(items: impl IntoIterator<Item = &'a String>) -> String {
+            items
+                .into_iter()
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+                .join(" ")
+        }
+        let mut parts = vec![];
+        if let Some(toolchain) = &self.toolchain {
+            parts.push(toolchain.clone());
+        }
+        if let Some(host) = &self.host {
+            parts.push(format!("host {host}"));
+        }
+        if !self.targets.is_empty() {
+            parts.push(format!("targets {}", join(&self.targets)));
+        }
+        if !self.profiles.is_empty() {
+            parts.push(format!("profiles {}", join(&self.profiles)));
+        }
+        if !self.cargo_args.is_empty() {
+            parts.push(format!("cargo args {}", join(&self.cargo_args)));
+        }
+        for (name, value) in &self.env {
+            parts.push(format!("{name}={value}"));
+        }
+        if parts.is_empty() {
+            return write!(f, "unknown");
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// Deserialize an environment from contents of an environment file.
+pub fn parse_environment(contents: &str) -> serde_json::Result<Environment> {
+    serde_json::from_str(contents)
+}
@@ -0,0 +1,75 @@
+//! Errors in the inputs of dice_box, which users can fix themselves: they say what's wrong and where, so the binary
+//! can report them without a backtrace. The binary adds the path of the offending file.
+use std::fmt;
+
+#[derive(Debug)]
+pub enum InputError {
+    /// Contents are malformed at a given line and column, both starting at 1.
+    Malformed {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    /// Contents are well-formed, but don't fit the build, e.g. because they refer to units that are not in it.
+    Invalid(Vec<String>),
+}
+
+impl InputError {
+    /// A problem with contents as a whole, rather than a particular spot in them.
+    pub(crate) fn invalid(problem: impl fmt::Display) -> Self {
+        Self::Invalid(vec![problem.to_string()])
+    }
+
+    /// Reports an error of JSON on a given `line` of a larger file, with a JSON value on each line.
+    pub(crate) fn on_line(line: usize, error: serde_json::Error) -> Self {
+        match Self::from(error) {
+            Self::Malformed {
+                column, message, ..
+            } => Self::Malformed {
+                line,
+                column,
+                message,
+            },
+            invalid => invalid,
+        }
+    }
+}
+
+impl From<serde_json::Error> for InputError {
+    fn from(error: serde_json::Error) -> Self {
+        // The location is reported separately.
+        let location = format!(" at line {} column {}", error.line(), error.column());
+        let message = error.to_string();
+        Self::Malformed {
+            line: error.line(),
+            column: error.column(),
+            message: message
+                .strip_suffix(&location)
+                .unwrap_or(&message)
+                .to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for InputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Malformed {
+                line,
+                column,
+                message,
+            } => write!(f, "line {line}, column {column}: {message}"),
+            Self::Invalid(problems) => match problems.as_slice() {
+                [problem] => write!(f, "{problem}"),
+                problems => write!(
+                    f,
+                    "{} problems:\n  {}",
+                    problems.len(),
+                    problems.join("\n  ")
+                ),
+            },
+        }
+    }
+}
+
+impl std::error::Error for InputError {}
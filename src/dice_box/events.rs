@@ -0,0 +1,83 @@
+//! Parser for the external events file.
+//!
+//! External events model things happening on the build machine outside of cargo's control, so that the wall time
+//! of a whole CI job can be simulated and not just the cargo portion of it. An example events file:
+//! ```json
+//! [
+//!     { "label": "docker image pull finishes", "at": 30.0, "cores": 8 },
+//!     { "label": "nightly toolchain install", "at": 0.0, "cores": -1, "duration": 40.0 }
+//! ]
+//! ```
+use serde::Deserialize;
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct ExternalEvent {
+    pub label: String,
+    /// Time at which the event happens, in seconds.
+    pub at: f64,
+    /// Change in the number of cores available to cargo. Negative values represent cores taken up by the event.
+    #[serde(default)]
+    pub cores: i64,
+    /// If set, the change in available cores is reverted after this many seconds.
+    #[serde(default)]
+    pub duration: Option<f64>,
+}
+
+impl ExternalEvent {
+    /// Changes in the number of available cores caused by this event, as (time in milliseconds, delta) pairs.
+    pub(crate) fn capacity_changes(&self) -> Vec<(u64, i64)> {
+        let start = (self.at * 1000.) as u64;
+        let mut changes = vec![(start, self.cores)];
+        if let Some(duration) = self.duration {
+            changes.push((start + (duration * 1000.) as u64, -self.cores));
+        }
+        changes
+    }
+
+    /// Time (in milliseconds) until which the event keeps the machine busy, if it's work on its own rather than
+    /// just a change in available resources.
+    pub(crate) fn busy_until(&self) -> Option<u64> {
+        let duration = self.duration.filter(|_| self.cores <= 0)?;
+        Some(((self.at + duration) * 1000.) as u64)
+    }
+}
+
+impl std::fmt::Display for ExternalEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.1}s: {}", self.at, self.label)?;
+        if self.cores != 0 {
+            write!(f, " ({:+} cores", self.cores)?;
+            if let Some(duration) = self.duration {
+                write!(f, " for {duration:.1}s")?;
+            }
+            write!(f, ")")?;
+        }
+        Ok(())
+    }
+}
+
+/// Events that make `cores` available at given times (in seconds), such as a CI runner scaling up, for a build
+/// that starts out with `num_threads` cores. Each point sets the number of cores rather than changing it.
+pub fn core_timeline(points: &[(f64, usize)], num_threads: usize) -> Vec<ExternalEvent> {
+    let mut sorted = points.to_vec();
+    sorted.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut previous = num_threads as i64;
+    sorted
+        .into_iter()
+        .map(|(at, cores)| {
+            let event = ExternalEvent {
+                label: format!("{cores} cores available"),
+                at,
+                cores: cores as i64 - previous,
+                duration: None,
+            };
+            previous = cores as i64;
+            event
+        })
+        .collect()
+}
+
+/// Deserialize external events from contents of an events file.
+pub fn parse_events(contents: &str) -> serde_json::Result<Vec<ExternalEvent>> {
+    serde_json::from_str(contents)
+}
@@ -0,0 +1,97 @@
+//! Focused diagnostic for "why does my crate build so late?".
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::{
+    artifact::Artifact, constraints::matches, critical_path::critical_path_lengths,
+    dependency_queue::DependencyQueueBuilder, runner::ScheduledTask, timings::TimingInfo,
+};
+
+/// Explanation of how a single unit was scheduled. All times are in seconds since the start of the build.
+#[derive(Clone, Debug, Serialize)]
+pub struct UnitExplanation {
+    pub artifact: Artifact,
+    /// Time at which all dependencies of the unit were done.
+    pub ready: f64,
+    pub start: f64,
+    pub end: f64,
+    /// Dependencies that finished last, making the unit ready.
+    pub waited_on: Vec<Artifact>,
+    /// Time the unit spent ready, but waiting for a free core.
+    pub waited_for_core: f64,
+    /// Units that became ready once this unit was done.
+    pub unlocked: Vec<Artifact>,
+    /// How much later the unit could have started without extending the build, assuming that everything after it
+    /// runs as soon as its dependencies allow.
+    pub slack: f64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ScenarioExplanation {
+    pub label: String,
+    pub units: Vec<UnitExplanation>,
+}
+
+pub fn explain(
+    package: &str,
+    schedule: &[ScheduledTask],
+    deps: &DependencyQueueBuilder,
+    timings: &BTreeMap<Artifact, TimingInfo>,
+) -> Vec<UnitExplanation> {
+    let tasks: BTreeMap<&Artifact, &ScheduledTask> =
+        schedule.iter().map(|task| (&task.artifact, task)).collect();
+    let makespan = schedule
+        .iter()
+        .map(|task| task.end)
+        .max()
+        .unwrap_or_default();
+    let critical_paths = critical_path_lengths(deps, timings);
+    let ready_time = |artifact: &Artifact| {
+        deps.dep_map
+            .get(artifact)
+            .into_iter()
+            .flatten()
+            .filter_map(|dep| tasks.get(dep).map(|task| task.end))
+            .max()
+            .unwrap_or_default()
+    };
+    let seconds = |time: u64| time as f64 / 1000.;
+    schedule
+        .iter()
+        .filter(|task| matches(&task.artifact.package_id, package))
+        .map(|task| {
+            let ready = ready_time(&task.artifact);
+            let waited_on = deps
+                .dep_map
+                .get(&task.artifact)
+                .into_iter()
+                .flatten()
+                .filter(|dep| tasks.get(dep).is_some_and(|dep| dep.end == ready))
+                .cloned()
+                .collect();
+            let unlocked = deps
+                .reverse_dep_map
+                .get(&task.artifact)
+                .into_iter()
+                .flatten()
+                .filter(|dependant| ready_time(dependant) == task.end)
+                .cloned()
+                .collect();
+            let critical_path = critical_paths
+                .get(&task.artifact)
+                .copied()
+                .unwrap_or_default();
+            UnitExplanation {
+                artifact: task.artifact.clone(),
+                ready: seconds(ready),
+                start: seconds(task.start),
+                end: seconds(task.end),
+                waited_on,
+                waited_for_core: seconds(task.start - ready),
+                unlocked,
+                slack: seconds(makespan.saturating_sub(task.start + critical_path)),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,135 @@
+//! Schedules computed by other tools (or recorded from a real build system), so that they can be evaluated on the
+//! same terms as the schedulers of dice_box. An external schedule says when and on which thread each unit starts:
+//! ```json
+//! {
+//!     "label": "ILP solver",
+//!     "units": [
+//!         { "unit": "syn:metadata", "start": 0.0, "thread": 0 },
+//!         { "unit": "serde:metadata", "start": 1.25, "thread": 1 }
+//!     ]
+//! }
+//! ```
+//! Units are given as `<package>:<type>`, with packages referred to by their full package id or just by their name.
+//! Start times are in seconds. Codegen units are always started as soon as they're ready, so they can be left out.
+//!
+//! The schedule is simulated with the durations of dice_box, not with the ones its author had in mind: each unit
+//! starts at its start time on its thread, or later if its dependencies or its thread are not done by then.
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+
+use crate::{
+    artifact::{Artifact, ArtifactType},
+    dependency_queue::DependencyQueueBuilder,
+    error::InputError,
+    hint_file::{parse_unit, resolve_unit},
+    timings::TimingInfo,
+};
+
+#[derive(Deserialize)]
+struct ExternalScheduleFile {
+    label: Option<String>,
+    units: Vec<ExternalUnit>,
+}
+
+#[derive(Deserialize)]
+struct ExternalUnit {
+    unit: String,
+    start: f64,
+    thread: usize,
+}
+
+/// When and where a unit starts in an external schedule.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FixedStart {
+    /// In milliseconds since the start of the build.
+    pub start: u64,
+    pub thread: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct ExternalSchedule {
+    pub label: Option<String>,
+    /// Number of threads the schedule was made for.
+    pub num_threads: usize,
+    pub units: BTreeMap<Artifact, FixedStart>,
+}
+
+impl ExternalSchedule {
+    /// Units in the order they start in.
+    pub fn order(&self) -> Vec<Artifact> {
+        let mut units: Vec<(&Artifact, &FixedStart)> = self.units.iter().collect();
+        units.sort_by_key(|(_, fixed)| (fixed.start, fixed.thread));
+        units
+            .into_iter()
+            .map(|(artifact, _)| artifact.clone())
+            .collect()
+    }
+}
+
+/// Reads an external schedule for a build with `num_threads` threads and checks it against the unit graph: every
+/// unit (save for codegen ones) has to be scheduled exactly once, on one of the threads, and no sooner than its
+/// dependencies.
+pub fn parse_external_schedule(
+    contents: &str,
+    deps: &DependencyQueueBuilder,
+    timings: &BTreeMap<Artifact, TimingInfo>,
+    num_threads: usize,
+) -> Result<ExternalSchedule, InputError> {
+    let file: ExternalScheduleFile = serde_json::from_str(contents)?;
+    let mut problems = vec![];
+    let mut units = BTreeMap::new();
+    for entry in file.units {
+        let artifact = match parse_unit(&entry.unit).and_then(|unit| resolve_unit(unit, timings)) {
+            Ok(artifact) => artifact,
+            Err(err) => {
+                problems.push(format!("{}: {err}", entry.unit));
+                continue;
+            }
+        };
+        if artifact.typ == ArtifactType::Codegen {
+            continue;
+        }
+        if entry.thread >= num_threads {
+            problems.push(format!(
+                "{artifact} runs on thread {}, but there are only {num_threads}",
+                entry.thread
+            ));
+        }
+        if !entry.start.is_finite() || entry.start < 0. {
+            problems.push(format!("{artifact} starts at {}s", entry.start));
+        }
+        let fixed = FixedStart {
+            start: (entry.start * 1000.) as u64,
+            thread: entry.thread,
+        };
+        if units.insert(artifact.clone(), fixed).is_some() {
+            problems.push(format!("{artifact} is scheduled more than once"));
+        }
+    }
+    for (artifact, dependencies) in &deps.dep_map {
+        let Some(fixed) = units.get(artifact) else {
+            if artifact.typ != ArtifactType::Codegen {
+                problems.push(format!("{artifact} is not scheduled"));
+            }
+            continue;
+        };
+        for dependency in dependencies {
+            if let Some(dependency_start) = units.get(dependency) {
+                if dependency_start.start > fixed.start {
+                    problems.push(format!(
+                        "{artifact} starts before its dependency {dependency}"
+                    ));
+                }
+            }
+        }
+    }
+    if !problems.is_empty() {
+        return Err(InputError::Invalid(problems));
+    }
+    Ok(ExternalSchedule {
+        label: file.label,
+        num_threads,
+        units,
+    })
+}
@@ -0,0 +1,133 @@
+//! Differences between two unit graphs of the same project (e.g. before and after removing a dependency), along with
+//! what they do to the makespan, so that changes to dependencies can come with their predicted impact on build times.
+use std::collections::{BTreeMap, BTreeSet};
+
+use tabled::Tabled;
+
+use crate::{
+    artifact::Artifact,
+    calibration::display_seconds,
+    critical_path::critical_path_lengths,
+    dependency_queue::DependencyQueueBuilder,
+    error::InputError,
+    registry::Registry,
+    runner::{Duration, ScheduledTask},
+    timings::TimingInfo,
+    Resources,
+};
+
+/// A unit or a dependency between two units that is only present in one of the graphs.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Tabled)]
+pub struct GraphChange {
+    /// `added` or `removed`.
+    pub change: String,
+    pub unit: String,
+    /// Dependency of the unit that was added or removed; empty if the unit itself was.
+    pub dependency: String,
+}
+
+/// Makespan of both graphs under the same scheduler.
+#[derive(Clone, Debug, PartialEq, Tabled)]
+pub struct MakespanChange {
+    pub scheduler: String,
+    pub before: Duration,
+    pub after: Duration,
+    /// How much longer the build of the changed graph takes, in seconds; negative if it's faster.
+    #[tabled(display_with = "display_seconds")]
+    pub change: f64,
+}
+
+/// Units and dependencies that are only present in either `before` or `after`.
+pub fn diff_graphs(
+    before: &DependencyQueueBuilder,
+    after: &DependencyQueueBuilder,
+) -> Vec<GraphChange> {
+    let edges = |deps: &DependencyQueueBuilder| -> BTreeSet<(Artifact, Artifact)> {
+        deps.dep_map
+            .iter()
+            .flat_map(|(unit, dependencies)| {
+                dependencies
+                    .iter()
+                    .map(move |dependency| (unit.clone(), dependency.clone()))
+            })
+            .collect()
+    };
+    let (edges_before, edges_after) = (edges(before), edges(after));
+    let units = |deps: &DependencyQueueBuilder| deps.units().cloned().collect::<BTreeSet<_>>();
+    let (units_before, units_after) = (units(before), units(after));
+    let mut changes = vec![];
+    for (change, units, other) in [
+        ("added", &units_after, &units_before),
+        ("removed", &units_before, &units_after),
+    ] {
+        changes.extend(units.difference(other).map(|unit| GraphChange {
+            change: change.into(),
+            unit: unit.to_string(),
+            dependency: String::new(),
+        }));
+    }
+    for (change, edges, other) in [
+        ("added", &edges_after, &edges_before),
+        ("removed", &edges_before, &edges_after),
+    ] {
+        changes.extend(
+            edges
+                .difference(other)
+                .map(|(unit, dependency)| GraphChange {
+                    change: change.into(),
+                    unit: unit.to_string(),
+                    dependency: dependency.to_string(),
+                }),
+        );
+    }
+    changes
+}
+
+/// Simulates both graphs with each of the `schedulers` from `registry` on `num_threads` threads. The critical path
+/// of both graphs is reported first, as the part of the change that's down to the shape of the graph alone.
+pub fn compare_graphs(
+    before: (&DependencyQueueBuilder, &BTreeMap<Artifact, TimingInfo>),
+    after: (&DependencyQueueBuilder, &BTreeMap<Artifact, TimingInfo>),
+    registry: &Registry,
+    schedulers: &[String],
+    num_threads: usize,
+) -> Result<Vec<MakespanChange>, InputError> {
+    let change = |scheduler: String, before: u64, after: u64| MakespanChange {
+        scheduler,
+        before: Duration::from_millis(before),
+        after: Duration::from_millis(after),
+        change: (after as f64 - before as f64) / 1000.,
+    };
+    let critical_path = |(deps, timings)| {
+        critical_path_lengths(deps, timings)
+            .into_values()
+            .max()
+            .unwrap_or_default()
+    };
+    let resources = Resources {
+        num_threads,
+        ..Default::default()
+    };
+    let makespan = |(deps, timings), scheduler: &str| -> Result<u64, InputError> {
+        let schedule =
+            crate::simulate(deps, timings, registry.get(scheduler).unwrap(), &resources)?;
+        Ok(schedule
+            .iter()
+            .map(|task: &ScheduledTask| task.end)
+            .max()
+            .unwrap_or_default())
+    };
+    std::iter::once(Ok(change(
+        "Critical path".into(),
+        critical_path(before),
+        critical_path(after),
+    )))
+    .chain(schedulers.iter().map(|scheduler| {
+        Ok(change(
+            scheduler.clone(),
+            makespan(before, scheduler)?,
+            makespan(after, scheduler)?,
+        ))
+    }))
+    .collect()
+}
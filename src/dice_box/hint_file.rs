@@ -0,0 +1,261 @@
+//! Hint files, carrying the priorities of a simulated schedule over to a real build system.
+//!
+//! A hint file lists units in the order they should be picked in. As timings of real builds vary from run to run,
+//! each entry comes with a confidence: the share of jitter trials (simulations with randomly perturbed durations)
+//! in which following that entry made the build shorter than leaving it out, i.e. letting Cargo's own heuristic make
+//! the decisions while that unit is ready. A consumer can then do the same for low-confidence entries.
+//!
+//! The format is meant to be read by a patched Cargo, and changes to it bump `format-version`:
+//! ```json
+//! {
+//!   "format-version": 1,
+//!   "scenario": "Critical Path",
+//!   "num-threads": 10,
+//!   "makespan": 55.937,
+//!   "hints": [
+//!     { "package-id": "registry+https://github.com/rust-lang/crates.io-index#syn@2.0.38", "artifact": "metadata", "priority": 2, "confidence": 0.9 },
+//!     { "package-id": "path+file:///ws/crates/api#0.1.0", "artifact": "link", "priority": 1, "confidence": 0.4 }
+//!   ]
+//! }
+//! ```
+//! Package ids are the ones of the unit graph, and units with higher priority are picked first, as in Cargo's own
+//! dependency queue. Codegen units are always started as soon as they're ready, so they're never hinted.
+//!
+//! Hint files can also be fed back with `--hints-file`, to simulate hand-crafted or externally computed orders. Next
+//! to JSON, a plain text format with one `<package>:<type>` line per unit is accepted:
+//! ```text
+//! # Comments and blank lines are skipped.
+//! syn:metadata
+//! serde_derive 1.0.188 (registry+https://github.com/rust-lang/crates.io-index):link
+//! ```
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    artifact::{Artifact, ArtifactType},
+    constraints::matches,
+    dependency_queue::{CargoHints, DependencyQueueBuilder},
+    error::InputError,
+    hints::PriorityListHints,
+    rng::Rng,
+    runner::{calculate_all, Runner, ScheduledTask},
+    timings::TimingInfo,
+};
+
+/// Version of the hint file format written by [emit_hints].
+pub const HINTS_FORMAT_VERSION: u32 = 1;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct HintsFile {
+    pub format_version: u32,
+    /// Label of the scenario whose order is hinted.
+    pub scenario: String,
+    pub num_threads: usize,
+    /// Simulated makespan of the scenario, in seconds.
+    pub makespan: f64,
+    pub hints: Vec<HintEntry>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct HintEntry {
+    pub package_id: String,
+    #[serde(
+        serialize_with = "serialize_type",
+        deserialize_with = "deserialize_type"
+    )]
+    pub artifact: ArtifactType,
+    /// Units with higher priority are picked first.
+    pub priority: usize,
+    pub confidence: f64,
+}
+
+fn serialize_type<S: Serializer>(typ: &ArtifactType, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(typ.name())
+}
+
+fn deserialize_type<'de, D: Deserializer<'de>>(deserializer: D) -> Result<ArtifactType, D::Error> {
+    String::deserialize(deserializer)?
+        .parse()
+        .map_err(serde::de::Error::custom)
+}
+
+/// Emits hints following the order in which units were started in `schedule` of a scenario labeled `scenario`,
+/// with confidences estimated over `trials` simulations in which each duration is scaled by a random factor from
+/// `1 - jitter..1 + jitter`. Each trial simulates the build once with all hints and once without each of them.
+pub fn emit_hints(
+    scenario: &str,
+    schedule: &[ScheduledTask],
+    deps: &DependencyQueueBuilder,
+    timings: &BTreeMap<Artifact, TimingInfo>,
+    num_threads: usize,
+    trials: usize,
+    jitter: f64,
+) -> Result<HintsFile, InputError> {
+    let mut tasks: Vec<&ScheduledTask> = schedule.iter().collect();
+    tasks.sort_by_key(|task| task.start);
+    // Codegen units are always started as soon as they're ready, so there's nothing to hint.
+    let order: Vec<Artifact> = tasks
+        .into_iter()
+        .filter(|task| task.artifact.typ != ArtifactType::Codegen)
+        .map(|task| task.artifact.clone())
+        .collect();
+    let runner = |order: &[Artifact], timings: &BTreeMap<Artifact, TimingInfo>| {
+        let hints = PriorityListHints::new(order, CargoHints::new(deps));
+        Runner::new(deps.clone().finish(hints), timings.clone(), num_threads)
+    };
+    let batch = std::thread::available_parallelism().map_or(1, |cores| cores.get());
+    let mut rng = Rng::new(0);
+    let mut improvements = vec![0; order.len()];
+    for _ in 0..trials {
+        let mut jittered = timings.clone();
+        for timing in jittered.values_mut() {
+            let factor = 1. - jitter + 2. * jitter * (rng.next_u64() as f64 / u64::MAX as f64);
+            timing.duration *= factor;
+            timing.rmeta_time = timing.rmeta_time.map(|rmeta_time| rmeta_time * factor);
+        }
+        let (with_all, _) = runner(&order, &jittered).calculate()?;
+        // There's a simulation without each of the hints, so they are run in parallel.
+        for (chunk, improvements) in improvements.chunks_mut(batch).enumerate() {
+            let mut scenarios: Vec<Runner> = (0..improvements.len())
+                .map(|offset| {
+                    let mut without = order.clone();
+                    without.remove(chunk * batch + offset);
+                    runner(&without, &jittered)
+                })
+                .collect();
+            for (improvement, (without, _)) in
+                improvements.iter_mut().zip(calculate_all(&mut scenarios)?)
+            {
+                if with_all.makespan < without.makespan {
+                    *improvement += 1;
+                }
+            }
+        }
+    }
+    let priorities = (1..=order.len()).rev();
+    let hints = order
+        .into_iter()
+        .zip(improvements)
+        .zip(priorities)
+        .map(|((artifact, improvement), priority)| HintEntry {
+            package_id: artifact.package_id,
+            artifact: artifact.typ,
+            priority,
+            confidence: if trials == 0 {
+                0.
+            } else {
+                improvement as f64 / trials as f64
+            },
+        })
+        .collect();
+    Ok(HintsFile {
+        format_version: HINTS_FORMAT_VERSION,
+        scenario: scenario.to_owned(),
+        num_threads,
+        makespan: schedule
+            .iter()
+            .map(|task| task.end)
+            .max()
+            .unwrap_or_default() as f64
+            / 1000.,
+        hints,
+    })
+}
+
+/// Reads the order of units from contents of a hint file, in either format. Packages can be given by their full
+/// package id or just by their name, as long as that's unambiguous; they're resolved against units in `timings`.
+pub fn parse_hints_file(
+    contents: &str,
+    timings: &BTreeMap<Artifact, TimingInfo>,
+) -> Result<Vec<Artifact>, InputError> {
+    let entries: Vec<Artifact> = if contents.trim_start().starts_with('{') {
+        let file: HintsFile = serde_json::from_str(contents)?;
+        if file.format_version > HINTS_FORMAT_VERSION {
+            return Err(InputError::invalid(format!(
+                "hint file format version {} is newer than the supported {HINTS_FORMAT_VERSION}",
+                file.format_version
+            )));
+        }
+        let mut hints = file.hints;
+        // Entries are expected in the order of priorities, but the priorities are what counts.
+        hints.sort_by_key(|hint| std::cmp::Reverse(hint.priority));
+        hints
+            .into_iter()
+            .map(|hint| Artifact {
+                typ: hint.artifact,
+                package_id: hint.package_id,
+            })
+            .collect()
+    } else {
+        contents
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+            .map(|(index, line)| {
+                parse_unit(line).map_err(|err| InputError::Malformed {
+                    line: index + 1,
+                    column: 1,
+                    message: err.to_string(),
+                })
+            })
+            .collect::<Result<_, _>>()?
+    };
+    let mut problems = vec![];
+    let units = entries
+        .into_iter()
+        .filter_map(|entry| {
+            resolve_unit(entry, timings)
+                .map_err(|err| problems.push(err.to_string()))
+                .ok()
+        })
+        .collect();
+    if !problems.is_empty() {
+        return Err(InputError::Invalid(problems));
+    }
+    Ok(units)
+}
+
+/// Parses a unit given as `<package>:<type>`, e.g. `syn:metadata`.
+pub(crate) fn parse_unit(unit: &str) -> Result<Artifact> {
+    // Package ids contain colons of their own, so the type is after the last one.
+    let (package, typ) = unit
+        .rsplit_once(':')
+        .with_context(|| format!("expected `<package>:<type>`, got `{unit}`"))?;
+    Ok(Artifact {
+        typ: typ.trim().parse().map_err(anyhow::Error::msg)?,
+        package_id: package.trim().to_owned(),
+    })
+}
+
+/// Finds the unit of `timings` that `entry` refers to, by its full package id or just by its name.
+pub(crate) fn resolve_unit(
+    entry: Artifact,
+    timings: &BTreeMap<Artifact, TimingInfo>,
+) -> Result<Artifact> {
+    if timings.contains_key(&entry) {
+        return Ok(entry);
+    }
+    let mut matching = timings
+        .keys()
+        .filter(|artifact| {
+            artifact.typ == entry.typ && matches(&artifact.package_id, &entry.package_id)
+        })
+        .cloned();
+    match (matching.next(), matching.next()) {
+        (Some(artifact), None) => Ok(artifact),
+        (None, _) => bail!(
+            "no {:?} unit of `{}` in the build",
+            entry.typ,
+            entry.package_id
+        ),
+        (Some(_), Some(_)) => bail!(
+            "`{}` matches several packages, use a full package id",
+            entry.package_id
+        ),
+    }
+}
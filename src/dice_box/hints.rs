@@ -1,36 +1,261 @@
 use std::collections::{BTreeMap, BTreeSet, HashMap};
 
+use clap::ValueEnum;
 use ordered_float::OrderedFloat;
+use tabled::Tabled;
 
 use crate::{
     artifact::{Artifact, ArtifactType},
+    critical_path::{critical_path_lengths, duration_ms},
     dependency_queue::DependencyQueueBuilder,
+    error::InputError,
+    rng::Rng,
+    runner::{Duration, Runner},
     timings::TimingInfo,
-    CargoHints,
 };
+/// State of the simulated build at the time of a scheduling decision.
+#[derive(Clone, Debug)]
+pub struct SchedulerContext<'a> {
+    /// Simulated time of the decision, in milliseconds since the start of the build.
+    pub now: u64,
+    /// Units that are currently running, along with the time (in milliseconds) left until each of them finishes.
+    pub running: Vec<(&'a Artifact, u64)>,
+    /// Number of threads that are free, including the one the decision is made for.
+    pub idle_slots: usize,
+    /// Speed of the core the decision is made for, relative to the one timings were recorded on.
+    pub core_speed: f64,
+    /// Speed of each busy core, along with the time (in milliseconds) left until it frees up.
+    pub busy_cores: Vec<(f64, u64)>,
+    /// Number of units that could be started right now.
+    pub ready: usize,
+}
+
+impl Default for SchedulerContext<'_> {
+    fn default() -> Self {
+        Self {
+            now: 0,
+            running: vec![],
+            idle_slots: 0,
+            core_speed: 1.,
+            busy_cores: vec![],
+            ready: 0,
+        }
+    }
+}
+
+/// What a hint provider wants done with a free thread.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Suggestion<T> {
+    /// Start a given unit on it.
+    Start(T),
+    /// Deliberately leave it idle until the next running unit finishes, e.g. to hold it for a critical unit that is
+    /// about to become ready. This is only honored while some unit is running, as otherwise there's nothing to wait
+    /// for; in that case [HintProvider::suggest_next] is consulted instead.
+    Wait,
+}
+
 /// Whenever Runner has a scheduling decision to make, it will consult it's hint provider.
-pub trait HintProvider: std::fmt::Debug {
-    fn suggest_next<'a>(&mut self, timings: &[&'a Artifact]) -> Option<&'a Artifact>;
+///
+/// Scenarios are simulated on separate threads, so hint providers must not share mutable state with one another.
+pub trait HintProvider: std::fmt::Debug + Send {
+    fn suggest_next<'a>(
+        &mut self,
+        timings: &[&'a Artifact],
+        context: &SchedulerContext,
+    ) -> Option<&'a Artifact>;
     fn label(&self) -> String;
+
+    /// Like [HintProvider::suggest_next], but may also decide to leave the thread idle. Providers that never do so
+    /// don't need to implement it.
+    fn decide<'a>(
+        &mut self,
+        timings: &[&'a Artifact],
+        context: &SchedulerContext,
+    ) -> Option<Suggestion<&'a Artifact>> {
+        self.suggest_next(timings, context).map(Suggestion::Start)
+    }
+
+    /// Value `artifact` is ranked by when picking between ready units, for decision logs. Whether higher or lower
+    /// values win is up to the provider; ones that don't rank units by a single value don't need to implement it.
+    fn score(&self, _artifact: &Artifact) -> Option<f64> {
+        None
+    }
+
+    /// Whether `artifact` may run on a core of a given `speed` (relative to the one timings were recorded on), for
+    /// providers that pick the class of core units run on. Declined units wait for a core they accept.
+    fn accepts_core(&self, _artifact: &Artifact, _speed: f64) -> bool {
+        true
+    }
+
+    /// Number of threads to give to the frontend of `artifact` when rustc can run it in parallel (see
+    /// [ParallelFrontend](crate::ParallelFrontend)); the runner caps it at the number of idle threads. By default the
+    /// idle threads are split evenly between the ready units.
+    fn threads_for(&self, _artifact: &Artifact, context: &SchedulerContext) -> usize {
+        (context.idle_slots / context.ready.max(1)).max(1)
+    }
+
+    /// Why the provider gave up on making decisions, if it did (e.g. because a script it runs failed). From then on
+    /// it starts ready units in the order they're given, so the simulation finishes but says nothing about it.
+    fn failure(&self) -> Option<String> {
+        None
+    }
+}
+
+/// Number of the longest units that [NHintsProvider] orders by default.
+pub const DEFAULT_N_HINTS: usize = 75;
+
+/// Makespan of N-Hints with a given N.
+#[derive(Clone, Debug, PartialEq, Tabled)]
+pub struct NHintsSweepPoint {
+    pub n: usize,
+    pub makespan: Duration,
+}
+
+/// How N-Hints picks between ready units that are equally close to the front of its list.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum NHintsTieBreak {
+    /// Whichever was queued first.
+    #[default]
+    QueueOrder,
+    /// The longest one.
+    Longest,
+    /// The one with the most transitive dependants.
+    MostDependants,
+}
+
+/// Parameters of [NHintsProvider].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NHintsParams {
+    /// Number of the longest units to order up front.
+    pub n: usize,
+    pub tie_break: NHintsTieBreak,
+    /// Whether metadata units are weighed together with their codegen, as rustc runs both in one go.
+    pub bundle_codegen: bool,
+}
+
+impl Default for NHintsParams {
+    fn default() -> Self {
+        Self {
+            n: DEFAULT_N_HINTS,
+            tie_break: NHintsTieBreak::default(),
+            bundle_codegen: true,
+        }
+    }
+}
+
+impl std::fmt::Display for NHintsParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "N={}", self.n)?;
+        if self.tie_break != NHintsTieBreak::default() {
+            let tie_break = self.tie_break.to_possible_value().unwrap();
+            write!(f, ", tie-break={}", tie_break.get_name())?;
+        }
+        if !self.bundle_codegen {
+            write!(f, ", separate codegen")?;
+        }
+        Ok(())
+    }
+}
+
+/// Makespan of N-Hints with given parameters.
+#[derive(Clone, Debug, PartialEq, Tabled)]
+pub struct NHintsScore {
+    pub n: usize,
+    #[tabled(display_with = "display_tie_break")]
+    pub tie_break: NHintsTieBreak,
+    pub bundle_codegen: bool,
+    pub makespan: Duration,
+}
+
+fn display_tie_break(tie_break: &NHintsTieBreak) -> String {
+    tie_break.to_possible_value().unwrap().get_name().to_owned()
+}
+
+impl NHintsScore {
+    pub fn params(&self) -> NHintsParams {
+        NHintsParams {
+            n: self.n,
+            tie_break: self.tie_break,
+            bundle_codegen: self.bundle_codegen,
+        }
+    }
+}
+
+/// Simulates N-Hints with every combination of list sizes from `ns`, tie-break policies and codegen bundling, and
+/// returns the results from the shortest makespan to the longest.
+pub fn tune_n_hints(
+    deps: &DependencyQueueBuilder,
+    timings: &BTreeMap<Artifact, TimingInfo>,
+    num_threads: usize,
+    ns: &[usize],
+) -> Result<Vec<NHintsScore>, InputError> {
+    let mut scores: Vec<NHintsScore> =
+        itertools::iproduct!(ns, NHintsTieBreak::value_variants(), [true, false])
+            .map(|(&n, &tie_break, bundle_codegen)| {
+                let params = NHintsParams {
+                    n,
+                    tie_break,
+                    bundle_codegen,
+                };
+                let hints = NHintsProvider::with_params(deps, timings, params);
+                let mut runner =
+                    Runner::new(deps.clone().finish(hints), timings.clone(), num_threads);
+                Ok(NHintsScore {
+                    n,
+                    tie_break,
+                    bundle_codegen,
+                    makespan: runner.calculate()?.0.makespan,
+                })
+            })
+            .collect::<Result<_, InputError>>()?;
+    scores.sort_by_key(|score| score.makespan);
+    Ok(scores)
 }
 
+/// Orders the N longest units up front, keeping dependencies of each ahead of it, and picks ready units by how
+/// close they are to the front of that list.
 #[derive(Debug)]
 pub struct NHintsProvider {
+    params: NHintsParams,
     n_hints: Vec<Artifact>,
-    inner: Box<dyn HintProvider>,
     reverse_dependencies: BTreeMap<Artifact, BTreeSet<Artifact>>,
     timings: BTreeMap<Artifact, TimingInfo>,
 }
 
 impl NHintsProvider {
+    #[allow(clippy::new_ret_no_self)]
     pub fn new(
         dependencies: &DependencyQueueBuilder,
         timings: &BTreeMap<Artifact, TimingInfo>,
+    ) -> Box<dyn HintProvider> {
+        Self::with_n(dependencies, timings, DEFAULT_N_HINTS)
+    }
+
+    /// Like [NHintsProvider::new], but orders the `n` longest units up front instead of [DEFAULT_N_HINTS].
+    pub fn with_n(
+        dependencies: &DependencyQueueBuilder,
+        timings: &BTreeMap<Artifact, TimingInfo>,
+        n: usize,
+    ) -> Box<dyn HintProvider> {
+        Self::with_params(
+            dependencies,
+            timings,
+            NHintsParams {
+                n,
+                ..Default::default()
+            },
+        )
+    }
+
+    pub fn with_params(
+        dependencies: &DependencyQueueBuilder,
+        timings: &BTreeMap<Artifact, TimingInfo>,
+        params: NHintsParams,
     ) -> Box<dyn HintProvider> {
         let old_timings = timings;
         let mut timings = timings.clone();
         for entry in timings.iter_mut() {
-            if entry.0.typ == ArtifactType::Metadata {
+            if params.bundle_codegen && entry.0.typ == ArtifactType::Metadata {
                 if let Some(codegen_timing) = old_timings.get(&Artifact {
                     typ: ArtifactType::Codegen,
                     package_id: entry.0.package_id.clone(),
@@ -41,7 +266,7 @@ impl NHintsProvider {
         }
 
         timings.retain(|k, v| {
-            if k.typ == ArtifactType::Metadata {
+            if params.bundle_codegen && k.typ == ArtifactType::Metadata {
                 if let Some(codegen_timing) = old_timings.get(&Artifact {
                     typ: ArtifactType::Codegen,
                     package_id: k.package_id.clone(),
@@ -57,7 +282,7 @@ impl NHintsProvider {
             .into_iter()
             .map(|(_, artifact)| artifact.clone())
             .rev()
-            .take(75)
+            .take(params.n)
             .collect();
         top_n_entries.sort_by_cached_key(|n| {
             dependencies
@@ -115,23 +340,33 @@ impl NHintsProvider {
             n_hints.insert(insertion_index, item);
         }
 
-        let inner = CargoHints::new(dependencies);
-
         Box::new(Self {
+            params,
             timings,
             n_hints,
-            inner,
             reverse_dependencies,
         })
     }
 }
+impl NHintsProvider {
+    /// Higher scores win ties under the tie-break policy.
+    fn tie_break_score(&self, artifact: &Artifact) -> OrderedFloat<f64> {
+        OrderedFloat(match self.params.tie_break {
+            NHintsTieBreak::QueueOrder => 0.,
+            NHintsTieBreak::Longest => self
+                .timings
+                .get(artifact)
+                .map_or(0., |timing| timing.duration),
+            NHintsTieBreak::MostDependants => self.reverse_dependencies[artifact].len() as f64,
+        })
+    }
+}
 impl HintProvider for NHintsProvider {
-    fn suggest_next<'a>(&mut self, timings: &[&'a Artifact]) -> Option<&'a Artifact> {
-        if let Some(codegen) = timings.iter().find(|t| t.typ == ArtifactType::Codegen) {
-            // Simulate how pipelining works right now. If there's some codegen task just pick it,
-            // as it was most likely just added to the candidate queue.
-            return Some(codegen);
-        }
+    fn suggest_next<'a>(
+        &mut self,
+        timings: &[&'a Artifact],
+        _context: &SchedulerContext,
+    ) -> Option<&'a Artifact> {
         let direct_hit = timings
             .iter()
             .filter_map(|artifact| {
@@ -160,7 +395,9 @@ impl HintProvider for NHintsProvider {
                         .unwrap_or(self.n_hints.len());
                     Some((artifact, position))
                 })
-                .min_by_key(|(_, priority)| *priority)
+                .min_by_key(|(artifact, priority)| {
+                    (*priority, std::cmp::Reverse(self.tie_break_score(artifact)))
+                })
                 .map(|a| a.0)
                 .cloned()
         })?;
@@ -168,6 +405,841 @@ impl HintProvider for NHintsProvider {
     }
 
     fn label(&self) -> String {
-        "N-Hints".into()
+        if self.params == NHintsParams::default() {
+            "N-Hints".into()
+        } else {
+            format!("N-Hints ({})", self.params)
+        }
+    }
+}
+
+/// The simplest possible baseline: units are picked in the order they were queued in (which is a topological order
+/// of the unit graph). When a seed is provided, independent units are shuffled around while still maintaining a
+/// valid topological order. Every serious heuristic should beat this one.
+#[derive(Debug)]
+pub struct FifoHints {
+    priority: BTreeMap<Artifact, usize>,
+    seed: Option<u64>,
+}
+
+impl FifoHints {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(dependencies: &DependencyQueueBuilder, seed: Option<u64>) -> Box<dyn HintProvider> {
+        let order = match seed {
+            Some(seed) => shuffled_topological_order(dependencies, seed),
+            None => dependencies.insertion_order.clone(),
+        };
+        let priority = order
+            .into_iter()
+            .enumerate()
+            .map(|(index, artifact)| (artifact, index))
+            .collect();
+        Box::new(Self { priority, seed })
+    }
+}
+
+/// Produces a random topological order of the dependency graph: at each step one of the artifacts with all of its
+/// dependencies already placed is picked at random.
+pub(crate) fn shuffled_topological_order(
+    dependencies: &DependencyQueueBuilder,
+    seed: u64,
+) -> Vec<Artifact> {
+    let mut rng = Rng::new(seed);
+    let mut remaining_deps: BTreeMap<&Artifact, usize> = dependencies
+        .dep_map
+        .iter()
+        .map(|(artifact, deps)| (artifact, deps.len()))
+        .collect();
+    let mut ready: Vec<&Artifact> = dependencies
+        .insertion_order
+        .iter()
+        .filter(|artifact| remaining_deps[artifact] == 0)
+        .collect();
+    let mut order = Vec::with_capacity(remaining_deps.len());
+    while !ready.is_empty() {
+        let next = ready.swap_remove(rng.below(ready.len()));
+        for dependant in dependencies.reverse_dep_map.get(next).into_iter().flatten() {
+            let count = remaining_deps.get_mut(dependant).unwrap();
+            *count -= 1;
+            if *count == 0 {
+                ready.push(dependant);
+            }
+        }
+        order.push(next.clone());
+    }
+    assert_eq!(
+        order.len(),
+        remaining_deps.len(),
+        "cycle in DependencyQueue"
+    );
+    order
+}
+
+impl HintProvider for FifoHints {
+    fn suggest_next<'a>(
+        &mut self,
+        timings: &[&'a Artifact],
+        _context: &SchedulerContext,
+    ) -> Option<&'a Artifact> {
+        timings
+            .iter()
+            .min_by_key(|artifact| self.priority[artifact])
+            .cloned()
+    }
+
+    fn label(&self) -> String {
+        match self.seed {
+            Some(seed) => format!("FIFO (seed {seed})"),
+            None => "FIFO".into(),
+        }
+    }
+}
+
+/// Picks one of the ready artifacts at random. Comparing the spread of its makespans with other schedulers shows how
+/// much scheduling decisions matter for a given graph at all.
+#[derive(Debug)]
+pub struct RandomHints {
+    rng: Rng,
+    seed: u64,
+}
+
+impl RandomHints {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(seed: u64) -> Box<dyn HintProvider> {
+        Box::new(Self {
+            rng: Rng::new(seed),
+            seed,
+        })
+    }
+}
+
+impl HintProvider for RandomHints {
+    fn suggest_next<'a>(
+        &mut self,
+        timings: &[&'a Artifact],
+        _context: &SchedulerContext,
+    ) -> Option<&'a Artifact> {
+        if timings.is_empty() {
+            return None;
+        }
+        Some(timings[self.rng.below(timings.len())])
+    }
+
+    fn label(&self) -> String {
+        format!("Random (seed {})", self.seed)
+    }
+}
+
+/// Classic list scheduling: always picks the ready artifact with the longest remaining critical path, i.e. the
+/// longest chain of work (weighted by durations) from it to the end of the build.
+#[derive(Debug)]
+pub struct CriticalPathHints {
+    lengths: BTreeMap<Artifact, u64>,
+}
+
+impl CriticalPathHints {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(
+        dependencies: &DependencyQueueBuilder,
+        timings: &BTreeMap<Artifact, TimingInfo>,
+    ) -> Box<dyn HintProvider> {
+        Box::new(Self {
+            lengths: critical_path_lengths(dependencies, timings),
+        })
+    }
+}
+
+impl HintProvider for CriticalPathHints {
+    fn suggest_next<'a>(
+        &mut self,
+        timings: &[&'a Artifact],
+        _context: &SchedulerContext,
+    ) -> Option<&'a Artifact> {
+        timings
+            .iter()
+            .max_by_key(|artifact| self.lengths.get(*artifact).copied().unwrap_or_default())
+            .cloned()
+    }
+
+    fn label(&self) -> String {
+        "Critical Path".into()
+    }
+
+    fn score(&self, artifact: &Artifact) -> Option<f64> {
+        self.lengths.get(artifact).map(|length| *length as f64)
+    }
+}
+
+/// Shortest job first: picks the ready artifact that takes the least time to build, ignoring the dependency graph.
+#[derive(Debug)]
+pub struct SjfHints {
+    durations: BTreeMap<Artifact, u64>,
+}
+
+impl SjfHints {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(timings: &BTreeMap<Artifact, TimingInfo>) -> Box<dyn HintProvider> {
+        Box::new(Self {
+            durations: durations(timings),
+        })
+    }
+}
+
+impl HintProvider for SjfHints {
+    fn suggest_next<'a>(
+        &mut self,
+        timings: &[&'a Artifact],
+        _context: &SchedulerContext,
+    ) -> Option<&'a Artifact> {
+        timings
+            .iter()
+            .min_by_key(|artifact| self.durations.get(*artifact).copied().unwrap_or_default())
+            .cloned()
+    }
+
+    fn label(&self) -> String {
+        "Shortest Job First".into()
+    }
+
+    fn score(&self, artifact: &Artifact) -> Option<f64> {
+        self.durations
+            .get(artifact)
+            .map(|duration| *duration as f64)
+    }
+}
+
+/// Longest processing time first: picks the ready artifact that takes the most time to build, ignoring the
+/// dependency graph.
+#[derive(Debug)]
+pub struct LptHints {
+    durations: BTreeMap<Artifact, u64>,
+}
+
+impl LptHints {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(timings: &BTreeMap<Artifact, TimingInfo>) -> Box<dyn HintProvider> {
+        Box::new(Self {
+            durations: durations(timings),
+        })
+    }
+}
+
+impl HintProvider for LptHints {
+    fn suggest_next<'a>(
+        &mut self,
+        timings: &[&'a Artifact],
+        _context: &SchedulerContext,
+    ) -> Option<&'a Artifact> {
+        timings
+            .iter()
+            .max_by_key(|artifact| self.durations.get(*artifact).copied().unwrap_or_default())
+            .cloned()
+    }
+
+    fn label(&self) -> String {
+        "Longest Processing Time".into()
+    }
+
+    fn score(&self, artifact: &Artifact) -> Option<f64> {
+        self.durations
+            .get(artifact)
+            .map(|duration| *duration as f64)
+    }
+}
+
+fn durations(timings: &BTreeMap<Artifact, TimingInfo>) -> BTreeMap<Artifact, u64> {
+    timings
+        .keys()
+        .map(|artifact| (artifact.clone(), duration_ms(timings, artifact)))
+        .collect()
+}
+
+/// Adversarial counterpart of [CriticalPathHints]: always picks the least useful ready artifact, the one with the
+/// shortest remaining critical path. Its makespan approximates the worst a scheduler could do, which together with
+/// the best one bounds how much scheduling decisions can matter for a project.
+#[derive(Debug)]
+pub struct WorstCaseHints {
+    lengths: BTreeMap<Artifact, u64>,
+}
+
+impl WorstCaseHints {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(
+        dependencies: &DependencyQueueBuilder,
+        timings: &BTreeMap<Artifact, TimingInfo>,
+    ) -> Box<dyn HintProvider> {
+        Box::new(Self {
+            lengths: critical_path_lengths(dependencies, timings),
+        })
+    }
+}
+
+impl HintProvider for WorstCaseHints {
+    fn suggest_next<'a>(
+        &mut self,
+        timings: &[&'a Artifact],
+        _context: &SchedulerContext,
+    ) -> Option<&'a Artifact> {
+        timings
+            .iter()
+            .min_by_key(|artifact| self.lengths.get(*artifact).copied().unwrap_or_default())
+            .cloned()
+    }
+
+    fn label(&self) -> String {
+        "Worst Case".into()
+    }
+
+    fn score(&self, artifact: &Artifact) -> Option<f64> {
+        self.lengths.get(artifact).map(|length| *length as f64)
+    }
+}
+
+/// HEFT (Heterogeneous Earliest Finish Time, Topcuoglu et al.).
+///
+/// Units are prioritized by their upward rank: their mean cost plus the largest upward rank of their dependants.
+/// Costs on the mean core are durations divided by the mean core speed, which scales all ranks alike, so units are
+/// ordered by critical path length, with ties broken by queue order. Each unit, in that order, then goes to the core
+/// that finishes it the earliest: if a faster core that is busy for now would finish it sooner than the free one, the
+/// unit is held for that core and the next one is considered. Simulated cores have no communication costs.
+#[derive(Debug)]
+pub struct HeftHints {
+    /// Position of each unit in the priority list.
+    priority: BTreeMap<Artifact, usize>,
+    durations: BTreeMap<Artifact, u64>,
+}
+
+impl HeftHints {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(
+        dependencies: &DependencyQueueBuilder,
+        timings: &BTreeMap<Artifact, TimingInfo>,
+    ) -> Box<dyn HintProvider> {
+        let upward_ranks = critical_path_lengths(dependencies, timings);
+        let mut order: Vec<&Artifact> = dependencies.insertion_order.iter().collect();
+        // Stable sort keeps the queue order among units of equal rank.
+        order.sort_by_key(|artifact| std::cmp::Reverse(upward_ranks[*artifact]));
+        let priority = order
+            .into_iter()
+            .enumerate()
+            .map(|(index, artifact)| (artifact.clone(), index))
+            .collect();
+        Box::new(Self {
+            priority,
+            durations: durations(timings),
+        })
+    }
+}
+
+impl HintProvider for HeftHints {
+    fn suggest_next<'a>(
+        &mut self,
+        timings: &[&'a Artifact],
+        context: &SchedulerContext,
+    ) -> Option<&'a Artifact> {
+        match self.decide(timings, context)? {
+            Suggestion::Start(artifact) => Some(artifact),
+            Suggestion::Wait => None,
+        }
+    }
+
+    fn decide<'a>(
+        &mut self,
+        timings: &[&'a Artifact],
+        context: &SchedulerContext,
+    ) -> Option<Suggestion<&'a Artifact>> {
+        let mut candidates = timings.to_vec();
+        candidates.sort_by_key(|artifact| self.priority[*artifact]);
+        // Time (in milliseconds from now) at which each busy core is free, including for units already held for it.
+        let mut busy_cores = context.busy_cores.clone();
+        for artifact in candidates {
+            let duration = self.durations.get(artifact).copied().unwrap_or_default() as f64;
+            let finish_here = duration / context.core_speed;
+            let finish_elsewhere = busy_cores
+                .iter_mut()
+                .map(|(speed, free_in)| (*free_in as f64 + duration / *speed, free_in))
+                .min_by(|(a, _), (b, _)| a.total_cmp(b));
+            match finish_elsewhere {
+                Some((finish, free_in)) if finish < finish_here => *free_in = finish.ceil() as u64,
+                _ => return Some(Suggestion::Start(artifact)),
+            }
+        }
+        (!timings.is_empty()).then_some(Suggestion::Wait)
+    }
+
+    fn label(&self) -> String {
+        "HEFT".into()
+    }
+}
+
+/// Combinator that makes any hint provider starvation-free: once a unit has been ready for `max_age` decisions, it is
+/// picked ahead of whatever the inner provider would prefer. The oldest such unit goes first.
+#[derive(Debug)]
+pub struct AgingHints {
+    inner: Box<dyn HintProvider>,
+    max_age: usize,
+    /// Number of decisions made so far, which is what ages are measured in.
+    decisions: usize,
+    /// Decision at which each of the candidates was first seen.
+    ready_since: BTreeMap<Artifact, usize>,
+}
+
+impl AgingHints {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(inner: Box<dyn HintProvider>, max_age: usize) -> Box<dyn HintProvider> {
+        Box::new(Self {
+            inner,
+            max_age,
+            decisions: 0,
+            ready_since: BTreeMap::new(),
+        })
+    }
+}
+
+impl HintProvider for AgingHints {
+    fn suggest_next<'a>(
+        &mut self,
+        timings: &[&'a Artifact],
+        context: &SchedulerContext,
+    ) -> Option<&'a Artifact> {
+        match self.decide(timings, context)? {
+            Suggestion::Start(next) => Some(next),
+            Suggestion::Wait => None,
+        }
+    }
+
+    fn decide<'a>(
+        &mut self,
+        timings: &[&'a Artifact],
+        context: &SchedulerContext,
+    ) -> Option<Suggestion<&'a Artifact>> {
+        for artifact in timings {
+            self.ready_since
+                .entry((*artifact).clone())
+                .or_insert(self.decisions);
+        }
+        self.decisions += 1;
+        let oldest = timings
+            .iter()
+            .map(|artifact| (self.ready_since[*artifact], *artifact))
+            .filter(|(since, _)| self.decisions - since > self.max_age)
+            .min()
+            .map(|(_, artifact)| Suggestion::Start(artifact));
+        let decision = oldest.or_else(|| self.inner.decide(timings, context))?;
+        if let Suggestion::Start(next) = decision {
+            self.ready_since.remove(next);
+        }
+        Some(decision)
+    }
+
+    fn label(&self) -> String {
+        format!("{} (aging after {})", self.inner.label(), self.max_age)
+    }
+
+    fn score(&self, artifact: &Artifact) -> Option<f64> {
+        self.inner.score(artifact)
+    }
+
+    fn accepts_core(&self, artifact: &Artifact, speed: f64) -> bool {
+        self.inner.accepts_core(artifact, speed)
+    }
+
+    fn threads_for(&self, artifact: &Artifact, context: &SchedulerContext) -> usize {
+        self.inner.threads_for(artifact, context)
+    }
+
+    fn failure(&self) -> Option<String> {
+        self.inner.failure()
+    }
+}
+
+/// Follows a fixed priority list, the way a build system would consume hints emitted for it: ready units are picked
+/// in the order of the list, but as soon as any of the ready units is not on it (e.g. because its hint was not
+/// trusted), the decision is left to the fallback provider.
+#[derive(Debug)]
+pub struct PriorityListHints {
+    positions: BTreeMap<Artifact, usize>,
+    fallback: Box<dyn HintProvider>,
+}
+
+impl PriorityListHints {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(order: &[Artifact], fallback: Box<dyn HintProvider>) -> Box<dyn HintProvider> {
+        Box::new(Self {
+            positions: order
+                .iter()
+                .enumerate()
+                .map(|(position, artifact)| (artifact.clone(), position))
+                .collect(),
+            fallback,
+        })
+    }
+}
+
+impl HintProvider for PriorityListHints {
+    fn suggest_next<'a>(
+        &mut self,
+        timings: &[&'a Artifact],
+        context: &SchedulerContext,
+    ) -> Option<&'a Artifact> {
+        let positions: Option<Vec<(usize, &'a Artifact)>> = timings
+            .iter()
+            .map(|artifact| Some((*self.positions.get(*artifact)?, *artifact)))
+            .collect();
+        match positions {
+            Some(positions) => positions.into_iter().min().map(|(_, artifact)| artifact),
+            None => self.fallback.suggest_next(timings, context),
+        }
+    }
+
+    fn label(&self) -> String {
+        format!("Priority list (falling back to {})", self.fallback.label())
+    }
+}
+
+/// Combinator consulting hint providers in order, until one of them makes a suggestion. A chain of two is a primary
+/// provider with a fallback, which [Fallback] is shorthand for.
+#[derive(Debug)]
+pub struct Chain {
+    providers: Vec<Box<dyn HintProvider>>,
+    label: Option<String>,
+}
+
+impl Chain {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(providers: Vec<Box<dyn HintProvider>>) -> Box<dyn HintProvider> {
+        Box::new(Self {
+            providers,
+            label: None,
+        })
+    }
+
+    /// Like [Chain::new], but reported under `label` instead of the labels of its providers.
+    pub fn labeled(
+        label: impl Into<String>,
+        providers: Vec<Box<dyn HintProvider>>,
+    ) -> Box<dyn HintProvider> {
+        Box::new(Self {
+            providers,
+            label: Some(label.into()),
+        })
+    }
+}
+
+impl HintProvider for Chain {
+    fn suggest_next<'a>(
+        &mut self,
+        timings: &[&'a Artifact],
+        context: &SchedulerContext,
+    ) -> Option<&'a Artifact> {
+        self.providers
+            .iter_mut()
+            .find_map(|provider| provider.suggest_next(timings, context))
+    }
+
+    fn decide<'a>(
+        &mut self,
+        timings: &[&'a Artifact],
+        context: &SchedulerContext,
+    ) -> Option<Suggestion<&'a Artifact>> {
+        self.providers
+            .iter_mut()
+            .find_map(|provider| provider.decide(timings, context))
+    }
+
+    fn label(&self) -> String {
+        if let Some(label) = &self.label {
+            return label.clone();
+        }
+        self.providers
+            .iter()
+            .map(|provider| provider.label())
+            .collect::<Vec<_>>()
+            .join(", then ")
+    }
+
+    /// Score of the first provider that ranks `artifact`.
+    fn score(&self, artifact: &Artifact) -> Option<f64> {
+        self.providers
+            .iter()
+            .find_map(|provider| provider.score(artifact))
+    }
+
+    /// A core is accepted only if none of the providers decline it.
+    fn accepts_core(&self, artifact: &Artifact, speed: f64) -> bool {
+        self.providers
+            .iter()
+            .all(|provider| provider.accepts_core(artifact, speed))
+    }
+
+    /// Threads are split the way the first provider splits them.
+    fn threads_for(&self, artifact: &Artifact, context: &SchedulerContext) -> usize {
+        match self.providers.first() {
+            Some(provider) => provider.threads_for(artifact, context),
+            None => (context.idle_slots / context.ready.max(1)).max(1),
+        }
+    }
+
+    fn failure(&self) -> Option<String> {
+        self.providers
+            .iter()
+            .find_map(|provider| provider.failure())
+    }
+}
+
+/// Combinator that leaves the decision to `fallback` whenever `primary` makes no suggestion.
+pub struct Fallback;
+
+impl Fallback {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(
+        primary: Box<dyn HintProvider>,
+        fallback: Box<dyn HintProvider>,
+    ) -> Box<dyn HintProvider> {
+        Chain::new(vec![primary, fallback])
+    }
+}
+
+/// Combinator that only lets the inner provider choose among the candidates matching a predicate, and makes no
+/// suggestion if there are none. Together with [Fallback] it expresses rules like "run build scripts as soon as
+/// they're ready, otherwise defer to N-Hints":
+/// ```ignore
+/// Fallback::new(
+///     FilterThen::new(
+///         "build scripts",
+///         |artifact| artifact.typ == ArtifactType::BuildScriptRun,
+///         FifoHints::new(deps, None),
+///     ),
+///     NHintsProvider::new(deps, timings),
+/// )
+/// ```
+/// Ready codegen is started by the queue before hint providers are consulted, as Cargo pipelines it, so filtering
+/// for it has no effect.
+pub struct FilterThen {
+    description: String,
+    predicate: Box<dyn Fn(&Artifact) -> bool + Send>,
+    inner: Box<dyn HintProvider>,
+}
+
+impl FilterThen {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(
+        description: impl Into<String>,
+        predicate: impl Fn(&Artifact) -> bool + Send + 'static,
+        inner: Box<dyn HintProvider>,
+    ) -> Box<dyn HintProvider> {
+        Box::new(Self {
+            description: description.into(),
+            predicate: Box::new(predicate),
+            inner,
+        })
+    }
+}
+
+impl std::fmt::Debug for FilterThen {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FilterThen")
+            .field("description", &self.description)
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+impl HintProvider for FilterThen {
+    fn suggest_next<'a>(
+        &mut self,
+        timings: &[&'a Artifact],
+        context: &SchedulerContext,
+    ) -> Option<&'a Artifact> {
+        match self.decide(timings, context)? {
+            Suggestion::Start(next) => Some(next),
+            Suggestion::Wait => None,
+        }
+    }
+
+    fn decide<'a>(
+        &mut self,
+        timings: &[&'a Artifact],
+        context: &SchedulerContext,
+    ) -> Option<Suggestion<&'a Artifact>> {
+        let matching: Vec<&'a Artifact> = timings
+            .iter()
+            .copied()
+            .filter(|artifact| (self.predicate)(artifact))
+            .collect();
+        if matching.is_empty() {
+            return None;
+        }
+        self.inner.decide(&matching, context)
+    }
+
+    fn label(&self) -> String {
+        format!("{} for {}", self.inner.label(), self.description)
+    }
+
+    /// Only units matching the predicate are ranked by the inner provider.
+    fn score(&self, artifact: &Artifact) -> Option<f64> {
+        if (self.predicate)(artifact) {
+            self.inner.score(artifact)
+        } else {
+            None
+        }
+    }
+
+    /// Only units matching the predicate may be declined by the inner provider.
+    fn accepts_core(&self, artifact: &Artifact, speed: f64) -> bool {
+        !(self.predicate)(artifact) || self.inner.accepts_core(artifact, speed)
+    }
+
+    fn threads_for(&self, artifact: &Artifact, context: &SchedulerContext) -> usize {
+        self.inner.threads_for(artifact, context)
+    }
+
+    fn failure(&self) -> Option<String> {
+        self.inner.failure()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn heft_holds_units_for_faster_cores() {
+        let timing = |name: &str, duration: f64| -> (Artifact, TimingInfo) {
+            let timing = serde_json::from_value(serde_json::json!({
+                "package_id": name,
+                "target": { "name": name, "crate_types": ["bin"] },
+                "mode": "build",
+                "duration": duration,
+                "rmeta_time": null,
+            }))
+            .unwrap();
+            let artifact = Artifact {
+                package_id: name.into(),
+                typ: ArtifactType::Link,
+            };
+            (artifact, timing)
+        };
+        let timings: BTreeMap<_, _> = [timing("long", 4.), timing("short", 1.)].into();
+        let (long, short) = (&timing("long", 0.).0, &timing("short", 0.).0);
+        let mut deps = DependencyQueueBuilder::new();
+        deps.queue(long.clone(), []);
+        deps.queue(short.clone(), []);
+        let mut hints = HeftHints::new(&deps, &timings);
+        // A core twice as fast frees up in a second, finishing the long unit a second earlier than this one.
+        let context = SchedulerContext {
+            busy_cores: vec![(2., 1000)],
+            ..Default::default()
+        };
+        assert_eq!(
+            hints.decide(&[long, short], &context),
+            Some(Suggestion::Start(short))
+        );
+        assert_eq!(hints.decide(&[long], &context), Some(Suggestion::Wait));
+        let idle = SchedulerContext::default();
+        assert_eq!(
+            hints.decide(&[long, short], &idle),
+            Some(Suggestion::Start(long))
+        );
+    }
+
+    #[test]
+    fn shuffled_order_is_topological() {
+        let mut builder = DependencyQueueBuilder::new();
+        let packages: Vec<Artifact> = ["A", "B", "C", "D", "E"]
+            .into_iter()
+            .map(|name| Artifact {
+                package_id: name.into(),
+                typ: ArtifactType::Link,
+            })
+            .collect();
+        let deps = [vec![], vec![], vec![0, 1], vec![0], vec![2, 3]];
+        for (index, package) in packages.iter().enumerate() {
+            builder.queue(
+                package.clone(),
+                deps[index].iter().map(|index| packages[*index].clone()),
+            );
+        }
+        for seed in 0..16 {
+            let order = shuffled_topological_order(&builder, seed);
+            assert_eq!(order.len(), packages.len());
+            for (index, package) in packages.iter().enumerate() {
+                let position = order.iter().position(|a| a == package).unwrap();
+                for dep in &deps[index] {
+                    let dep_position = order.iter().position(|a| a == &packages[*dep]).unwrap();
+                    assert!(dep_position < position);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn filter_then_falls_back_when_nothing_matches() {
+        let artifact = |name: &str, typ| Artifact {
+            package_id: name.into(),
+            typ,
+        };
+        let metadata = artifact("A", ArtifactType::Metadata);
+        let codegen = artifact("B", ArtifactType::Codegen);
+        let mut hints = Fallback::new(
+            FilterThen::new(
+                "codegen",
+                |artifact| artifact.typ == ArtifactType::Codegen,
+                RandomHints::new(0),
+            ),
+            PriorityListHints::new(std::slice::from_ref(&metadata), RandomHints::new(0)),
+        );
+        let context = SchedulerContext::default();
+        assert_eq!(
+            hints.suggest_next(&[&metadata, &codegen], &context),
+            Some(&codegen)
+        );
+        assert_eq!(hints.suggest_next(&[&metadata], &context), Some(&metadata));
+        assert_eq!(hints.suggest_next(&[], &context), None);
+    }
+
+    #[test]
+    fn combinators_forward_waiting() {
+        /// Leaves every thread idle, as a reservation would.
+        #[derive(Debug)]
+        struct Waiting;
+        impl HintProvider for Waiting {
+            fn suggest_next<'a>(
+                &mut self,
+                _: &[&'a Artifact],
+                _: &SchedulerContext,
+            ) -> Option<&'a Artifact> {
+                None
+            }
+            fn label(&self) -> String {
+                "Waiting".into()
+            }
+            fn decide<'a>(
+                &mut self,
+                _: &[&'a Artifact],
+                _: &SchedulerContext,
+            ) -> Option<Suggestion<&'a Artifact>> {
+                Some(Suggestion::Wait)
+            }
+        }
+        let artifact = Artifact {
+            package_id: "A".into(),
+            typ: ArtifactType::Codegen,
+        };
+        let context = SchedulerContext::default();
+        let combinators = [
+            AgingHints::new(Box::new(Waiting), 10),
+            Chain::new(vec![Box::new(Waiting), RandomHints::new(0)]),
+            FilterThen::new("everything", |_| true, Box::new(Waiting)),
+        ];
+        for mut hints in combinators {
+            assert_eq!(
+                hints.decide(&[&artifact], &context),
+                Some(Suggestion::Wait),
+                "{}",
+                hints.label()
+            );
+        }
     }
 }
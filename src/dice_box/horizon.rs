@@ -0,0 +1,71 @@
+//! Evaluation of schedulers on a truncated horizon, for interactive workflows where early progress matters more than
+//! the tail of the build.
+use std::collections::BTreeMap;
+
+use tabled::Tabled;
+
+use crate::runner::ScheduledTask;
+
+/// Progress made by a scenario within the horizon.
+#[derive(Clone, Debug, PartialEq, Tabled)]
+pub struct HorizonScore {
+    pub rank: usize,
+    pub label: String,
+    /// Percentage of all work (CPU time) that was done within the horizon.
+    #[tabled(display_with = "display_percentage")]
+    pub work_done: f64,
+    /// Number of workspace members that were fully built within the horizon.
+    pub workspace_members_done: usize,
+}
+
+fn display_percentage(percentage: &f64) -> String {
+    format!("{percentage:.1}%")
+}
+
+fn score(label: String, schedule: &[ScheduledTask], horizon: u64) -> HorizonScore {
+    let total: u64 = schedule.iter().map(|task| task.end - task.start).sum();
+    let done: u64 = schedule
+        .iter()
+        .map(|task| task.end.min(horizon).saturating_sub(task.start))
+        .sum();
+    let mut workspace_members: BTreeMap<&str, bool> = BTreeMap::new();
+    for task in schedule
+        .iter()
+        .filter(|task| task.artifact.is_workspace_member())
+    {
+        *workspace_members
+            .entry(&task.artifact.package_id)
+            .or_insert(true) &= task.end <= horizon;
+    }
+    HorizonScore {
+        rank: 0,
+        label,
+        work_done: if total == 0 {
+            100.
+        } else {
+            done as f64 / total as f64 * 100.
+        },
+        workspace_members_done: workspace_members.values().filter(|done| **done).count(),
+    }
+}
+
+/// Ranks scenarios by the progress made in the first `horizon` milliseconds: workspace members built first, then the
+/// share of work done.
+pub fn rank_on_horizon<'a>(
+    scenarios: impl IntoIterator<Item = (String, &'a [ScheduledTask])>,
+    horizon: u64,
+) -> Vec<HorizonScore> {
+    let mut scores: Vec<HorizonScore> = scenarios
+        .into_iter()
+        .map(|(label, schedule)| score(label, schedule, horizon))
+        .collect();
+    scores.sort_by(|a, b| {
+        b.workspace_members_done
+            .cmp(&a.workspace_members_done)
+            .then(b.work_done.partial_cmp(&a.work_done).unwrap())
+    });
+    for (rank, score) in scores.iter_mut().enumerate() {
+        score.rank = rank + 1;
+    }
+    scores
+}
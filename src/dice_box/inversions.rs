@@ -0,0 +1,138 @@
+//! Priority inversion and starvation detection.
+//!
+//! A priority inversion happens when the most valuable ready artifact (per the chosen [ValueMetric]) has to wait
+//! while all cores are busy with work that is strictly less valuable than it. These are a concrete diagnostic for why a given
+//! scheduler underperformed.
+use std::collections::{BTreeMap, BTreeSet};
+
+use clap::ValueEnum;
+
+use crate::{
+    artifact::Artifact,
+    critical_path::critical_path_lengths,
+    dependency_queue::{reverse_dependencies, DependencyQueueBuilder},
+    timings::TimingInfo,
+};
+
+/// Metric used to judge how valuable it is to build a given artifact.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ValueMetric {
+    /// Length of the longest chain of work starting at the artifact.
+    CriticalPath,
+    /// Number of (transitive) dependants of the artifact.
+    Dependants,
+}
+
+impl ValueMetric {
+    pub fn values(
+        self,
+        deps: &DependencyQueueBuilder,
+        timings: &BTreeMap<Artifact, TimingInfo>,
+    ) -> BTreeMap<Artifact, u64> {
+        match self {
+            ValueMetric::CriticalPath => critical_path_lengths(deps, timings),
+            ValueMetric::Dependants => reverse_dependencies(deps)
+                .into_iter()
+                .map(|(artifact, dependants)| (artifact, dependants.len() as u64))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PriorityInversion {
+    /// Artifact that was ready to run but had to wait.
+    pub artifact: Artifact,
+    /// Time (in milliseconds) at which the inversion started.
+    pub start: u64,
+    /// Time (in milliseconds) at which the inversion was resolved.
+    pub end: u64,
+    /// Less valuable tasks that were occupying the cores in the meantime.
+    pub culprits: BTreeSet<Artifact>,
+}
+
+impl std::fmt::Display for PriorityInversion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let culprits: Vec<_> = self.culprits.iter().map(ToString::to_string).collect();
+        write!(
+            f,
+            "{:.3}s-{:.3}s: {} waited behind {}",
+            self.start as f64 / 1000.,
+            self.end as f64 / 1000.,
+            self.artifact,
+            culprits.join(", ")
+        )
+    }
+}
+
+/// Keeps track of inversions as the simulation progresses.
+pub(crate) struct InversionDetector {
+    values: BTreeMap<Artifact, u64>,
+    ongoing: BTreeMap<Artifact, PriorityInversion>,
+    finished: Vec<PriorityInversion>,
+}
+
+impl InversionDetector {
+    pub(crate) fn new(values: BTreeMap<Artifact, u64>) -> Self {
+        Self {
+            values,
+            ongoing: Default::default(),
+            finished: Default::default(),
+        }
+    }
+
+    fn value(&self, artifact: &Artifact) -> u64 {
+        self.values.get(artifact).copied().unwrap_or_default()
+    }
+
+    /// Records the state of the simulation at time `now`. `all_cores_busy` should be set if there are no free
+    /// slots left for `waiting` artifacts to run on.
+    pub(crate) fn observe<'a>(
+        &mut self,
+        now: u64,
+        all_cores_busy: bool,
+        waiting: impl Iterator<Item = &'a Artifact>,
+        running: &[&Artifact],
+    ) {
+        let max_running_value = running
+            .iter()
+            .map(|artifact| self.value(artifact))
+            .max()
+            .unwrap_or_default();
+        let inverted: Option<&Artifact> = waiting
+            .max_by_key(|artifact| self.value(artifact))
+            .filter(|artifact| all_cores_busy && self.value(artifact) > max_running_value);
+        let resolved: Vec<Artifact> = self
+            .ongoing
+            .keys()
+            .filter(|artifact| inverted != Some(*artifact))
+            .cloned()
+            .collect();
+        for artifact in resolved {
+            let mut inversion = self.ongoing.remove(&artifact).unwrap();
+            inversion.end = now;
+            self.finished.push(inversion);
+        }
+        if let Some(artifact) = inverted {
+            let inversion =
+                self.ongoing
+                    .entry(artifact.clone())
+                    .or_insert_with(|| PriorityInversion {
+                        artifact: artifact.clone(),
+                        start: now,
+                        end: now,
+                        culprits: BTreeSet::new(),
+                    });
+            inversion
+                .culprits
+                .extend(running.iter().map(|artifact| (*artifact).clone()));
+        }
+    }
+
+    /// Returns all inversions found, closing the ones that are still ongoing at `now`.
+    pub(crate) fn finish(&mut self, now: u64) -> &[PriorityInversion] {
+        self.observe(now, false, std::iter::empty(), &[]);
+        self.finished.sort_by_key(|inversion| inversion.start);
+        &self.finished
+    }
+}
@@ -1,20 +1,138 @@
+mod advise;
+mod animation;
+mod anonymize;
 mod artifact;
+mod attribution;
+mod audit;
+mod blame;
+mod cache;
+mod calibration;
+mod capture;
 mod cli;
+mod colocation;
+mod compaction;
+mod compare;
+mod constraints;
+mod cost;
+mod critical_path;
+mod davinci;
+mod decision_log;
 mod dependency_queue;
+mod dev_loop;
+mod distribution;
+mod environment;
+mod error;
+mod events;
+mod explain;
+mod external_schedule;
+mod graph_diff;
+mod hint_file;
 mod hints;
+mod horizon;
+mod inversions;
+mod memory;
+mod package;
+mod payoff;
+mod pipeline;
+#[cfg(feature = "plugins")]
+mod plugin;
+mod queries;
+mod registry;
+mod report_limits;
+mod reservation;
+mod results;
+mod rng;
+mod rollout;
 mod runner;
+mod scalability;
+#[cfg(feature = "scripting")]
+mod script;
+mod search;
 mod timings;
+mod trace;
+mod transforms;
 mod unit_graph;
+mod waterfall;
+mod weighted;
 
-pub use cli::Cli;
+pub use advise::{advise, Advice};
+pub use animation::write_animation;
+pub use anonymize::{verify_bucketing, Anonymizer, BucketingError};
+pub use artifact::{Artifact, ArtifactType, ThreadPool};
+pub use attribution::{attribute, Attribution};
+pub use audit::{audit_decisions, DecisionAudit};
+pub use blame::{blame, Blame};
+pub use cache::{Preprocessed, ScenarioCache};
+pub use calibration::{calibrate, Calibration, UnitCalibration};
+pub use capture::{
+    capture, capture_unit_graph, parse_observed_schedule, start_time_errors, StartTimeError,
+};
+pub use cli::{Cli, Command, WhatIf};
+pub use colocation::{colocate, Job, JobAllocation};
+pub use compaction::{compact, compaction, Compaction};
+pub use compare::{compare, ComparisonRow};
+pub use constraints::{parse_constraints, Constraints, ExclusivityCost};
+pub use cost::{mark_pareto_optimal, CostEstimate, MachineProfile};
+pub use critical_path::{
+    critical_path, critical_path_lengths, critical_path_segments, CriticalPathSegment,
+};
+pub use davinci::write_editor;
+pub use decision_log::{write_decision_log, DecisionReason, LoggedCandidate, LoggedDecision};
 pub use dependency_queue::CargoHints;
-use dependency_queue::DependencyQueueBuilder;
-pub use hints::NHintsProvider;
-pub use runner::Runner;
-pub use timings::parse;
+pub use dependency_queue::DependencyQueueBuilder;
+pub use dev_loop::{DevLoop, DevLoopMakespan};
+pub use distribution::{MakespanDistribution, ScheduleSpread};
+pub use environment::{parse_environment, Environment};
+pub use error::InputError;
+pub use events::{core_timeline, parse_events, ExternalEvent};
+pub use explain::{explain, ScenarioExplanation, UnitExplanation};
+pub use external_schedule::{parse_external_schedule, ExternalSchedule, FixedStart};
+pub use graph_diff::{compare_graphs, diff_graphs, GraphChange, MakespanChange};
+pub use hint_file::{emit_hints, parse_hints_file, HintEntry, HintsFile, HINTS_FORMAT_VERSION};
+pub use hints::{
+    tune_n_hints, AgingHints, Chain, CriticalPathHints, Fallback, FifoHints, FilterThen, HeftHints,
+    HintProvider, LptHints, NHintsParams, NHintsProvider, NHintsScore, NHintsSweepPoint,
+    NHintsTieBreak, PriorityListHints, RandomHints, SchedulerContext, SjfHints, Suggestion,
+    WorstCaseHints, DEFAULT_N_HINTS,
+};
+pub use horizon::{rank_on_horizon, HorizonScore};
+pub use inversions::{PriorityInversion, ValueMetric};
+pub use memory::MemoryPressure;
+pub use package::{group_by_package, PackageSummary};
+pub use payoff::{rank_payoffs, Payoff};
+pub use pipeline::{Pipeline, PipelineMakespan};
+#[cfg(feature = "plugins")]
+pub use plugin::{PluginHints, ABI_VERSION};
+pub use queries::{
+    package_units, shared_dependencies, shortest_chain, transitive_dependants,
+    transitive_dependencies,
+};
+pub use registry::{HintProviderFactory, Registry};
+pub use report_limits::ReportLimits;
+pub use reservation::ReservationHints;
+pub use results::{OutputFormat, Results};
+pub use rollout::RolloutHints;
+pub use runner::{
+    calculate_all, CoreSizing, DispatchOverhead, Duration, IoBandwidth, IoContention, Jobserver,
+    Makespan, ObservedBuild, ParallelFrontend, Runner, ScheduledTask, SerialStretches, Tail,
+    Throttling, Utilization, WaitTime,
+};
+pub use scalability::{fit_scalability, sweep_points, ScalabilityFit, SweepPoint};
+#[cfg(feature = "scripting")]
+pub use script::ScriptHints;
+pub use search::{anneal, search, SearchGap, SearchResult};
 pub use timings::Timings;
+pub use timings::{
+    parse, parse_start_order, parse_wall_time, parse_with_anomalies, TimingAnomaly, TimingInfo,
+};
+pub use trace::{Decision, ScenarioTrace, Trace};
+pub use transforms::{apply_transforms, parse_transforms, Rewrite};
 use unit_graph::unit_graph_to_artifacts;
-pub use unit_graph::UnitGraph;
+pub use unit_graph::{UnitGraph, UnitGraphFilter};
+pub use waterfall::{waterfall, write_csv, write_svg, Segment};
+pub use weighted::{
+    parse_weights, tune_weights, WeightSearch, WeightedHints, Weights, WeightsScore,
+};
 type PackageId = String;
 
 pub fn create_dependency_queue(graph: unit_graph::UnitGraph) -> DependencyQueueBuilder {
@@ -25,3 +143,35 @@ pub fn create_dependency_queue(graph: unit_graph::UnitGraph) -> DependencyQueueB
     }
     ret
 }
+
+/// Resources available to a simulated build.
+#[derive(Clone, Debug, Default)]
+pub struct Resources {
+    pub num_threads: usize,
+    /// Limits on the number of concurrently running units of a given kind.
+    pub pool_limits: std::collections::BTreeMap<ThreadPool, usize>,
+}
+
+/// Simulates a build of `graph` with hint providers created by `scheduler` and returns the resulting schedule. Fails
+/// if some units can never be started.
+///
+/// This does no I/O and has no global state, so it can be embedded in other tools (e.g. to predict build times
+/// before starting a build).
+pub fn simulate(
+    graph: &DependencyQueueBuilder,
+    durations: &std::collections::BTreeMap<Artifact, TimingInfo>,
+    scheduler: &dyn HintProviderFactory,
+    resources: &Resources,
+) -> Result<Vec<ScheduledTask>, InputError> {
+    let hints = scheduler.create(graph, durations);
+    let mut runner = resources.pool_limits.iter().fold(
+        Runner::new(
+            graph.clone().finish(hints),
+            durations.clone(),
+            resources.num_threads,
+        ),
+        |runner, (pool, limit)| runner.with_pool_limit(*pool, *limit),
+    );
+    runner.calculate()?;
+    Ok(runner.schedule().to_vec())
+}
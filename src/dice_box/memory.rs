@@ -0,0 +1,79 @@
+//! Memory pressure of simulated builds, for machines where a few big crates being built at once would run out of
+//! memory.
+use tabled::Tabled;
+
+use crate::{
+    constraints::Constraints,
+    runner::{Duration, ScheduledTask},
+};
+
+/// How close to running out of memory a scheduler would get if it ignored the memory budget.
+#[derive(Clone, Debug, PartialEq, Tabled)]
+pub struct MemoryPressure {
+    pub label: String,
+    pub makespan: Duration,
+    /// Makespan when the memory budget is not enforced.
+    pub unlimited_makespan: Duration,
+    /// Peak memory use (in GB) when the memory budget is not enforced.
+    #[tabled(display_with = "display_gigabytes")]
+    pub peak_memory: f64,
+    /// Number of times memory use went over the budget when it was not enforced.
+    pub oom_episodes: usize,
+    pub time_over_budget: Duration,
+}
+
+fn display_gigabytes(gigabytes: &f64) -> String {
+    format!("{gigabytes:.1} GB")
+}
+
+impl MemoryPressure {
+    /// Measures memory use over time in `unlimited`, a schedule simulated without enforcing the memory budget of
+    /// `constraints`.
+    pub fn new(
+        label: String,
+        makespan: Duration,
+        unlimited: &[ScheduledTask],
+        constraints: &Constraints,
+    ) -> Self {
+        let budget = constraints.memory_budget().unwrap_or(f64::INFINITY);
+        // Ends sort before starts at the same time, so that back-to-back units don't count as co-resident.
+        let mut changes: Vec<(u64, bool, f64)> = unlimited
+            .iter()
+            .map(|task| (task, constraints.memory(&task.artifact)))
+            .filter(|(_, memory)| *memory > 0.)
+            .flat_map(|(task, memory)| [(task.start, true, memory), (task.end, false, -memory)])
+            .collect();
+        changes.sort_by_key(|(time, starts, _)| (*time, *starts));
+        let mut memory = 0.;
+        let mut peak_memory: f64 = 0.;
+        let mut oom_episodes = 0;
+        let mut time_over_budget = 0;
+        let mut last_time = 0;
+        for (time, _, change) in changes {
+            if memory > budget {
+                time_over_budget += time - last_time;
+            }
+            let was_over = memory > budget;
+            memory += change;
+            peak_memory = peak_memory.max(memory);
+            if !was_over && memory > budget {
+                oom_episodes += 1;
+            }
+            last_time = time;
+        }
+        Self {
+            label,
+            makespan,
+            unlimited_makespan: Duration::from_millis(
+                unlimited
+                    .iter()
+                    .map(|task| task.end)
+                    .max()
+                    .unwrap_or_default(),
+            ),
+            peak_memory,
+            oom_episodes,
+            time_over_budget: Duration::from_millis(time_over_budget),
+        }
+    }
+}
@@ -0,0 +1,76 @@
+//! Aggregation of units at the package level, since users think in crates, not artifacts.
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use tabled::Tabled;
+
+use crate::{
+    artifact::{Artifact, ArtifactType},
+    runner::{Duration, ScheduledTask},
+};
+
+/// Summary of all units of a single package in a simulated build.
+#[derive(Clone, Debug, PartialEq, Serialize, Tabled)]
+pub struct PackageSummary {
+    pub package: String,
+    pub units: usize,
+    /// Time spent compiling and running build scripts.
+    pub build_script: Duration,
+    pub metadata: Duration,
+    pub codegen: Duration,
+    pub link: Duration,
+    /// Total CPU time taken by the units of the package.
+    pub total: Duration,
+    /// Time at which the first unit of the package started.
+    pub start: Duration,
+    /// Time at which the last unit of the package finished.
+    pub end: Duration,
+}
+
+/// Groups tasks of a simulated build by package. Packages are sorted by the time they were done at.
+pub fn group_by_package(schedule: &[ScheduledTask]) -> Vec<PackageSummary> {
+    let mut packages: BTreeMap<&str, Vec<&ScheduledTask>> = BTreeMap::new();
+    for task in schedule {
+        packages
+            .entry(&task.artifact.package_id)
+            .or_default()
+            .push(task);
+    }
+    let millis = |ms: u64| Duration::from_millis(ms);
+    let mut summaries: Vec<PackageSummary> = packages
+        .into_values()
+        .map(|tasks| {
+            let time_in = |types: &[ArtifactType]| {
+                millis(
+                    tasks
+                        .iter()
+                        .filter(|task| types.contains(&task.artifact.typ))
+                        .map(|task| task.end - task.start)
+                        .sum(),
+                )
+            };
+            PackageSummary {
+                package: Artifact::name_ver(&tasks[0].artifact).to_owned(),
+                units: tasks.len(),
+                build_script: time_in(&[
+                    ArtifactType::BuildScriptBuild,
+                    ArtifactType::BuildScriptRun,
+                ]),
+                metadata: time_in(&[ArtifactType::Metadata]),
+                codegen: time_in(&[ArtifactType::Codegen]),
+                link: time_in(&[ArtifactType::Link]),
+                total: millis(tasks.iter().map(|task| task.end - task.start).sum()),
+                start: millis(
+                    tasks
+                        .iter()
+                        .map(|task| task.start)
+                        .min()
+                        .unwrap_or_default(),
+                ),
+                end: millis(tasks.iter().map(|task| task.end).max().unwrap_or_default()),
+            }
+        })
+        .collect();
+    summaries.sort_by(|a, b| a.end.partial_cmp(&b.end).unwrap());
+    summaries
+}
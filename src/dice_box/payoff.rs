@@ -0,0 +1,77 @@
+//! Ranking of units by how much the build would gain from compiling them faster, to point at the crates worth
+//! optimizing first. A unit that takes long is not necessarily one of them: if other units keep the threads busy
+//! in the meantime, making it faster does nothing for the makespan.
+use std::collections::BTreeMap;
+
+use tabled::Tabled;
+
+use crate::{
+    artifact::Artifact,
+    dependency_queue::DependencyQueueBuilder,
+    error::InputError,
+    registry::HintProviderFactory,
+    runner::{calculate_all, Duration, Runner},
+    timings::TimingInfo,
+};
+
+/// Makespan of the build with one unit sped up.
+#[derive(Clone, Debug, PartialEq, Tabled)]
+pub struct Payoff {
+    pub unit: String,
+    /// Duration of the unit before it was sped up.
+    pub duration: Duration,
+    pub makespan: Duration,
+    pub saving: Duration,
+}
+
+/// Simulates the build with `scheduler` on `num_threads` threads once per unit, with the duration of that unit cut by
+/// `reduction` (e.g. 1 for making it free, 0.2 for making it 20% faster). Returns at most `limit` units whose speedup
+/// shortens the build, the biggest savings first. Simulations run in parallel, one batch per available core.
+pub fn rank_payoffs(
+    deps: &DependencyQueueBuilder,
+    timings: &BTreeMap<Artifact, TimingInfo>,
+    scheduler: &dyn HintProviderFactory,
+    num_threads: usize,
+    reduction: f64,
+    limit: usize,
+) -> Result<Vec<Payoff>, InputError> {
+    let runner = |timings: BTreeMap<Artifact, TimingInfo>| {
+        let hints = scheduler.create(deps, &timings);
+        Runner::new(deps.clone().finish(hints), timings, num_threads)
+    };
+    let baseline = runner(timings.clone()).calculate()?.0.makespan;
+    let units: Vec<&Artifact> = timings
+        .iter()
+        .filter(|(_, timing)| timing.duration > 0.)
+        .map(|(artifact, _)| artifact)
+        .collect();
+    let batch = std::thread::available_parallelism().map_or(1, |cores| cores.get());
+    let mut payoffs = vec![];
+    for chunk in units.chunks(batch) {
+        let mut scenarios: Vec<Runner> = chunk
+            .iter()
+            .map(|unit| {
+                let mut timings = timings.clone();
+                let timing = timings.get_mut(*unit).unwrap();
+                timing.duration *= 1. - reduction;
+                if let Some(rmeta_time) = timing.rmeta_time.as_mut() {
+                    *rmeta_time *= 1. - reduction;
+                }
+                runner(timings)
+            })
+            .collect();
+        for (unit, (makespan, _)) in chunk.iter().zip(calculate_all(&mut scenarios)?) {
+            if makespan.makespan < baseline {
+                payoffs.push(Payoff {
+                    unit: unit.to_string(),
+                    duration: Duration::from_secs_f64(timings[*unit].duration),
+                    makespan: makespan.makespan,
+                    saving: baseline - makespan.makespan,
+                });
+            }
+        }
+    }
+    payoffs.sort_by(|a, b| b.saving.cmp(&a.saving).then(b.duration.cmp(&a.duration)));
+    payoffs.truncate(limit);
+    Ok(payoffs)
+}
@@ -0,0 +1,114 @@
+//! Simulation of the two-phase CI pipeline: `cargo build` followed by `cargo test`.
+//!
+//! The test phase is described by its own unit graph and timings (e.g. from `cargo test --no-run --unit-graph`).
+//! Units that were already built by the build phase are reused as is, so only test-specific units (test harnesses
+//! and whatever dev-dependencies pull in) are left for the second phase.
+use std::collections::{BTreeMap, BTreeSet};
+
+use tabled::Tabled;
+
+use crate::{
+    artifact::Artifact,
+    dependency_queue::DependencyQueueBuilder,
+    error::InputError,
+    hints::HintProvider,
+    runner::{Duration, Runner},
+    timings::{TimingInfo, Timings},
+};
+
+/// Results of simulating the build & test pipeline with a single scheduler.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Tabled)]
+pub struct PipelineMakespan {
+    pub label: String,
+    pub num_threads: usize,
+    pub build: Duration,
+    /// Time taken by `cargo test` after the build, excluding units reused from the build.
+    pub test: Duration,
+    pub total: Duration,
+}
+
+pub struct Pipeline {
+    build: DependencyQueueBuilder,
+    build_timings: BTreeMap<Artifact, TimingInfo>,
+    test: DependencyQueueBuilder,
+    test_timings: BTreeMap<Artifact, TimingInfo>,
+}
+
+impl Pipeline {
+    pub fn new(
+        build: &DependencyQueueBuilder,
+        build_timings: &BTreeMap<Artifact, TimingInfo>,
+        test: &DependencyQueueBuilder,
+        test_timings: &BTreeMap<Artifact, TimingInfo>,
+    ) -> Self {
+        let reused: BTreeSet<&Artifact> = test
+            .insertion_order
+            .iter()
+            .filter(|artifact| build.contains(artifact))
+            .collect();
+        let mut remaining = DependencyQueueBuilder::new();
+        let mut remaining_timings = BTreeMap::new();
+        for artifact in test
+            .insertion_order
+            .iter()
+            .filter(|artifact| !reused.contains(artifact))
+        {
+            remaining.queue(
+                artifact.clone(),
+                test.dep_map[artifact]
+                    .iter()
+                    .filter(|dep| !reused.contains(dep))
+                    .cloned(),
+            );
+            remaining_timings.extend(
+                test_timings
+                    .get(artifact)
+                    .map(|timing| (artifact.clone(), timing.clone())),
+            );
+        }
+        Self {
+            build: build.clone(),
+            build_timings: build_timings.clone(),
+            test: remaining,
+            test_timings: remaining_timings,
+        }
+    }
+
+    /// Simulates the pipeline, with scheduling decisions of both phases made by hint providers created with
+    /// `hints`. Returns the combined timeline of both phases.
+    pub fn run(
+        &self,
+        hints: impl Fn(
+            &DependencyQueueBuilder,
+            &BTreeMap<Artifact, TimingInfo>,
+        ) -> Box<dyn HintProvider>,
+        num_threads: usize,
+    ) -> Result<(PipelineMakespan, Timings), InputError> {
+        let build_hints = hints(&self.build, &self.build_timings);
+        let label = build_hints.label();
+        let (build, build_timings) = Runner::new(
+            self.build.clone().finish(build_hints),
+            self.build_timings.clone(),
+            num_threads,
+        )
+        .calculate()?;
+        let (test, test_timings) = Runner::new(
+            self.test
+                .clone()
+                .finish(hints(&self.test, &self.test_timings)),
+            self.test_timings.clone(),
+            num_threads,
+        )
+        .calculate()?;
+        Ok((
+            PipelineMakespan {
+                label,
+                num_threads,
+                total: build.makespan + test.makespan,
+                build: build.makespan,
+                test: test.makespan,
+            },
+            build_timings.followed_by(test_timings),
+        ))
+    }
+}
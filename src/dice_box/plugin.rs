@@ -0,0 +1,211 @@
+//! Hint providers compiled to WebAssembly, so that scheduling policies can be shared without merging them into
+//! dice_box. Only available with the `plugins` feature.
+//!
+//! A plugin is a core WebAssembly module without imports. Version 1 of the interface expects it to export:
+//! - `memory`, the memory that inputs are passed in;
+//! - `dice_box_abi_version() -> i32`, returning the version of the interface the plugin implements ([ABI_VERSION]);
+//! - `label_ptr() -> i32` and `label_len() -> i32`, locating a UTF-8 label of the plugin in its memory;
+//! - `alloc(len: i32) -> i32` and `dealloc(ptr: i32, len: i32)`, to manage buffers for inputs;
+//! - `suggest_next(ptr: i32, len: i32) -> i32`, returning the index of the candidate to start next, or -1 to start
+//!   none of them.
+//!
+//! The input of `suggest_next` is JSON, describing candidates the same way as for scripted hint providers:
+//! ```json
+//! {
+//!   "candidates": [
+//!     { "package-id": "path+file:///ws/crates/api#0.1.0", "name": "api", "type": "metadata", "duration": 1.5, "dependants": 3 }
+//!   ],
+//!   "context": { "now": 12.5, "running": 3, "idle-slots": 1 }
+//! }
+//! ```
+//! Durations and times are in seconds. New fields may be added without bumping the version; anything else does.
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use wasmtime::{Engine, Instance, Memory, Module, Store, TypedFunc};
+
+use crate::{
+    artifact::{split_package_id, Artifact},
+    critical_path::duration_ms,
+    dependency_queue::DependencyQueueBuilder,
+    hints::{HintProvider, SchedulerContext},
+    timings::TimingInfo,
+};
+
+/// Version of the plugin interface implemented by dice_box.
+pub const ABI_VERSION: i32 = 1;
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct PluginCandidate<'a> {
+    package_id: &'a str,
+    name: &'a str,
+    #[serde(rename = "type")]
+    typ: &'static str,
+    duration: f64,
+    dependants: usize,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct PluginContext {
+    now: f64,
+    running: usize,
+    idle_slots: usize,
+}
+
+#[derive(Serialize)]
+struct PluginInput<'a> {
+    candidates: Vec<PluginCandidate<'a>>,
+    context: PluginContext,
+}
+
+pub struct PluginHints {
+    store: Store<()>,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    dealloc: TypedFunc<(i32, i32), ()>,
+    suggest: TypedFunc<(i32, i32), i32>,
+    label: String,
+    durations: BTreeMap<Artifact, u64>,
+    dependants: BTreeMap<Artifact, usize>,
+    /// First error of the plugin, after which it's no longer consulted.
+    failure: Option<String>,
+}
+
+impl std::fmt::Debug for PluginHints {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PluginHints")
+            .field("label", &self.label)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PluginHints {
+    /// Loads the plugin at `path`, checking that it implements a supported version of the interface.
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(
+        path: &Path,
+        dependencies: &DependencyQueueBuilder,
+        timings: &BTreeMap<Artifact, TimingInfo>,
+    ) -> Result<Box<dyn HintProvider>> {
+        let engine = Engine::default();
+        let module = Module::from_file(&engine, path)
+            .with_context(|| format!("failed to load {}", path.display()))?;
+        let mut store = Store::new(&engine, ());
+        let instance = Instance::new(&mut store, &module, &[])?;
+        let version = instance
+            .get_typed_func::<(), i32>(&mut store, "dice_box_abi_version")?
+            .call(&mut store, ())?;
+        if version != ABI_VERSION {
+            bail!(
+                "{} implements version {version} of the plugin interface, but only {ABI_VERSION} is supported",
+                path.display()
+            );
+        }
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .context("plugins have to export their memory")?;
+        let label_ptr = instance
+            .get_typed_func::<(), i32>(&mut store, "label_ptr")?
+            .call(&mut store, ())?;
+        let label_len = instance
+            .get_typed_func::<(), i32>(&mut store, "label_len")?
+            .call(&mut store, ())?;
+        let mut label = vec![0; label_len as usize];
+        memory.read(&store, label_ptr as usize, &mut label)?;
+        Ok(Box::new(Self {
+            alloc: instance.get_typed_func(&mut store, "alloc")?,
+            dealloc: instance.get_typed_func(&mut store, "dealloc")?,
+            suggest: instance.get_typed_func(&mut store, "suggest_next")?,
+            store,
+            memory,
+            label: format!("Plugin ({})", String::from_utf8(label)?),
+            durations: dependencies
+                .dep_map
+                .keys()
+                .map(|artifact| (artifact.clone(), duration_ms(timings, artifact)))
+                .collect(),
+            dependants: dependencies
+                .reverse_dep_map
+                .iter()
+                .map(|(artifact, dependants)| (artifact.clone(), dependants.len()))
+                .collect(),
+            failure: None,
+        }))
+    }
+
+    fn call(&mut self, input: &[u8]) -> Result<i32> {
+        let len = i32::try_from(input.len())?;
+        let ptr = self.alloc.call(&mut self.store, len)?;
+        self.memory.write(&mut self.store, ptr as usize, input)?;
+        let choice = self.suggest.call(&mut self.store, (ptr, len))?;
+        self.dealloc.call(&mut self.store, (ptr, len))?;
+        Ok(choice)
+    }
+
+    /// Index of the candidate the plugin picks, if any.
+    fn pick(
+        &mut self,
+        timings: &[&Artifact],
+        context: &SchedulerContext,
+    ) -> Result<Option<usize>, String> {
+        let input = PluginInput {
+            candidates: timings
+                .iter()
+                .map(|artifact| PluginCandidate {
+                    package_id: &artifact.package_id,
+                    name: split_package_id(&artifact.package_id).0,
+                    typ: artifact.typ.name(),
+                    duration: self.durations.get(*artifact).copied().unwrap_or_default() as f64
+                        / 1000.,
+                    dependants: self.dependants.get(*artifact).copied().unwrap_or_default(),
+                })
+                .collect(),
+            context: PluginContext {
+                now: context.now as f64 / 1000.,
+                running: context.running.len(),
+                idle_slots: context.idle_slots,
+            },
+        };
+        let input = serde_json::to_vec(&input).map_err(|err| format!("failed: {err}"))?;
+        let choice = self.call(&input).map_err(|err| format!("failed: {err}"))?;
+        if choice == -1 {
+            return Ok(None);
+        }
+        usize::try_from(choice)
+            .ok()
+            .filter(|index| *index < timings.len())
+            .map(Some)
+            .ok_or_else(|| format!("returned an out of range index {choice}"))
+    }
+}
+
+impl HintProvider for PluginHints {
+    fn suggest_next<'a>(
+        &mut self,
+        timings: &[&'a Artifact],
+        context: &SchedulerContext,
+    ) -> Option<&'a Artifact> {
+        if self.failure.is_some() {
+            return timings.first().copied();
+        }
+        match self.pick(timings, context) {
+            Ok(choice) => choice.map(|index| timings[index]),
+            Err(failure) => {
+                self.failure = Some(failure);
+                timings.first().copied()
+            }
+        }
+    }
+
+    fn label(&self) -> String {
+        self.label.clone()
+    }
+
+    fn failure(&self) -> Option<String> {
+        self.failure.clone()
+    }
+}
@@ -0,0 +1,93 @@
+//! Queries over the dependency graph: what a unit needs, what needs it, what two units have in common and how one
+//! leads to the other. Units of a package can be looked up with [package_units].
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use crate::{
+    artifact::Artifact,
+    constraints::matches,
+    dependency_queue::{reverse_dependencies, DependencyQueueBuilder},
+};
+
+/// Units of `package`, given by its name or full package id.
+pub fn package_units(deps: &DependencyQueueBuilder, package: &str) -> Vec<Artifact> {
+    deps.dep_map
+        .keys()
+        .filter(|artifact| matches(&artifact.package_id, package))
+        .cloned()
+        .collect()
+}
+
+/// Units that any of `units` depends on, directly or transitively, save for `units` themselves.
+pub fn transitive_dependencies(
+    deps: &DependencyQueueBuilder,
+    units: &[Artifact],
+) -> BTreeSet<Artifact> {
+    let mut closure: BTreeSet<&Artifact> = BTreeSet::new();
+    let mut stack: Vec<&Artifact> = units.iter().collect();
+    while let Some(artifact) = stack.pop() {
+        if closure.insert(artifact) {
+            stack.extend(deps.dep_map.get(artifact).into_iter().flatten());
+        }
+    }
+    closure
+        .into_iter()
+        .filter(|artifact| !units.contains(artifact))
+        .cloned()
+        .collect()
+}
+
+/// Units that depend on any of `units`, directly or transitively, save for `units` themselves.
+pub fn transitive_dependants(
+    deps: &DependencyQueueBuilder,
+    units: &[Artifact],
+) -> BTreeSet<Artifact> {
+    let dependants = reverse_dependencies(deps);
+    units
+        .iter()
+        .flat_map(|unit| dependants.get(unit).into_iter().flatten())
+        .filter(|artifact| !units.contains(artifact))
+        .cloned()
+        .collect()
+}
+
+/// Units that both `left` and `right` depend on.
+pub fn shared_dependencies(
+    deps: &DependencyQueueBuilder,
+    left: &[Artifact],
+    right: &[Artifact],
+) -> BTreeSet<Artifact> {
+    let left = transitive_dependencies(deps, left);
+    let right = transitive_dependencies(deps, right);
+    left.intersection(&right).cloned().collect()
+}
+
+/// Shortest chain of dependency edges from one of `from` to one of `to`, with both ends included, if any of `from`
+/// depends on any of `to`.
+pub fn shortest_chain(
+    deps: &DependencyQueueBuilder,
+    from: &[Artifact],
+    to: &[Artifact],
+) -> Option<Vec<Artifact>> {
+    let mut reached_from: BTreeMap<&Artifact, Option<&Artifact>> =
+        from.iter().map(|artifact| (artifact, None)).collect();
+    let mut queue: VecDeque<&Artifact> = from.iter().collect();
+    while let Some(artifact) = queue.pop_front() {
+        if to.contains(artifact) {
+            let mut chain = vec![artifact.clone()];
+            let mut current = artifact;
+            while let Some(previous) = reached_from[current] {
+                chain.push(previous.clone());
+                current = previous;
+            }
+            chain.reverse();
+            return Some(chain);
+        }
+        for dependency in deps.dep_map.get(artifact).into_iter().flatten() {
+            if !reached_from.contains_key(dependency) {
+                reached_from.insert(dependency, Some(artifact));
+                queue.push_back(dependency);
+            }
+        }
+    }
+    None
+}
@@ -0,0 +1,106 @@
+//! Registry of hint providers, so that schedulers defined outside of dice_box can be simulated alongside the
+//! built-in ones.
+//!
+//! A downstream crate implements [HintProviderFactory] (or just writes a closure), registers it under a name and
+//! can then refer to it by that name, e.g. with `--scheduler`:
+//! ```ignore
+//! let mut registry = dice_box::Registry::builtin();
+//! registry.register("my-scheduler", |deps: &DependencyQueueBuilder, timings: &BTreeMap<Artifact, TimingInfo>| {
+//!     MyHints::new(deps, timings)
+//! });
+//! ```
+use std::collections::BTreeMap;
+
+use crate::{
+    artifact::Artifact, dependency_queue::DependencyQueueBuilder, hints::HintProvider,
+    timings::TimingInfo, CargoHints, CriticalPathHints, FifoHints, HeftHints, LptHints,
+    NHintsProvider, RandomHints, SjfHints, WeightedHints, Weights, WorstCaseHints,
+};
+
+/// Creates hint providers for a given dependency graph.
+pub trait HintProviderFactory {
+    fn create(
+        &self,
+        deps: &DependencyQueueBuilder,
+        timings: &BTreeMap<Artifact, TimingInfo>,
+    ) -> Box<dyn HintProvider>;
+}
+
+impl<F> HintProviderFactory for F
+where
+    F: Fn(&DependencyQueueBuilder, &BTreeMap<Artifact, TimingInfo>) -> Box<dyn HintProvider>,
+{
+    fn create(
+        &self,
+        deps: &DependencyQueueBuilder,
+        timings: &BTreeMap<Artifact, TimingInfo>,
+    ) -> Box<dyn HintProvider> {
+        self(deps, timings)
+    }
+}
+
+/// Hint provider factories by name.
+#[derive(Default)]
+pub struct Registry {
+    factories: BTreeMap<String, Box<dyn HintProviderFactory>>,
+}
+
+impl Registry {
+    /// Registry with all of the schedulers that come with dice_box.
+    pub fn builtin() -> Self {
+        let mut registry = Self::default();
+        registry.register("cargo", |deps: &DependencyQueueBuilder, _: &_| {
+            CargoHints::new(deps)
+        });
+        registry.register("n-hints", NHintsProvider::new);
+        registry.register("critical-path", CriticalPathHints::new);
+        registry.register("heft", HeftHints::new);
+        registry.register("sjf", |_: &_, timings: &_| SjfHints::new(timings));
+        registry.register("lpt", |_: &_, timings: &_| LptHints::new(timings));
+        registry.register("worst-case", WorstCaseHints::new);
+        registry.register("fifo", |deps: &DependencyQueueBuilder, _: &_| {
+            FifoHints::new(deps, None)
+        });
+        registry.register("random", |_: &_, _: &_| RandomHints::new(0));
+        registry.register("weighted", |deps: &_, timings: &_| {
+            WeightedHints::new(
+                deps,
+                timings,
+                Weights {
+                    critical_path: 1.,
+                    ..Default::default()
+                },
+            )
+        });
+        registry
+    }
+
+    /// Registers `factory` under `name`, replacing the factory registered under that name before (if any).
+    pub fn register(
+        &mut self,
+        name: impl Into<String>,
+        factory: impl HintProviderFactory + 'static,
+    ) {
+        self.factories.insert(name.into(), Box::new(factory));
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.factories.keys().map(String::as_str)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn HintProviderFactory> {
+        self.factories.get(name).map(|factory| &**factory)
+    }
+
+    /// Creates a hint provider with the factory registered under `name`, if there's one.
+    pub fn create(
+        &self,
+        name: &str,
+        deps: &DependencyQueueBuilder,
+        timings: &BTreeMap<Artifact, TimingInfo>,
+    ) -> Option<Box<dyn HintProvider>> {
+        self.factories
+            .get(name)
+            .map(|factory| factory.create(deps, timings))
+    }
+}
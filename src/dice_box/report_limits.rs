@@ -0,0 +1,68 @@
+//! Summarized reports for huge graphs.
+//!
+//! Reports drawing every unit grow with the size of the graph, and for graphs with tens of thousands of units they
+//! end up hundreds of megabytes large and too slow to open. Beyond a configurable number of units, reports switch to
+//! a summarized mode: units of packages outside of the workspace are collapsed into one entry per package, only the
+//! longest entries are drawn and graphs over time are down-sampled.
+use crate::runner::ScheduledTask;
+
+#[derive(Clone, Copy, Debug)]
+pub struct ReportLimits {
+    /// Graphs with more units than this are reported in summarized mode.
+    pub max_units: usize,
+    /// Number of entries (longest first) drawn in summarized reports.
+    pub top_units: usize,
+    /// Number of points graphs over time are down-sampled to in summarized reports.
+    pub max_points: usize,
+}
+
+impl ReportLimits {
+    pub fn exceeded_by(&self, units: usize) -> bool {
+        units > self.max_units
+    }
+
+    /// The longest `top_units` tasks of `schedule`, in their original order.
+    pub fn longest(&self, schedule: &[ScheduledTask]) -> Vec<ScheduledTask> {
+        let mut indices: Vec<usize> = (0..schedule.len()).collect();
+        indices
+            .sort_by_key(|index| std::cmp::Reverse(schedule[*index].end - schedule[*index].start));
+        indices.truncate(self.top_units);
+        indices.sort_unstable();
+        indices
+            .into_iter()
+            .map(|index| schedule[index].clone())
+            .collect()
+    }
+}
+
+/// Down-samples a series of `(time, value)` points sorted by time to at most `max_points` evenly spaced points,
+/// keeping the largest value within each of them so that peaks are not lost.
+pub(crate) fn downsample<T: Copy + PartialOrd>(
+    points: Vec<(f64, T)>,
+    max_points: usize,
+) -> Vec<(f64, T)> {
+    let (Some(first), Some(last)) = (points.first(), points.last()) else {
+        return points;
+    };
+    // Both ends of the series are always kept.
+    let max_points = max_points.max(2);
+    if points.len() <= max_points {
+        return points;
+    }
+    let (start, span) = (first.0, (last.0 - first.0).max(f64::EPSILON));
+    let mut buckets: Vec<Option<(f64, T)>> = vec![None; max_points];
+    for (time, value) in points {
+        let bucket = (((time - start) / span) * (max_points - 1) as f64).round() as usize;
+        let slot = &mut buckets[bucket.min(max_points - 1)];
+        match slot {
+            Some((_, max)) if *max >= value => {}
+            _ => {
+                *slot = Some((
+                    start + span * bucket as f64 / (max_points - 1) as f64,
+                    value,
+                ))
+            }
+        }
+    }
+    buckets.into_iter().flatten().collect()
+}
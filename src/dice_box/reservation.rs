@@ -0,0 +1,136 @@
+//! Scheduling with reservations: instead of filling a free thread with whatever is ready, [ReservationHints] may
+//! hold it for a unit that is about to become ready and that is more critical than anything that is ready now.
+//!
+//! Hint providers are only told about ready and running units, so [ReservationHints] infers which units are done
+//! from what it's seen: dependencies of ready units are done, and so are the units that stopped running.
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    artifact::{Artifact, ArtifactType},
+    critical_path::critical_path_lengths,
+    dependency_queue::DependencyQueueBuilder,
+    hints::{HintProvider, SchedulerContext, Suggestion},
+    timings::TimingInfo,
+};
+
+/// Critical path scheduler that holds a thread for a unit becoming ready within `max_wait` milliseconds, if that
+/// unit's critical path is longer than the one of the best ready unit by more than the wait.
+#[derive(Debug)]
+pub struct ReservationHints {
+    lengths: BTreeMap<Artifact, u64>,
+    dependencies: BTreeMap<Artifact, BTreeSet<Artifact>>,
+    dependants: BTreeMap<Artifact, BTreeSet<Artifact>>,
+    max_wait: u64,
+    done: BTreeSet<Artifact>,
+    /// Units that were running as of the last decision.
+    running: BTreeSet<Artifact>,
+    /// Time of the last decision, along with the number of threads held at that time.
+    held: (u64, usize),
+}
+
+impl ReservationHints {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(
+        dependencies: &DependencyQueueBuilder,
+        timings: &BTreeMap<Artifact, TimingInfo>,
+        max_wait: u64,
+    ) -> Box<dyn HintProvider> {
+        Box::new(Self {
+            lengths: critical_path_lengths(dependencies, timings),
+            dependencies: dependencies.dep_map.clone(),
+            dependants: dependencies.reverse_dep_map.clone(),
+            max_wait,
+            done: BTreeSet::new(),
+            running: BTreeSet::new(),
+            held: (0, 0),
+        })
+    }
+
+    fn length(&self, artifact: &Artifact) -> u64 {
+        self.lengths.get(artifact).copied().unwrap_or_default()
+    }
+
+    fn observe(&mut self, timings: &[&Artifact], context: &SchedulerContext) {
+        let running: BTreeSet<Artifact> = context
+            .running
+            .iter()
+            .map(|(artifact, _)| (*artifact).clone())
+            .collect();
+        self.done.extend(self.running.difference(&running).cloned());
+        for candidate in timings {
+            self.done
+                .extend(self.dependencies[*candidate].iter().cloned());
+        }
+        self.running = running;
+        if self.held.0 != context.now {
+            self.held = (context.now, 0);
+        }
+    }
+
+    /// Units that will become ready once some of the running ones finish, along with the time until then.
+    fn imminent(&self, timings: &[&Artifact], context: &SchedulerContext) -> Vec<(&Artifact, u64)> {
+        let remaining: BTreeMap<&Artifact, u64> = context.running.iter().copied().collect();
+        let mut imminent: BTreeSet<&Artifact> = BTreeSet::new();
+        for (running, _) in &context.running {
+            imminent.extend(self.dependants.get(*running).into_iter().flatten());
+        }
+        imminent
+            .into_iter()
+            // Codegen units are started right away by the queue, so there's no point in holding threads for them.
+            .filter(|unit| unit.typ != ArtifactType::Codegen)
+            .filter(|unit| {
+                !self.done.contains(*unit)
+                    && !remaining.contains_key(*unit)
+                    && !timings.contains(unit)
+            })
+            .filter_map(|unit| {
+                let ready_in = self.dependencies[unit]
+                    .iter()
+                    .map(|dependency| match remaining.get(dependency) {
+                        Some(remaining) => Some(*remaining),
+                        None => self.done.contains(dependency).then_some(0),
+                    })
+                    .try_fold(0, |latest, ready_in| Some(latest.max(ready_in?)))?;
+                Some((unit, ready_in))
+            })
+            .collect()
+    }
+}
+
+impl HintProvider for ReservationHints {
+    fn suggest_next<'a>(
+        &mut self,
+        timings: &[&'a Artifact],
+        context: &SchedulerContext,
+    ) -> Option<&'a Artifact> {
+        self.observe(timings, context);
+        timings
+            .iter()
+            .max_by_key(|artifact| self.length(artifact))
+            .cloned()
+    }
+
+    fn decide<'a>(
+        &mut self,
+        timings: &[&'a Artifact],
+        context: &SchedulerContext,
+    ) -> Option<Suggestion<&'a Artifact>> {
+        let best = self.suggest_next(timings, context)?;
+        let reservations = self
+            .imminent(timings, context)
+            .into_iter()
+            .filter(|(unit, ready_in)| {
+                *ready_in <= self.max_wait && self.length(unit) > self.length(best) + ready_in
+            })
+            .count();
+        if self.held.1 < reservations {
+            self.held.1 += 1;
+            return Some(Suggestion::Wait);
+        }
+        Some(Suggestion::Start(best))
+    }
+
+    fn label(&self) -> String {
+        format!("Reservation (up to {}ms)", self.max_wait)
+    }
+}
@@ -0,0 +1,68 @@
+//! Machine-readable results of a comparison, for CI jobs and other tooling that can't parse the text table. Results
+//! are written as a single JSON object, with durations in seconds; reports that are not part of it are printed to
+//! stderr if it goes to stdout:
+//! ```json
+//! {"environment":{"toolchain":null,"host":null,"targets":["host"],"profiles":["dev"],"cargo-args":[],"env":{}},"baseline":"Cargo Hints","scenarios":[{"label":"Cargo Hints","num-threads":4,"makespan":19.058,...}]}
+//! ```
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::{
+    distribution::ScheduleSpread,
+    environment::Environment,
+    runner::{CoreSizing, Makespan},
+    scalability::{ScalabilityFit, SweepPoint},
+};
+
+/// Format of the results of a comparison.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// A text table.
+    #[default]
+    Table,
+    /// A JSON object.
+    Json,
+}
+
+/// Results of all simulated scenarios, along with what they were compared against.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Results {
+    pub environment: Environment,
+    /// Label of the scenario the others were compared against.
+    pub baseline: String,
+    pub scenarios: Vec<Makespan>,
+    /// Best and worst makespans across schedulers, with `--worst-case`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spread: Option<ScheduleSpread>,
+    /// Makespans of the scenarios on each thread count of `--thread-sweep`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub thread_sweep: Vec<SweepPoint>,
+    /// Scalability laws fitted to the thread sweep of each scenario.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub scalability: Vec<ScalabilityFit>,
+    /// Fewest threads each scenario needs to get close to its makespan with unlimited threads, with `--min-cores`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub core_sizing: Vec<CoreSizing>,
+}
+
+impl Results {
+    /// Writes the results as JSON to a given file, or to stdout if there's none.
+    pub fn write_json(&self, path: Option<&Path>) -> Result<()> {
+        match path {
+            Some(path) => {
+                serde_json::to_writer(std::io::BufWriter::new(std::fs::File::create(path)?), self)?
+            }
+            None => {
+                let mut stdout = std::io::stdout().lock();
+                serde_json::to_writer(&mut stdout, self)?;
+                writeln!(stdout)?;
+            }
+        }
+        Ok(())
+    }
+}
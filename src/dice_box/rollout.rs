@@ -0,0 +1,277 @@
+//! Lookahead scheduling with rollouts.
+//!
+//! At each decision, [RolloutHints] simulates the rest of the build once per candidate, with the candidate started
+//! first and a cheap base policy (longest critical path first) making all of the later decisions, and picks the
+//! candidate with the shortest resulting makespan. Rollouts run on [Simulation], a light-weight copy of the core of
+//! the [Runner](crate::Runner) that is cheap to clone: units are referred to by indices and the graph is shared.
+//! Constraints, thread pool limits and external events are not modelled by it.
+//!
+//! Hint providers are only consulted for some of the decisions (ready codegen units are started right away), so
+//! [RolloutHints] keeps its own copy of the simulation in sync with the runner by inferring what happened in between
+//! from the candidates it's offered.
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap};
+use std::sync::Arc;
+
+use crate::{
+    artifact::{Artifact, ArtifactType},
+    critical_path::{critical_path_lengths, duration_ms},
+    dependency_queue::DependencyQueueBuilder,
+    hints::{HintProvider, SchedulerContext},
+    timings::TimingInfo,
+};
+
+#[derive(Debug)]
+struct Graph {
+    artifacts: Vec<Artifact>,
+    indices: BTreeMap<Artifact, usize>,
+    durations: Vec<u64>,
+    dependants: Vec<Vec<usize>>,
+    is_codegen: Vec<bool>,
+    /// Priorities of units under the base policy.
+    priorities: Vec<u64>,
+}
+
+/// State of a simulated build, as seen by the scheduler.
+#[derive(Clone, Debug)]
+struct Simulation {
+    graph: Arc<Graph>,
+    time: u64,
+    free_threads: usize,
+    /// Number of dependencies of each unit that are yet to be built.
+    remaining: Vec<usize>,
+    ready: Vec<usize>,
+    running: BinaryHeap<Reverse<(u64, usize)>>,
+}
+
+impl Simulation {
+    fn new(graph: Arc<Graph>, deps: &DependencyQueueBuilder, num_threads: usize) -> Self {
+        let remaining: Vec<usize> = graph
+            .artifacts
+            .iter()
+            .map(|artifact| deps.dep_map[artifact].len())
+            .collect();
+        let ready = (0..remaining.len())
+            .filter(|unit| remaining[*unit] == 0)
+            .collect();
+        Self {
+            graph,
+            time: 0,
+            free_threads: num_threads,
+            remaining,
+            ready,
+            running: BinaryHeap::new(),
+        }
+    }
+
+    fn start(&mut self, unit: usize) {
+        if let Some(position) = self.ready.iter().position(|ready| *ready == unit) {
+            self.ready.swap_remove(position);
+        }
+        self.free_threads = self.free_threads.saturating_sub(1);
+        self.running
+            .push(Reverse((self.time + self.graph.durations[unit], unit)));
+    }
+
+    /// Runs the unit that finishes first to completion. Returns `false` if nothing was running.
+    fn advance(&mut self) -> bool {
+        let Some(Reverse((end, unit))) = self.running.pop() else {
+            return false;
+        };
+        self.time = end;
+        self.free_threads += 1;
+        for dependant in &self.graph.dependants[unit] {
+            self.remaining[*dependant] -= 1;
+            if self.remaining[*dependant] == 0 {
+                self.ready.push(*dependant);
+            }
+        }
+        true
+    }
+
+    /// Ready codegen unit that is started ahead of everything else, the same way the
+    /// [DependencyQueue](crate::dependency_queue::DependencyQueue) does it.
+    fn ready_codegen(&self) -> Option<usize> {
+        self.ready
+            .iter()
+            .copied()
+            .filter(|unit| self.graph.is_codegen[*unit])
+            .min()
+    }
+
+    /// Finishes the build with the base policy and returns its makespan.
+    fn rollout(mut self) -> u64 {
+        loop {
+            while self.free_threads > 0 && !self.ready.is_empty() {
+                // Ties are broken the same way as by the runner, which goes through ready units in order.
+                let next = self.ready_codegen().unwrap_or_else(|| {
+                    *self
+                        .ready
+                        .iter()
+                        .max_by_key(|unit| (self.graph.priorities[**unit], **unit))
+                        .unwrap()
+                });
+                self.start(next);
+            }
+            if !self.advance() {
+                return self.time;
+            }
+        }
+    }
+}
+
+/// Picks the candidate whose rollout finishes the build the earliest, out of a bounded number of candidates that
+/// the base policy considers the most promising.
+#[derive(Debug)]
+pub struct RolloutHints {
+    simulation: Simulation,
+    max_candidates: usize,
+}
+
+impl RolloutHints {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(
+        dependencies: &DependencyQueueBuilder,
+        timings: &BTreeMap<Artifact, TimingInfo>,
+        num_threads: usize,
+        max_candidates: usize,
+    ) -> Box<dyn HintProvider> {
+        let lengths = critical_path_lengths(dependencies, timings);
+        let artifacts: Vec<Artifact> = dependencies.dep_map.keys().cloned().collect();
+        let indices: BTreeMap<Artifact, usize> = artifacts
+            .iter()
+            .enumerate()
+            .map(|(index, artifact)| (artifact.clone(), index))
+            .collect();
+        let graph = Graph {
+            durations: artifacts
+                .iter()
+                .map(|artifact| duration_ms(timings, artifact))
+                .collect(),
+            dependants: artifacts
+                .iter()
+                .map(|artifact| {
+                    dependencies
+                        .reverse_dep_map
+                        .get(artifact)
+                        .into_iter()
+                        .flatten()
+                        .map(|dependant| indices[dependant])
+                        .collect()
+                })
+                .collect(),
+            is_codegen: artifacts
+                .iter()
+                .map(|artifact| artifact.typ == ArtifactType::Codegen)
+                .collect(),
+            priorities: artifacts
+                .iter()
+                .map(|artifact| lengths.get(artifact).copied().unwrap_or_default())
+                .collect(),
+            artifacts,
+            indices,
+        };
+        Box::new(Self {
+            simulation: Simulation::new(Arc::new(graph), dependencies, num_threads),
+            max_candidates: max_candidates.max(1),
+        })
+    }
+}
+
+impl HintProvider for RolloutHints {
+    fn suggest_next<'a>(
+        &mut self,
+        timings: &[&'a Artifact],
+        _context: &SchedulerContext,
+    ) -> Option<&'a Artifact> {
+        let graph = self.simulation.graph.clone();
+        let mut candidates: Vec<(&'a Artifact, usize)> = timings
+            .iter()
+            .map(|artifact| (*artifact, graph.indices[*artifact]))
+            .collect();
+        // Catch up with the runner: it's only asking because a thread is free and all of the candidates are ready,
+        // and it has started all ready codegen units in the meantime.
+        loop {
+            let behind = self.simulation.free_threads == 0
+                || candidates
+                    .iter()
+                    .any(|(_, unit)| self.simulation.remaining[*unit] > 0);
+            if let Some(codegen) = self
+                .simulation
+                .ready_codegen()
+                .filter(|_| self.simulation.free_threads > 0)
+            {
+                self.simulation.start(codegen);
+            } else if !behind || !self.simulation.advance() {
+                break;
+            }
+        }
+        candidates.sort_by_key(|(_, unit)| (Reverse(graph.priorities[*unit]), Reverse(*unit)));
+        candidates.truncate(self.max_candidates);
+        let (chosen, unit) = candidates.into_iter().min_by_key(|(_, unit)| {
+            let mut simulation = self.simulation.clone();
+            simulation.start(*unit);
+            simulation.rollout()
+        })?;
+        self.simulation.start(unit);
+        Some(chosen)
+    }
+
+    fn label(&self) -> String {
+        format!("Rollout (top {})", self.max_candidates)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::Runner;
+
+    fn timing(package_id: &str, duration: f64) -> (Artifact, TimingInfo) {
+        let timing: TimingInfo = serde_json::from_value(serde_json::json!({
+            "package_id": package_id,
+            "target": { "name": package_id, "crate_types": ["bin"] },
+            "mode": "build",
+            "duration": duration,
+            "rmeta_time": null,
+        }))
+        .unwrap();
+        (
+            Artifact {
+                typ: ArtifactType::Link,
+                package_id: package_id.into(),
+            },
+            timing,
+        )
+    }
+
+    #[test]
+    fn rollouts_look_past_the_next_decision() {
+        // Picking units in the order they're queued in starts `c` and `d` first and leaves `a` -> `b` for last,
+        // which takes 7s; starting the chain right away finishes in 5s.
+        let timings: BTreeMap<Artifact, TimingInfo> = [
+            timing("c", 2.),
+            timing("d", 2.),
+            timing("e", 1.),
+            timing("a", 1.),
+            timing("b", 4.),
+        ]
+        .into_iter()
+        .collect();
+        let mut deps = DependencyQueueBuilder::new();
+        for name in ["c", "d", "e", "a"] {
+            deps.queue(timing(name, 0.).0, []);
+        }
+        deps.queue(timing("b", 0.).0, [timing("a", 0.).0]);
+        let makespan = |hints| {
+            Runner::new(deps.clone().finish(hints), timings.clone(), 2)
+                .calculate()
+                .unwrap()
+                .0
+                .makespan
+                .as_millis()
+        };
+        assert_eq!(makespan(crate::FifoHints::new(&deps, None)), 7000);
+        assert_eq!(makespan(RolloutHints::new(&deps, &timings, 2, 5)), 5000);
+    }
+}
@@ -1,35 +1,578 @@
-use std::collections::BTreeMap;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::hash::{Hash, Hasher};
 
-use crate::artifact::{Artifact, ArtifactType};
+use crate::artifact::{Artifact, ArtifactType, ThreadPool};
+use crate::cache::timings_fingerprint;
+use crate::constraints::Constraints;
+use crate::decision_log::{DecisionReason, LoggedCandidate, LoggedDecision};
 use crate::dependency_queue::DependencyQueue;
+use crate::error::InputError;
+use crate::events::ExternalEvent;
+use crate::external_schedule::ExternalSchedule;
+use crate::hints::{SchedulerContext, Suggestion};
+use crate::inversions::{InversionDetector, PriorityInversion};
+use crate::package::group_by_package;
 use crate::timings::{TimingInfo, Timings};
+use crate::trace::{Decision, ScenarioTrace};
 
 use log::trace;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use tabled::Tabled;
 
-#[derive(Clone, Debug, PartialEq, PartialOrd)]
-pub struct Duration(std::time::Duration);
+/// Length of (or point in) a simulated build. Displayed like [std::time::Duration] is debug-printed, and serialized
+/// as seconds, like the other times dice_box writes out. Subtraction saturates at zero; use
+/// [Duration::difference] for signed differences.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Duration(pub(crate) std::time::Duration);
 
 pub type StartTime = u64;
 
+impl Duration {
+    pub fn from_millis(millis: u64) -> Self {
+        Self(std::time::Duration::from_millis(millis))
+    }
+
+    pub fn from_secs_f64(secs: f64) -> Self {
+        Self(std::time::Duration::from_secs_f64(secs))
+    }
+
+    pub fn as_millis(&self) -> u64 {
+        self.0.as_millis() as u64
+    }
+
+    pub fn as_secs_f64(&self) -> f64 {
+        self.0.as_secs_f64()
+    }
+
+    /// How much longer this is than `other`, in seconds; negative if it's shorter.
+    pub fn difference(&self, other: Duration) -> f64 {
+        self.as_secs_f64() - other.as_secs_f64()
+    }
+
+    /// This as a percentage of `whole`, which is 100% if both are zero.
+    pub fn percentage_of(&self, whole: Duration) -> f64 {
+        if whole.0.is_zero() {
+            if self.0.is_zero() {
+                100.
+            } else {
+                f64::INFINITY
+            }
+        } else {
+            self.as_secs_f64() / whole.as_secs_f64() * 100.
+        }
+    }
+}
+
+impl From<std::time::Duration> for Duration {
+    fn from(duration: std::time::Duration) -> Self {
+        Self(duration)
+    }
+}
+
+impl From<Duration> for std::time::Duration {
+    fn from(duration: Duration) -> Self {
+        duration.0
+    }
+}
+
+impl std::ops::Add for Duration {
+    type Output = Duration;
+    fn add(self, other: Duration) -> Duration {
+        Duration(self.0 + other.0)
+    }
+}
+
+impl std::ops::AddAssign for Duration {
+    fn add_assign(&mut self, other: Duration) {
+        self.0 += other.0;
+    }
+}
+
+impl std::ops::Sub for Duration {
+    type Output = Duration;
+    fn sub(self, other: Duration) -> Duration {
+        Duration(self.0.saturating_sub(other.0))
+    }
+}
+
+impl std::iter::Sum for Duration {
+    fn sum<I: Iterator<Item = Duration>>(iter: I) -> Duration {
+        iter.fold(Duration::default(), |sum, duration| sum + duration)
+    }
+}
+
 impl std::fmt::Display for Duration {
     fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
         write!(fmt, "{:?}", self.0)
     }
 }
 
+impl Serialize for Duration {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(self.as_secs_f64())
+    }
+}
+
+impl<'de> Deserialize<'de> for Duration {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let secs = f64::deserialize(deserializer)?;
+        std::time::Duration::try_from_secs_f64(secs)
+            .map(Duration)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
 /// Makespan length, in seconds, of a given schedule.
-#[derive(Clone, Debug, PartialEq, PartialOrd, Tabled)]
+#[derive(Clone, Debug, PartialEq, PartialOrd, Serialize, Deserialize, Tabled)]
+#[serde(rename_all = "kebab-case")]
 pub struct Makespan {
     pub label: String,
     pub num_threads: usize,
     pub makespan: Duration,
+    /// Time at which the last unit of a workspace member finished, which is what developers actually wait for.
+    pub workspace_makespan: Duration,
+    /// Largest number of units that were ready to be built but not running at the same time.
+    pub peak_ready: usize,
+    /// Time-weighted average number of units that were ready to be built but not running. A deep ready queue means
+    /// that the build is starved of cores, while a shallow one means it's limited by the shape of the graph.
+    pub avg_ready: f64,
+    /// Wall-clock time it took to construct the hint provider for this scenario.
+    pub setup_time: Duration,
+    /// Length of the longest chain of dependent units; no scheduler can beat it.
+    pub critical_path: Duration,
+    /// Total work spread evenly across all threads; no scheduler can beat it either.
+    pub work_bound: Duration,
+    /// Makespan relative to the larger of the two lower bounds. The closer to 1, the less headroom is left.
+    pub bound_ratio: f64,
+    /// Larger of the two lower bounds relative to makespan: the share of the build that is down to compile times
+    /// rather than scheduling.
+    pub efficiency: f64,
+    /// Time during which ready units were held back by a limit on their thread pool (e.g. on concurrent links)
+    /// while threads sat idle, i.e. the limit was the bottleneck. Only set if there are pool limits.
+    #[serde(default)]
+    #[tabled(display_with = "display_pool_limited")]
+    pub pool_limited: Option<Duration>,
+    /// How much longer than the baseline scenario this one takes, in seconds; negative if it's faster.
+    #[serde(default)]
+    #[tabled(display_with = "display_delta")]
+    pub vs_baseline: Option<f64>,
+    /// [vs_baseline](Makespan::vs_baseline) as a percentage of the makespan of the baseline.
+    #[serde(default)]
+    #[tabled(display_with = "display_delta_percentage")]
+    pub vs_baseline_pct: Option<f64>,
+    /// Time-weighted average number of busy threads.
+    #[serde(default)]
+    #[tabled(display_with = "display_average")]
+    pub avg_concurrency: f64,
+    /// Busy thread time as a percentage of the thread time available over the build.
+    #[serde(default)]
+    #[tabled(display_with = "display_percentage")]
+    pub cpu_utilization: f64,
+    /// Thread time that was available but not used over the build.
+    #[serde(default)]
+    pub idle_core_time: Duration,
+}
+
+fn display_pool_limited(pool_limited: &Option<Duration>) -> String {
+    pool_limited.map_or_else(|| "-".into(), |duration| duration.to_string())
+}
+
+fn display_average(average: &f64) -> String {
+    // Round to two decimal places.
+    ((average * 100.).round() / 100.).to_string()
+}
+
+fn display_delta(delta: &Option<f64>) -> String {
+    delta.map_or_else(|| "-".into(), |delta| format!("{delta:+.3}s"))
+}
+
+fn display_delta_percentage(percentage: &Option<f64>) -> String {
+    percentage.map_or_else(|| "-".into(), |percentage| format!("{percentage:+.1}%"))
+}
+
+impl Makespan {
+    /// Fills in how each of `results` compares to the one at index `baseline`.
+    pub fn compare_to_baseline(results: &mut [Makespan], baseline: usize) {
+        let Some(baseline) = results.get(baseline).map(|result| result.makespan) else {
+            return;
+        };
+        for result in results {
+            result.vs_baseline = Some(result.makespan.difference(baseline));
+            result.vs_baseline_pct =
+                (baseline.as_millis() > 0).then(|| result.makespan.percentage_of(baseline) - 100.);
+        }
+    }
+}
+
+/// How efficient the recorded build was, measured the same way as [efficiency](Makespan::efficiency) of simulated
+/// ones, so that the two can be compared.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Tabled)]
+pub struct ObservedBuild {
+    pub label: String,
+    pub num_threads: usize,
+    /// Time from the start of the recorded build until its last unit finished.
+    pub wall_time: Duration,
+    pub critical_path: Duration,
+    pub work_bound: Duration,
+    pub efficiency: f64,
+}
+
+impl ObservedBuild {
+    /// Compares `wall_time` (in milliseconds) of the recorded build against lower bounds of a simulated `makespan`
+    /// of the same build.
+    pub fn new(wall_time: u64, makespan: &Makespan) -> Self {
+        let bound = makespan.critical_path.0.max(makespan.work_bound.0);
+        Self {
+            label: "Observed build".into(),
+            num_threads: makespan.num_threads,
+            wall_time: Duration::from_millis(wall_time),
+            critical_path: makespan.critical_path,
+            work_bound: makespan.work_bound,
+            efficiency: efficiency(bound.as_millis() as u64, wall_time),
+        }
+    }
+}
+
+/// Lower `bound` of a build relative to its `makespan`, rounded to two decimal places for display.
+fn efficiency(bound: u64, makespan: u64) -> f64 {
+    if makespan == 0 {
+        1.
+    } else {
+        (bound as f64 / makespan as f64 * 100.).round() / 100.
+    }
+}
+
+/// How much of the makespan of a scenario is down to the dispatch latency of Cargo's main loop.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Tabled)]
+pub struct DispatchOverhead {
+    pub label: String,
+    pub makespan: Duration,
+    pub without_latency: Duration,
+    pub overhead: Duration,
+    /// Overhead as a percentage of the makespan.
+    #[tabled(display_with = "display_percentage")]
+    pub share: f64,
+}
+
+fn display_percentage(percentage: &f64) -> String {
+    format!("{percentage:.1}%")
+}
+
+impl DispatchOverhead {
+    pub fn new(label: String, makespan: Duration, without_latency: Duration) -> Self {
+        let overhead = makespan - without_latency;
+        Self {
+            label,
+            makespan,
+            without_latency,
+            overhead,
+            share: overhead.percentage_of(makespan),
+        }
+    }
+}
+
+/// Longest time a unit spent waiting for a core after it became ready.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Tabled)]
+pub struct WaitTime {
+    pub label: String,
+    pub makespan: Duration,
+    pub max_wait: Duration,
+    pub unit: String,
+}
+
+/// What was going on during the final stretch of a build, which is typically the final crate and a link step
+/// running while most cores sit idle.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Tabled)]
+pub struct Tail {
+    pub label: String,
+    /// Time at which the tail starts.
+    pub start: Duration,
+    /// Average number of idle threads during the tail.
+    #[tabled(display_with = "display_idle_threads")]
+    pub idle_threads: f64,
+    /// Units that were running during the tail, the longest running ones first.
+    pub units: String,
+}
+
+/// Stretches of a build during which at most a given number of units were running, which serialize the build no
+/// matter how many threads it has.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Tabled)]
+pub struct SerialStretches {
+    pub label: String,
+    pub stretches: usize,
+    /// Total time spent in these stretches.
+    pub serialized: Duration,
+    /// Thread time left idle during these stretches.
+    pub lost: Duration,
+    /// Units that were running during these stretches, the ones that ran the longest in them first.
+    pub units: String,
+}
+
+fn display_idle_threads(idle_threads: &f64) -> String {
+    format!("{idle_threads:.2}")
+}
+
+/// How many of the allotted threads a scenario actually used.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Tabled)]
+pub struct Utilization {
+    pub label: String,
+    pub num_threads: usize,
+    /// Largest number of units that were running at the same time.
+    pub peak_threads: usize,
+    /// Total time during which all of the allotted threads were busy.
+    pub time_at_full: Duration,
+    /// Smallest number of threads achieving the same makespan, if the scenario didn't use most of its threads.
+    #[tabled(display_with = "display_right_size")]
+    pub right_size: Option<usize>,
+}
+
+fn display_right_size(right_size: &Option<usize>) -> String {
+    right_size.map_or_else(|| "-".into(), |threads| threads.to_string())
+}
+
+/// Smallest number of threads on which a scenario gets close to the makespan it would have with unlimited threads.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Serialize, Tabled)]
+#[serde(rename_all = "kebab-case")]
+pub struct CoreSizing {
+    pub label: String,
+    /// Makespan with as many threads as there are units.
+    pub unbounded: Duration,
+    pub min_threads: usize,
+    /// Makespan on `min_threads` threads.
+    pub makespan: Duration,
+}
+
+/// How much of the makespan of a scenario is lost to contention for disk IO bandwidth.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Tabled)]
+pub struct IoContention {
+    pub label: String,
+    pub makespan: Duration,
+    pub unlimited_io: Duration,
+    pub lost: Duration,
+    /// Lost time as a percentage of the makespan.
+    #[tabled(display_with = "display_percentage")]
+    pub share: f64,
+}
+
+impl IoContention {
+    pub fn new(label: String, makespan: Duration, unlimited_io: Duration) -> Self {
+        let lost = makespan - unlimited_io;
+        Self {
+            label,
+            makespan,
+            unlimited_io,
+            lost,
+            share: lost.percentage_of(makespan),
+        }
+    }
+}
+
+/// Disk IO bandwidth of the machine, as a resource on top of threads: running units take up some of it depending
+/// on their type, and units are not started if that would take more than there is.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IoBandwidth {
+    /// Bandwidth available to the build, in the units of `consumption`.
+    pub capacity: f64,
+    /// Bandwidth taken up by a running unit of a given type.
+    pub consumption: BTreeMap<ArtifactType, f64>,
+}
+
+impl IoBandwidth {
+    /// Bandwidth for `capacity` links running at once, with build scripts taking half as much as a link (building
+    /// one links it, running one often writes out generated code), codegen a quarter for writing object files and
+    /// metadata a tenth.
+    pub fn new(capacity: f64) -> Self {
+        Self {
+            capacity,
+            consumption: BTreeMap::from([
+                (ArtifactType::Link, 1.),
+                (ArtifactType::BuildScriptBuild, 0.5),
+                (ArtifactType::BuildScriptRun, 0.5),
+                (ArtifactType::Codegen, 0.25),
+                (ArtifactType::Metadata, 0.1),
+            ]),
+        }
+    }
+
+    fn consumption(&self, artifact: &Artifact) -> f64 {
+        self.consumption
+            .get(&artifact.typ)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Whether `artifact` can start while running units take up `in_use`. A unit that takes more than there is on
+    /// its own can still run alone.
+    fn allows(&self, artifact: &Artifact, in_use: f64) -> bool {
+        let consumption = self.consumption(artifact);
+        consumption == 0. || in_use == 0. || in_use + consumption <= self.capacity
+    }
+}
+
+/// Thermal throttling: cores slow down the longer the machine is kept busy, as laptops do once they heat up.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Throttling {
+    /// Speed of the cores at a given sustained load (the number of busy threads, averaged over `window`), as points
+    /// of a piecewise linear curve sorted by load. The curve starts at full speed with no load. Load is not relative
+    /// to the number of threads of the scenario, so that builds with different `-j` heat up the machine differently.
+    pub curve: Vec<(f64, f64)>,
+    /// Time over which load is averaged, in milliseconds; the longer, the slower the machine heats up and cools
+    /// down.
+    pub window: u64,
+}
+
+impl Throttling {
+    /// Speed of the cores at a given sustained `load`, relative to the one timings were recorded at.
+    pub fn speed(&self, load: f64) -> f64 {
+        let mut previous = (0., 1.);
+        for &(point_load, speed) in &self.curve {
+            if load <= point_load {
+                let (previous_load, previous_speed) = previous;
+                if point_load <= previous_load {
+                    return speed;
+                }
+                let progress = (load - previous_load) / (point_load - previous_load);
+                return previous_speed + (speed - previous_speed) * progress;
+            }
+            previous = (point_load, speed);
+        }
+        previous.1
+    }
+}
+
+/// GNU make style jobserver that Cargo and rustc share: every unit needs a token to run, which it holds until it
+/// finishes. Rustc keeps the token of a library once its metadata is done, for the codegen that follows. Cargo owns
+/// one token implicitly, while the others have to be acquired, which takes a while.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Jobserver {
+    pub tokens: usize,
+    /// Time it takes to acquire a token other than the implicit one, in milliseconds.
+    pub acquisition_latency: u64,
+}
+
+/// Tokens of a [Jobserver] as the simulation goes.
+#[derive(Clone, Debug)]
+struct Tokens {
+    jobserver: Jobserver,
+    /// Number of tokens held by running units and by codegen units that inherited them from metadata.
+    in_use: usize,
+    /// Codegen units that hold the token of their metadata, which finished already.
+    inherited: BTreeSet<Artifact>,
+}
+
+impl Tokens {
+    fn available_for(&self, artifact: &Artifact) -> bool {
+        self.in_use < self.jobserver.tokens || self.inherited.contains(artifact)
+    }
+    /// Number of tokens left for extra threads of `artifact`, once it has a token of its own.
+    fn spare_for(&self, artifact: &Artifact) -> usize {
+        let own = !self.inherited.contains(artifact) as usize;
+        self.jobserver.tokens.saturating_sub(self.in_use + own)
+    }
+    /// Takes a token for `artifact` that is about to start. Returns how long it takes to acquire it.
+    fn acquire(&mut self, artifact: &Artifact) -> u64 {
+        if self.inherited.remove(artifact) {
+            return 0;
+        }
+        self.in_use += 1;
+        if self.in_use == 1 {
+            // The implicit token of Cargo.
+            0
+        } else {
+            self.jobserver.acquisition_latency
+        }
+    }
+    /// Passes the token of a `finished` unit on to its codegen, if there's one, or returns it to the jobserver.
+    fn release(&mut self, finished: &Artifact, timings: &BTreeMap<Artifact, TimingInfo>) {
+        let codegen = Artifact {
+            typ: ArtifactType::Codegen,
+            package_id: finished.package_id.clone(),
+        };
+        if finished.typ == ArtifactType::Metadata && timings.contains_key(&codegen) {
+            self.inherited.insert(codegen);
+        } else {
+            self.in_use -= 1;
+        }
+    }
+}
+
+/// Parallel frontend of rustc (`-Zthreads`): metadata units can be given several threads, which speeds them up
+/// according to Amdahl's law.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ParallelFrontend {
+    /// Largest number of threads a single unit can be given.
+    pub max_threads: usize,
+    /// Fraction of the work of a unit that parallelizes.
+    pub parallel_fraction: f64,
+}
+
+impl ParallelFrontend {
+    /// How many times faster a unit is with `threads` threads than with one.
+    pub fn speedup(&self, threads: usize) -> f64 {
+        1. / (1. - self.parallel_fraction + self.parallel_fraction / threads.max(1) as f64)
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
 struct Task {
     artifact: Artifact,
     end_time: u64,
+    /// Whether the slot is only lent to a unit running on another one, as an extra thread of its frontend or its
+    /// codegen. Extra threads hold a jobserver token each, if there's a jobserver.
+    helper: bool,
+}
+
+/// Thread a unit is about to start on, for duration models to adjust its duration to.
+struct Placement<'a> {
+    unit: &'a Artifact,
+    slot: usize,
+    /// Threads the unit is given, counting the one of `slot`.
+    threads: usize,
+}
+
+/// Time at which a ready unit can be started given the dispatch `latency`, if it's held back at all. Units without
+/// dependencies are ready from the start.
+fn dispatch_time(
+    ready_at: &BTreeMap<Artifact, u64>,
+    latency: u64,
+    artifact: &Artifact,
+) -> Option<u64> {
+    (latency > 0 && artifact.typ != ArtifactType::Codegen)
+        .then(|| ready_at.get(artifact).copied().unwrap_or_default() + latency)
+}
+
+/// Simulates all `scenarios`, each on its own thread. Runners share no mutable state, so the results are the same as
+/// if they were calculated one after another.
+pub fn calculate_all(scenarios: &mut [Runner]) -> Result<Vec<(Makespan, Timings)>, InputError> {
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = scenarios
+            .iter_mut()
+            .map(|runner| scope.spawn(|| runner.calculate()))
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect()
+    })
+}
+
+/// A task as it was executed in a simulated build, with times in milliseconds.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScheduledTask {
+    pub artifact: Artifact,
+    pub start: u64,
+    pub end: u64,
+}
+
+/// Units of `schedule` in the order they were started in.
+pub(crate) fn start_order(schedule: &[ScheduledTask]) -> Vec<Artifact> {
+    let mut tasks: Vec<&ScheduledTask> = schedule.iter().collect();
+    tasks.sort_by_key(|task| task.start);
+    tasks
+        .into_iter()
+        .map(|task| task.artifact.clone())
+        .collect()
 }
 
 pub struct Runner {
@@ -39,7 +582,90 @@ pub struct Runner {
     running_tasks: Vec<Option<Task>>,
     running_tasks_count: usize,
     label: String,
+    /// Whether this is a reference schedule, not achievable on the simulated machine (see [Runner::with_optimal]).
+    optimal: bool,
     order: Vec<(StartTime, Artifact)>,
+    setup_time: std::time::Duration,
+    /// When set, the real time spent by the hint provider making decisions is charged as simulated time.
+    budgeted: bool,
+    /// Bookkeeping time of the coordinator per running unit, charged before each decision.
+    coordinator_cost: std::time::Duration,
+    /// Time (in milliseconds) between a unit becoming ready and the coordinator being able to start it.
+    dispatch_latency: u64,
+    /// Time (in milliseconds) it takes to spawn the process of a unit, on top of its duration.
+    spawn_overhead: u64,
+    /// Decision time that was measured but not yet charged, as it does not add up to a full millisecond.
+    pending_overhead: std::time::Duration,
+    constraints: Constraints,
+    inversions: Option<InversionDetector>,
+    history: Vec<ScheduledTask>,
+    /// Limits on the number of tasks of a given kind that can run at the same time.
+    pool_limits: BTreeMap<ThreadPool, usize>,
+    tokens: Option<Tokens>,
+    io_bandwidth: Option<IoBandwidth>,
+    throttling: Option<Throttling>,
+    /// Number of busy threads, averaged over the throttling window.
+    sustained_load: f64,
+    num_threads: usize,
+    /// Net number of cores currently available; it can be changed over time by external events. It can drop below
+    /// zero while events overlap, so that later changes still balance out; read it through
+    /// [capacity](Runner::capacity).
+    capacity: i64,
+    /// Pending changes in available cores, as (time, delta) pairs sorted by time.
+    capacity_changes: Vec<(u64, i64)>,
+    /// Time until which external events keep the machine busy.
+    external_work_end: u64,
+    events: Vec<ExternalEvent>,
+    /// Fraction of time saved by a codegen unit that runs on the same core right after metadata of its package.
+    affinity_discount: Option<f64>,
+    /// Whether to prefer picking units of the package that just ran on a core, to benefit from warm caches.
+    affinity_placement: bool,
+    /// Speed of each of the cores, relative to the one timings were recorded on.
+    core_speeds: Vec<f64>,
+    parallel_frontend: Option<ParallelFrontend>,
+    /// Number of codegen units of each codegen unit, when their codegen runs in parallel.
+    codegen_units: Option<BTreeMap<Artifact, usize>>,
+    /// Last unit started on each of the cores.
+    last_on_slot: Vec<Option<Artifact>>,
+    /// Every scheduling decision made so far.
+    decisions: Vec<Decision>,
+    /// Decisions to make instead of consulting the hint provider, when replaying a trace.
+    replay: Option<VecDeque<Decision>>,
+    /// Whether to consult the hint provider once the replayed decisions run out, instead of failing.
+    replay_prefix: bool,
+    /// Why the replayed decisions were abandoned, if they didn't fit the simulation.
+    replay_failure: Option<String>,
+    /// Fingerprint of the runner as of the start of the simulation, recorded in its trace.
+    initial_fingerprint: Option<u64>,
+    /// Time at which each unit had all of its dependencies built.
+    ready_at: BTreeMap<Artifact, u64>,
+    /// Number of ready units that were not running as of the last step, along with the time of that step.
+    ready_depth: (u64, usize),
+    peak_ready: usize,
+    /// Sum of ready queue depths over time, in unit-milliseconds.
+    ready_area: u64,
+    /// Number of running units and of available threads as of the last step, along with the time of that step.
+    running: (u64, usize, usize),
+    /// Sum of busy threads over time, in thread-milliseconds.
+    busy_area: u64,
+    /// Sum of available threads over time, in thread-milliseconds.
+    capacity_area: u64,
+    peak_running: usize,
+    /// Time (in milliseconds) during which all of the allotted threads were busy.
+    time_at_full: u64,
+    /// Whether a pool limit held back ready units while threads were idle as of the last step, along with the time
+    /// of that step.
+    pool_bound: (u64, bool),
+    /// Time (in milliseconds) during which pool limits were the bottleneck.
+    pool_limited: u64,
+    /// Critical path length and total work of the whole build, in milliseconds.
+    lower_bounds: (u64, u64),
+    /// Times (in milliseconds) before which units of an external schedule must not start.
+    fixed_starts: BTreeMap<Artifact, u64>,
+    /// Threads that units of an external schedule have to run on.
+    fixed_threads: BTreeMap<Artifact, usize>,
+    /// Decisions made by consulting the hint provider, along with the candidates it was given, if logging them.
+    decision_log: Option<Vec<LoggedDecision>>,
 }
 
 impl Runner {
@@ -49,13 +675,59 @@ impl Runner {
         num_threads: usize,
     ) -> Self {
         Self {
+            lower_bounds: queue.lower_bounds(&timings),
             running_tasks: vec![None; num_threads],
             label: queue.hints().label(),
+            optimal: false,
             queue,
             timings,
             current_time: 0,
             running_tasks_count: 0,
             order: Default::default(),
+            setup_time: Default::default(),
+            budgeted: false,
+            coordinator_cost: Default::default(),
+            dispatch_latency: 0,
+            spawn_overhead: 0,
+            pending_overhead: Default::default(),
+            constraints: Default::default(),
+            inversions: None,
+            history: Default::default(),
+            pool_limits: Default::default(),
+            tokens: None,
+            io_bandwidth: None,
+            throttling: None,
+            sustained_load: 0.,
+            num_threads,
+            capacity: num_threads as i64,
+            capacity_changes: Default::default(),
+            external_work_end: 0,
+            events: Default::default(),
+            affinity_discount: None,
+            affinity_placement: false,
+            core_speeds: vec![1.; num_threads],
+            parallel_frontend: None,
+            codegen_units: None,
+            last_on_slot: vec![None; num_threads],
+            decisions: Default::default(),
+            replay: None,
+            replay_prefix: false,
+            replay_failure: None,
+            initial_fingerprint: None,
+            ready_at: Default::default(),
+            ready_depth: (0, 0),
+            peak_ready: 0,
+            ready_area: 0,
+            running: (0, 0, num_threads),
+            busy_area: 0,
+            capacity_area: 0,
+            peak_running: 0,
+            time_at_full: 0,
+            pool_bound: (0, false),
+            pool_limited: 0,
+            fixed_starts: Default::default(),
+            fixed_threads: Default::default(),
+            decision_log: None,
         }
     }
 
@@ -63,23 +735,483 @@ impl Runner {
         self.label = label;
         self
     }
+
+    /// Marks the runner as a reference schedule that isn't achievable on the simulated machine (e.g. because it has
+    /// more threads), so it's left out of comparisons between schedulers.
+    pub fn with_optimal(mut self, optimal: bool) -> Self {
+        self.optimal = optimal;
+        self
+    }
+
+    /// Whether the runner was marked with [Runner::with_optimal].
+    pub fn is_optimal(&self) -> bool {
+        self.optimal
+    }
+
+    /// Records how long it took to set up the hint provider driving this runner, so that it can be reported
+    /// alongside the simulated makespan.
+    pub fn with_setup_time(mut self, setup_time: std::time::Duration) -> Self {
+        self.setup_time = setup_time;
+        self
+    }
+
+    /// Charges the real decision-time of the hint provider as scheduling overhead in the simulation, which
+    /// penalizes sophisticated-but-slow heuristics the way they would be inside of a real cargo process.
+    pub fn with_budgeted_scheduling(mut self, budgeted: bool) -> Self {
+        self.budgeted = budgeted;
+        self
+    }
+
+    /// Charges `cost` for every running unit before each decision, as Cargo's main loop does bookkeeping
+    /// (fingerprints, file system notifications, jobserver tokens) for all of them on a single thread. This keeps
+    /// simulations on many cores from scaling better than Cargo ever does.
+    pub fn with_coordinator_cost(mut self, cost: std::time::Duration) -> Self {
+        self.coordinator_cost = cost;
+        self
+    }
+
+    /// Holds back units for `latency` milliseconds after they become ready, as Cargo's main loop drains messages
+    /// and checks fingerprints before it starts them. Codegen continues in the process that built metadata, so it's
+    /// not held back.
+    pub fn with_dispatch_latency(mut self, latency: u64) -> Self {
+        self.dispatch_latency = latency;
+        self
+    }
+
+    /// Adds `overhead` milliseconds to every unit for spawning its process (rustc, a build script or wrappers like
+    /// sccache). Codegen continues in the process that built metadata, so it does not pay it again.
+    pub fn with_spawn_overhead(mut self, overhead: u64) -> Self {
+        self.spawn_overhead = overhead;
+        self
+    }
+
+    /// Gives cores different `speeds` (e.g. performance and efficiency cores), as multiples of the speed of the
+    /// machine timings were recorded on; cores beyond the listed ones run at the fastest listed speed, so that runners
+    /// with more threads than the machine has still bound it. Units take their duration divided by the speed of their
+    /// core, and free cores are filled fastest first.
+    pub fn with_core_speeds(mut self, speeds: &[f64]) -> Self {
+        let Some(fastest) = speeds.iter().copied().max_by(f64::total_cmp) else {
+            return self;
+        };
+        let mut speeds = speeds.to_vec();
+        speeds.resize(speeds.len().max(self.core_speeds.len()), fastest);
+        speeds.sort_by(|a, b| b.total_cmp(a));
+        speeds.truncate(self.core_speeds.len());
+        self.core_speeds = speeds;
+        self
+    }
+
+    /// Lets metadata units run on several threads at once, as many as their hint provider
+    /// [asks for](crate::HintProvider::threads_for) out of the idle ones.
+    pub fn with_parallel_frontend(mut self, parallel_frontend: ParallelFrontend) -> Self {
+        self.parallel_frontend = Some(parallel_frontend);
+        self
+    }
+
+    /// Runs codegen of each unit on as many idle threads as it has `codegen_units` (a thread per codegen unit at
+    /// most), rather than on one. Extra threads are taken when codegen starts and held until it finishes, while
+    /// rustc acquires and releases them as it goes.
+    pub fn with_parallel_codegen(mut self, codegen_units: BTreeMap<Artifact, usize>) -> Self {
+        self.codegen_units = Some(codegen_units);
+        self
+    }
+
+    /// Makes the runner enforce external constraints on when and where units can run.
+    pub fn with_constraints(mut self, constraints: Constraints) -> Self {
+        self.constraints = constraints;
+        self
+    }
+
+    /// Limits the number of tasks of a given kind that can run at once; they still occupy one of the
+    /// `num_threads` slots while running.
+    pub fn with_pool_limit(mut self, pool: ThreadPool, limit: usize) -> Self {
+        self.pool_limits.insert(pool, limit);
+        self
+    }
+
+    /// Makes units acquire tokens of a `jobserver` to run, on top of a free slot.
+    pub fn with_jobserver(mut self, jobserver: Jobserver) -> Self {
+        self.tokens = Some(Tokens {
+            jobserver,
+            in_use: 0,
+            inherited: Default::default(),
+        });
+        self
+    }
+
+    /// Makes units share disk IO `bandwidth`, on top of threads.
+    pub fn with_io_bandwidth(mut self, bandwidth: IoBandwidth) -> Self {
+        self.io_bandwidth = Some(bandwidth);
+        self
+    }
+
+    /// Lifts the limit on disk IO bandwidth, if any, e.g. to see how much time was lost to it.
+    pub fn without_io_limit(mut self) -> Self {
+        self.io_bandwidth = None;
+        self
+    }
+
+    /// Slows cores down under sustained load according to `throttling`. A unit runs at the speed the cores had
+    /// when it started.
+    pub fn with_throttling(mut self, throttling: Throttling) -> Self {
+        self.throttling = Some(throttling);
+        self
+    }
+
+    /// Injects external events into the simulation timeline.
+    pub fn with_external_events(mut self, events: Vec<ExternalEvent>) -> Self {
+        self.capacity_changes = events
+            .iter()
+            .flat_map(ExternalEvent::capacity_changes)
+            .collect();
+        self.capacity_changes.sort_by_key(|(time, _)| *time);
+        // Make room for the largest number of cores that can be available at once.
+        let mut capacity = self.num_threads as i64;
+        let mut max_capacity = capacity;
+        for (_, delta) in &self.capacity_changes {
+            capacity += delta;
+            max_capacity = max_capacity.max(capacity);
+        }
+        self.running_tasks.resize(max_capacity as usize, None);
+        self.last_on_slot.resize(max_capacity as usize, None);
+        self.core_speeds.resize(max_capacity as usize, 1.);
+        self.external_work_end = events
+            .iter()
+            .filter_map(ExternalEvent::busy_until)
+            .max()
+            .unwrap_or_default();
+        self.events = events;
+        self
+    }
+
+    /// Experimental model of warm caches: a codegen unit started on the same core right after the metadata unit of
+    /// its package takes `discount` (a fraction) less time.
+    pub fn with_affinity_discount(mut self, discount: f64) -> Self {
+        self.affinity_discount = Some(discount);
+        self
+    }
+
+    /// Makes the runner prefer starting units of the package that last ran on a freed-up core, falling back to
+    /// the regular pick of the hint provider if there are none.
+    pub fn with_affinity_placement(mut self, affinity_placement: bool) -> Self {
+        self.affinity_placement = affinity_placement;
+        self
+    }
+
+    /// Makes the runner repeat decisions recorded in `trace` instead of consulting its hint provider.
+    pub fn with_replay(mut self, trace: ScenarioTrace) -> Self {
+        self.replay = Some(trace.decisions.into());
+        self
+    }
+
+    /// Makes the runner repeat `decisions` before it starts consulting its hint provider, e.g. to see where a
+    /// different decision at some point of a recorded simulation would have led.
+    pub fn with_replayed_prefix(mut self, decisions: Vec<Decision>) -> Self {
+        self.replay = Some(decisions.into());
+        self.replay_prefix = true;
+        self
+    }
+
+    /// Makes the runner log every decision it consults its hint provider for; see [crate::write_decision_log].
+    pub fn with_decision_log(mut self) -> Self {
+        self.decision_log = Some(vec![]);
+        self
+    }
+
+    /// Makes the runner look for priority inversions, judging the value of each artifact by `values`.
+    pub fn with_inversion_detection(mut self, values: BTreeMap<Artifact, u64>) -> Self {
+        self.inversions = Some(InversionDetector::new(values));
+        self
+    }
+
+    /// Makes the runner follow a schedule computed elsewhere: its units don't start before their start times, and
+    /// they run on their threads if the schedule was made for as many threads as the runner has.
+    pub fn with_external_schedule(mut self, schedule: &ExternalSchedule) -> Self {
+        self.fixed_starts = schedule
+            .units
+            .iter()
+            .map(|(artifact, fixed)| (artifact.clone(), fixed.start))
+            .collect();
+        if schedule.num_threads == self.num_threads {
+            self.fixed_threads = schedule
+                .units
+                .iter()
+                .map(|(artifact, fixed)| (artifact.clone(), fixed.thread))
+                .collect();
+        }
+        self
+    }
+
+    /// A fresh runner driven by `queue`, configured like this one: same timings, threads, constraints, pool limits,
+    /// jobserver, external events, affinity model and coordinator cost. Scheduling overhead is not charged.
+    pub fn reconfigured(&self, queue: DependencyQueue) -> Runner {
+        let mut runner = Runner::new(queue, self.timings.clone(), self.num_threads)
+            .with_constraints(self.constraints.clone())
+            .with_external_events(self.events.clone())
+            .with_affinity_placement(self.affinity_placement);
+        runner.pool_limits = self.pool_limits.clone();
+        runner.tokens = self.tokens.as_ref().map(|tokens| Tokens {
+            jobserver: tokens.jobserver,
+            in_use: 0,
+            inherited: Default::default(),
+        });
+        runner.coordinator_cost = self.coordinator_cost;
+        runner.dispatch_latency = self.dispatch_latency;
+        runner.spawn_overhead = self.spawn_overhead;
+        runner.core_speeds = self.core_speeds.clone();
+        runner.parallel_frontend = self.parallel_frontend;
+        runner.codegen_units = self.codegen_units.clone();
+        runner.io_bandwidth = self.io_bandwidth.clone();
+        runner.throttling = self.throttling.clone();
+        runner.affinity_discount = self.affinity_discount;
+        runner.fixed_starts = self.fixed_starts.clone();
+        runner.fixed_threads = self.fixed_threads.clone();
+        runner
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// Why the hint provider of this scenario gave up on making decisions, if it did.
+    pub fn hint_failure(&self) -> Option<String> {
+        self.queue.hints().failure()
+    }
+
+    /// Priority inversions found during the simulation. Only available after [Runner::calculate] is called
+    /// on a runner with inversion detection enabled.
+    pub fn priority_inversions(&mut self) -> &[PriorityInversion] {
+        match self.inversions.as_mut() {
+            Some(detector) => detector.finish(self.current_time),
+            None => &[],
+        }
+    }
+
+    /// All tasks executed during the simulation, in the order they were started in. Only available after
+    /// [Runner::calculate] is called.
+    pub fn schedule(&self) -> &[ScheduledTask] {
+        &self.history
+    }
+
+    /// Describes the first way in which the simulation differs from `other`, if any. Wall-clock setup time is not
+    /// compared, as it varies from run to run.
+    pub fn difference(&self, other: &Runner) -> Option<String> {
+        if self.label != other.label {
+            return Some(format!(
+                "scenario \"{}\" vs \"{}\"",
+                self.label, other.label
+            ));
+        }
+        if self.current_time != other.current_time {
+            return Some(format!(
+                "\"{}\": makespan of {}ms vs {}ms",
+                self.label, self.current_time, other.current_time
+            ));
+        }
+        if let Some((index, (ours, theirs))) = self
+            .history
+            .iter()
+            .zip(&other.history)
+            .enumerate()
+            .find(|(_, (ours, theirs))| ours != theirs)
+        {
+            return Some(format!(
+                "\"{}\": unit #{index} is {} ({}ms..{}ms) vs {} ({}ms..{}ms)",
+                self.label,
+                ours.artifact,
+                ours.start,
+                ours.end,
+                theirs.artifact,
+                theirs.start,
+                theirs.end
+            ));
+        }
+        (self.history.len() != other.history.len() || self.decisions != other.decisions)
+            .then(|| format!("\"{}\": scheduling decisions differ", self.label))
+    }
+
+    /// Decisions logged during the simulation, if the runner was made to log them.
+    pub fn decision_log(&self) -> &[LoggedDecision] {
+        self.decision_log.as_deref().unwrap_or_default()
+    }
+
+    /// Decisions made during the simulation, which can be used to replay it later. Only available after
+    /// [Runner::calculate] is called.
+    pub fn trace(&self) -> ScenarioTrace {
+        ScenarioTrace {
+            label: self.label.clone(),
+            num_threads: self.num_threads,
+            decisions: self.decisions.clone(),
+            fingerprint: self.initial_fingerprint,
+        }
+    }
+
+    /// Checks that decisions of `trace` can be replayed by this runner, which has to be set up like the one that
+    /// recorded them. Traces that don't say how that one was set up are only checked against the build graph and
+    /// the thread count.
+    pub fn check_replay(&self, trace: &ScenarioTrace) -> Result<(), InputError> {
+        if let Some(fingerprint) = trace.fingerprint {
+            if fingerprint != self.fingerprint() {
+                return Err(InputError::invalid(format!(
+                    "{} was recorded with different inputs or options (e.g. --num-threads, --dirty or --io-capacity)",
+                    trace.label
+                )));
+            }
+            return Ok(());
+        }
+        self.constraints.check(trace.num_threads)?;
+        let mut started = BTreeSet::new();
+        for artifact in trace
+            .decisions
+            .iter()
+            .filter(|decision| !decision.wait)
+            .filter_map(|decision| decision.artifact.as_ref())
+        {
+            let Some(dependencies) = self.queue.pending_dependencies(artifact) else {
+                return Err(InputError::invalid(format!(
+                    "{} starts {artifact}, which is not in the build",
+                    trace.label
+                )));
+            };
+            if let Some(dependency) = dependencies.iter().find(|dep| !started.contains(*dep)) {
+                return Err(InputError::invalid(format!(
+                    "{} starts {artifact} before its dependency {dependency}",
+                    trace.label
+                )));
+            }
+            if !started.insert(artifact) {
+                return Err(InputError::invalid(format!(
+                    "{} starts {artifact} twice",
+                    trace.label
+                )));
+            }
+        }
+        match self
+            .queue
+            .remaining()
+            .find(|artifact| !started.contains(artifact))
+        {
+            Some(artifact) => Err(InputError::invalid(format!(
+                "{} never starts {artifact}",
+                trace.label
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Why the replayed decisions didn't fit the simulation, if they didn't. The hint provider is consulted for the
+    /// rest of the decisions then.
+    pub fn replay_failure(&self) -> Option<&str> {
+        self.replay_failure.as_deref()
+    }
+
+    /// Hash of everything that affects the simulation, save for the hint provider, which is only represented by
+    /// the label. Only meaningful before [Runner::calculate] is called, as it covers the units left to build.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.label.hash(&mut hasher);
+        self.num_threads.hash(&mut hasher);
+        self.budgeted.hash(&mut hasher);
+        self.coordinator_cost.hash(&mut hasher);
+        self.dispatch_latency.hash(&mut hasher);
+        self.spawn_overhead.hash(&mut hasher);
+        format!("{:?}", self.core_speeds).hash(&mut hasher);
+        format!("{:?}", self.parallel_frontend).hash(&mut hasher);
+        self.codegen_units.hash(&mut hasher);
+        format!("{:?}", self.io_bandwidth).hash(&mut hasher);
+        format!("{:?}", self.throttling).hash(&mut hasher);
+        self.queue
+            .remaining()
+            .for_each(|artifact| artifact.hash(&mut hasher));
+        timings_fingerprint(&self.timings).hash(&mut hasher);
+        format!("{:?}", self.constraints).hash(&mut hasher);
+        format!("{:?}", self.events).hash(&mut hasher);
+        self.pool_limits.hash(&mut hasher);
+        self.tokens
+            .as_ref()
+            .map(|tokens| tokens.jobserver)
+            .hash(&mut hasher);
+        self.affinity_discount.map(f64::to_bits).hash(&mut hasher);
+        self.affinity_placement.hash(&mut hasher);
+        self.fixed_starts.hash(&mut hasher);
+        self.fixed_threads.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// The earliest point in the future at which a unit held back by constraints can start or the number of
+    /// available cores changes.
+    fn next_wakeup_time(&self) -> Option<u64> {
+        let next_release_time = self
+            .queue
+            .remaining()
+            .filter_map(|artifact| {
+                self.constraints
+                    .release_time(artifact)
+                    .max(self.fixed_starts.get(artifact).copied())
+            })
+            .filter(|time| *time > self.current_time)
+            .min();
+        let next_dispatch_time = self
+            .queue
+            .ready()
+            .filter_map(|artifact| dispatch_time(&self.ready_at, self.dispatch_latency, artifact))
+            .filter(|time| *time > self.current_time)
+            .min();
+        let next_capacity_change = self
+            .capacity_changes
+            .iter()
+            .map(|(time, _)| *time)
+            .find(|time| *time > self.current_time);
+        next_release_time
+            .into_iter()
+            .chain(next_dispatch_time)
+            .chain(next_capacity_change)
+            .min()
+    }
+
+    /// Number of cores currently available.
+    fn capacity(&self) -> usize {
+        self.capacity.max(0) as usize
+    }
+    fn apply_capacity_changes(&mut self) {
+        let applied = self
+            .capacity_changes
+            .iter()
+            .take_while(|(time, _)| *time <= self.current_time)
+            .count();
+        for (time, delta) in self.capacity_changes.drain(..applied) {
+            trace!("Capacity change of {delta} cores at {time}");
+            self.capacity += delta;
+        }
+    }
     fn run_next_task_to_completion(&mut self) {
         let mut counter = 0;
         let Some(last_active_task) = self.running_tasks.iter().position(|item| {
             counter += item.is_some() as usize;
             counter == self.running_tasks_count
         }) else {
-            // No task is running.
+            // No task is running; fast-forward to the point where something can change.
+            if let Some(wakeup_time) = self.next_wakeup_time() {
+                self.current_time = wakeup_time;
+            }
             return;
         };
         let Some(task_to_remove) = self.running_tasks[..=last_active_task]
             .iter()
             .cloned()
             .filter_map(|key| key)
+            .filter(|task| !task.helper)
             .min_by_key(|task| task.end_time)
         else {
             return;
         };
+        if let Some(wakeup_time) = self.next_wakeup_time() {
+            if wakeup_time < task_to_remove.end_time {
+                // Something changes before any running task finishes.
+                self.current_time = wakeup_time;
+                return;
+            }
+        }
 
         self.running_tasks[..=last_active_task]
             .iter_mut()
@@ -89,59 +1221,734 @@ impl Runner {
                     if task.end_time == task_to_remove.end_time {
                         self.running_tasks_count -= 1;
                         let finished = maybe_task.take().unwrap();
+                        if finished.helper {
+                            if let Some(tokens) = self.tokens.as_mut() {
+                                tokens.in_use -= 1;
+                            }
+                            return;
+                        }
                         trace!("Finished {:?}", &finished);
+                        if let Some(tokens) = self.tokens.as_mut() {
+                            tokens.release(&finished.artifact, &self.timings);
+                        }
                         let unlocked_units = self.queue.finish(&finished.artifact);
+                        for unit in &unlocked_units {
+                            self.ready_at.insert((*unit).clone(), finished.end_time);
+                        }
                         if !unlocked_units.is_empty() {
                             trace!("Unlocked units: {:?}", unlocked_units);
                         }
                     }
                 }
             });
-        self.current_time = task_to_remove.end_time;
+        // With budgeted scheduling the clock might've been pushed past the end of this task already.
+        self.current_time = self.current_time.max(task_to_remove.end_time);
+    }
+    /// Takes the next of the replayed decisions, if there are any left. Decisions that don't fit the simulation are
+    /// abandoned, and the hint provider consulted instead.
+    fn next_replayed(&mut self) -> Option<(Option<Suggestion<Artifact>>, u64, usize)> {
+        let replay = self
+            .replay
+            .as_mut()
+            .filter(|replay| !self.replay_prefix || !replay.is_empty())?;
+        let failure = match replay.pop_front() {
+            Some(decision) => {
+                let suggestion = match &decision.artifact {
+                    _ if decision.wait => Ok(Some(Suggestion::Wait)),
+                    Some(artifact) => self
+                        .queue
+                        .dequeue_exact(artifact)
+                        .map(|artifact| Some(Suggestion::Start(artifact)))
+                        .ok_or_else(|| format!("started {artifact} before it was ready")),
+                    None => Ok(None),
+                };
+                match suggestion {
+                    Ok(suggestion) => {
+                        return Some((suggestion, decision.overhead, decision.threads.unwrap_or(1)))
+                    }
+                    Err(failure) => failure,
+                }
+            }
+            None => "ended before the simulation did".to_owned(),
+        };
+        self.replay_failure = Some(failure);
+        self.replay = None;
+        None
     }
     fn busy_slots(&self) -> usize {
         self.running_tasks_count
     }
-    fn schedule_new_tasks(&mut self) {
-        while let Some(slot_for_task) = self.running_tasks.iter_mut().find(|slot| slot.is_none()) {
-            if let Some(new_task) = self.queue.dequeue() {
-                trace!("Scheduling {:?}", &new_task);
-                if new_task.typ != ArtifactType::Codegen {
-                    self.order.push((self.current_time, new_task.clone()));
+    fn schedule_new_tasks(&mut self) -> Result<(), InputError> {
+        self.apply_capacity_changes();
+        // Units that take no time might unlock units that can start right away, on the slots they didn't take up.
+        while self.start_tasks_on_free_slots()? {}
+        Ok(())
+    }
+    /// Fills free slots with new tasks. Returns whether any of them took no time, and so finished already.
+    fn start_tasks_on_free_slots(&mut self) -> Result<bool, InputError> {
+        let mut finished_instantly = false;
+        for slot in 0..self.running_tasks.len() {
+            if self.running_tasks_count >= self.capacity() {
+                break;
+            }
+            if self.running_tasks[slot].is_some() {
+                continue;
+            }
+            let mut pool_usage = BTreeMap::<ThreadPool, usize>::new();
+            let mut exclusive_running = false;
+            let mut memory_in_use = 0.;
+            let mut io_in_use = 0.;
+            for task in self
+                .running_tasks
+                .iter()
+                .flatten()
+                .filter(|task| !task.helper)
+            {
+                *pool_usage.entry(task.artifact.typ.pool()).or_default() += 1;
+                exclusive_running |= self.constraints.is_exclusive(&task.artifact);
+                memory_in_use += self.constraints.memory(&task.artifact);
+                io_in_use += self
+                    .io_bandwidth
+                    .as_ref()
+                    .map_or(0., |bandwidth| bandwidth.consumption(&task.artifact));
+            }
+            let (suggestion, overhead_ms, threads) = match self.next_replayed() {
+                Some(replayed) => replayed,
+                None => self.pick_next(
+                    slot,
+                    &pool_usage,
+                    exclusive_running,
+                    memory_in_use,
+                    io_in_use,
+                ),
+            };
+            self.current_time += overhead_ms;
+            let new_task = match &suggestion {
+                Some(Suggestion::Start(new_task)) => Some(new_task.clone()),
+                Some(Suggestion::Wait) | None => None,
+            };
+            self.decisions.push(Decision {
+                artifact: new_task.clone(),
+                overhead: overhead_ms,
+                wait: suggestion.as_ref() == Some(&Suggestion::Wait),
+                threads: (threads > 1).then_some(threads),
+            });
+            let Some(new_task) = new_task else {
+                if self.constraints.has_pins()
+                    || !self.fixed_threads.is_empty()
+                    || suggestion.is_some()
+                {
+                    // Units pinned to other threads might still be able to run, and the hint provider might
+                    // want to fill other threads even though it's holding this one.
+                    continue;
                 }
+                break;
+            };
+            trace!("Scheduling {:?}", &new_task);
+            if new_task.typ != ArtifactType::Codegen {
+                self.order.push((self.current_time, new_task.clone()));
+            }
 
-                *slot_for_task = Some(Task {
-                    end_time: self.current_time + (self.timings[&new_task].duration * 1000.) as u64,
-                    artifact: new_task,
+            let acquisition_latency = self
+                .tokens
+                .as_mut()
+                .map_or(0, |tokens| tokens.acquire(&new_task));
+            let start_time = self.current_time + acquisition_latency;
+            let placement = Placement {
+                unit: &new_task,
+                slot,
+                threads,
+            };
+            let duration = Self::DURATION_MODELS
+                .iter()
+                .fold(self.timings[&new_task].duration, |duration, model| {
+                    model(self, &placement, duration)
+                });
+            let spawn_overhead = self.spawn_overhead_of(&new_task, duration);
+            let end_time = start_time
+                .checked_add(spawn_overhead)
+                .and_then(|time| time.checked_add((duration * 1000.) as u64))
+                .ok_or_else(|| {
+                    InputError::invalid(format!(
+                        "{}: {new_task} would finish past the longest build that can be simulated",
+                        self.label
+                    ))
+                })?;
+            self.last_on_slot[slot] = Some(new_task.clone());
+            self.history.push(ScheduledTask {
+                artifact: new_task.clone(),
+                start: start_time,
+                end: end_time,
+            });
+            if end_time == self.current_time {
+                // Don't hold up the slot (and the event loop) for a task that is done as soon as it starts.
+                trace!("Finished {:?} instantly", &new_task);
+                if let Some(tokens) = self.tokens.as_mut() {
+                    tokens.release(&new_task, &self.timings);
+                }
+                for unit in self.queue.finish(&new_task) {
+                    self.ready_at.insert(unit.clone(), end_time);
+                }
+                finished_instantly = true;
+                continue;
+            }
+            let helper_slots: Vec<usize> = (0..self.running_tasks.len())
+                .filter(|other| *other != slot && self.running_tasks[*other].is_none())
+                .take(threads - 1)
+                .collect();
+            if let Some(tokens) = self.tokens.as_mut() {
+                tokens.in_use += helper_slots.len();
+            }
+            for helper_slot in helper_slots {
+                self.running_tasks[helper_slot] = Some(Task {
+                    end_time,
+                    artifact: new_task.clone(),
+                    helper: true,
                 });
                 self.running_tasks_count += 1;
-            } else {
-                break;
             }
+            self.running_tasks[slot] = Some(Task {
+                end_time,
+                artifact: new_task,
+                helper: false,
+            });
+            self.running_tasks_count += 1;
+        }
+        Ok(finished_instantly)
+    }
+    /// Asks the hint provider for a unit to start on `slot`. Returns it along with the decision time to charge, in
+    /// milliseconds.
+    /// Models adjusting the recorded duration of a unit (in seconds) to how it's run in the simulation, applied in
+    /// this order.
+    const DURATION_MODELS: [fn(&Self, &Placement, f64) -> f64; 4] = [
+        Self::affinity_model,
+        Self::core_speed_model,
+        Self::throttling_model,
+        Self::parallelism_model,
+    ];
+
+    /// Codegen right after the metadata of the same crate on a thread finds its caches warm.
+    fn affinity_model(&self, placement: &Placement, duration: f64) -> f64 {
+        let Some(discount) = self.affinity_discount else {
+            return duration;
+        };
+        let warm = self.last_on_slot[placement.slot]
+            .as_ref()
+            .is_some_and(|last| {
+                last.typ == ArtifactType::Metadata
+                    && placement.unit.typ == ArtifactType::Codegen
+                    && last.package_id == placement.unit.package_id
+            });
+        if warm {
+            duration * (1. - discount)
+        } else {
+            duration
+        }
+    }
+
+    fn core_speed_model(&self, placement: &Placement, duration: f64) -> f64 {
+        duration / self.core_speeds[placement.slot]
+    }
+
+    fn throttling_model(&self, _: &Placement, duration: f64) -> f64 {
+        match &self.throttling {
+            Some(throttling) => duration / throttling.speed(self.sustained_load),
+            None => duration,
+        }
+    }
+
+    /// Speedup of the frontend or codegen of a unit from the extra threads it was given.
+    fn parallelism_model(&self, placement: &Placement, duration: f64) -> f64 {
+        let unit = placement.unit;
+        match (&self.parallel_frontend, &self.codegen_units) {
+            (Some(parallel_frontend), _) if unit.typ == ArtifactType::Metadata => {
+                duration / parallel_frontend.speedup(placement.threads)
+            }
+            (_, Some(codegen_units)) if unit.typ == ArtifactType::Codegen => {
+                // Codegen takes as long as the most codegen units any of its threads goes through.
+                let count = codegen_units.get(unit).copied().unwrap_or(1);
+                duration * (count.div_ceil(placement.threads) as f64 / count as f64)
+            }
+            _ => duration,
         }
     }
-    fn step(&mut self) {
+
+    /// Milliseconds it takes to spawn the process of `unit`, which runs for `duration` seconds.
+    fn spawn_overhead_of(&self, unit: &Artifact, duration: f64) -> u64 {
+        // Fresh units are not rebuilt, so there's no process to spawn for them.
+        if unit.typ == ArtifactType::Codegen || duration <= 0. {
+            0
+        } else {
+            self.spawn_overhead
+        }
+    }
+
+    fn pick_next(
+        &mut self,
+        slot: usize,
+        pool_usage: &BTreeMap<ThreadPool, usize>,
+        exclusive_running: bool,
+        memory_in_use: f64,
+        io_in_use: f64,
+    ) -> (Option<Suggestion<Artifact>>, u64, usize) {
+        let constraints = &self.constraints;
+        let pool_limits = &self.pool_limits;
+        let (fixed_starts, fixed_threads) = (&self.fixed_starts, &self.fixed_threads);
+        let now = self.current_time;
+        let (tokens, io_bandwidth) = (&self.tokens, &self.io_bandwidth);
+        let (ready_at, dispatch_latency) = (&self.ready_at, self.dispatch_latency);
+        let allowed = |artifact: &Artifact| {
+            let pool = artifact.typ.pool();
+            dispatch_time(ready_at, dispatch_latency, artifact).is_none_or(|time| time <= now)
+                && tokens
+                    .as_ref()
+                    .is_none_or(|tokens| tokens.available_for(artifact))
+                && io_bandwidth
+                    .as_ref()
+                    .is_none_or(|bandwidth| bandwidth.allows(artifact, io_in_use))
+                && constraints.allows(artifact, slot, now, exclusive_running, memory_in_use)
+                && fixed_starts.get(artifact).is_none_or(|start| *start <= now)
+                && fixed_threads
+                    .get(artifact)
+                    .is_none_or(|thread| *thread == slot)
+                && pool_limits
+                    .get(&pool)
+                    .is_none_or(|limit| pool_usage.get(&pool).copied().unwrap_or_default() < *limit)
+        };
+        // Collected before the decision is timed, so that logging does not count as scheduling overhead.
+        let candidates: Option<Vec<LoggedCandidate>> = self.decision_log.is_some().then(|| {
+            self.queue
+                .ready()
+                .filter(|artifact| {
+                    allowed(artifact)
+                        && self
+                            .queue
+                            .hints()
+                            .accepts_core(artifact, self.core_speeds[slot])
+                })
+                .map(|artifact| LoggedCandidate {
+                    artifact: artifact.clone(),
+                    score: self.queue.hints().score(artifact),
+                })
+                .collect()
+        });
+        let decision_start = std::time::Instant::now();
+        let context = SchedulerContext {
+            now,
+            running: self
+                .running_tasks
+                .iter()
+                .flatten()
+                .filter(|task| !task.helper)
+                .map(|task| (&task.artifact, task.end_time.saturating_sub(now)))
+                .collect(),
+            idle_slots: self.capacity().saturating_sub(self.running_tasks_count),
+            core_speed: self.core_speeds[slot],
+            busy_cores: self
+                .running_tasks
+                .iter()
+                .zip(&self.core_speeds)
+                .filter_map(|(task, speed)| {
+                    Some((*speed, task.as_ref()?.end_time.saturating_sub(now)))
+                })
+                .collect(),
+            ready: self
+                .queue
+                .ready()
+                .filter(|artifact| allowed(artifact))
+                .count(),
+        };
+        let warm_package = self.last_on_slot[slot]
+            .as_ref()
+            .filter(|_| self.affinity_placement)
+            .map(|last| last.package_id.clone());
+        let warm_task = warm_package.and_then(|package_id| {
+            self.queue.dequeue_filtered(&context, |artifact| {
+                artifact.package_id == package_id && allowed(artifact)
+            })
+        });
+        let warm = warm_task.is_some();
+        let new_task = warm_task.or_else(|| self.queue.dequeue_filtered(&context, allowed));
+        let threads = match &new_task {
+            Some(Suggestion::Start(artifact)) => {
+                let spare_tokens = self
+                    .tokens
+                    .as_ref()
+                    .map_or(usize::MAX, |tokens| tokens.spare_for(artifact));
+                let max_threads = context.idle_slots.min(spare_tokens.saturating_add(1));
+                match (&self.parallel_frontend, &self.codegen_units) {
+                    (Some(parallel_frontend), _) if artifact.typ == ArtifactType::Metadata => self
+                        .queue
+                        .hints()
+                        .threads_for(artifact, &context)
+                        .clamp(1, parallel_frontend.max_threads.min(max_threads).max(1)),
+                    (_, Some(codegen_units)) if artifact.typ == ArtifactType::Codegen => {
+                        let count = codegen_units.get(artifact).copied().unwrap_or(1);
+                        count.min(max_threads).max(1)
+                    }
+                    _ => 1,
+                }
+            }
+            Some(Suggestion::Wait) | None => 1,
+        };
+        if self.budgeted {
+            self.pending_overhead += decision_start.elapsed();
+        }
+        self.pending_overhead += self.coordinator_cost * self.running_tasks_count as u32;
+        let overhead_ms = self.pending_overhead.as_millis() as u64;
+        self.pending_overhead -= std::time::Duration::from_millis(overhead_ms);
+        if let (Some(log), Some(candidates)) = (self.decision_log.as_mut(), candidates) {
+            if !candidates.is_empty() {
+                let choice = match &new_task {
+                    Some(Suggestion::Start(artifact)) => Some(artifact.clone()),
+                    Some(Suggestion::Wait) | None => None,
+                };
+                let reason = match &choice {
+                    Some(artifact) if artifact.typ == ArtifactType::Codegen => {
+                        DecisionReason::Codegen
+                    }
+                    _ if warm => DecisionReason::Affinity,
+                    _ => DecisionReason::HintProvider,
+                };
+                log.push(LoggedDecision {
+                    index: self.decisions.len(),
+                    time: now,
+                    thread: slot,
+                    candidates,
+                    choice,
+                    wait: new_task == Some(Suggestion::Wait),
+                    reason,
+                });
+            }
+        }
+        (new_task, overhead_ms, threads)
+    }
+    fn step(&mut self) -> Result<(), InputError> {
         self.run_next_task_to_completion();
-        self.schedule_new_tasks();
+        self.schedule_new_tasks()?;
+        self.detect_inversions();
+        self.observe_ready_queue();
+        self.observe_utilization();
+        self.observe_pool_limits();
+        if self.busy_slots() == 0 && !self.queue.is_empty() {
+            // Nothing could be started; fast-forward to the point where something can change.
+            match self.next_wakeup_time() {
+                Some(time) => self.current_time = time,
+                // Nothing ever will, if the replayed decisions don't fit the simulation.
+                None if self.replay.is_some() => {
+                    self.replay_failure = Some("left the build stuck".into());
+                    self.replay = None;
+                }
+                None if self.capacity() == 0 => {
+                    return Err(InputError::invalid(format!(
+                        "{}: no cores are available after {}, so the build can never finish",
+                        self.label,
+                        Duration::from_millis(self.current_time)
+                    )));
+                }
+                None => {
+                    return Err(InputError::invalid(format!(
+                        "{}: remaining units can never be scheduled; are the constraints satisfiable?",
+                        self.label
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+    fn observe_utilization(&mut self) {
+        let (last_time, last_running, last_capacity) = self.running;
+        if last_running >= self.num_threads {
+            self.time_at_full += self.current_time - last_time;
+        }
+        self.busy_area += last_running as u64 * (self.current_time - last_time);
+        self.capacity_area +=
+            last_capacity.max(last_running) as u64 * (self.current_time - last_time);
+        if let Some(throttling) = &self.throttling {
+            // Exponential moving average, so that load long ago matters less and less.
+            let elapsed = (self.current_time - last_time) as f64;
+            let decay = (-elapsed / throttling.window.max(1) as f64).exp();
+            self.sustained_load = self.sustained_load * decay + last_running as f64 * (1. - decay);
+        }
+        self.peak_running = self.peak_running.max(self.running_tasks_count);
+        self.running = (self.current_time, self.running_tasks_count, self.capacity());
+    }
+    fn observe_pool_limits(&mut self) {
+        let (last_time, last_bound) = self.pool_bound;
+        if last_bound {
+            self.pool_limited += self.current_time - last_time;
+        }
+        let bound = !self.pool_limits.is_empty()
+            && self.running_tasks_count < self.capacity()
+            && self.queue.ready().any(|artifact| {
+                let pool = artifact.typ.pool();
+                self.pool_limits.get(&pool).is_some_and(|limit| {
+                    self.running_tasks
+                        .iter()
+                        .flatten()
+                        .filter(|task| !task.helper && task.artifact.typ.pool() == pool)
+                        .count()
+                        >= *limit
+                })
+            });
+        self.pool_bound = (self.current_time, bound);
+    }
+    fn observe_ready_queue(&mut self) {
+        let (last_time, last_depth) = self.ready_depth;
+        self.ready_area += last_depth as u64 * (self.current_time - last_time);
+        let depth = self.queue.ready().count();
+        self.peak_ready = self.peak_ready.max(depth);
+        self.ready_depth = (self.current_time, depth);
+    }
+    fn detect_inversions(&mut self) {
+        let saturated = self.running_tasks_count >= self.capacity();
+        let Some(detector) = self.inversions.as_mut() else {
+            return;
+        };
+        let running: Vec<&Artifact> = self
+            .running_tasks
+            .iter()
+            .flatten()
+            .filter(|task| !task.helper)
+            .map(|task| &task.artifact)
+            .collect();
+        detector.observe(self.current_time, saturated, self.queue.ready(), &running);
+    }
+    /// How many of the allotted threads were used. Only available after [Runner::calculate] is called.
+    pub fn utilization(&self) -> Utilization {
+        Utilization {
+            label: self.label.clone(),
+            num_threads: self.num_threads,
+            peak_threads: self.peak_running,
+            time_at_full: Duration::from_millis(self.time_at_full),
+            right_size: None,
+        }
+    }
+
+    /// Units running during the last `fraction` (e.g. 0.1) of the build, and how many threads sat idle meanwhile.
+    pub fn tail(&self, fraction: f64) -> Tail {
+        let end = self.current_time;
+        let start = end - (end as f64 * fraction.clamp(0., 1.)) as u64;
+        let mut running: Vec<(u64, &Artifact)> = self
+            .history
+            .iter()
+            .filter(|task| task.end > start && task.start < end)
+            .map(|task| (task.end.min(end) - task.start.max(start), &task.artifact))
+            .collect();
+        running.sort_by_key(|(overlap, artifact)| (std::cmp::Reverse(*overlap), *artifact));
+        let busy: u64 = running.iter().map(|(overlap, _)| overlap).sum();
+        let idle_threads = if end > start {
+            self.num_threads as f64 - busy as f64 / (end - start) as f64
+        } else {
+            0.
+        };
+        const SHOWN: usize = 5;
+        let mut units: Vec<String> = running
+            .iter()
+            .take(SHOWN)
+            .map(|(overlap, artifact)| format!("{artifact} {:.2}s", *overlap as f64 / 1000.))
+            .collect();
+        if running.len() > SHOWN {
+            units.push(format!("and {} more", running.len() - SHOWN));
+        }
+        Tail {
+            label: self.label.clone(),
+            start: Duration::from_millis(start),
+            idle_threads,
+            units: units.join(", "),
+        }
+    }
+
+    /// Stretches of the build during which no more than `threshold` units were running, attributed to the units that
+    /// were. Only available after [Runner::calculate] is called.
+    pub fn serial_stretches(&self, threshold: usize) -> SerialStretches {
+        let mut times: Vec<u64> = self
+            .history
+            .iter()
+            .flat_map(|task| [task.start, task.end])
+            .chain([0, self.current_time])
+            .collect();
+        times.sort_unstable();
+        times.dedup();
+        let mut blame: BTreeMap<&Artifact, u64> = BTreeMap::new();
+        let (mut stretches, mut serialized, mut lost) = (0, 0, 0);
+        let mut in_stretch = false;
+        for window in times.windows(2) {
+            let (start, end) = (window[0], window[1]);
+            let running: Vec<&Artifact> = self
+                .history
+                .iter()
+                .filter(|task| task.start <= start && task.end >= end && task.end > task.start)
+                .map(|task| &task.artifact)
+                .collect();
+            if running.len() > threshold {
+                in_stretch = false;
+                continue;
+            }
+            if !in_stretch {
+                stretches += 1;
+                in_stretch = true;
+            }
+            serialized += end - start;
+            lost += (self.num_threads.saturating_sub(running.len())) as u64 * (end - start);
+            for artifact in running {
+                *blame.entry(artifact).or_default() += end - start;
+            }
+        }
+        let mut blame: Vec<(&Artifact, u64)> = blame.into_iter().collect();
+        blame.sort_by_key(|(artifact, time)| (std::cmp::Reverse(*time), *artifact));
+        const SHOWN: usize = 5;
+        let mut units: Vec<String> = blame
+            .iter()
+            .take(SHOWN)
+            .map(|(artifact, time)| format!("{artifact} {:.2}s", *time as f64 / 1000.))
+            .collect();
+        if blame.len() > SHOWN {
+            units.push(format!("and {} more", blame.len() - SHOWN));
+        }
+        SerialStretches {
+            label: self.label.clone(),
+            stretches,
+            serialized: Duration::from_millis(serialized),
+            lost: Duration::from_millis(lost),
+            units: units.join(", "),
+        }
     }
-    pub fn calculate(&mut self) -> (Makespan, Timings) {
+
+    /// The unit that waited the longest for a core after becoming ready. Only available after [Runner::calculate]
+    /// is called.
+    pub fn max_wait(&self) -> WaitTime {
+        let (wait, unit) = self
+            .history
+            .iter()
+            .map(|task| {
+                let ready_at = self
+                    .ready_at
+                    .get(&task.artifact)
+                    .copied()
+                    .unwrap_or_default();
+                (
+                    task.start.saturating_sub(ready_at),
+                    task.artifact.to_string(),
+                )
+            })
+            .max()
+            .unwrap_or_default();
+        WaitTime {
+            label: self.label.clone(),
+            makespan: Duration::from_millis(self.current_time),
+            max_wait: Duration::from_millis(wait),
+            unit,
+        }
+    }
+    /// Simulates the build to its end. Fails if the remaining units can never be started, e.g. because the
+    /// constraints can't be satisfied or there are no cores left.
+    pub fn calculate(&mut self) -> Result<(Makespan, Timings), InputError> {
+        if self.initial_fingerprint.is_none() {
+            self.initial_fingerprint = Some(self.fingerprint());
+        }
         while !self.queue.is_empty() || self.busy_slots() > 0 {
-            self.step();
+            self.step()?;
         }
         assert_eq!(self.busy_slots(), 0);
+        // The job is not done until external work is done too.
+        self.current_time = self.current_time.max(self.external_work_end);
+        self.observe_ready_queue();
+        self.observe_utilization();
+        self.observe_pool_limits();
+        let avg_ready = if self.current_time == 0 {
+            0.
+        } else {
+            // Round to two decimal places for display.
+            (self.ready_area as f64 / self.current_time as f64 * 100.).round() / 100.
+        };
+        let (critical_path, total_work) = self.lower_bounds;
+        let work_bound = total_work.div_ceil(self.num_threads as u64);
+        let bound = critical_path.max(work_bound);
+        let bound_ratio = if bound == 0 {
+            1.
+        } else {
+            (self.current_time as f64 / bound as f64 * 100.).round() / 100.
+        };
         let timings = Timings::new(
             &self.order,
             &self.timings,
-            self.running_tasks.len(),
+            self.num_threads,
             self.current_time,
-        );
-        (
+        )
+        .with_events(&self.events)
+        .with_packages(group_by_package(&self.history));
+        Ok((
             Makespan {
                 label: self.label.clone(),
-                num_threads: self.running_tasks.len(),
-                makespan: Duration(std::time::Duration::from_millis(self.current_time)),
+                num_threads: self.num_threads,
+                makespan: Duration::from_millis(self.current_time),
+                workspace_makespan: Duration::from_millis(
+                    self.history
+                        .iter()
+                        .filter(|task| task.artifact.is_workspace_member())
+                        .map(|task| task.end)
+                        .max()
+                        .unwrap_or_default(),
+                ),
+                peak_ready: self.peak_ready,
+                avg_ready,
+                setup_time: Duration(self.setup_time),
+                critical_path: Duration::from_millis(critical_path),
+                work_bound: Duration::from_millis(work_bound),
+                bound_ratio,
+                efficiency: efficiency(bound, self.current_time),
+                pool_limited: (!self.pool_limits.is_empty())
+                    .then(|| Duration::from_millis(self.pool_limited)),
+                avg_concurrency: if self.current_time == 0 {
+                    0.
+                } else {
+                    self.busy_area as f64 / self.current_time as f64
+                },
+                cpu_utilization: if self.capacity_area == 0 {
+                    0.
+                } else {
+                    self.busy_area as f64 / self.capacity_area as f64 * 100.
+                },
+                idle_core_time: Duration::from_millis(self.capacity_area - self.busy_area),
+                vs_baseline: None,
+                vs_baseline_pct: None,
             },
             timings,
-        )
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duration_subtraction_saturates() {
+        let short = Duration::from_millis(5);
+        let long = Duration::from_millis(10);
+        assert_eq!(short - long, Duration::default());
+        assert_eq!(long - short, short);
+        assert_eq!(short.difference(long), -0.005);
+    }
+
+    #[test]
+    fn duration_percentage_of_zero() {
+        let zero = Duration::default();
+        assert_eq!(zero.percentage_of(zero), 100.);
+        assert_eq!(Duration::from_millis(1).percentage_of(zero), f64::INFINITY);
+        assert_eq!(
+            Duration::from_millis(5).percentage_of(Duration::from_millis(20)),
+            25.
+        );
+    }
+
+    #[test]
+    fn duration_serializes_as_seconds() {
+        let duration = Duration::from_millis(1500);
+        let json = serde_json::to_string(&duration).unwrap();
+        assert_eq!(json, "1.5");
+        assert_eq!(serde_json::from_str::<Duration>(&json).unwrap(), duration);
+        assert!(serde_json::from_str::<Duration>("-1.5").is_err());
     }
 }
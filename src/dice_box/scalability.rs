@@ -0,0 +1,138 @@
+//! Characterization of how a build scales with the number of threads, by fitting scalability laws to the makespans
+//! of a thread sweep.
+//!
+//! Amdahl's law models the speedup on N threads as `N / (1 + σ(N - 1))`, where σ is the fraction of the build
+//! that can't be parallelized. The Universal Scalability Law adds a coherency term, `N / (1 + α(N - 1) +
+//! βN(N - 1))`, which makes the speedup peak and then fall off. In a build, β > 0 points at costs that grow with the
+//! number of threads (such as the bookkeeping of Cargo's main loop), or at a sweep that goes past the point where the
+//! critical path caps the speedup. Both laws are fitted by least squares on the linearized form `N / S(N) - 1`.
+use serde::Serialize;
+use tabled::Tabled;
+
+use crate::runner::Duration;
+
+/// Makespan of a scenario on a given number of threads.
+#[derive(Clone, Debug, PartialEq, Serialize, Tabled)]
+#[serde(rename_all = "kebab-case")]
+pub struct SweepPoint {
+    pub label: String,
+    pub num_threads: usize,
+    pub makespan: Duration,
+    /// Makespan on one thread divided by the makespan on `num_threads` threads.
+    #[tabled(display_with = "display_coefficient")]
+    pub speedup: f64,
+}
+
+/// Scalability laws fitted to the speedups of a scenario.
+#[derive(Clone, Debug, PartialEq, Serialize, Tabled)]
+#[serde(rename_all = "kebab-case")]
+pub struct ScalabilityFit {
+    pub label: String,
+    /// Serial fraction σ of Amdahl's law.
+    #[tabled(display_with = "display_coefficient")]
+    pub serial_fraction: f64,
+    /// Contention coefficient α of the Universal Scalability Law.
+    #[tabled(display_with = "display_coefficient")]
+    pub contention: f64,
+    /// Coherency coefficient β of the Universal Scalability Law.
+    #[tabled(display_with = "display_coefficient")]
+    pub coherency: f64,
+    /// Number of threads past which the Universal Scalability Law predicts no further speedup, if it does.
+    #[tabled(display_with = "display_peak_threads")]
+    pub peak_threads: Option<f64>,
+}
+
+fn display_coefficient(coefficient: &f64) -> String {
+    format!("{coefficient:.4}")
+}
+
+fn display_peak_threads(peak_threads: &Option<f64>) -> String {
+    peak_threads.map_or_else(|| "-".into(), |threads| format!("{threads:.0}"))
+}
+
+/// Speedups of a scenario labeled `label` given its makespans on various numbers of threads, which have to include
+/// a single thread.
+pub fn sweep_points(label: &str, makespans: &[(usize, Duration)]) -> Vec<SweepPoint> {
+    let serial = makespans
+        .iter()
+        .find(|(num_threads, _)| *num_threads == 1)
+        .map_or(0., |(_, makespan)| makespan.as_secs_f64());
+    makespans
+        .iter()
+        .map(|(num_threads, makespan)| SweepPoint {
+            label: label.to_owned(),
+            num_threads: *num_threads,
+            makespan: *makespan,
+            speedup: serial / makespan.as_secs_f64().max(f64::EPSILON),
+        })
+        .collect()
+}
+
+/// Fits Amdahl's law and the Universal Scalability Law to the speedups of a single scenario.
+pub fn fit_scalability(points: &[SweepPoint]) -> ScalabilityFit {
+    // Points on a single thread carry no information about either law.
+    let samples: Vec<(f64, f64)> = points
+        .iter()
+        .filter(|point| point.num_threads > 1 && point.speedup > 0.)
+        .map(|point| {
+            let threads = point.num_threads as f64;
+            (threads, threads / point.speedup - 1.)
+        })
+        .collect();
+    let (mut xx, mut xy, mut xz, mut zz, mut zy) = (0., 0., 0., 0., 0.);
+    for (threads, y) in &samples {
+        let x = threads - 1.;
+        let z = threads * (threads - 1.);
+        xx += x * x;
+        xy += x * y;
+        xz += x * z;
+        zz += z * z;
+        zy += z * y;
+    }
+    let serial_fraction = if xx > 0. { xy / xx } else { 0. };
+    let determinant = xx * zz - xz * xz;
+    // With fewer than two distinct thread counts there's nothing to tell contention from coherency, in which case
+    // all of the slowdown is put down to contention.
+    let (contention, coherency) = if determinant.abs() > f64::EPSILON {
+        (
+            (xy * zz - zy * xz) / determinant,
+            (zy * xx - xy * xz) / determinant,
+        )
+    } else {
+        (serial_fraction, 0.)
+    };
+    let peak_threads =
+        (coherency > 0. && contention < 1.).then(|| ((1. - contention) / coherency).sqrt());
+    ScalabilityFit {
+        label: points
+            .first()
+            .map(|point| point.label.clone())
+            .unwrap_or_default(),
+        serial_fraction,
+        contention,
+        coherency,
+        peak_threads,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recovers_coefficients_of_synthetic_builds() {
+        let usl =
+            |threads: f64| threads / (1. + 0.1 * (threads - 1.) + 0.01 * threads * (threads - 1.));
+        let makespans: Vec<(usize, Duration)> = [1, 2, 4, 8, 16]
+            .into_iter()
+            .map(|threads| (threads, Duration::from_secs_f64(100. / usl(threads as f64))))
+            .collect();
+        let fit = fit_scalability(&sweep_points("usl", &makespans));
+        assert!((fit.contention - 0.1).abs() < 1e-3, "{fit:?}");
+        assert!((fit.coherency - 0.01).abs() < 1e-4, "{fit:?}");
+        assert!(
+            (fit.peak_threads.unwrap() - 90f64.sqrt()).abs() < 0.1,
+            "{fit:?}"
+        );
+    }
+}
@@ -0,0 +1,165 @@
+//! Hint providers written as [Rhai](https://rhai.rs) scripts, so that new policies can be tried out without
+//! recompiling dice_box. Only available with the `scripting` feature.
+//!
+//! A script defines a `suggest_next` function, which gets the ready units and the state of the build, and returns
+//! the index of the unit to start next, or `()` to start none of them:
+//! ```rhai
+//! // Longest unit first, preferring ones that unlock more work on ties.
+//! fn suggest_next(candidates, context) {
+//!     let best = ();
+//!     for i in 0..candidates.len() {
+//!         let candidate = candidates[i];
+//!         if best == () || candidate.duration > candidates[best].duration
+//!             || (candidate.duration == candidates[best].duration
+//!                 && candidate.dependants > candidates[best].dependants) {
+//!             best = i;
+//!         }
+//!     }
+//!     best
+//! }
+//! ```
+//! Each candidate has a `package_id`, a `name`, a `type` (`metadata`, `link`, `build-script-run`...), a `duration` in
+//! seconds and the number of units directly depending on it (`dependants`). The context has the current time `now`
+//! in seconds, the number of `running` units and the number of `idle_slots`.
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use rhai::{Array, Dynamic, Engine, Map, Scope, AST};
+
+use crate::{
+    artifact::{split_package_id, Artifact},
+    critical_path::duration_ms,
+    dependency_queue::DependencyQueueBuilder,
+    hints::{HintProvider, SchedulerContext},
+    timings::TimingInfo,
+};
+
+pub struct ScriptHints {
+    engine: Engine,
+    ast: AST,
+    label: String,
+    durations: BTreeMap<Artifact, u64>,
+    dependants: BTreeMap<Artifact, usize>,
+    /// First error of the script, after which it's no longer consulted.
+    failure: Option<String>,
+}
+
+impl std::fmt::Debug for ScriptHints {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ScriptHints")
+            .field("label", &self.label)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ScriptHints {
+    /// Compiles the script at `path`.
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(
+        path: &Path,
+        dependencies: &DependencyQueueBuilder,
+        timings: &BTreeMap<Artifact, TimingInfo>,
+    ) -> Result<Box<dyn HintProvider>> {
+        let engine = Engine::new();
+        let source = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let ast = engine
+            .compile(source)
+            .with_context(|| format!("failed to compile {}", path.display()))?;
+        Ok(Box::new(Self {
+            engine,
+            ast,
+            label: format!("Script ({})", path.display()),
+            durations: dependencies
+                .dep_map
+                .keys()
+                .map(|artifact| (artifact.clone(), duration_ms(timings, artifact)))
+                .collect(),
+            dependants: dependencies
+                .reverse_dep_map
+                .iter()
+                .map(|(artifact, dependants)| (artifact.clone(), dependants.len()))
+                .collect(),
+            failure: None,
+        }))
+    }
+
+    fn candidate(&self, artifact: &Artifact) -> Dynamic {
+        let mut candidate = Map::new();
+        candidate.insert("package_id".into(), artifact.package_id.clone().into());
+        candidate.insert(
+            "name".into(),
+            split_package_id(&artifact.package_id).0.to_owned().into(),
+        );
+        candidate.insert("type".into(), artifact.typ.name().into());
+        let duration = self.durations.get(artifact).copied().unwrap_or_default();
+        candidate.insert("duration".into(), (duration as f64 / 1000.).into());
+        let dependants = self.dependants.get(artifact).copied().unwrap_or_default();
+        candidate.insert("dependants".into(), (dependants as i64).into());
+        candidate.into()
+    }
+
+    /// Index of the candidate the script picks, if any.
+    fn pick(
+        &mut self,
+        timings: &[&Artifact],
+        context: &SchedulerContext,
+    ) -> Result<Option<usize>, String> {
+        let candidates: Array = timings
+            .iter()
+            .map(|artifact| self.candidate(artifact))
+            .collect();
+        let mut state = Map::new();
+        state.insert("now".into(), (context.now as f64 / 1000.).into());
+        state.insert("running".into(), (context.running.len() as i64).into());
+        state.insert("idle_slots".into(), (context.idle_slots as i64).into());
+        let choice: Dynamic = self
+            .engine
+            .call_fn(
+                &mut Scope::new(),
+                &self.ast,
+                "suggest_next",
+                (candidates, state),
+            )
+            .map_err(|err| format!("failed: {err}"))?;
+        if choice.is_unit() {
+            return Ok(None);
+        }
+        let index = choice
+            .as_int()
+            .map_err(|typ| format!("returned {typ} instead of an index"))?;
+        usize::try_from(index)
+            .ok()
+            .filter(|index| *index < timings.len())
+            .map(Some)
+            .ok_or_else(|| format!("returned an out of range index {index}"))
+    }
+}
+
+impl HintProvider for ScriptHints {
+    fn suggest_next<'a>(
+        &mut self,
+        timings: &[&'a Artifact],
+        context: &SchedulerContext,
+    ) -> Option<&'a Artifact> {
+        if self.failure.is_some() {
+            return timings.first().copied();
+        }
+        match self.pick(timings, context) {
+            Ok(choice) => choice.map(|index| timings[index]),
+            Err(failure) => {
+                self.failure = Some(failure);
+                timings.first().copied()
+            }
+        }
+    }
+
+    fn label(&self) -> String {
+        self.label.clone()
+    }
+
+    fn failure(&self) -> Option<String> {
+        self.failure.clone()
+    }
+}
@@ -0,0 +1,320 @@
+//! Offline search for good schedules, showing how far the heuristics are from what's achievable.
+//!
+//! With the genetic algorithm ([search]), a chromosome is a topological order of the unit graph, which is followed
+//! as a priority list during simulation. Crossover takes a prefix of one parent and fills in the rest in the order
+//! of the other one, and mutation moves a single unit to a random position between its last dependency and its
+//! first dependant; both keep the order topological. The initial population is seeded with the orders chosen by all
+//! of the registered schedulers, so the result is never worse than the best of them.
+//!
+//! Simulated annealing ([anneal]) works on priorities of units instead, starting from their critical path lengths.
+//! Each step perturbs the priority of a single unit; units are then picked in the order of their priorities.
+use std::collections::{BTreeMap, BTreeSet};
+
+use tabled::Tabled;
+
+use crate::{
+    artifact::Artifact,
+    critical_path::critical_path_lengths,
+    dependency_queue::{CargoHints, DependencyQueueBuilder},
+    error::InputError,
+    hints::{shuffled_topological_order, PriorityListHints},
+    registry::Registry,
+    rng::Rng,
+    runner::{start_order, Duration, Runner},
+    timings::TimingInfo,
+    trace::ScenarioTrace,
+};
+
+/// How far a scheduler is from the best schedule found.
+#[derive(Clone, Debug, PartialEq, Tabled)]
+pub struct SearchGap {
+    pub label: String,
+    pub makespan: Duration,
+    pub gap: Duration,
+}
+
+pub struct SearchResult {
+    pub makespan: Duration,
+    pub gaps: Vec<SearchGap>,
+    /// Decisions following the best order found, which can be replayed with `--replay`.
+    pub trace: ScenarioTrace,
+}
+
+fn crossover(left: &[Artifact], right: &[Artifact], cut: usize) -> Vec<Artifact> {
+    let prefix: BTreeSet<&Artifact> = left[..cut].iter().collect();
+    left[..cut]
+        .iter()
+        .chain(right.iter().filter(|artifact| !prefix.contains(artifact)))
+        .cloned()
+        .collect()
+}
+
+fn mutate(order: &mut Vec<Artifact>, deps: &DependencyQueueBuilder, rng: &mut Rng) {
+    let unit = order.remove(rng.below(order.len()));
+    let dependencies = &deps.dep_map[&unit];
+    let dependants = deps.reverse_dep_map.get(&unit);
+    let earliest = order
+        .iter()
+        .rposition(|artifact| dependencies.contains(artifact))
+        .map_or(0, |position| position + 1);
+    let latest = order
+        .iter()
+        .position(|artifact| dependants.is_some_and(|dependants| dependants.contains(artifact)))
+        .unwrap_or(order.len());
+    order.insert(earliest + rng.below(latest - earliest + 1), unit);
+}
+
+/// Simulates the build picking units in the given order whenever they're ready.
+fn simulate_order(
+    order: &[Artifact],
+    deps: &DependencyQueueBuilder,
+    timings: &BTreeMap<Artifact, TimingInfo>,
+    num_threads: usize,
+    label: &str,
+) -> Result<Runner, InputError> {
+    let hints = PriorityListHints::new(order, CargoHints::new(deps));
+    let mut runner = Runner::new(deps.clone().finish(hints), timings.clone(), num_threads)
+        .with_label(label.to_owned());
+    runner.calculate()?;
+    Ok(runner)
+}
+
+fn makespan(runner: &Runner) -> u64 {
+    runner
+        .schedule()
+        .iter()
+        .map(|task| task.end)
+        .max()
+        .unwrap_or_default()
+}
+
+/// Labels, makespans and start orders of all registered schedulers.
+fn heuristics(
+    deps: &DependencyQueueBuilder,
+    timings: &BTreeMap<Artifact, TimingInfo>,
+    registry: &Registry,
+    num_threads: usize,
+) -> Result<Vec<(String, u64, Vec<Artifact>)>, InputError> {
+    registry
+        .names()
+        .map(|name| {
+            let hints = registry.create(name, deps, timings).unwrap();
+            let mut runner = Runner::new(deps.clone().finish(hints), timings.clone(), num_threads);
+            let (result, _) = runner.calculate()?;
+            Ok((
+                result.label,
+                result.makespan.as_millis(),
+                start_order(runner.schedule()),
+            ))
+        })
+        .collect()
+}
+
+fn result(
+    heuristics: Vec<(String, u64, Vec<Artifact>)>,
+    best: u64,
+    trace: ScenarioTrace,
+) -> SearchResult {
+    let millis = |millis| Duration::from_millis(millis);
+    SearchResult {
+        makespan: millis(best),
+        gaps: heuristics
+            .into_iter()
+            .map(|(label, makespan, _)| SearchGap {
+                label,
+                makespan: millis(makespan),
+                gap: millis(makespan.saturating_sub(best)),
+            })
+            .collect(),
+        trace,
+    }
+}
+
+/// Runs the genetic algorithm for `generations` generations of `population` chromosomes each.
+pub fn search(
+    deps: &DependencyQueueBuilder,
+    timings: &BTreeMap<Artifact, TimingInfo>,
+    registry: &Registry,
+    num_threads: usize,
+    population: usize,
+    generations: usize,
+    seed: u64,
+) -> Result<SearchResult, InputError> {
+    const LABEL: &str = "Genetic search";
+    let makespan = |order: &[Artifact]| -> Result<u64, InputError> {
+        Ok(makespan(&simulate_order(
+            order,
+            deps,
+            timings,
+            num_threads,
+            LABEL,
+        )?))
+    };
+    let heuristics = heuristics(deps, timings, registry, num_threads)?;
+    let mut rng = Rng::new(seed);
+    let mut chromosomes: Vec<(u64, Vec<Artifact>)> = heuristics
+        .iter()
+        .map(|(_, _, order)| order.clone())
+        .chain(std::iter::repeat_with(|| {
+            shuffled_topological_order(deps, rng.next_u64())
+        }))
+        .take(population.max(heuristics.len()))
+        .map(|order| Ok((makespan(&order)?, order)))
+        .collect::<Result<_, InputError>>()?;
+    for _ in 0..generations {
+        chromosomes.sort_by_key(|(makespan, _)| *makespan);
+        // Elitism: the better half survives and breeds the other half.
+        let survivors = chromosomes.len().div_ceil(2);
+        chromosomes.truncate(survivors);
+        for _ in survivors..population.max(heuristics.len()) {
+            let mut tournament = || {
+                let (a, b) = (rng.below(survivors), rng.below(survivors));
+                a.min(b)
+            };
+            let (left, right) = (tournament(), tournament());
+            let cut = rng.below(chromosomes[left].1.len() + 1);
+            let mut child = crossover(&chromosomes[left].1, &chromosomes[right].1, cut);
+            if !child.is_empty() {
+                mutate(&mut child, deps, &mut rng);
+            }
+            chromosomes.push((makespan(&child)?, child));
+        }
+    }
+    let (best, order) = chromosomes
+        .into_iter()
+        .min_by_key(|(makespan, _)| *makespan)
+        .unwrap();
+    let trace = simulate_order(&order, deps, timings, num_threads, LABEL)?.trace();
+    Ok(result(heuristics, best, trace))
+}
+
+/// Runs simulated annealing for `iterations` steps, multiplying the temperature by `cooling` after each of them.
+pub fn anneal(
+    deps: &DependencyQueueBuilder,
+    timings: &BTreeMap<Artifact, TimingInfo>,
+    registry: &Registry,
+    num_threads: usize,
+    iterations: usize,
+    cooling: f64,
+    seed: u64,
+) -> Result<SearchResult, InputError> {
+    const LABEL: &str = "Simulated annealing";
+    let units: Vec<Artifact> = deps.dep_map.keys().cloned().collect();
+    let order = |priorities: &[f64]| {
+        let mut order: Vec<(f64, &Artifact)> = priorities.iter().copied().zip(&units).collect();
+        order.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(b.1)));
+        order
+            .into_iter()
+            .map(|(_, artifact)| artifact.clone())
+            .collect::<Vec<_>>()
+    };
+    let cost = |priorities: &[f64]| -> Result<u64, InputError> {
+        Ok(makespan(&simulate_order(
+            &order(priorities),
+            deps,
+            timings,
+            num_threads,
+            LABEL,
+        )?))
+    };
+    let lengths = critical_path_lengths(deps, timings);
+    let mut current: Vec<f64> = units
+        .iter()
+        .map(|artifact| lengths.get(artifact).copied().unwrap_or_default() as f64)
+        .collect();
+    // Perturbations are scaled to the range of priorities.
+    let scale = current.iter().copied().fold(1., f64::max) * 0.1;
+    let mut rng = Rng::new(seed);
+    let mut current_cost = cost(&current)?;
+    let mut best = (current.clone(), current_cost);
+    let mut temperature = current_cost as f64 * 0.1;
+    for _ in 0..iterations {
+        if units.is_empty() {
+            break;
+        }
+        let mut candidate = current.clone();
+        let unit = rng.below(units.len());
+        candidate[unit] += scale * (2. * (rng.next_u64() as f64 / u64::MAX as f64) - 1.);
+        let candidate_cost = cost(&candidate)?;
+        let accept = candidate_cost <= current_cost || {
+            let probability =
+                (-((candidate_cost - current_cost) as f64) / temperature.max(1.)).exp();
+            (rng.next_u64() as f64 / u64::MAX as f64) < probability
+        };
+        if accept {
+            current = candidate;
+            current_cost = candidate_cost;
+            if current_cost < best.1 {
+                best = (current.clone(), current_cost);
+            }
+        }
+        temperature *= cooling;
+    }
+    let (priorities, best) = best;
+    let trace = simulate_order(&order(&priorities), deps, timings, num_threads, LABEL)?.trace();
+    Ok(result(
+        heuristics(deps, timings, registry, num_threads)?,
+        best,
+        trace,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::artifact::ArtifactType;
+
+    fn artifact(package_id: &str) -> Artifact {
+        Artifact {
+            typ: ArtifactType::Link,
+            package_id: package_id.into(),
+        }
+    }
+
+    /// A diamond (`d` depends on `b` and `c`, which depend on `a`) next to an independent chain `e` -> `f`.
+    fn graph() -> DependencyQueueBuilder {
+        let mut deps = DependencyQueueBuilder::new();
+        deps.queue(artifact("a"), []);
+        deps.queue(artifact("b"), [artifact("a")]);
+        deps.queue(artifact("c"), [artifact("a")]);
+        deps.queue(artifact("d"), [artifact("b"), artifact("c")]);
+        deps.queue(artifact("e"), []);
+        deps.queue(artifact("f"), [artifact("e")]);
+        deps
+    }
+
+    fn assert_topological(order: &[Artifact], deps: &DependencyQueueBuilder) {
+        assert_eq!(order.len(), deps.dep_map.len(), "{order:?}");
+        for (position, artifact) in order.iter().enumerate() {
+            for dependency in &deps.dep_map[artifact] {
+                assert!(
+                    order[..position].contains(dependency),
+                    "{dependency:?} comes after {artifact:?} in {order:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn crossover_keeps_orders_topological() {
+        let deps = graph();
+        for seed in 0..8 {
+            let left = shuffled_topological_order(&deps, seed);
+            let right = shuffled_topological_order(&deps, seed + 100);
+            for cut in 0..=left.len() {
+                assert_topological(&crossover(&left, &right, cut), &deps);
+            }
+        }
+    }
+
+    #[test]
+    fn mutation_keeps_orders_topological() {
+        let deps = graph();
+        let mut order = shuffled_topological_order(&deps, 0);
+        let mut rng = Rng::new(0);
+        for _ in 0..200 {
+            mutate(&mut order, &deps, &mut rng);
+            assert_topological(&order, &deps);
+        }
+    }
+}
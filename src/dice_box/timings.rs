@@ -4,10 +4,13 @@ pub use visualization::{Timings, UnitTime};
 
 use std::collections::BTreeMap;
 
+use ordered_float::OrderedFloat;
 use serde::{Deserialize, Serialize};
+use tabled::Tabled;
 
 use crate::{
     artifact::{Artifact, ArtifactType},
+    error::InputError,
     PackageId,
 };
 #[derive(Clone, Copy, Debug, Hash, Serialize, Deserialize, PartialEq, PartialOrd, Eq)]
@@ -15,6 +18,8 @@ use crate::{
 pub enum BuildMode {
     RunCustomBuild,
     Build,
+    /// Test harness of a target, as built by `cargo test`.
+    Test,
 }
 
 // Parsed output of --timings=json
@@ -52,6 +57,8 @@ pub(crate) fn node_type(mode: &BuildMode, target: &Target) -> ArtifactType {
             ArtifactType::Link
         }
         (BuildMode::Build, false) => ArtifactType::Metadata,
+        // Test harnesses are always linked into an executable.
+        (BuildMode::Test, _) => ArtifactType::Link,
 
         (BuildMode::RunCustomBuild, false) => unreachable!("{target:?}"),
     }
@@ -80,24 +87,139 @@ impl Target {
     fn is_build_script(&self) -> bool {
         self.name == "build-script-build" || self.name == "build-script-main"
     }
+
+    pub(crate) fn is_proc_macro(&self) -> bool {
+        self.crate_types.contains(&CrateType::ProcMacro)
+    }
+}
+
+/// Line of a timings file, along with the start time of the unit, if it was recorded.
+#[derive(Deserialize)]
+struct StartedTiming {
+    start: Option<f64>,
+    #[serde(flatten)]
+    info: TimingInfo,
+}
+
+/// Timings that record when their unit started (in seconds since the start of the build), as the ones saved by
+/// `dice_box verify --save` do. Timings written by Cargo itself don't, in which case there are none.
+pub(crate) fn parse_started(contents: &str) -> Result<Vec<(f64, TimingInfo)>, InputError> {
+    let mut started = vec![];
+    for (index, line) in contents.lines().enumerate() {
+        if !line.starts_with('{') {
+            continue;
+        }
+        let timing: StartedTiming =
+            serde_json::from_str(line).map_err(|err| InputError::on_line(index + 1, err))?;
+        if let Some(start) = timing.start {
+            started.push((start, timing.info));
+        }
+    }
+    Ok(started)
+}
+
+/// Units of a timings file in the order they started in, if the file records start times. Codegen units are left
+/// out, as they start as soon as their metadata is done.
+pub fn parse_start_order(contents: &str) -> Result<Vec<Artifact>, InputError> {
+    let mut starts = parse_started(contents)?
+        .into_iter()
+        .map(|(start, timing)| {
+            let artifact = Artifact {
+                package_id: timing.package_id.clone(),
+                typ: timing.node_type(),
+            };
+            (OrderedFloat(start), artifact)
+        })
+        .collect::<Vec<_>>();
+    starts.sort();
+    Ok(starts.into_iter().map(|(_, artifact)| artifact).collect())
+}
+
+/// Wall time (in milliseconds) of the recorded build, up until its last unit finished, if the timings file records
+/// start times.
+pub fn parse_wall_time(contents: &str) -> Result<Option<u64>, InputError> {
+    Ok(parse_started(contents)?
+        .into_iter()
+        .map(|(start, timing)| ((start + timing.duration) * 1000.).round() as u64)
+        .max())
+}
+
+/// A timing that could not have been measured as captured (e.g. due to clock skew or rounding), along with how
+/// it was repaired.
+#[derive(Clone, Debug, PartialEq, Tabled)]
+pub struct TimingAnomaly {
+    pub unit: String,
+    pub problem: String,
+    pub repair: String,
 }
 
 /// Deserialize timings from contents of a timings.json file.
-pub fn parse(contents: String) -> BTreeMap<Artifact, TimingInfo> {
+pub fn parse(contents: String) -> Result<BTreeMap<Artifact, TimingInfo>, InputError> {
+    Ok(parse_with_anomalies(contents)?.0)
+}
+
+/// Deserialize timings from contents of a timings.json file, repairing negative durations and rmeta times that
+/// fall outside of their unit. Returns the timings along with the repairs that were made.
+pub fn parse_with_anomalies(
+    contents: String,
+) -> Result<(BTreeMap<Artifact, TimingInfo>, Vec<TimingAnomaly>), InputError> {
     let mut out = BTreeMap::new();
-    for line in contents.lines() {
+    let mut anomalies = vec![];
+    for (index, line) in contents.lines().enumerate() {
         if !line.starts_with('{') {
             continue;
         }
-        let mut timing: TimingInfo = serde_json::from_str(line).unwrap();
+        let mut timing: TimingInfo =
+            serde_json::from_str(line).map_err(|err| InputError::on_line(index + 1, err))?;
         let typ = timing.node_type();
+        let artifact = Artifact {
+            package_id: timing.package_id.clone(),
+            typ,
+        };
+        let mut report = |problem: String, repair: &str| {
+            anomalies.push(TimingAnomaly {
+                unit: artifact.to_string(),
+                problem,
+                repair: repair.to_owned(),
+            })
+        };
+        if timing.duration.is_nan() || timing.duration < 0. {
+            report(
+                format!("duration of {}s", timing.duration),
+                "duration set to 0s",
+            );
+            timing.duration = 0.;
+        }
+        if let Some(rmeta_time) = timing.rmeta_time {
+            if rmeta_time.is_nan() || rmeta_time < 0. {
+                report(
+                    format!("rmeta time of {rmeta_time}s"),
+                    "rmeta time set to 0s",
+                );
+                timing.rmeta_time = Some(0.);
+            } else if rmeta_time > timing.duration {
+                report(
+                    format!(
+                        "rmeta time of {rmeta_time}s after the end of the unit at {}s",
+                        timing.duration
+                    ),
+                    "rmeta time set to the end of the unit",
+                );
+                timing.rmeta_time = Some(timing.duration);
+            }
+        }
         if typ == ArtifactType::Metadata {
             // Pipelining support
-            assert!(
-                timing.rmeta_time.is_some(),
-                "{:?}",
-                timing.target.crate_types
-            );
+            if timing.rmeta_time.is_none() {
+                return Err(InputError::Malformed {
+                    line: index + 1,
+                    column: 1,
+                    message: format!(
+                        "{artifact} has no rmeta time (crate types {:?})",
+                        timing.target.crate_types
+                    ),
+                });
+            }
             let mut codegen_timing = timing.clone();
             // Normalize codegen time
             codegen_timing.duration -= codegen_timing.rmeta_time.take().unwrap();
@@ -111,13 +233,31 @@ pub fn parse(contents: String) -> BTreeMap<Artifact, TimingInfo> {
             // ... and for Metadata unit we're about to insert, just use rmeta_time
             timing.duration = timing.rmeta_time.take().unwrap();
         }
-        let _ = out.insert(
-            Artifact {
-                package_id: timing.package_id.clone(),
-                typ,
-            },
-            timing,
-        );
+        let _ = out.insert(artifact, timing);
+    }
+    Ok((out, anomalies))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn impossible_timings_are_repaired() {
+        let line = |duration: f64, rmeta_time: f64| {
+            format!(
+                r#"{{"reason":"timing-info","package_id":"a 0.1.0 (path+file:///a)","target":{{"kind":["lib"],"crate_types":["lib"],"name":"a","src_path":"/a/src/lib.rs","edition":"2021","doc":true,"doctest":true,"test":true}},"mode":"build","duration":{duration},"rmeta_time":{rmeta_time}}}"#
+            )
+        };
+        let codegen = Artifact {
+            package_id: "a 0.1.0 (path+file:///a)".into(),
+            typ: ArtifactType::Codegen,
+        };
+        let (timings, anomalies) = parse_with_anomalies(line(1.5, 1.500001)).unwrap();
+        assert_eq!(anomalies.len(), 1);
+        assert_eq!(timings[&codegen].duration, 0.);
+        let (timings, anomalies) = parse_with_anomalies(line(-0.25, -0.5)).unwrap();
+        assert_eq!(anomalies.len(), 2);
+        assert!(timings.values().all(|timing| timing.duration == 0.));
     }
-    out
 }
@@ -2,11 +2,14 @@
 //!
 //! This module implements visualization of simulated build process. Large parts of it are pulled verbatim from cargo. Notably I've stripped tracking of units unlocked by finished rmeta/codegen.
 use anyhow::Result;
-use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::collections::{btree_map::Entry, BTreeMap, BTreeSet, HashMap};
 use std::io::{BufWriter, Write};
 use std::time::SystemTime;
 
 use crate::artifact::{Artifact, ArtifactType};
+use crate::events::ExternalEvent;
+use crate::package::PackageSummary;
+use crate::report_limits::{downsample, ReportLimits};
 use crate::runner::StartTime;
 use crate::timings::BuildMode;
 use crate::unit_graph::Unit;
@@ -31,6 +34,12 @@ pub struct Timings {
     /// system.
     cpu_usage: Vec<(f64, f64)>,
     total_time: f64,
+    /// Descriptions of external events that happened during the build.
+    events: Vec<String>,
+    /// Per-package summary of the build.
+    packages: Vec<PackageSummary>,
+    /// Number of units left out of a summarized report, and their total duration in seconds.
+    omitted: Option<(usize, f64)>,
 }
 
 /// Tracking information for an individual unit.
@@ -101,6 +110,9 @@ impl Timings {
                     pkg_id: info.package_id.clone(),
                     target: info.target.clone(),
                     mode: info.mode.clone(),
+                    platform: None,
+                    features: vec![],
+                    profile: None,
                     dependencies: vec![],
                 },
                 target: info.target.name.to_owned(),
@@ -133,9 +145,123 @@ impl Timings {
             concurrency,
             cpu_usage,
             total_time,
+            events: vec![],
+            packages: vec![],
+            omitted: None,
         }
     }
 
+    /// Summarizes the report if the build has more units than `limits` allow: units of packages outside of the
+    /// workspace are collapsed into one row per package, only the longest rows are kept and graphs are down-sampled.
+    pub fn summarized(mut self, limits: &ReportLimits) -> Timings {
+        if !limits.exceeded_by(self.unit_times.len()) {
+            return self;
+        }
+        let total_units = self.unit_times.len();
+        let total_work: f64 = self.unit_times.iter().map(|ut| ut.duration).sum();
+        let mut rows: Vec<UnitTime> = vec![];
+        let mut externals: BTreeMap<String, UnitTime> = BTreeMap::new();
+        for ut in std::mem::take(&mut self.unit_times) {
+            let artifact = Artifact {
+                typ: ArtifactType::Link,
+                package_id: ut.unit.pkg_id.clone(),
+            };
+            if artifact.is_workspace_member() {
+                rows.push(ut);
+                continue;
+            }
+            match externals.entry(ut.unit.pkg_id.clone()) {
+                Entry::Vacant(entry) => {
+                    entry.insert(UnitTime {
+                        rmeta_time: None,
+                        ..ut
+                    });
+                }
+                Entry::Occupied(mut entry) => {
+                    let collapsed = entry.get_mut();
+                    let end = (collapsed.start + collapsed.duration).max(ut.start + ut.duration);
+                    collapsed.start = collapsed.start.min(ut.start);
+                    collapsed.duration = end - collapsed.start;
+                }
+            }
+        }
+        rows.extend(externals.into_values());
+        rows.sort_by(|a, b| b.duration.total_cmp(&a.duration));
+        rows.truncate(limits.top_units);
+        rows.sort_by(|a, b| a.start.total_cmp(&b.start));
+        let kept_work: f64 = rows.iter().map(|ut| ut.duration).sum();
+        self.unit_times = rows;
+        self.omitted = Some((
+            total_units - self.unit_times.len(),
+            (total_work - kept_work).max(0.),
+        ));
+        self.concurrency = downsample(
+            self.concurrency
+                .iter()
+                .map(|concurrency| (concurrency.t, concurrency.active))
+                .collect(),
+            limits.max_points,
+        )
+        .into_iter()
+        .map(|(t, active)| Concurrency {
+            t,
+            active,
+            waiting: 0,
+            inactive: 0,
+        })
+        .collect();
+        self.cpu_usage = downsample(std::mem::take(&mut self.cpu_usage), limits.max_points);
+        self
+    }
+
+    /// Attaches per-package summary of the build, to be rendered below the timeline.
+    pub fn with_packages(mut self, packages: Vec<PackageSummary>) -> Timings {
+        self.packages = packages;
+        self
+    }
+
+    /// Annotates the timeline with external events.
+    pub fn with_events(mut self, events: &[ExternalEvent]) -> Timings {
+        self.events = events.iter().map(ToString::to_string).collect();
+        self
+    }
+
+    /// Appends timeline of `other` to this one, as if it was started right after this one has finished.
+    pub fn followed_by(mut self, other: Timings) -> Timings {
+        let offset = self.total_time;
+        self.unit_times
+            .extend(other.unit_times.into_iter().map(|mut unit_time| {
+                unit_time.start += offset;
+                unit_time
+            }));
+        self.concurrency
+            .extend(other.concurrency.into_iter().map(|mut concurrency| {
+                concurrency.t += offset;
+                concurrency
+            }));
+        self.cpu_usage.extend(
+            other
+                .cpu_usage
+                .into_iter()
+                .map(|(time, usage)| (time + offset, usage)),
+        );
+        self.total_time += other.total_time;
+        self.events.extend(other.events);
+        self.omitted = match (self.omitted, other.omitted) {
+            (Some((units, work)), Some((other_units, other_work))) => {
+                Some((units + other_units, work + other_work))
+            }
+            (omitted, other_omitted) => omitted.or(other_omitted),
+        };
+        self.packages
+            .extend(other.packages.into_iter().map(|mut package| {
+                package.start.0 += std::time::Duration::from_secs_f64(offset);
+                package.end.0 += std::time::Duration::from_secs_f64(offset);
+                package
+            }));
+        self
+    }
+
     /// Save HTML report to disk.
     pub fn report_html(&self, timings_suffix: String) -> Result<()> {
         let timestamp = self.start_str.replace(&['-', ':'][..], "");
@@ -158,11 +284,16 @@ impl Timings {
             f,
             "{}\n\
              </script>\n\
-             </body>\n\
-             </html>\n\
              ",
             include_str!("timings.js")
         )?;
+        self.write_package_table(&mut f)?;
+        write!(
+            f,
+            "</body>\n\
+             </html>\n\
+             "
+        )?;
         drop(f);
         Ok(())
     }
@@ -175,6 +306,24 @@ impl Timings {
             "".to_string()
         };
         let total_time = format!("{:.1}s{}", duration, time_human);
+        let omitted = match self.omitted {
+            Some((units, work)) => format!(
+                "  <tr>\n    <td>Omitted:</td><td>{units} units ({work:.1}s of work); units of external packages are collapsed</td>\n  </tr>\n"
+            ),
+            None => "".to_string(),
+        };
+        let events = if self.events.is_empty() {
+            "".to_string()
+        } else {
+            format!(
+                "  <tr>\n    <td>External events:</td><td>{}</td>\n  </tr>\n",
+                self.events
+                    .iter()
+                    .map(|event| event.replace('&', "&amp;").replace('<', "&lt;"))
+                    .collect::<Vec<_>>()
+                    .join("<br>")
+            )
+        };
         write!(
             f,
             r#"
@@ -185,9 +334,59 @@ impl Timings {
   <tr>
     <td>Total time:</td><td>{}</td>
   </tr>
-</table>
+{}{}</table>
 "#,
-            self.start_str, total_time,
+            self.start_str, total_time, omitted, events,
+        )?;
+        Ok(())
+    }
+
+    /// Render the per-package summary table.
+    fn write_package_table(&self, f: &mut impl Write) -> Result<()> {
+        if self.packages.is_empty() {
+            return Ok(());
+        }
+        let secs = |duration: &crate::runner::Duration| format!("{:.1}s", duration.as_secs_f64());
+        write!(
+            f,
+            r#"
+<table class="my-table">
+  <thead>
+    <tr>
+      <th>Package</th>
+      <th>Units</th>
+      <th>Build script</th>
+      <th>Metadata</th>
+      <th>Codegen</th>
+      <th>Link</th>
+      <th>Total</th>
+      <th>Start</th>
+      <th>End</th>
+    </tr>
+  </thead>
+  <tbody>
+"#
+        )?;
+        for package in &self.packages {
+            writeln!(
+                f,
+                "    <tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                package.package,
+                package.units,
+                secs(&package.build_script),
+                secs(&package.metadata),
+                secs(&package.codegen),
+                secs(&package.link),
+                secs(&package.total),
+                secs(&package.start),
+                secs(&package.end),
+            )?;
+        }
+        write!(
+            f,
+            r#"  </tbody>
+</table>
+"#
         )?;
         Ok(())
     }
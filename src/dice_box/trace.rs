@@ -0,0 +1,55 @@
+//! Traces of simulations, allowing a surprising result to be replayed deterministically (e.g. with extra logging
+//! enabled) without running the hint providers again.
+//!
+//! Randomness only ever affects the simulation through decisions of the hint providers, so recording the decisions
+//! covers the RNG draws too.
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{artifact::Artifact, environment::Environment};
+
+/// A single decision of the runner: the unit it started (if any) and the decision time it charged for it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Decision {
+    pub artifact: Option<Artifact>,
+    /// Scheduling overhead, in milliseconds.
+    pub overhead: u64,
+    /// Whether the hint provider deliberately left the thread idle.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub wait: bool,
+    /// Number of threads the unit was given, if more than one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub threads: Option<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ScenarioTrace {
+    pub label: String,
+    pub num_threads: usize,
+    pub decisions: Vec<Decision>,
+    /// Fingerprint of the runner that made the decisions (see [crate::Runner::fingerprint]), to check that the one
+    /// replaying them is set up the same way. Missing in traces written before it was recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<u64>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Trace {
+    pub scenarios: Vec<ScenarioTrace>,
+    /// Environment the simulated build was captured in, if known.
+    #[serde(default)]
+    pub environment: Option<Environment>,
+}
+
+impl Trace {
+    pub fn read(path: &std::path::Path) -> Result<Trace> {
+        Ok(serde_json::from_reader(std::io::BufReader::new(
+            std::fs::File::open(path)?,
+        ))?)
+    }
+
+    pub fn write(&self, path: &std::path::Path) -> Result<()> {
+        serde_json::to_writer(std::io::BufWriter::new(std::fs::File::create(path)?), self)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,400 @@
+//! Declarative what-if transforms of the build, applied to the dependency graph and timings before any scenario
+//! runs, so that hypotheticals can be written down, reproduced and composed.
+//!
+//! A transforms file is an ordered list of rewrites, each applied to the result of the previous ones:
+//! ```json
+//! [
+//!     { "rewrite": "scale", "package": "syn", "factor": 0.5 },
+//!     { "rewrite": "split", "package": "regex-automata" },
+//!     { "rewrite": "split-into", "package": "tokio", "ratio": [2, 1, 1] },
+//!     { "rewrite": "merge", "package": "serde_derive_internals", "into": "serde_derive" },
+//!     { "rewrite": "remove-dependency", "dependant": "serde_json", "dependency": "serde" },
+//!     { "rewrite": "cache-build-script", "package": "ring" },
+//!     { "rewrite": "no-pipelining", "package": "tokio" },
+//!     { "rewrite": "clamp", "min": 0.05 },
+//!     { "rewrite": "dirty", "packages": ["serde_json"] }
+//! ]
+//! ```
+//! Packages can be referred to either by their full package id or just by their name.
+use std::collections::{btree_map::Entry, BTreeMap, BTreeSet};
+
+use serde::Deserialize;
+
+use crate::{
+    artifact::{split_package_id, Artifact, ArtifactType},
+    constraints::matches,
+    dependency_queue::DependencyQueueBuilder,
+    error::InputError,
+    queries::transitive_dependants,
+    timings::TimingInfo,
+};
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(tag = "rewrite", rename_all = "kebab-case")]
+pub enum Rewrite {
+    /// Multiplies durations of all units of a package (or of all packages, if none is given) by a factor.
+    Scale {
+        #[serde(default)]
+        package: Option<String>,
+        factor: f64,
+    },
+    /// Splits a crate into two halves that can be built in parallel, approximated by halving its metadata and
+    /// codegen time.
+    Split { package: String },
+    /// Splits the library of a crate into as many crates as there are elements of `ratio`, with its metadata and
+    /// codegen time divided between them in that ratio. The parts are named `<package>-part<N>` and can be built in
+    /// parallel: each of them has all the dependencies of the crate, and everything that depended on the crate
+    /// depends on all of them.
+    SplitInto { package: String, ratio: Vec<f64> },
+    /// Merges units of one crate into units of the same kind of another crate, summing their durations. The merged
+    /// crate keeps the name of `into`, and has the dependencies and dependants of both crates.
+    Merge { package: String, into: String },
+    /// Removes all dependency edges from units of one package to units of another.
+    RemoveDependency {
+        dependant: String,
+        dependency: String,
+    },
+    /// Makes build script runs of a package free, as if their outputs were cached.
+    CacheBuildScript { package: String },
+    /// Makes dependants of a package (or of all packages, if none is given) wait for its codegen instead of
+    /// starting as soon as its metadata is ready.
+    NoPipelining {
+        #[serde(default)]
+        package: Option<String>,
+    },
+    /// Clamps durations (in seconds) of all units of a package (or of all packages, if none is given) to a range.
+    /// A floor accounts for the overhead of spawning a process, which timings of fast machines can understate; a
+    /// ceiling caps outliers, to study the structure of the graph rather than a few slow crates. Metadata and
+    /// codegen of a crate are clamped separately, and codegen units without any time of their own are left alone.
+    Clamp {
+        #[serde(default)]
+        package: Option<String>,
+        #[serde(default)]
+        min: Option<f64>,
+        #[serde(default)]
+        max: Option<f64>,
+    },
+    /// Makes all units of a package (or of all packages, if none is given) fresh: they take no time, but still have
+    /// to be "built" before their dependants can start.
+    Fresh {
+        #[serde(default)]
+        package: Option<String>,
+    },
+    /// Simulates an incremental rebuild after touching `packages`: only their units and units that depend on them
+    /// (directly or transitively) are rebuilt, while everything else is fresh.
+    Dirty { packages: Vec<String> },
+}
+
+impl Rewrite {
+    pub fn apply(
+        &self,
+        deps: &mut DependencyQueueBuilder,
+        timings: &mut BTreeMap<Artifact, TimingInfo>,
+    ) -> Result<(), InputError> {
+        let selects = |package: &Option<String>, artifact: &Artifact| {
+            package
+                .as_ref()
+                .is_none_or(|package| matches(&artifact.package_id, package))
+        };
+        match self {
+            Rewrite::Scale { package, factor } => {
+                for (_, timing) in timings
+                    .iter_mut()
+                    .filter(|(artifact, _)| selects(package, artifact))
+                {
+                    timing.duration *= factor;
+                }
+            }
+            Rewrite::Clamp { package, min, max } => {
+                for (artifact, timing) in timings
+                    .iter_mut()
+                    .filter(|(artifact, _)| selects(package, artifact))
+                {
+                    if artifact.typ == ArtifactType::Codegen && timing.duration <= 0. {
+                        continue;
+                    }
+                    if let Some(min) = min {
+                        timing.duration = timing.duration.max(*min);
+                    }
+                    if let Some(max) = max {
+                        timing.duration = timing.duration.min(*max);
+                    }
+                }
+            }
+            Rewrite::Split { package } => {
+                for (artifact, timing) in timings.iter_mut() {
+                    if matches(&artifact.package_id, package)
+                        && matches!(artifact.typ, ArtifactType::Metadata | ArtifactType::Codegen)
+                    {
+                        timing.duration /= 2.;
+                    }
+                }
+            }
+            Rewrite::SplitInto { package, ratio } => split_into(deps, timings, package, ratio)?,
+            Rewrite::Merge { package, into } => merge(deps, timings, package, into)?,
+            Rewrite::RemoveDependency {
+                dependant,
+                dependency,
+            } => {
+                let removed: Vec<(Artifact, Artifact)> = deps
+                    .dep_map
+                    .iter()
+                    .filter(|(artifact, _)| matches(&artifact.package_id, dependant))
+                    .flat_map(|(artifact, dependencies)| {
+                        dependencies
+                            .iter()
+                            .filter(|dep| matches(&dep.package_id, dependency))
+                            .map(|dep| (artifact.clone(), dep.clone()))
+                    })
+                    .collect();
+                for (artifact, dep) in removed {
+                    deps.remove_dependency(&artifact, &dep);
+                }
+            }
+            Rewrite::CacheBuildScript { package } => {
+                for (artifact, timing) in timings.iter_mut() {
+                    if artifact.typ == ArtifactType::BuildScriptRun
+                        && matches(&artifact.package_id, package)
+                    {
+                        timing.duration = 0.;
+                    }
+                }
+            }
+            Rewrite::NoPipelining { package } => {
+                let pipelined: Vec<(Artifact, Artifact)> = deps
+                    .dep_map
+                    .iter()
+                    .flat_map(|(artifact, dependencies)| {
+                        dependencies
+                            .iter()
+                            .filter(|dep| {
+                                dep.typ == ArtifactType::Metadata && selects(package, dep)
+                            })
+                            // Codegen of a package always waits for its own metadata.
+                            .filter(|dep| dep.package_id != artifact.package_id)
+                            .map(|dep| (artifact.clone(), dep.clone()))
+                    })
+                    .collect();
+                for (artifact, metadata) in pipelined {
+                    let codegen = Artifact {
+                        typ: ArtifactType::Codegen,
+                        package_id: metadata.package_id.clone(),
+                    };
+                    if deps.contains(&codegen) {
+                        deps.remove_dependency(&artifact, &metadata);
+                        deps.add_dependency(&artifact, codegen);
+                    }
+                }
+            }
+            Rewrite::Fresh { package } => {
+                for (_, timing) in timings
+                    .iter_mut()
+                    .filter(|(artifact, _)| selects(package, artifact))
+                {
+                    make_fresh(timing);
+                }
+            }
+            Rewrite::Dirty { packages } => {
+                let touched: Vec<Artifact> = deps
+                    .dep_map
+                    .keys()
+                    .filter(|artifact| {
+                        packages
+                            .iter()
+                            .any(|package| matches(&artifact.package_id, package))
+                    })
+                    .cloned()
+                    .collect();
+                let dependants = transitive_dependants(deps, &touched);
+                for (_, timing) in timings.iter_mut().filter(|(artifact, _)| {
+                    !touched.contains(artifact) && !dependants.contains(artifact)
+                }) {
+                    make_fresh(timing);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn split_into(
+    deps: &mut DependencyQueueBuilder,
+    timings: &mut BTreeMap<Artifact, TimingInfo>,
+    package: &str,
+    ratio: &[f64],
+) -> Result<(), InputError> {
+    if ratio.len() < 2 {
+        return Err(InputError::invalid(format!(
+            "splitting {package} needs at least two shares, got {}",
+            ratio.len()
+        )));
+    }
+    if let Some(share) = ratio
+        .iter()
+        .find(|share| !share.is_finite() || **share <= 0.)
+    {
+        return Err(InputError::invalid(format!(
+            "shares of {package} must be positive, got {share}"
+        )));
+    }
+    let total: f64 = ratio.iter().sum();
+    let is_split = |artifact: &Artifact| {
+        matches!(artifact.typ, ArtifactType::Metadata | ArtifactType::Codegen)
+            && matches(&artifact.package_id, package)
+    };
+    let parts = |artifact: &Artifact| -> Vec<Artifact> {
+        let (name, version, source) = split_package_id(&artifact.package_id);
+        (1..=ratio.len())
+            .map(|part| {
+                let mut package_id = format!("{name}-part{part} {version}");
+                if !source.is_empty() {
+                    package_id.push_str(&format!(" ({source})"));
+                }
+                Artifact {
+                    typ: artifact.typ,
+                    package_id,
+                }
+            })
+            .collect()
+    };
+    let mut split = DependencyQueueBuilder::new();
+    for artifact in &deps.insertion_order {
+        let dependencies = &deps.dep_map[artifact];
+        if !is_split(artifact) {
+            split.queue(
+                artifact.clone(),
+                dependencies.iter().flat_map(|dep| {
+                    if is_split(dep) {
+                        parts(dep)
+                    } else {
+                        vec![dep.clone()]
+                    }
+                }),
+            );
+            continue;
+        }
+        let timing = timings.remove(artifact);
+        for (index, (part, share)) in parts(artifact).into_iter().zip(ratio).enumerate() {
+            // Codegen of each part only waits for metadata of the same part.
+            split.queue(
+                part.clone(),
+                dependencies.iter().map(|dep| {
+                    if is_split(dep) {
+                        parts(dep).swap_remove(index)
+                    } else {
+                        dep.clone()
+                    }
+                }),
+            );
+            if let Some(timing) = &timing {
+                let mut timing = timing.clone();
+                timing.duration *= share / total;
+                if let Some(rmeta_time) = timing.rmeta_time.as_mut() {
+                    *rmeta_time *= share / total;
+                }
+                timing.package_id = part.package_id.clone();
+                timings.insert(part, timing);
+            }
+        }
+    }
+    *deps = split;
+    Ok(())
+}
+
+fn merge(
+    deps: &mut DependencyQueueBuilder,
+    timings: &mut BTreeMap<Artifact, TimingInfo>,
+    package: &str,
+    into: &str,
+) -> Result<(), InputError> {
+    let Some(into_id) = deps
+        .dep_map
+        .keys()
+        .find(|artifact| matches(&artifact.package_id, into))
+        .map(|artifact| artifact.package_id.clone())
+    else {
+        return Ok(());
+    };
+    let rename = |artifact: &Artifact| {
+        if matches(&artifact.package_id, package) {
+            Artifact {
+                typ: artifact.typ,
+                package_id: into_id.clone(),
+            }
+        } else {
+            artifact.clone()
+        }
+    };
+    let mut order = vec![];
+    let mut dep_map: BTreeMap<Artifact, BTreeSet<Artifact>> = BTreeMap::new();
+    for artifact in &deps.insertion_order {
+        let merged = rename(artifact);
+        if !dep_map.contains_key(&merged) {
+            order.push(merged.clone());
+        }
+        let dependencies = dep_map.entry(merged.clone()).or_default();
+        dependencies.extend(
+            deps.dep_map[artifact]
+                .iter()
+                .map(rename)
+                .filter(|dep| *dep != merged),
+        );
+    }
+    let mut merged = DependencyQueueBuilder::new();
+    for artifact in order {
+        let dependencies = dep_map.remove(&artifact).unwrap_or_default();
+        merged.queue(artifact, dependencies);
+    }
+    if !merged.is_acyclic() {
+        return Err(InputError::invalid(format!(
+            "merging {package} into {into} would make them depend on each other through other crates"
+        )));
+    }
+    let (moved, kept): (BTreeMap<_, _>, BTreeMap<_, _>) = std::mem::take(timings)
+        .into_iter()
+        .partition(|(artifact, _)| matches(&artifact.package_id, package));
+    *timings = kept;
+    for (artifact, timing) in moved {
+        let merged = rename(&artifact);
+        match timings.entry(merged.clone()) {
+            Entry::Occupied(mut entry) => {
+                let existing = entry.get_mut();
+                if let Some(rmeta_time) = existing.rmeta_time.as_mut() {
+                    *rmeta_time += timing.rmeta_time.unwrap_or(timing.duration);
+                }
+                existing.duration += timing.duration;
+            }
+            Entry::Vacant(entry) => {
+                let mut timing = timing;
+                timing.package_id = merged.package_id;
+                entry.insert(timing);
+            }
+        }
+    }
+    *deps = merged;
+    Ok(())
+}
+
+fn make_fresh(timing: &mut TimingInfo) {
+    timing.duration = 0.;
+    if let Some(rmeta_time) = timing.rmeta_time.as_mut() {
+        *rmeta_time = 0.;
+    }
+}
+
+/// Applies `rewrites` in order.
+pub fn apply_transforms(
+    rewrites: &[Rewrite],
+    deps: &mut DependencyQueueBuilder,
+    timings: &mut BTreeMap<Artifact, TimingInfo>,
+) -> Result<(), InputError> {
+    for rewrite in rewrites {
+        rewrite.apply(deps, timings)?;
+    }
+    Ok(())
+}
+
+/// Deserialize rewrites from contents of a transforms file.
+pub fn parse_transforms(contents: &str) -> serde_json::Result<Vec<Rewrite>> {
+    serde_json::from_str(contents)
+}
@@ -1,10 +1,14 @@
 //! Parser for the unit-graph file.
-use std::collections::HashSet;
+use std::{
+    collections::{BTreeMap, HashSet},
+    num::NonZeroUsize,
+};
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     artifact::{Artifact, ArtifactType},
+    constraints::matches,
     timings::node_type,
     PackageId,
 };
@@ -12,19 +16,38 @@ use crate::{
 /// 0-based index of Unit in `units` array of unit graph.
 type UnitIndex = usize;
 
-#[derive(Clone, Debug, Deserialize, Hash, PartialEq, Eq)]
+#[derive(Clone, Debug, Serialize, Deserialize, Hash, PartialEq, Eq)]
 pub(crate) struct Dependency {
     index: UnitIndex,
+    #[serde(default)]
+    pub(crate) extern_crate_name: Option<String>,
 }
 
-#[derive(Clone, Debug, Deserialize, Hash, PartialEq, Eq)]
+#[derive(Clone, Debug, Serialize, Deserialize, Hash, PartialEq, Eq)]
 pub(crate) struct Unit {
     pub(crate) pkg_id: PackageId,
     pub(crate) target: super::timings::Target,
     pub(crate) mode: super::timings::BuildMode,
+    /// Target triple this unit is built for; `None` for host units (or if the build didn't specify a target).
+    #[serde(default)]
+    pub(crate) platform: Option<String>,
+    #[serde(default)]
+    pub(crate) features: Vec<String>,
+    #[serde(default)]
+    pub(crate) profile: Option<Profile>,
     pub(crate) dependencies: Vec<Dependency>,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize, Hash, PartialEq, Eq)]
+pub(crate) struct Profile {
+    pub(crate) name: String,
+    /// Set explicitly in the profile; Cargo picks a default otherwise.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) codegen_units: Option<NonZeroUsize>,
+    #[serde(default)]
+    pub(crate) incremental: bool,
+}
+
 pub(crate) struct ArtifactUnit {
     pub(crate) artifact: Artifact,
     pub(crate) dependencies: HashSet<Artifact>,
@@ -71,7 +94,210 @@ pub(crate) fn unit_graph_to_artifacts(graph: UnitGraph) -> Vec<ArtifactUnit> {
     }
     ret
 }
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct UnitGraph {
     pub(crate) units: Vec<Unit>,
+    #[serde(default)]
+    pub(crate) roots: Vec<UnitIndex>,
+}
+
+/// Selection of units that a particular build would compile, for unit graphs captured with `--all-features` or
+/// for multiple targets.
+#[derive(Clone, Debug, Default)]
+pub struct UnitGraphFilter {
+    /// Target triple of the build. Units built for other targets are dropped.
+    pub platform: Option<String>,
+    /// Features (as `package/feature` pairs) that are not enabled in the build. The unit graph doesn't record what
+    /// a feature does, so only the features enabling optional dependencies can be pruned.
+    pub disabled_features: Vec<String>,
+}
+
+impl UnitGraphFilter {
+    fn is_disabled(&self, unit: &Unit, dependency: &Dependency) -> bool {
+        let Some(extern_crate_name) = dependency.extern_crate_name.as_ref() else {
+            return false;
+        };
+        self.disabled_features.iter().any(|disabled| {
+            let Some((package, feature)) = disabled.rsplit_once('/') else {
+                return false;
+            };
+            matches(&unit.pkg_id, package)
+                && unit.features.iter().any(|enabled| enabled == feature)
+                && feature.replace('-', "_") == *extern_crate_name
+        })
+    }
+}
+
+impl UnitGraph {
+    /// Number of codegen units rustc splits the codegen of each library into: as set in its profile, unless
+    /// `overrides` (pairs of profile name and count) say otherwise, or Cargo's default (256 for incremental builds,
+    /// 16 otherwise).
+    pub fn codegen_units(&self, overrides: &[(String, usize)]) -> BTreeMap<Artifact, usize> {
+        self.units
+            .iter()
+            .filter(|unit| node_type(&unit.mode, &unit.target) == ArtifactType::Metadata)
+            .map(|unit| {
+                let profile = unit.profile.as_ref();
+                let count = profile
+                    .and_then(|profile| {
+                        overrides
+                            .iter()
+                            .find(|(name, _)| *name == profile.name)
+                            .map(|(_, count)| *count)
+                            .or(profile.codegen_units.map(NonZeroUsize::get))
+                    })
+                    .unwrap_or(if profile.is_some_and(|profile| profile.incremental) {
+                        256
+                    } else {
+                        16
+                    });
+                let artifact = Artifact {
+                    typ: ArtifactType::Codegen,
+                    package_id: unit.pkg_id.clone(),
+                };
+                (artifact, count)
+            })
+            .collect()
+    }
+
+    /// Drops units that wouldn't be compiled by a build selected with `filter`, along with everything that is no
+    /// longer reachable from roots of the graph.
+    pub fn filtered(self, filter: &UnitGraphFilter) -> UnitGraph {
+        // Without a platform to simulate, units of all platforms are built.
+        let is_kept = |unit: &Unit| {
+            filter.platform.is_none()
+                || unit.platform.is_none()
+                || unit.platform.as_ref() == filter.platform.as_ref()
+        };
+        let roots: Vec<UnitIndex> = if self.roots.is_empty() {
+            (0..self.units.len()).collect()
+        } else {
+            self.roots.clone()
+        };
+        let mut reachable = vec![false; self.units.len()];
+        let mut stack: Vec<UnitIndex> = roots
+            .into_iter()
+            .filter(|root| is_kept(&self.units[*root]))
+            .collect();
+        while let Some(index) = stack.pop() {
+            if std::mem::replace(&mut reachable[index], true) {
+                continue;
+            }
+            let unit = &self.units[index];
+            stack.extend(
+                unit.dependencies
+                    .iter()
+                    .filter(|dep| is_kept(&self.units[dep.index]) && !filter.is_disabled(unit, dep))
+                    .map(|dep| dep.index),
+            );
+        }
+        let new_indices: BTreeMap<UnitIndex, UnitIndex> = (0..self.units.len())
+            .filter(|index| reachable[*index])
+            .enumerate()
+            .map(|(new_index, old_index)| (old_index, new_index))
+            .collect();
+        let units = self
+            .units
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| reachable[*index])
+            .map(|(_, unit)| Unit {
+                dependencies: unit
+                    .dependencies
+                    .iter()
+                    .filter(|dep| !filter.is_disabled(unit, dep))
+                    .filter_map(|dep| {
+                        let index = *new_indices.get(&dep.index)?;
+                        Some(Dependency {
+                            index,
+                            ..dep.clone()
+                        })
+                    })
+                    .collect(),
+                ..unit.clone()
+            })
+            .collect();
+        let roots = self
+            .roots
+            .iter()
+            .filter_map(|root| new_indices.get(root).copied())
+            .collect();
+        UnitGraph { units, roots }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filtering_prunes_other_platforms_and_optional_deps() {
+        let unit = |name: &str,
+                    platform: Option<&str>,
+                    features: &[&str],
+                    deps: &[(usize, &str)]| {
+            serde_json::json!({
+                "pkg_id": format!("{name} 0.1.0"),
+                "target": { "name": name, "crate_types": ["lib"] },
+                "mode": "build",
+                "platform": platform,
+                "features": features,
+                "dependencies": deps
+                    .iter()
+                    .map(|(index, name)| serde_json::json!({ "index": index, "extern_crate_name": name }))
+                    .collect::<Vec<_>>(),
+            })
+        };
+        let graph: UnitGraph = serde_json::from_value(serde_json::json!({
+            "units": [
+                unit("app", Some("x86_64-unknown-linux-gnu"), &["serde"], &[(1, "serde"), (2, "libc"), (3, "winapi")]),
+                unit("serde", Some("x86_64-unknown-linux-gnu"), &[], &[]),
+                unit("libc", Some("x86_64-unknown-linux-gnu"), &[], &[]),
+                unit("winapi", Some("x86_64-pc-windows-msvc"), &[], &[]),
+                unit("app", Some("x86_64-pc-windows-msvc"), &[], &[(3, "winapi")]),
+            ],
+            "roots": [0, 4],
+        }))
+        .unwrap();
+        let filtered = graph.filtered(&UnitGraphFilter {
+            platform: Some("x86_64-unknown-linux-gnu".into()),
+            disabled_features: vec!["app/serde".into()],
+        });
+        let names: Vec<_> = filtered
+            .units
+            .iter()
+            .map(|unit| unit.pkg_id.as_str())
+            .collect();
+        assert_eq!(names, ["app 0.1.0", "libc 0.1.0"]);
+        assert_eq!(filtered.units[0].dependencies.len(), 1);
+        assert_eq!(filtered.units[0].dependencies[0].index, 1);
+        assert_eq!(filtered.roots, [0]);
+    }
+
+    #[test]
+    fn filtering_without_platform_keeps_all_platforms() {
+        let graph: UnitGraph = serde_json::from_value(serde_json::json!({
+            "units": [
+                {
+                    "pkg_id": "app 0.1.0",
+                    "target": { "name": "app", "crate_types": ["bin"] },
+                    "mode": "build",
+                    "platform": "x86_64-unknown-linux-gnu",
+                    "dependencies": [{ "index": 1, "extern_crate_name": "libc" }],
+                },
+                {
+                    "pkg_id": "libc 0.1.0",
+                    "target": { "name": "libc", "crate_types": ["lib"] },
+                    "mode": "build",
+                    "platform": "x86_64-unknown-linux-gnu",
+                    "dependencies": [],
+                },
+            ],
+            "roots": [0],
+        }))
+        .unwrap();
+        let filtered = graph.filtered(&UnitGraphFilter::default());
+        assert_eq!(filtered.units.len(), 2);
+        assert_eq!(filtered.roots, [0]);
+    }
 }
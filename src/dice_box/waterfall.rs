@@ -0,0 +1,200 @@
+//! Makespan decomposition into sequential segments.
+//!
+//! Starting from the task that finished last, we walk back through the simulated schedule, at each step moving to
+//! whatever the current task had to wait for - either its last dependency to finish or a core to free up. The
+//! result is a chain of segments spanning the whole build, which answers the "where did the time go" question.
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use anyhow::Result;
+
+use crate::{artifact::Artifact, dependency_queue::DependencyQueueBuilder, runner::ScheduledTask};
+
+/// A single segment of the makespan, with times in milliseconds.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Segment {
+    /// Artifact that was being built, or `None` if nothing on the critical chain was running.
+    pub artifact: Option<Artifact>,
+    pub start: u64,
+    pub end: u64,
+}
+
+pub fn waterfall(schedule: &[ScheduledTask], deps: &DependencyQueueBuilder) -> Vec<Segment> {
+    let tasks: BTreeMap<&Artifact, &ScheduledTask> =
+        schedule.iter().map(|task| (&task.artifact, task)).collect();
+    let mut segments = vec![];
+    let mut current = schedule.iter().max_by_key(|task| task.end);
+    while let Some(task) = current {
+        segments.push(Segment {
+            artifact: Some(task.artifact.clone()),
+            start: task.start,
+            end: task.end,
+        });
+        if task.start == 0 {
+            break;
+        }
+        let last_dependency = deps
+            .dep_map
+            .get(&task.artifact)
+            .into_iter()
+            .flatten()
+            .filter_map(|dep| tasks.get(dep).copied())
+            .max_by_key(|dep| dep.end);
+        // If the task didn't start right after its dependencies were done, it must've been waiting for a core.
+        // Units that took no time and started along with it (the task itself among them) didn't hold the core.
+        let freed_core = || {
+            schedule
+                .iter()
+                .filter(|other| other.end == task.start && other.start < task.start)
+                .max_by_key(|other| other.end - other.start)
+        };
+        current = match last_dependency {
+            Some(dep) if dep.end == task.start => Some(dep),
+            _ => freed_core().or(last_dependency),
+        };
+        let idle_since = current.map_or(0, |task| task.end);
+        if idle_since < task.start {
+            segments.push(Segment {
+                artifact: None,
+                start: idle_since,
+                end: task.start,
+            });
+        }
+    }
+    segments.reverse();
+    segments
+}
+
+pub fn write_csv(segments: &[Segment], f: &mut impl Write) -> Result<()> {
+    writeln!(f, "start,end,duration,package_id,artifact_type")?;
+    for segment in segments {
+        let (package_id, typ) = segment
+            .artifact
+            .as_ref()
+            .map(|artifact| (artifact.package_id.clone(), format!("{:?}", artifact.typ)))
+            .unwrap_or_else(|| ("".into(), "Idle".into()));
+        writeln!(
+            f,
+            "{:.3},{:.3},{:.3},\"{}\",{}",
+            segment.start as f64 / 1000.,
+            segment.end as f64 / 1000.,
+            (segment.end - segment.start) as f64 / 1000.,
+            package_id.replace('"', "\"\""),
+            typ
+        )?;
+    }
+    Ok(())
+}
+
+pub fn write_svg(segments: &[Segment], f: &mut impl Write) -> Result<()> {
+    const ROW_HEIGHT: u64 = 20;
+    const WIDTH: f64 = 1000.;
+    const LABEL_WIDTH: f64 = 300.;
+    let total = segments.last().map_or(1, |segment| segment.end.max(1)) as f64;
+    let scale = WIDTH / total;
+    let height = ROW_HEIGHT * segments.len() as u64;
+    writeln!(
+        f,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" font-family="sans-serif" font-size="12">"#,
+        LABEL_WIDTH + WIDTH + 100.,
+        height
+    )?;
+    for (row, segment) in segments.iter().enumerate() {
+        let y = row as u64 * ROW_HEIGHT;
+        let (label, color) = match &segment.artifact {
+            Some(artifact) => (artifact.to_string(), "#95cce8"),
+            None => ("(idle)".to_owned(), "#e0e0e0"),
+        };
+        let x = LABEL_WIDTH + segment.start as f64 * scale;
+        let width = ((segment.end - segment.start) as f64 * scale).max(1.);
+        writeln!(
+            f,
+            r#"  <text x="{:.1}" y="{}" text-anchor="end">{}</text>"#,
+            LABEL_WIDTH - 5.,
+            y + ROW_HEIGHT - 6,
+            label.replace('&', "&amp;").replace('<', "&lt;")
+        )?;
+        writeln!(
+            f,
+            r#"  <rect x="{x:.1}" y="{}" width="{width:.1}" height="{}" fill="{color}"/>"#,
+            y + 2,
+            ROW_HEIGHT - 4
+        )?;
+        writeln!(
+            f,
+            r#"  <text x="{:.1}" y="{}">{:.1}s</text>"#,
+            x + width + 5.,
+            y + ROW_HEIGHT - 6,
+            (segment.end - segment.start) as f64 / 1000.
+        )?;
+    }
+    writeln!(f, "</svg>")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::artifact::ArtifactType;
+
+    fn artifact(package_id: &str) -> Artifact {
+        Artifact {
+            typ: ArtifactType::Link,
+            package_id: package_id.into(),
+        }
+    }
+
+    fn task(package_id: &str, start: u64, end: u64) -> ScheduledTask {
+        ScheduledTask {
+            artifact: artifact(package_id),
+            start,
+            end,
+        }
+    }
+
+    fn segment(package_id: Option<&str>, start: u64, end: u64) -> Segment {
+        Segment {
+            artifact: package_id.map(artifact),
+            start,
+            end,
+        }
+    }
+
+    #[test]
+    fn waterfall_follows_dependencies_across_idle_gaps() {
+        let mut deps = DependencyQueueBuilder::new();
+        deps.queue(artifact("a"), []);
+        deps.queue(artifact("z"), [artifact("a")]);
+        deps.queue(artifact("b"), [artifact("z")]);
+        // z takes no time, and each unit is dispatched a while after its dependency is done.
+        let schedule = [task("a", 0, 10), task("z", 15, 15), task("b", 20, 30)];
+        assert_eq!(
+            waterfall(&schedule, &deps),
+            [
+                segment(Some("a"), 0, 10),
+                segment(None, 10, 15),
+                segment(Some("z"), 15, 15),
+                segment(None, 15, 20),
+                segment(Some("b"), 20, 30),
+            ]
+        );
+    }
+
+    #[test]
+    fn waterfall_follows_units_that_freed_a_core() {
+        let mut deps = DependencyQueueBuilder::new();
+        deps.queue(artifact("a"), []);
+        deps.queue(artifact("b"), []);
+        deps.queue(artifact("c"), [artifact("a")]);
+        // On a single thread, c was ready at 10 but had to wait for b to free the core.
+        let schedule = [task("a", 0, 10), task("b", 10, 25), task("c", 25, 30)];
+        assert_eq!(
+            waterfall(&schedule, &deps),
+            [
+                segment(Some("a"), 0, 10),
+                segment(Some("b"), 10, 25),
+                segment(Some("c"), 25, 30),
+            ]
+        );
+    }
+}
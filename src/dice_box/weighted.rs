@@ -0,0 +1,269 @@
+//! A scheduler scoring ready units by a weighted sum of several signals, and a search for the weights that work best
+//! for a given project.
+//!
+//! Each signal is normalized to `0..=1` by dividing it by its largest value in the graph, so that weights are
+//! comparable with each other. Weights can be given on the command line or in a JSON file:
+//! ```json
+//! { "duration": 0.0, "transitive-dependants": 0.5, "critical-path": 1.0, "unlocked": 0.25 }
+//! ```
+//! Weights that are left out are 0.
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use tabled::Tabled;
+
+use crate::{
+    artifact::Artifact,
+    critical_path::{critical_path_lengths, duration_ms},
+    dependency_queue::{reverse_dependencies, DependencyQueueBuilder},
+    error::InputError,
+    hints::{HintProvider, SchedulerContext},
+    rng::Rng,
+    runner::{Duration, Runner},
+    timings::TimingInfo,
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Weights {
+    /// Time it takes to build the unit itself.
+    pub duration: f64,
+    /// Number of units that depend on the unit, directly or not.
+    pub transitive_dependants: f64,
+    /// Length of the longest chain of work from the unit to the end of the build.
+    pub critical_path: f64,
+    /// Number of units directly waiting on the unit, i.e. how much parallelism finishing it can unlock.
+    pub unlocked: f64,
+}
+
+impl Weights {
+    /// Sets a single weight, by its name in the weights file.
+    pub fn set(&mut self, name: &str, weight: f64) -> Result<(), String> {
+        let slot = match name {
+            "duration" => &mut self.duration,
+            "transitive-dependants" => &mut self.transitive_dependants,
+            "critical-path" => &mut self.critical_path,
+            "unlocked" => &mut self.unlocked,
+            _ => return Err(format!("unknown signal `{name}`")),
+        };
+        *slot = weight;
+        Ok(())
+    }
+
+    fn as_array(&self) -> [f64; 4] {
+        [
+            self.duration,
+            self.transitive_dependants,
+            self.critical_path,
+            self.unlocked,
+        ]
+    }
+
+    fn from_array([duration, transitive_dependants, critical_path, unlocked]: [f64; 4]) -> Self {
+        Self {
+            duration,
+            transitive_dependants,
+            critical_path,
+            unlocked,
+        }
+    }
+}
+
+impl fmt::Display for Weights {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "duration={},transitive-dependants={},critical-path={},unlocked={}",
+            self.duration, self.transitive_dependants, self.critical_path, self.unlocked
+        )
+    }
+}
+
+/// Deserialize weights from contents of a weights file.
+pub fn parse_weights(contents: &str) -> serde_json::Result<Weights> {
+    serde_json::from_str(contents)
+}
+
+/// Normalized signals of every unit, in the order of [Weights] fields.
+fn signals(
+    dependencies: &DependencyQueueBuilder,
+    timings: &BTreeMap<Artifact, TimingInfo>,
+) -> BTreeMap<Artifact, [f64; 4]> {
+    let lengths = critical_path_lengths(dependencies, timings);
+    let dependants = reverse_dependencies(dependencies);
+    let raw: BTreeMap<Artifact, [f64; 4]> = dependencies
+        .dep_map
+        .keys()
+        .map(|artifact| {
+            let signals = [
+                duration_ms(timings, artifact) as f64,
+                dependants.get(artifact).map_or(0, |d| d.len()) as f64,
+                lengths.get(artifact).copied().unwrap_or_default() as f64,
+                dependencies
+                    .reverse_dep_map
+                    .get(artifact)
+                    .map_or(0, |d| d.len()) as f64,
+            ];
+            (artifact.clone(), signals)
+        })
+        .collect();
+    let mut max = [0f64; 4];
+    for signals in raw.values() {
+        for (max, signal) in max.iter_mut().zip(signals) {
+            *max = max.max(*signal);
+        }
+    }
+    raw.into_iter()
+        .map(|(artifact, mut signals)| {
+            for (signal, max) in signals.iter_mut().zip(max) {
+                if max > 0. {
+                    *signal /= max;
+                }
+            }
+            (artifact, signals)
+        })
+        .collect()
+}
+
+/// Picks the ready unit with the highest weighted sum of its signals.
+#[derive(Debug)]
+pub struct WeightedHints {
+    weights: Weights,
+    scores: BTreeMap<Artifact, f64>,
+}
+
+impl WeightedHints {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(
+        dependencies: &DependencyQueueBuilder,
+        timings: &BTreeMap<Artifact, TimingInfo>,
+        weights: Weights,
+    ) -> Box<dyn HintProvider> {
+        Box::new(Self::with_signals(&signals(dependencies, timings), weights))
+    }
+
+    fn with_signals(signals: &BTreeMap<Artifact, [f64; 4]>, weights: Weights) -> Self {
+        let scores = signals
+            .iter()
+            .map(|(artifact, signals)| {
+                let score = signals
+                    .iter()
+                    .zip(weights.as_array())
+                    .map(|(signal, weight)| signal * weight)
+                    .sum();
+                (artifact.clone(), score)
+            })
+            .collect();
+        Self { weights, scores }
+    }
+}
+
+impl HintProvider for WeightedHints {
+    fn suggest_next<'a>(
+        &mut self,
+        timings: &[&'a Artifact],
+        _context: &SchedulerContext,
+    ) -> Option<&'a Artifact> {
+        timings
+            .iter()
+            .max_by(|a, b| {
+                let score =
+                    |artifact: &Artifact| self.scores.get(artifact).copied().unwrap_or_default();
+                score(a).total_cmp(&score(b))
+            })
+            .cloned()
+    }
+
+    fn label(&self) -> String {
+        format!("Weighted ({})", self.weights)
+    }
+
+    fn score(&self, artifact: &Artifact) -> Option<f64> {
+        self.scores.get(artifact).copied()
+    }
+}
+
+/// How to pick the weights to try when tuning.
+#[derive(Clone, Copy, Debug)]
+pub enum WeightSearch {
+    /// Every combination of weights from `0..=1` in steps of `1 / steps`.
+    Grid { steps: usize },
+    /// A number of weights drawn uniformly from `0..=1`.
+    Random { samples: usize, seed: u64 },
+}
+
+#[derive(Clone, Debug, PartialEq, Tabled)]
+pub struct WeightsScore {
+    #[tabled(display_with = "display_weight")]
+    pub duration: f64,
+    #[tabled(display_with = "display_weight")]
+    pub transitive_dependants: f64,
+    #[tabled(display_with = "display_weight")]
+    pub critical_path: f64,
+    #[tabled(display_with = "display_weight")]
+    pub unlocked: f64,
+    pub makespan: Duration,
+}
+
+impl WeightsScore {
+    pub fn weights(&self) -> Weights {
+        Weights::from_array([
+            self.duration,
+            self.transitive_dependants,
+            self.critical_path,
+            self.unlocked,
+        ])
+    }
+}
+
+fn display_weight(weight: &f64) -> String {
+    format!("{weight:.2}")
+}
+
+/// Simulates [WeightedHints] with each of the weights picked by `mode`, and returns the results from the shortest
+/// makespan to the longest.
+pub fn tune_weights(
+    deps: &DependencyQueueBuilder,
+    timings: &BTreeMap<Artifact, TimingInfo>,
+    num_threads: usize,
+    mode: WeightSearch,
+) -> Result<Vec<WeightsScore>, InputError> {
+    let candidates: Vec<[f64; 4]> = match mode {
+        WeightSearch::Grid { steps } => {
+            let steps = steps.max(1);
+            let values: Vec<f64> = (0..=steps).map(|step| step as f64 / steps as f64).collect();
+            itertools::iproduct!(&values, &values, &values, &values)
+                .map(|(a, b, c, d)| [*a, *b, *c, *d])
+                // All-zero weights are a plain queue order, which the other weights already cover when tied.
+                .filter(|weights| weights.iter().any(|weight| *weight > 0.))
+                .collect()
+        }
+        WeightSearch::Random { samples, seed } => {
+            let mut rng = Rng::new(seed);
+            (0..samples)
+                .map(|_| std::array::from_fn(|_| rng.next_u64() as f64 / u64::MAX as f64))
+                .collect()
+        }
+    };
+    // Signals don't depend on the weights, so compute them once and score units for each candidate.
+    let signals = signals(deps, timings);
+    let mut scores: Vec<WeightsScore> = candidates
+        .into_iter()
+        .map(|weights| {
+            let weights = Weights::from_array(weights);
+            let hints = Box::new(WeightedHints::with_signals(&signals, weights));
+            let mut runner = Runner::new(deps.clone().finish(hints), timings.clone(), num_threads);
+            let (result, _) = runner.calculate()?;
+            Ok(WeightsScore {
+                duration: weights.duration,
+                transitive_dependants: weights.transitive_dependants,
+                critical_path: weights.critical_path,
+                unlocked: weights.unlocked,
+                makespan: result.makespan,
+            })
+        })
+        .collect::<Result<_, InputError>>()?;
+    scores.sort_by_key(|score| score.makespan);
+    Ok(scores)
+}
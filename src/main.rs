@@ -1,48 +1,2175 @@
-use clap::Parser;
-use dice_box::{Cli, Runner};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use clap::{Parser, ValueEnum};
+use dice_box::{Artifact, Cli, Command, Makespan, Runner, TimingInfo};
 use tabled::Table;
 
 fn main() {
     env_logger::init();
-    let opts = Cli::parse();
-
-    let timings_contents = std::fs::read_to_string(&opts.timings_file).unwrap();
-    let timings = dice_box::parse(timings_contents);
-    let unit_graph = std::fs::read_to_string(&opts.unit_graph_file).unwrap();
-    let unit_graph: dice_box::UnitGraph = serde_json::from_str(&unit_graph).unwrap();
-    let dependency_queue = dice_box::create_dependency_queue(unit_graph);
-    let dep_graph_n = {
-        let hints = dice_box::NHintsProvider::new(&dependency_queue, &timings);
-        dependency_queue.clone().finish(hints)
-    };
-    let dep_graph = {
-        let hints = dice_box::CargoHints::new(&dependency_queue);
-        dependency_queue.clone().finish(hints)
-    };
-    let optimal_dep_graph = {
-        let hints = dice_box::CargoHints::new(&dependency_queue);
-        dependency_queue.finish(hints)
-    };
-    let mut scenarios = [
-        dice_box::Runner::new(dep_graph, timings.clone(), opts.num_threads),
-        dice_box::Runner::new(dep_graph_n, timings.clone(), opts.num_threads),
-        dice_box::Runner::new(optimal_dep_graph, timings, u8::MAX as usize)
-            .with_label("Optimal build schedule (current Cargo algo)".into()),
-    ];
-    let (results, timings): (Vec<_>, Vec<_>) = scenarios
-        .iter_mut()
-        .map(|runner| runner.calculate())
-        .unzip();
-    let results = Table::new(results).to_string();
-    println!("{}", results);
+    if let Err(err) = run(Cli::parse()) {
+        exit_with(err);
+    }
+}
+
+fn run(opts: Cli) -> Result<()> {
+    if opts.num_threads == 0 {
+        bail!("--num-threads must be at least 1");
+    }
+    if opts.max_concurrent_links == Some(0) {
+        bail!("--max-concurrent-links must be at least 1, or link units could never run");
+    }
+    let mut build = Build::load(&opts)?;
+    if let Some(Command::Deps {
+        package,
+        reverse,
+        shared_with,
+        chain_to,
+    }) = &opts.command
+    {
+        return deps(
+            &build,
+            package,
+            *reverse,
+            shared_with.as_deref(),
+            chain_to.as_deref(),
+        );
+    }
+    let (registry, weights) = registry(&opts)?;
+    if let (Some(Command::Anonymize { output, .. }), Some(anonymized)) =
+        (&opts.command, build.anonymized.take())
+    {
+        return anonymize(&opts, &build, &registry, anonymized, output);
+    }
+    match &opts.command {
+        Some(Command::Advise { limit }) => advise(&opts, &build, &registry, *limit),
+        Some(Command::CriticalPath) => critical_path(&opts, &build, &registry),
+        Some(Command::Optimize { reduction, limit }) => {
+            optimize(&opts, &build, &registry, *reduction, *limit)
+        }
+        Some(Command::TuneWeights {
+            steps,
+            samples,
+            seed,
+            limit,
+        }) => tune_weights(&opts, &build, *steps, *samples, *seed, *limit),
+        Some(Command::TuneNHints { n, limit }) => tune_n_hints(&opts, &build, n, *limit),
+        Some(Command::Search {
+            population,
+            generations,
+            seed,
+            output,
+        }) => {
+            let result = dice_box::search(
+                &build.dependency_queue,
+                &build.timings,
+                &registry,
+                opts.num_threads,
+                *population,
+                *generations,
+                *seed,
+            )?;
+            search(build, result, output)
+        }
+        Some(Command::Anneal {
+            iterations,
+            cooling,
+            seed,
+            output,
+        }) => {
+            let result = dice_box::anneal(
+                &build.dependency_queue,
+                &build.timings,
+                &registry,
+                opts.num_threads,
+                *iterations,
+                *cooling,
+                *seed,
+            )?;
+            search(build, result, output)
+        }
+        Some(Command::Colocate {
+            jobs,
+            iterations,
+            seed,
+        }) => colocate(&opts, &build, &registry, jobs, *iterations, *seed),
+        Some(Command::Diff {
+            unit_graph,
+            timings,
+        }) => diff(&opts, &build, &registry, unit_graph, timings.as_deref()),
+        Some(Command::WhatIf { change }) => what_if(&opts, &build, &registry, change),
+        _ => simulate(&opts, &build, &registry, weights),
+    }
+}
+
+/// The build all subcommands work on: its dependency graph and timings, with the options that change them applied.
+struct Build {
+    timings_contents: String,
+    cache_key: u64,
+    timings: BTreeMap<Artifact, TimingInfo>,
+    dependency_queue: dice_box::DependencyQueueBuilder,
+    filter: dice_box::UnitGraphFilter,
+    environment: dice_box::Environment,
+    constraints: dice_box::Constraints,
+    events: Vec<dice_box::ExternalEvent>,
+    codegen_units: Option<BTreeMap<Artifact, usize>>,
+    /// Anonymized timings and unit graph, for the `anonymize` subcommand.
+    anonymized: Option<(String, dice_box::UnitGraph, dice_box::Anonymizer)>,
+}
+
+impl Build {
+    fn load(opts: &Cli) -> Result<Self> {
+        let timings_contents = read(&opts.timings_file)?;
+        let unit_graph_contents = read(&opts.unit_graph_file)?;
+        let constraints_contents = opts.constraints.as_deref().map(read).transpose()?;
+        let transforms_contents = opts.transforms.as_deref().map(read).transpose()?;
+        let cache_key = {
+            let mut hasher = DefaultHasher::new();
+            timings_contents.hash(&mut hasher);
+            unit_graph_contents.hash(&mut hasher);
+            constraints_contents.hash(&mut hasher);
+            transforms_contents.hash(&mut hasher);
+            opts.platform.hash(&mut hasher);
+            opts.disable_feature.hash(&mut hasher);
+            opts.min_duration.map(f64::to_bits).hash(&mut hasher);
+            opts.max_duration.map(f64::to_bits).hash(&mut hasher);
+            opts.dirty.hash(&mut hasher);
+            hasher.finish()
+        };
+        let (mut timings, anomalies) =
+            parse_input(&opts.timings_file, &timings_contents, |contents: &str| {
+                dice_box::parse_with_anomalies(contents.to_owned())
+            })?;
+        if !anomalies.is_empty() {
+            eprintln!(
+                "Warning: repaired {} timings that could not have been measured:\n{}",
+                anomalies.len(),
+                Table::new(&anomalies)
+            );
+        }
+        let unit_graph: dice_box::UnitGraph =
+            parse_input(&opts.unit_graph_file, &unit_graph_contents, |contents| {
+                serde_json::from_str(contents)
+            })?;
+        let filter = dice_box::UnitGraphFilter {
+            platform: opts.platform.clone(),
+            disabled_features: opts.disable_feature.clone(),
+        };
+        let unit_graph = unit_graph.filtered(&filter);
+        let environment = opts
+            .environment
+            .as_ref()
+            .map(|path| load(path, dice_box::parse_environment))
+            .transpose()?
+            .unwrap_or_default()
+            .with_unit_graph(&unit_graph);
+        let anonymized = match &opts.command {
+            Some(dice_box::Command::Anonymize {
+                bucket, symbolic, ..
+            }) => {
+                let anonymizer = dice_box::Anonymizer::new(&unit_graph, *bucket, *symbolic);
+                Some((
+                    anonymizer.timings(&timings_contents),
+                    anonymizer.unit_graph(unit_graph.clone()),
+                    anonymizer,
+                ))
+            }
+            _ => None,
+        };
+        let codegen_units = opts
+            .parallel_codegen
+            .then(|| unit_graph.codegen_units(&opts.codegen_units));
+        let mut dependency_queue = dice_box::create_dependency_queue(unit_graph);
+        // Timings may include units that were pruned from the graph.
+        timings.retain(|artifact, _| dependency_queue.contains(artifact));
+        let constraints = opts
+            .constraints
+            .as_deref()
+            .zip(constraints_contents.as_deref())
+            .map(|(path, contents)| parse_input(path, contents, dice_box::parse_constraints))
+            .transpose()?
+            .unwrap_or_default();
+        if let Some(path) = &opts.constraints {
+            constraints
+                .check(opts.num_threads)
+                .and_then(|()| constraints.add_orderings(&mut dependency_queue))
+                .with_context(|| path.display().to_string())?;
+        }
+        if let Some((path, contents)) = opts
+            .transforms
+            .as_deref()
+            .zip(transforms_contents.as_deref())
+        {
+            let rewrites = parse_input(path, contents, dice_box::parse_transforms)?;
+            dice_box::apply_transforms(&rewrites, &mut dependency_queue, &mut timings)
+                .with_context(|| path.display().to_string())?;
+        }
+        if opts.min_duration.is_some() || opts.max_duration.is_some() {
+            dice_box::Rewrite::Clamp {
+                package: None,
+                min: opts.min_duration,
+                max: opts.max_duration,
+            }
+            .apply(&mut dependency_queue, &mut timings)?;
+        }
+        if !opts.dirty.is_empty() {
+            if let Some(package) = opts
+                .dirty
+                .iter()
+                .find(|package| dice_box::package_units(&dependency_queue, package).is_empty())
+            {
+                bail!("no package `{package}` in the build");
+            }
+            dice_box::Rewrite::Dirty {
+                packages: opts.dirty.clone(),
+            }
+            .apply(&mut dependency_queue, &mut timings)?;
+        }
+        let mut events = opts
+            .events
+            .as_ref()
+            .map(|path| load(path, dice_box::parse_events))
+            .transpose()?
+            .unwrap_or_default();
+        if let Some(timeline) = &opts.core_timeline {
+            events.extend(dice_box::core_timeline(&timeline.0, opts.num_threads));
+        }
+        if let Some(cache_dir) = &opts.cache_dir {
+            let preprocessed = dice_box::Preprocessed::load_or_compute(
+                cache_dir,
+                cache_key,
+                &dependency_queue,
+                &timings,
+            )?;
+            dependency_queue.set_preprocessed(preprocessed);
+        }
+        Ok(Build {
+            timings_contents,
+            cache_key,
+            timings,
+            dependency_queue,
+            filter,
+            environment,
+            constraints,
+            events,
+            codegen_units,
+            anonymized,
+        })
+    }
+}
+
+fn deps(
+    build: &Build,
+    package: &str,
+    reverse: bool,
+    shared_with: Option<&str>,
+    chain_to: Option<&str>,
+) -> Result<()> {
+    let dependency_queue = &build.dependency_queue;
+    let units = dice_box::package_units(dependency_queue, package);
+    if units.is_empty() {
+        bail!("no package `{package}` in the build");
+    }
+    if let Some(other) = chain_to {
+        let other_units = dice_box::package_units(dependency_queue, other);
+        let chain = dice_box::shortest_chain(dependency_queue, &units, &other_units)
+            .or_else(|| dice_box::shortest_chain(dependency_queue, &other_units, &units));
+        match chain {
+            Some(chain) => {
+                for artifact in chain {
+                    println!("{artifact}");
+                }
+            }
+            None => println!("Neither of {package} and {other} depends on the other"),
+        }
+        return Ok(());
+    }
+    let (description, closure) = match (reverse, shared_with) {
+        (true, _) => (
+            format!("Units depending on {package}"),
+            dice_box::transitive_dependants(dependency_queue, &units),
+        ),
+        (false, Some(other)) => (
+            format!("Dependencies shared by {package} and {other}"),
+            dice_box::shared_dependencies(
+                dependency_queue,
+                &units,
+                &dice_box::package_units(dependency_queue, other),
+            ),
+        ),
+        (false, None) => (
+            format!("Dependencies of {package}"),
+            dice_box::transitive_dependencies(dependency_queue, &units),
+        ),
+    };
+    println!("{description} ({}):", closure.len());
+    for artifact in closure {
+        println!("  {artifact}");
+    }
+    Ok(())
+}
+
+/// Registry of schedulers that subcommands can pick from by name, along with the weights of the weighted one.
+fn registry(opts: &Cli) -> Result<(dice_box::Registry, Option<dice_box::Weights>)> {
+    let mut registry = dice_box::Registry::builtin();
+    let fifo_seed = opts.fifo_seed;
+    registry.register(
+        "fifo",
+        move |deps: &dice_box::DependencyQueueBuilder, _: &_| {
+            dice_box::FifoHints::new(deps, fifo_seed)
+        },
+    );
+    let n_hints_params = opts.n_hints_params();
+    registry.register("n-hints", move |deps: &_, timings: &_| {
+        dice_box::NHintsProvider::with_params(deps, timings, n_hints_params)
+    });
+    let weights = (opts.weights.is_some() || !opts.weight.is_empty())
+        .then(|| -> Result<_> {
+            let mut weights = opts
+                .weights
+                .as_ref()
+                .map(|path| load(path, dice_box::parse_weights))
+                .transpose()?
+                .unwrap_or_default();
+            for (signal, weight) in &opts.weight {
+                weights.set(signal, *weight).map_err(anyhow::Error::msg)?;
+            }
+            Ok(weights)
+        })
+        .transpose()?;
+    if let Some(weights) = weights {
+        registry.register("weighted", move |deps: &_, timings: &_| {
+            dice_box::WeightedHints::new(deps, timings, weights)
+        });
+    }
+    Ok((registry, weights))
+}
+
+fn anonymize(
+    opts: &Cli,
+    build: &Build,
+    registry: &dice_box::Registry,
+    (anonymized_timings, anonymized_unit_graph, anonymizer): (
+        String,
+        dice_box::UnitGraph,
+        dice_box::Anonymizer,
+    ),
+    output: &Path,
+) -> Result<()> {
+    std::fs::create_dir_all(output)
+        .and_then(|()| std::fs::write(output.join("timings.json"), &anonymized_timings))
+        .and_then(|()| {
+            std::fs::write(
+                output.join("unit-graph.json"),
+                serde_json::to_string(&anonymized_unit_graph).unwrap(),
+            )
+        })
+        .with_context(|| format!("failed to write to {}", output.display()))?;
+    let anonymized_queue = dice_box::create_dependency_queue(anonymized_unit_graph);
+    let mut anonymized_timings = dice_box::parse(anonymized_timings)?;
+    anonymized_timings.retain(|artifact, _| anonymized_queue.contains(artifact));
+    let errors = dice_box::verify_bucketing(
+        registry,
+        (&build.dependency_queue, &build.timings),
+        (&anonymized_queue, &anonymized_timings),
+        anonymizer.critical_path_error(&build.dependency_queue, &build.timings),
+        opts.num_threads,
+    )?;
+    println!("Environment: {}", build.environment);
+    println!("{}", Table::new(errors));
+    Ok(())
+}
+
+fn advise(opts: &Cli, build: &Build, registry: &dice_box::Registry, limit: usize) -> Result<()> {
+    let advice = dice_box::advise(
+        &build.dependency_queue,
+        &build.timings,
+        registry.get("cargo").unwrap(),
+        opts.num_threads,
+        limit,
+    )?;
+    println!("Environment: {}", build.environment);
+    println!("{}", Table::new(advice));
+    Ok(())
+}
+
+fn critical_path(opts: &Cli, build: &Build, registry: &dice_box::Registry) -> Result<()> {
+    let resources = dice_box::Resources {
+        num_threads: opts.num_threads,
+        ..Default::default()
+    };
+    let schedule = dice_box::simulate(
+        &build.dependency_queue,
+        &build.timings,
+        registry.get("cargo").unwrap(),
+        &resources,
+    )?;
+    let makespan = dice_box::Duration::from_millis(
+        schedule
+            .iter()
+            .map(|task| task.end)
+            .max()
+            .unwrap_or_default(),
+    );
+    let segments =
+        dice_box::critical_path_segments(&build.dependency_queue, &build.timings, makespan);
+    let length = segments
+        .last()
+        .map_or(dice_box::Duration::default(), |last| {
+            last.start + last.duration
+        });
+    println!("Environment: {}", build.environment);
+    println!("{}", Table::new(&segments));
+    println!(
+        "Critical path: {length} ({:.1}% of the {makespan} makespan under Cargo's current algorithm)",
+        length.as_secs_f64() / makespan.as_secs_f64().max(f64::EPSILON) * 100.
+    );
+    Ok(())
+}
+
+fn optimize(
+    opts: &Cli,
+    build: &Build,
+    registry: &dice_box::Registry,
+    reduction: f64,
+    limit: usize,
+) -> Result<()> {
+    if !(0. ..=100.).contains(&reduction) {
+        bail!("--reduction must be between 0 and 100 percent, got {reduction}");
+    }
+    let payoffs = dice_box::rank_payoffs(
+        &build.dependency_queue,
+        &build.timings,
+        registry.get("cargo").unwrap(),
+        opts.num_threads,
+        reduction / 100.,
+        limit,
+    )?;
+    println!("Environment: {}", build.environment);
+    println!("{}", Table::new(payoffs));
+    Ok(())
+}
+
+fn tune_weights(
+    opts: &Cli,
+    build: &Build,
+    steps: usize,
+    samples: Option<usize>,
+    seed: u64,
+    limit: usize,
+) -> Result<()> {
+    let mode = match samples {
+        Some(samples) => dice_box::WeightSearch::Random { samples, seed },
+        None => dice_box::WeightSearch::Grid { steps },
+    };
+    let scores = dice_box::tune_weights(
+        &build.dependency_queue,
+        &build.timings,
+        opts.num_threads,
+        mode,
+    )?;
+    println!("Environment: {}", build.environment);
+    if let Some(best) = scores.first() {
+        println!(
+            "Best weights: {}",
+            serde_json::to_string(&best.weights()).unwrap()
+        );
+    }
+    println!("{}", Table::new(scores.into_iter().take(limit)));
+    Ok(())
+}
+
+fn tune_n_hints(opts: &Cli, build: &Build, n: &[usize], limit: usize) -> Result<()> {
+    let scores =
+        dice_box::tune_n_hints(&build.dependency_queue, &build.timings, opts.num_threads, n)?;
+    println!("Environment: {}", build.environment);
+    if let Some(best) = scores.first() {
+        let params = best.params();
+        let mut args = format!("--n-hints {}", params.n);
+        if params.tie_break != dice_box::NHintsTieBreak::default() {
+            let tie_break = params.tie_break.to_possible_value().unwrap();
+            args += &format!(" --n-hints-tie-break {}", tie_break.get_name());
+        }
+        if !params.bundle_codegen {
+            args += " --n-hints-separate-codegen";
+        }
+        println!("Best configuration: {params} ({args})");
+    }
+    println!("{}", Table::new(scores.into_iter().take(limit)));
+    Ok(())
+}
+
+/// Reports the best schedule found by the `search` or `anneal` subcommand, and writes it to `output`.
+fn search(build: Build, result: dice_box::SearchResult, output: &Path) -> Result<()> {
+    println!("Environment: {}", build.environment);
+    println!("Best schedule found: {}", result.makespan);
+    println!("{}", Table::new(result.gaps));
+    dice_box::Trace {
+        scenarios: vec![result.trace],
+        environment: Some(build.environment),
+    }
+    .write(output)
+    .with_context(|| format!("failed to write {}", output.display()))
+}
+
+fn colocate(
+    opts: &Cli,
+    build: &Build,
+    registry: &dice_box::Registry,
+    jobs: &[PathBuf],
+    iterations: usize,
+    seed: u64,
+) -> Result<()> {
+    let job = |label: &Path, deps, timings| dice_box::Job {
+        label: label.display().to_string(),
+        deps,
+        timings,
+    };
+    let jobs = [Ok(job(
+        &opts.timings_file,
+        build.dependency_queue.clone(),
+        build.timings.clone(),
+    ))]
+    .into_iter()
+    .chain(jobs.chunks(2).map(|files| {
+        let mut timings = load(&files[0], |contents: &str| {
+            dice_box::parse(contents.to_owned())
+        })?;
+        let unit_graph: dice_box::UnitGraph =
+            load(&files[1], |contents| serde_json::from_str(contents))?;
+        let deps = dice_box::create_dependency_queue(unit_graph.filtered(&build.filter));
+        timings.retain(|artifact, _| deps.contains(artifact));
+        Ok(job(&files[0], deps, timings))
+    }))
+    .collect::<Result<Vec<_>>>()?;
+    let allocation = dice_box::colocate(
+        &jobs,
+        opts.num_threads,
+        registry.get("cargo").unwrap(),
+        iterations,
+        seed,
+    )?;
+    println!("Environment: {}", build.environment);
+    println!("{}", Table::new(allocation));
+    Ok(())
+}
+
+/// Checks that schedulers picked with `--scheduler` are registered.
+fn check_schedulers(opts: &Cli, registry: &dice_box::Registry) -> Result<()> {
+    for name in &opts.scheduler {
+        if !registry.names().any(|registered| registered == name) {
+            bail!(
+                "unknown scheduler `{name}`, expected one of: {}",
+                registry.names().collect::<Vec<_>>().join(", ")
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Schedulers that builds compared by `diff` and `what-if`, the dev loop and the pipeline are simulated with.
+fn compared_schedulers(opts: &Cli, registry: &dice_box::Registry) -> Vec<String> {
+    if opts.scheduler.is_empty() {
+        registry.names().map(str::to_owned).collect()
+    } else {
+        opts.scheduler.clone()
+    }
+}
+
+fn diff(
+    opts: &Cli,
+    build: &Build,
+    registry: &dice_box::Registry,
+    unit_graph: &Path,
+    changed_timings: Option<&Path>,
+) -> Result<()> {
+    check_schedulers(opts, registry)?;
+    let changed_graph: dice_box::UnitGraph =
+        load(unit_graph, |contents| serde_json::from_str(contents))?;
+    let changed_deps = dice_box::create_dependency_queue(changed_graph.filtered(&build.filter));
+    let mut changed_timings = match changed_timings {
+        Some(path) => load(path, |contents: &str| dice_box::parse(contents.to_owned()))?,
+        None => build.timings.clone(),
+    };
+    changed_timings.retain(|artifact, _| changed_deps.contains(artifact));
+    let untimed: Vec<_> = changed_deps
+        .units()
+        .filter(|artifact| !changed_timings.contains_key(artifact))
+        .map(ToString::to_string)
+        .collect();
+    if !untimed.is_empty() {
+        bail!(
+            "{} units of {} have no timings, pass them with --timings:\n  {}",
+            untimed.len(),
+            unit_graph.display(),
+            untimed.join("\n  ")
+        );
+    }
+    println!("Environment: {}", build.environment);
+    println!(
+        "{}",
+        Table::new(dice_box::diff_graphs(
+            &build.dependency_queue,
+            &changed_deps
+        ))
+    );
+    println!(
+        "{}",
+        Table::new(dice_box::compare_graphs(
+            (&build.dependency_queue, &build.timings),
+            (&changed_deps, &changed_timings),
+            registry,
+            &compared_schedulers(opts, registry),
+            opts.num_threads,
+        )?)
+    );
+    Ok(())
+}
+
+fn what_if(
+    opts: &Cli,
+    build: &Build,
+    registry: &dice_box::Registry,
+    change: &dice_box::WhatIf,
+) -> Result<()> {
+    check_schedulers(opts, registry)?;
+    let Build {
+        dependency_queue,
+        timings,
+        environment,
+        ..
+    } = build;
+    let mut changed_deps = dependency_queue.clone();
+    let mut changed_timings = timings.clone();
+    match change {
+        dice_box::WhatIf::RemoveDep { from, to } => {
+            for package in [from, to] {
+                if dice_box::package_units(dependency_queue, package).is_empty() {
+                    bail!("no package `{package}` in the build");
+                }
+            }
+            dice_box::Rewrite::RemoveDependency {
+                dependant: from.clone(),
+                dependency: to.clone(),
+            }
+            .apply(&mut changed_deps, &mut changed_timings)?;
+        }
+        dice_box::WhatIf::Split {
+            package,
+            parts,
+            ratio,
+        } => {
+            if dice_box::package_units(dependency_queue, package).is_empty() {
+                bail!("no package `{package}` in the build");
+            }
+            if *parts < 2 {
+                bail!("a crate has to be split into at least two parts");
+            }
+            dice_box::Rewrite::SplitInto {
+                package: package.clone(),
+                ratio: ratio
+                    .as_ref()
+                    .map_or_else(|| vec![1.; *parts], |ratio| ratio.0.clone()),
+            }
+            .apply(&mut changed_deps, &mut changed_timings)?;
+        }
+        dice_box::WhatIf::Merge { into, package } => {
+            for package in [into, package] {
+                if dice_box::package_units(dependency_queue, package).is_empty() {
+                    bail!("no package `{package}` in the build");
+                }
+            }
+            dice_box::Rewrite::Merge {
+                package: package.clone(),
+                into: into.clone(),
+            }
+            .apply(&mut changed_deps, &mut changed_timings)?;
+        }
+        dice_box::WhatIf::Speedup { package, factor } => {
+            let units = dice_box::package_units(dependency_queue, package);
+            if units.is_empty() {
+                bail!("no package `{package}` in the build");
+            }
+            if *factor <= 0. {
+                bail!("the speedup factor must be positive, got {factor}");
+            }
+            for (artifact, timing) in changed_timings.iter_mut() {
+                if matches!(
+                    artifact.typ,
+                    dice_box::ArtifactType::Metadata | dice_box::ArtifactType::Codegen
+                ) && units.contains(artifact)
+                {
+                    timing.duration /= factor;
+                    if let Some(rmeta_time) = timing.rmeta_time.as_mut() {
+                        *rmeta_time /= factor;
+                    }
+                }
+            }
+        }
+    }
+    let changes = dice_box::diff_graphs(dependency_queue, &changed_deps);
+    if changes.is_empty() && changed_timings == *timings {
+        bail!("the change leaves the build as it is");
+    }
+    println!("Environment: {environment}");
+    if !changes.is_empty() {
+        println!("{}", Table::new(changes));
+    }
+    println!(
+        "{}",
+        Table::new(dice_box::compare_graphs(
+            (dependency_queue, timings),
+            (&changed_deps, &changed_timings),
+            registry,
+            &compared_schedulers(opts, registry),
+            opts.num_threads,
+        )?)
+    );
+    Ok(())
+}
+
+/// The scenarios compared by default, and everything they are built from.
+struct Simulation<'a> {
+    opts: &'a Cli,
+    build: &'a Build,
+    registry: &'a dice_box::Registry,
+    weights: Option<dice_box::Weights>,
+    inversion_values: Option<BTreeMap<Artifact, u64>>,
+    external_schedule: Option<(&'a Path, dice_box::ExternalSchedule)>,
+    hints_order: Option<(&'a Path, Vec<Artifact>)>,
+    real_build_order: Option<Vec<Artifact>>,
+    core_speeds: Vec<f64>,
+    logging_decisions: bool,
+}
+
+impl<'a> Simulation<'a> {
+    fn new(
+        opts: &'a Cli,
+        build: &'a Build,
+        registry: &'a dice_box::Registry,
+        weights: Option<dice_box::Weights>,
+    ) -> Result<Self> {
+        let Build {
+            dependency_queue,
+            timings,
+            ..
+        } = build;
+        let inversion_values = opts
+            .inversions
+            .map(|metric| metric.values(dependency_queue, timings));
+        let external_schedule = opts
+            .external_schedule
+            .as_deref()
+            .map(|path| -> Result<_> {
+                let schedule = load(path, |contents| {
+                    dice_box::parse_external_schedule(
+                        contents,
+                        dependency_queue,
+                        timings,
+                        opts.num_threads,
+                    )
+                })?;
+                Ok((path, schedule))
+            })
+            .transpose()?;
+        let hints_order = opts
+            .hints_file
+            .as_deref()
+            .map(|path| -> Result<_> {
+                let order = load(path, |contents| {
+                    dice_box::parse_hints_file(contents, timings)
+                })?;
+                Ok((path, order))
+            })
+            .transpose()?;
+        let real_build_order = opts
+            .real_build_order
+            .then(|| -> Result<_> {
+                let mut order = parse_input(
+                    &opts.timings_file,
+                    &build.timings_contents,
+                    dice_box::parse_start_order,
+                )?;
+                if order.is_empty() {
+                    bail!(
+                        "{} has no start times, only timings saved by `dice_box verify --save` do",
+                        opts.timings_file.display()
+                    );
+                }
+                order.retain(|artifact| timings.contains_key(artifact));
+                Ok(order)
+            })
+            .transpose()?;
+        // Audits judge the decisions hint providers were consulted for, so they need them logged.
+        let logging_decisions =
+            opts.decision_log.is_some() || matches!(opts.command, Some(Command::Audit { .. }));
+        let core_speeds: Vec<f64> = opts
+            .cores
+            .iter()
+            .flat_map(|&(count, speed)| std::iter::repeat_n(speed, count))
+            .collect();
+        if !core_speeds.is_empty() && core_speeds.len() != opts.num_threads {
+            bail!(
+                "--cores add up to {} cores, but the build runs on {} threads",
+                core_speeds.len(),
+                opts.num_threads
+            );
+        }
+        Ok(Simulation {
+            opts,
+            build,
+            registry,
+            weights,
+            inversion_values,
+            external_schedule,
+            hints_order,
+            real_build_order,
+            core_speeds,
+            logging_decisions,
+        })
+    }
+
+    /// Applies the options that model the machine and the build to a scenario.
+    fn configure(&self, runner: Runner, constraints: &dice_box::Constraints) -> Runner {
+        let Simulation {
+            opts,
+            build,
+            inversion_values,
+            core_speeds,
+            logging_decisions,
+            ..
+        } = self;
+        let Build {
+            events,
+            codegen_units,
+            ..
+        } = build;
+        let runner = match opts.affinity {
+            Some(discount) => runner.with_affinity_discount(discount),
+            None => runner,
+        };
+        let runner = opts.pool.iter().fold(
+            runner
+                .with_budgeted_scheduling(opts.budgeted)
+                .with_constraints(constraints.clone())
+                .with_external_events(events.clone()),
+            |runner, (pool, limit)| runner.with_pool_limit(*pool, *limit),
+        );
+        let runner = match opts.max_concurrent_links {
+            Some(limit) => runner.with_pool_limit(dice_box::ThreadPool::Link, limit),
+            None => runner,
+        };
+        let runner = match opts.dispatch_latency_ms {
+            Some(latency) => runner.with_dispatch_latency(latency),
+            None => runner,
+        };
+        let runner = runner.with_core_speeds(core_speeds);
+        let runner = match opts.rustc_threads {
+            Some(max_threads) => runner.with_parallel_frontend(dice_box::ParallelFrontend {
+                max_threads,
+                parallel_fraction: opts.parallel_fraction,
+            }),
+            None => runner,
+        };
+        let runner = match &codegen_units {
+            Some(codegen_units) => runner.with_parallel_codegen(codegen_units.clone()),
+            None => runner,
+        };
+        let runner = match opts.io_capacity {
+            Some(capacity) => {
+                let mut bandwidth = dice_box::IoBandwidth::new(capacity);
+                bandwidth
+                    .consumption
+                    .extend(opts.io_consumption.iter().copied());
+                runner.with_io_bandwidth(bandwidth)
+            }
+            None => runner,
+        };
+        let runner = match &opts.throttling {
+            Some(curve) => runner.with_throttling(dice_box::Throttling {
+                curve: curve.0.clone(),
+                window: (opts.throttling_window * 1000.) as u64,
+            }),
+            None => runner,
+        };
+        let runner = match opts.spawn_overhead {
+            Some(overhead) => runner.with_spawn_overhead(overhead),
+            None => runner,
+        };
+        let runner = match opts.jobserver_tokens {
+            Some(tokens) => runner.with_jobserver(dice_box::Jobserver {
+                tokens,
+                acquisition_latency: opts.token_latency,
+            }),
+            None => runner,
+        };
+        let runner = match &inversion_values {
+            Some(values) => runner.with_inversion_detection(values.clone()),
+            None => runner,
+        };
+        if *logging_decisions {
+            runner.with_decision_log()
+        } else {
+            runner
+        }
+    }
+
+    /// Scenarios of all schedulers, on a given number of threads.
+    fn scenarios(
+        &self,
+        constraints: &dice_box::Constraints,
+        num_threads: usize,
+    ) -> Result<Vec<Runner>> {
+        self.lazy_scenarios(constraints, num_threads).collect()
+    }
+
+    /// Scenario at `index` of [Simulation::scenarios], without constructing any of the others.
+    fn scenario(
+        &self,
+        constraints: &dice_box::Constraints,
+        num_threads: usize,
+        index: usize,
+    ) -> Result<Runner> {
+        self.lazy_scenarios(constraints, num_threads)
+            .nth(index)
+            .unwrap()
+    }
+
+    /// Scenarios of all schedulers, each constructed only once it's iterated over. Fails on the ones whose hint
+    /// provider can't be set up.
+    fn lazy_scenarios<'s>(
+        &'s self,
+        constraints: &'s dice_box::Constraints,
+        num_threads: usize,
+    ) -> impl Iterator<Item = Result<Runner>> + 's {
+        let Simulation {
+            opts,
+            build,
+            registry,
+            weights,
+            external_schedule,
+            hints_order,
+            real_build_order,
+            ..
+        } = self;
+        let Build {
+            dependency_queue,
+            timings,
+            ..
+        } = build;
+        let runner = move |(hints, setup_time): (Box<dyn dice_box::HintProvider>, _)| {
+            dice_box::Runner::new(
+                dependency_queue.clone().finish(hints),
+                timings.clone(),
+                num_threads,
+            )
+            .with_setup_time(setup_time)
+        };
+        #[cfg(feature = "scripting")]
+        let scripted = opts.script_hints.iter().map(move |path| -> Result<_> {
+            let (hints, setup_time) =
+                timed(|| dice_box::ScriptHints::new(path, dependency_queue, timings));
+            Ok(runner((hints?, setup_time)))
+        });
+        #[cfg(not(feature = "scripting"))]
+        let scripted = std::iter::empty();
+        #[cfg(feature = "plugins")]
+        let plugin = opts.plugin.iter().map(move |path| -> Result<_> {
+            let (hints, setup_time) =
+                timed(|| dice_box::PluginHints::new(path, dependency_queue, timings));
+            Ok(runner((hints?, setup_time)))
+        });
+        #[cfg(not(feature = "plugins"))]
+        let plugin = std::iter::empty();
+        std::iter::once_with(move || runner(timed(|| dice_box::CargoHints::new(dependency_queue))))
+            .chain(std::iter::once_with(move || {
+                runner(timed(|| {
+                    dice_box::NHintsProvider::with_params(
+                        dependency_queue,
+                        timings,
+                        opts.n_hints_params(),
+                    )
+                }))
+            }))
+            .chain(std::iter::once_with(move || {
+                runner(timed(|| {
+                    dice_box::FifoHints::new(dependency_queue, opts.fifo_seed)
+                }))
+            }))
+            .chain(std::iter::once_with(move || {
+                runner(timed(|| {
+                    dice_box::CriticalPathHints::new(dependency_queue, timings)
+                }))
+            }))
+            .chain(std::iter::once_with(move || {
+                runner(timed(|| dice_box::SjfHints::new(timings)))
+            }))
+            .chain(std::iter::once_with(move || {
+                runner(timed(|| dice_box::LptHints::new(timings)))
+            }))
+            .chain(opts.affinity.into_iter().map(move |_| {
+                runner(timed(|| dice_box::CargoHints::new(dependency_queue)))
+                    .with_label(AFFINITY_LABEL.into())
+                    .with_affinity_placement(true)
+            }))
+            .chain(opts.scheduler.iter().map(move |name| {
+                runner(timed(|| {
+                    registry.create(name, dependency_queue, timings).unwrap()
+                }))
+            }))
+            .chain(opts.aging.into_iter().flat_map(move |max_age| {
+                ["cargo", "n-hints", "fifo"].map(|name| {
+                    runner(timed(|| {
+                        dice_box::AgingHints::new(
+                            registry.create(name, dependency_queue, timings).unwrap(),
+                            max_age,
+                        )
+                    }))
+                })
+            }))
+            .chain(opts.rollout.into_iter().map(move |max_candidates| {
+                runner(timed(|| {
+                    dice_box::RolloutHints::new(
+                        dependency_queue,
+                        timings,
+                        num_threads,
+                        max_candidates,
+                    )
+                }))
+            }))
+            .chain(opts.reservation.into_iter().map(move |max_wait| {
+                runner(timed(|| {
+                    dice_box::ReservationHints::new(
+                        dependency_queue,
+                        timings,
+                        (max_wait * 1000.) as u64,
+                    )
+                }))
+            }))
+            .chain(weights.iter().map(move |&weights| {
+                runner(timed(|| {
+                    dice_box::WeightedHints::new(dependency_queue, timings, weights)
+                }))
+            }))
+            .chain(hints_order.iter().map(move |(path, order)| {
+                runner(timed(|| {
+                    dice_box::PriorityListHints::new(
+                        order,
+                        dice_box::CargoHints::new(dependency_queue),
+                    )
+                }))
+                .with_label(format!("Hints file {}", path.display()))
+            }))
+            .chain(real_build_order.iter().map(move |order| {
+                runner(timed(|| {
+                    dice_box::PriorityListHints::new(
+                        order,
+                        dice_box::CargoHints::new(dependency_queue),
+                    )
+                }))
+                .with_label("Real build order".into())
+            }))
+            .chain(external_schedule.iter().map(move |(path, schedule)| {
+                runner(timed(|| {
+                    dice_box::PriorityListHints::new(
+                        &schedule.order(),
+                        dice_box::CargoHints::new(dependency_queue),
+                    )
+                }))
+                .with_label(external_schedule_label(path, schedule))
+                .with_external_schedule(schedule)
+            }))
+            .map(Ok::<_, anyhow::Error>)
+            .chain(scripted)
+            .chain(plugin)
+            .chain(opts.worst_case.then_some(()).into_iter().map(move |_| {
+                Ok(runner(timed(|| {
+                    dice_box::WorstCaseHints::new(dependency_queue, timings)
+                })))
+            }))
+            .chain(std::iter::once_with(move || {
+                let (hints, setup_time) = timed(|| dice_box::CargoHints::new(dependency_queue));
+                Ok(dice_box::Runner::new(
+                    dependency_queue.clone().finish(hints),
+                    timings.clone(),
+                    u8::MAX as usize,
+                )
+                .with_label(OPTIMAL_LABEL.into())
+                .with_optimal(true)
+                .with_setup_time(setup_time))
+            }))
+            .map(move |runner| Ok(self.configure(runner?, constraints)))
+    }
+
+    /// Scenarios replaying the decisions recorded in a trace, set up like the ones that recorded them. Hint providers
+    /// are not consulted during a replay, so there's no point in constructing expensive ones.
+    fn replays(&self, trace: &dice_box::Trace) -> Result<Vec<Runner>, dice_box::InputError> {
+        let Build {
+            dependency_queue,
+            timings,
+            constraints,
+            ..
+        } = self.build;
+        trace
+            .scenarios
+            .iter()
+            .map(|scenario| {
+                let runner = Runner::new(
+                    dependency_queue
+                        .clone()
+                        .finish(dice_box::CargoHints::new(dependency_queue)),
+                    timings.clone(),
+                    scenario.num_threads,
+                )
+                .with_label(scenario.label.clone())
+                .with_optimal(scenario.label == OPTIMAL_LABEL)
+                .with_affinity_placement(scenario.label == AFFINITY_LABEL);
+                let runner = match &self.external_schedule {
+                    Some((path, schedule))
+                        if scenario.label == external_schedule_label(path, schedule) =>
+                    {
+                        runner.with_external_schedule(schedule)
+                    }
+                    _ => runner,
+                };
+                let runner = self.configure(runner, constraints);
+                runner.check_replay(scenario)?;
+                Ok(runner.with_replay(scenario.clone()))
+            })
+            .collect()
+    }
+}
+
+/// Label of the scenario building with the Cargo algorithm on as many threads as it can use.
+const OPTIMAL_LABEL: &str = "Optimal build schedule (current Cargo algo)";
+
+/// Label of the scenario placing units on the core their package last ran on.
+const AFFINITY_LABEL: &str = "Cargo Hints with core affinity";
+
+/// Label of the scenario following an external schedule read from `path`.
+fn external_schedule_label(path: &Path, schedule: &dice_box::ExternalSchedule) -> String {
+    let label = schedule
+        .label
+        .clone()
+        .unwrap_or_else(|| path.display().to_string());
+    format!("External schedule {label}")
+}
+
+/// Simulates the build with all schedulers, then runs the subcommand that inspects the scenarios, or reports on them
+/// if there's none.
+fn simulate(
+    opts: &Cli,
+    build: &Build,
+    registry: &dice_box::Registry,
+    weights: Option<dice_box::Weights>,
+) -> Result<()> {
+    check_schedulers(opts, registry)?;
+    if opts.jobserver_tokens == Some(0) {
+        bail!("--jobserver-tokens must be at least 1, for the token Cargo holds itself");
+    }
+    if opts.random_runs == Some(0) {
+        bail!("--random-runs must be at least 1");
+    }
+    if !opts.thread_sweep.is_empty() && build.constraints.min_threads() > 1 {
+        bail!("--thread-sweep needs a build on a single thread, which the pins of the constraints rule out");
+    }
+    let simulation = Simulation::new(opts, build, registry, weights)?;
+    let Build {
+        cache_key,
+        constraints,
+        environment,
+        ..
+    } = build;
+    let replay = opts
+        .replay
+        .as_ref()
+        .map(|path| {
+            dice_box::Trace::read(path)
+                .with_context(|| format!("failed to read trace {}", path.display()))
+        })
+        .transpose()?;
+    if let Some(recorded) = replay.as_ref().and_then(|trace| trace.environment.as_ref()) {
+        let differences = recorded.differences(environment);
+        if !differences.is_empty() {
+            eprintln!("Warning: the trace was recorded in a different environment:");
+            for difference in differences {
+                eprintln!("  {difference}");
+            }
+        }
+    }
+    let make_scenarios = || -> Result<_> {
+        match (&replay, &opts.replay) {
+            (Some(trace), Some(path)) => simulation
+                .replays(trace)
+                .with_context(|| path.display().to_string()),
+            _ => simulation.scenarios(constraints, opts.num_threads),
+        }
+    };
+    let mut scenarios = make_scenarios()?;
+    // Checked up front, as makespans are only compared to the baseline once every other report has run.
+    scenario(&scenarios, opts.baseline)?;
+    // Decision time varies from run to run, so budgeted scenarios are never taken from the cache. Neither are
+    // logged ones, as replayed decisions are not logged.
+    let scenario_cache = opts
+        .cache_dir
+        .as_ref()
+        .filter(|_| replay.is_none() && !opts.budgeted && !simulation.logging_decisions)
+        .map(|cache_dir| -> Result<_> {
+            // Labels of hint providers reflect their parameters, but not contents of the files they're read from.
+            let mut hasher = DefaultHasher::new();
+            cache_key.hash(&mut hasher);
+            let files = [&opts.hints_file, &opts.external_schedule].into_iter();
+            #[cfg(feature = "scripting")]
+            let files = files.chain([&opts.script_hints]);
+            #[cfg(feature = "plugins")]
+            let files = files.chain([&opts.plugin]);
+            for path in files.flatten() {
+                std::fs::read(path)
+                    .with_context(|| format!("failed to read {}", path.display()))?
+                    .hash(&mut hasher);
+            }
+            Ok(dice_box::ScenarioCache::new(cache_dir, hasher.finish()))
+        })
+        .transpose()?;
+    let uncached = scenario_cache.as_ref().map(|cache| {
+        let (restored, keys) = cache.restore(std::mem::take(&mut scenarios));
+        scenarios = restored;
+        keys
+    });
+    let (results, reports): (Vec<_>, Vec<_>) =
+        dice_box::calculate_all(&mut scenarios)?.into_iter().unzip();
+    if let Some((label, failure)) = scenarios
+        .iter()
+        .find_map(|runner| Some((runner.label(), runner.hint_failure()?)))
+    {
+        bail!("{label} {failure}");
+    }
+    if let Some((label, failure)) = scenarios
+        .iter()
+        .find_map(|runner| Some((runner.label(), runner.replay_failure()?)))
+    {
+        bail!("the replayed decisions of {label} don't fit the simulation: the trace {failure}");
+    }
+    if let (Some(cache), Some(keys)) = (&scenario_cache, &uncached) {
+        cache.store(&scenarios, keys)?;
+    }
+    if opts.verify_determinism {
+        let mut serial = make_scenarios()?;
+        for runner in &mut serial {
+            runner.calculate()?;
+        }
+        let differences: Vec<_> = scenarios
+            .iter()
+            .zip(&serial)
+            .filter_map(|(parallel, serial)| parallel.difference(serial))
+            .collect();
+        if !differences.is_empty() {
+            bail!(
+                "parallel and serial runs differ:\n  {}",
+                differences.join("\n  ")
+            );
+        }
+        writeln!(
+            report_output(opts),
+            "Parallel and serial runs of {} scenarios are identical",
+            scenarios.len()
+        )?;
+    }
+    if let Some(path) = &opts.decision_log {
+        dice_box::write_decision_log(path, &scenarios)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+    }
+    if let Some(path) = &opts.record {
+        dice_box::Trace {
+            scenarios: scenarios.iter().map(dice_box::Runner::trace).collect(),
+            environment: Some(environment.clone()),
+        }
+        .write(path)
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    }
+    match &opts.command {
+        Some(Command::Explain { package }) => explain(build, &scenarios, package),
+        Some(Command::Compare {
+            package,
+            left,
+            right,
+        }) => compare(build, &scenarios, package, *left, *right),
+        Some(Command::Attribute { left, right }) => attribute(build, &scenarios, *left, *right),
+        Some(Command::Audit {
+            scenario: index,
+            top,
+        }) => {
+            if replay.is_some() {
+                bail!(
+                    "Replayed decisions can't be audited, as hint providers are not consulted for them"
+                );
+            }
+            audit(build, &scenarios, *index, *top)
+        }
+        Some(Command::Verify { save, cargo_args }) => verify(
+            opts,
+            build,
+            &scenarios,
+            &results,
+            save.as_deref(),
+            cargo_args,
+        ),
+        _ => report(&simulation, scenarios, results, reports, replay.is_some()),
+    }
+}
+
+fn explain(build: &Build, scenarios: &[Runner], package: &str) -> Result<()> {
+    let explanations: Vec<_> = scenarios
+        .iter()
+        .map(|runner| dice_box::ScenarioExplanation {
+            label: runner.label().to_owned(),
+            units: dice_box::explain(
+                package,
+                runner.schedule(),
+                &build.dependency_queue,
+                &build.timings,
+            ),
+        })
+        .collect();
+    println!("{}", serde_json::to_string_pretty(&explanations).unwrap());
+    Ok(())
+}
+
+fn compare(
+    build: &Build,
+    scenarios: &[Runner],
+    package: &str,
+    left: usize,
+    right: usize,
+) -> Result<()> {
+    let (left, right) = (scenario(scenarios, left)?, scenario(scenarios, right)?);
+    println!("{} vs {}", left.label(), right.label());
+    let rows = dice_box::compare(
+        package,
+        left.schedule(),
+        right.schedule(),
+        &build.dependency_queue,
+    );
+    println!("{}", Table::new(rows));
+    Ok(())
+}
+
+fn attribute(build: &Build, scenarios: &[Runner], left: usize, right: usize) -> Result<()> {
+    let attribution = dice_box::attribute(
+        scenario(scenarios, left)?,
+        scenario(scenarios, right)?,
+        &build.dependency_queue,
+    )?;
+    println!("{}", Table::new([attribution]));
+    Ok(())
+}
+
+fn audit(build: &Build, scenarios: &[Runner], index: usize, top: usize) -> Result<()> {
+    let audits = dice_box::audit_decisions(
+        scenario(scenarios, index)?,
+        &build.dependency_queue,
+        &build.timings,
+        top,
+    )?;
+    println!("{}", Table::new(audits));
+    Ok(())
+}
+
+fn verify(
+    opts: &Cli,
+    build: &Build,
+    scenarios: &[Runner],
+    results: &[Makespan],
+    save: Option<&Path>,
+    cargo_args: &[String],
+) -> Result<()> {
+    // Cargo Hints model the scheduler of Cargo itself.
+    let predicted = &scenarios[0];
+    let mut output: Box<dyn std::io::Write> = match save {
+        Some(path) => Box::new(BufWriter::new(create(path)?)),
+        None => Box::new(std::io::sink()),
+    };
+    let actual_environment = dice_box::Environment::capture(cargo_args)?;
+    if let Some(path) = save {
+        let path = path.with_extension("environment.json");
+        std::fs::write(
+            &path,
+            serde_json::to_string_pretty(&actual_environment).unwrap(),
+        )
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    }
+    let actual = dice_box::capture(opts.num_threads, cargo_args, &mut output)?;
+    let actual_makespan = actual.iter().map(|task| task.end).max().unwrap_or_default();
+    println!("Environment of the prediction: {}", build.environment);
+    println!("Environment of the real build: {actual_environment}");
+    println!(
+        "Predicted makespan ({}): {}",
+        predicted.label(),
+        results[0].makespan
+    );
+    println!(
+        "Actual makespan: {:?}",
+        std::time::Duration::from_millis(actual_makespan)
+    );
+    println!(
+        "{}",
+        Table::new(dice_box::start_time_errors(predicted.schedule(), &actual))
+    );
+    Ok(())
+}
+
+/// Indices of the scenarios that are achievable with the configured number of threads, i.e. all but the optimal
+/// schedule.
+fn achievable(scenarios: &[Runner]) -> impl Iterator<Item = usize> + '_ {
+    scenarios
+        .iter()
+        .enumerate()
+        .filter(|(_, runner)| !runner.is_optimal())
+        .map(|(index, _)| index)
+}
+
+/// Achievable scenario with the shortest makespan, if there's any (a trace may hold the optimal schedule alone).
+fn best_scenario(scenarios: &[Runner], results: &[Makespan]) -> Option<usize> {
+    achievable(scenarios).min_by(|a, b| {
+        results[*a]
+            .makespan
+            .partial_cmp(&results[*b].makespan)
+            .unwrap()
+    })
+}
+
+/// Writes visualizations of the scenarios that were asked for.
+fn visualize(simulation: &Simulation, scenarios: &[Runner], results: &[Makespan]) -> Result<()> {
+    let Simulation { opts, build, .. } = simulation;
+    let Build {
+        dependency_queue,
+        timings,
+        ..
+    } = build;
+    let best_scenario = || {
+        best_scenario(scenarios, results).context(
+            "there is no scenario achievable with the configured number of threads to pick the best one from",
+        )
+    };
+    if let Some(path) = &opts.waterfall {
+        let segments =
+            dice_box::waterfall(scenarios[best_scenario()?].schedule(), dependency_queue);
+        let (csv_path, svg_path) = (path.with_extension("csv"), path.with_extension("svg"));
+        let mut csv = BufWriter::new(create(&csv_path)?);
+        dice_box::write_csv(&segments, &mut csv)
+            .with_context(|| format!("failed to write {}", csv_path.display()))?;
+        let mut svg = BufWriter::new(create(&svg_path)?);
+        dice_box::write_svg(&segments, &mut svg)
+            .with_context(|| format!("failed to write {}", svg_path.display()))?;
+    }
+    if let Some(path) = &opts.emit_hints {
+        let best_scenario = best_scenario()?;
+        let hints = dice_box::emit_hints(
+            scenarios[best_scenario].label(),
+            scenarios[best_scenario].schedule(),
+            dependency_queue,
+            timings,
+            results[best_scenario].num_threads,
+            opts.jitter_trials,
+            opts.jitter,
+        )?;
+        std::fs::write(path, serde_json::to_string_pretty(&hints).unwrap())
+            .with_context(|| format!("failed to write {}", path.display()))?;
+    }
+    let report_limits = report_limits(opts);
+    let summarized = report_limits.exceeded_by(timings.len());
+    if let Some(index) = opts.animate {
+        let runner = scenario(scenarios, index)?;
+        let schedule = if summarized {
+            report_limits.longest(runner.schedule())
+        } else {
+            runner.schedule().to_vec()
+        };
+        let mut svg = BufWriter::new(create(Path::new("animation.svg"))?);
+        dice_box::write_animation(&schedule, runner.label(), opts.animation_length, &mut svg)
+            .context("failed to write animation.svg")?;
+    }
+    if opts.da_vinci.is_some() && summarized {
+        eprintln!(
+            "Not writing da-vinci.html: the graph has {} units, more than --report-max-units allows",
+            timings.len()
+        );
+    } else if let Some(index) = opts.da_vinci {
+        let runner = scenario(scenarios, index)?;
+        let mut html = BufWriter::new(create(Path::new("da-vinci.html"))?);
+        dice_box::write_editor(
+            runner.schedule(),
+            dependency_queue,
+            results[index].num_threads,
+            runner.label(),
+            &mut html,
+        )
+        .context("failed to write da-vinci.html")?;
+    }
+    Ok(())
+}
+
+/// Where reports are printed: to stderr if JSON results are printed to stdout, so that they stay parseable.
+fn report_output(opts: &Cli) -> Box<dyn Write> {
+    if opts.output == dice_box::OutputFormat::Json && opts.output_file.is_none() {
+        Box::new(std::io::stderr())
+    } else {
+        Box::new(std::io::stdout())
+    }
+}
+
+fn report_limits(opts: &Cli) -> dice_box::ReportLimits {
+    dice_box::ReportLimits {
+        max_units: opts.report_max_units,
+        top_units: opts.report_top_units,
+        max_points: opts.report_max_points,
+    }
+}
+
+/// Prints the results of the scenarios, along with the reports that were asked for.
+fn report(
+    simulation: &Simulation,
+    mut scenarios: Vec<Runner>,
+    results: Vec<Makespan>,
+    reports: Vec<dice_box::Timings>,
+    replayed: bool,
+) -> Result<()> {
+    let Simulation {
+        opts,
+        build,
+        registry,
+        ..
+    } = simulation;
+    let Build {
+        timings_contents,
+        timings,
+        dependency_queue,
+        filter,
+        environment,
+        constraints,
+        ..
+    } = build;
+    let dev_loop = opts
+        .dev_loop
+        .then(|| dice_box::DevLoop::new(dependency_queue, timings));
+    let pipeline = opts
+        .test_timings
+        .as_ref()
+        .zip(opts.test_unit_graph.as_ref())
+        .map(|(timings_file, unit_graph_file)| -> Result<_> {
+            let mut test_timings = load(timings_file, |contents: &str| {
+                dice_box::parse(contents.to_owned())
+            })?;
+            let test_unit_graph: dice_box::UnitGraph =
+                load(unit_graph_file, |contents| serde_json::from_str(contents))?;
+            let test_queue = dice_box::create_dependency_queue(test_unit_graph.filtered(filter));
+            test_timings.retain(|artifact, _| test_queue.contains(artifact));
+            Ok(dice_box::Pipeline::new(
+                dependency_queue,
+                timings,
+                &test_queue,
+                &test_timings,
+            ))
+        })
+        .transpose()?;
+    visualize(simulation, &scenarios, &results)?;
+    // Decisions recorded in a trace are only valid for the thread count and constraints they were made with, so
+    // reports that simulate the scenarios again under different ones are left out of replays.
+    let resimulable = !replayed;
+    let dispatch_overheads = (opts.dispatch_latency_ms.is_some() && resimulable)
+        .then(|| -> Result<_> {
+            let relaxed = simulation.scenarios(constraints, opts.num_threads)?;
+            results
+                .iter()
+                .zip(relaxed)
+                .map(|(result, relaxed)| -> Result<_> {
+                    let without_latency = relaxed.with_dispatch_latency(0).calculate()?.0.makespan;
+                    Ok(dice_box::DispatchOverhead::new(
+                        result.label.clone(),
+                        result.makespan,
+                        without_latency,
+                    ))
+                })
+                .collect::<Result<Vec<_>>>()
+        })
+        .transpose()?;
+    let io_contention = (opts.io_capacity.is_some() && resimulable)
+        .then(|| -> Result<_> {
+            let unlimited = simulation.scenarios(constraints, opts.num_threads)?;
+            results
+                .iter()
+                .zip(unlimited)
+                .map(|(result, unlimited)| -> Result<_> {
+                    let unlimited_io = unlimited.without_io_limit().calculate()?.0.makespan;
+                    Ok(dice_box::IoContention::new(
+                        result.label.clone(),
+                        result.makespan,
+                        unlimited_io,
+                    ))
+                })
+                .collect::<Result<Vec<_>>>()
+        })
+        .transpose()?;
+    let exclusivity_costs = (constraints.has_exclusive() && resimulable)
+        .then(|| -> Result<_> {
+            let mut relaxed =
+                simulation.scenarios(&constraints.without_exclusive(), opts.num_threads)?;
+            results
+                .iter()
+                .zip(relaxed.iter_mut())
+                .map(|(result, relaxed)| -> Result<_> {
+                    Ok(dice_box::ExclusivityCost::new(
+                        result.label.clone(),
+                        result.makespan,
+                        relaxed.calculate()?.0.makespan,
+                    ))
+                })
+                .collect::<Result<Vec<_>>>()
+        })
+        .transpose()?;
+    let memory_pressure = (constraints.memory_budget().is_some() && resimulable)
+        .then(|| -> Result<_> {
+            let mut relaxed =
+                simulation.scenarios(&constraints.without_memory_budget(), opts.num_threads)?;
+            results
+                .iter()
+                .zip(relaxed.iter_mut())
+                .map(|(result, relaxed)| -> Result<_> {
+                    relaxed.calculate()?;
+                    Ok(dice_box::MemoryPressure::new(
+                        result.label.clone(),
+                        result.makespan,
+                        relaxed.schedule(),
+                        constraints,
+                    ))
+                })
+                .collect::<Result<Vec<_>>>()
+        })
+        .transpose()?;
+    let compactions = opts.compaction.then(|| {
+        scenarios
+            .iter()
+            .zip(&results)
+            .map(|(runner, result)| {
+                dice_box::compaction(
+                    result.label.clone(),
+                    runner.schedule(),
+                    dependency_queue,
+                    result.num_threads,
+                )
+            })
+            .collect::<Vec<_>>()
+    });
+    let utilization = opts
+        .utilization
+        .then(|| -> Result<_> {
+            achievable(&scenarios)
+                .map(|index| -> Result<_> {
+                    let utilization = scenarios[index].utilization();
+                    if !resimulable || utilization.peak_threads * 10 > utilization.num_threads * 6 {
+                        return Ok(utilization);
+                    }
+                    let makespan = &results[index].makespan;
+                    let mut right_size = utilization.peak_threads;
+                    while right_size > 1
+                        && simulation
+                            .scenario(constraints, right_size - 1, index)?
+                            .calculate()?
+                            .0
+                            .makespan
+                            <= *makespan
+                    {
+                        right_size -= 1;
+                    }
+                    Ok(dice_box::Utilization {
+                        right_size: Some(right_size),
+                        ..utilization
+                    })
+                })
+                .collect::<Result<Vec<_>>>()
+        })
+        .transpose()?;
+    let mut core_sizing = opts
+        .min_cores
+        .filter(|_| resimulable)
+        .map(|tolerance| -> Result<_> {
+            let unlimited = dependency_queue
+                .units()
+                .count()
+                .max(constraints.min_threads());
+            let makespan = |index: usize, num_threads: usize| -> Result<_> {
+                Ok(simulation
+                    .scenario(constraints, num_threads, index)?
+                    .calculate()?
+                    .0
+                    .makespan)
+            };
+            achievable(&scenarios)
+                .map(|index| -> Result<_> {
+                    let unbounded = makespan(index, unlimited)?;
+                    let target = unbounded.as_secs_f64() * (1. + tolerance / 100.);
+                    let (mut low, mut high) = (constraints.min_threads(), unlimited);
+                    while low < high {
+                        let middle = (low + high) / 2;
+                        if makespan(index, middle)?.as_secs_f64() <= target {
+                            high = middle;
+                        } else {
+                            low = middle + 1;
+                        }
+                    }
+                    Ok(dice_box::CoreSizing {
+                        label: scenarios[index].label().to_owned(),
+                        unbounded,
+                        min_threads: low,
+                        makespan: makespan(index, low)?,
+                    })
+                })
+                .collect::<Result<Vec<_>>>()
+        })
+        .transpose()?;
+    let mut thread_sweep = (!opts.thread_sweep.is_empty() && resimulable)
+        .then(|| -> Result<_> {
+            let mut counts: Vec<usize> = opts
+                .thread_sweep
+                .iter()
+                .copied()
+                .filter(|count| *count > 0)
+                .chain([1])
+                .collect();
+            counts.sort_unstable();
+            counts.dedup();
+            let mut makespans = vec![vec![]; scenarios.len()];
+            for &count in &counts {
+                let mut swept = simulation.scenarios(constraints, count)?;
+                swept.retain(|runner| !runner.is_optimal());
+                for (index, (result, _)) in
+                    achievable(&scenarios).zip(dice_box::calculate_all(&mut swept)?)
+                {
+                    makespans[index].push((count, result.makespan));
+                }
+            }
+            Ok(achievable(&scenarios)
+                .map(|index| dice_box::sweep_points(scenarios[index].label(), &makespans[index]))
+                .collect::<Vec<_>>())
+        })
+        .transpose()?;
+    let cost_estimates = (!opts.machine.is_empty() && resimulable)
+        .then(|| -> Result<_> {
+            let mut estimates = vec![];
+            for machine in &opts.machine {
+                let mut scenarios = simulation.scenarios(constraints, machine.cores)?;
+                // The optimal schedule doesn't depend on the machine.
+                scenarios.retain(|runner| !runner.is_optimal());
+                for runner in scenarios {
+                    let result = runner
+                        .with_coordinator_cost(machine.coordinator_cost)
+                        .calculate()?
+                        .0;
+                    estimates.push(dice_box::CostEstimate::new(
+                        result.label,
+                        *machine,
+                        result.makespan,
+                    ));
+                }
+            }
+            dice_box::mark_pareto_optimal(&mut estimates);
+            Ok(estimates)
+        })
+        .transpose()?;
+    let random_distribution = opts
+        .random_runs
+        .map(|runs| -> Result<_> {
+            let last_seed = opts.random_seed.checked_add(runs as u64).with_context(|| {
+                format!(
+                    "--random-seed {} leaves no room for {runs} consecutive seeds",
+                    opts.random_seed
+                )
+            })?;
+            let makespans = (opts.random_seed..last_seed)
+                .map(|seed| -> Result<_> {
+                    let runner = Runner::new(
+                        dependency_queue
+                            .clone()
+                            .finish(dice_box::RandomHints::new(seed)),
+                        timings.clone(),
+                        opts.num_threads,
+                    );
+                    Ok(simulation
+                        .configure(runner, constraints)
+                        .calculate()?
+                        .0
+                        .makespan)
+                })
+                .collect::<Result<_>>()?;
+            Ok(dice_box::MakespanDistribution::new(
+                "Random".into(),
+                makespans,
+            ))
+        })
+        .transpose()?;
+    let n_hints_sweep: Vec<_> = opts
+        .n_hints_sweep
+        .iter()
+        .map(|&n| -> Result<_> {
+            let runner = Runner::new(
+                dependency_queue
+                    .clone()
+                    .finish(dice_box::NHintsProvider::with_params(
+                        dependency_queue,
+                        timings,
+                        dice_box::NHintsParams {
+                            n,
+                            ..opts.n_hints_params()
+                        },
+                    )),
+                timings.clone(),
+                opts.num_threads,
+            );
+            let makespan = simulation
+                .configure(runner, constraints)
+                .calculate()?
+                .0
+                .makespan;
+            Ok(dice_box::NHintsSweepPoint { n, makespan })
+        })
+        .collect::<Result<_>>()?;
+    let mut spread = opts.worst_case.then(|| {
+        dice_box::ScheduleSpread::new(achievable(&scenarios).map(|index| {
+            let result = &results[index];
+            (result.label.as_str(), &result.makespan)
+        }))
+    });
+    // Timings saved by `verify --save` tell how long the recorded build took, to compare it against simulations.
+    let observed = parse_input(
+        &opts.timings_file,
+        timings_contents,
+        dice_box::parse_wall_time,
+    )?
+    .map(|wall_time| dice_box::ObservedBuild::new(wall_time, &results[0]));
+    let calibration = opts
+        .calibrate
+        .then(|| -> Result<_> {
+            let actual = parse_input(
+                &opts.timings_file,
+                timings_contents,
+                dice_box::parse_observed_schedule,
+            )?;
+            if actual.is_empty() {
+                bail!(
+                    "{} has no start times, only timings saved by `dice_box verify --save` do",
+                    opts.timings_file.display()
+                );
+            }
+            // Cargo Hints model the scheduler of Cargo itself.
+            Ok(dice_box::calibrate(
+                scenarios[0].label().to_owned(),
+                scenarios[0].schedule(),
+                &actual,
+                dependency_queue,
+            ))
+        })
+        .transpose()?;
+    let mut results = results;
+    dice_box::Makespan::compare_to_baseline(&mut results, opts.baseline);
+    match opts.output {
+        dice_box::OutputFormat::Table => {
+            let results = Table::new(results).to_string();
+            match &opts.output_file {
+                Some(path) => {
+                    std::fs::write(path, format!("Environment: {environment}\n{results}\n"))
+                        .with_context(|| format!("failed to write {}", path.display()))?
+                }
+                None => {
+                    println!("Environment: {environment}");
+                    println!("{}", results);
+                }
+            }
+        }
+        dice_box::OutputFormat::Json => {
+            // Reports that are part of the comparison go into the results, instead of being printed after them.
+            let thread_sweep = thread_sweep.take().unwrap_or_default();
+            dice_box::Results {
+                environment: environment.clone(),
+                baseline: scenarios[opts.baseline].label().to_owned(),
+                scenarios: results,
+                spread: spread.take(),
+                scalability: thread_sweep
+                    .iter()
+                    .map(|points| dice_box::fit_scalability(points))
+                    .collect(),
+                thread_sweep: thread_sweep.into_iter().flatten().collect(),
+                core_sizing: core_sizing.take().unwrap_or_default(),
+            }
+            .write_json(opts.output_file.as_deref())
+            .with_context(|| match &opts.output_file {
+                Some(path) => format!("failed to write {}", path.display()),
+                None => "failed to write the results".into(),
+            })?
+        }
+    }
+    let mut out = report_output(opts);
+    if let Some(observed) = observed {
+        writeln!(out, "{}", Table::new([observed]))?;
+    }
+    if let Some((calibration, units)) = calibration {
+        writeln!(out, "Calibration against the recorded build:")?;
+        writeln!(out, "{}", Table::new([calibration]))?;
+        writeln!(out, "{}", Table::new(units))?;
+    }
+    if let Some(spread) = spread {
+        writeln!(out, "{}", Table::new([spread]))?;
+    }
+    if let Some(random_distribution) = random_distribution {
+        writeln!(out, "{}", Table::new([random_distribution]))?;
+    }
+    if !n_hints_sweep.is_empty() {
+        writeln!(out, "{}", Table::new(n_hints_sweep))?;
+    }
+    if let Some(compactions) = compactions {
+        writeln!(out, "{}", Table::new(compactions))?;
+    }
+    if let Some(utilization) = utilization {
+        writeln!(out, "{}", Table::new(utilization))?;
+    }
+    if let Some(fraction) = opts.tail {
+        writeln!(out, "Last {}% of the build:", fraction * 100.)?;
+        writeln!(
+            out,
+            "{}",
+            Table::new(achievable(&scenarios).map(|index| scenarios[index].tail(fraction)))
+        )?;
+    }
+    if let Some(threshold) = opts.serial_stretches {
+        writeln!(out, "Stretches with at most {threshold} units running:")?;
+        writeln!(
+            out,
+            "{}",
+            Table::new(
+                achievable(&scenarios).map(|index| scenarios[index].serial_stretches(threshold))
+            )
+        )?;
+    }
+    if let Some(limit) = opts.blame {
+        // Cargo Hints model the scheduler of Cargo itself.
+        writeln!(
+            out,
+            "Idle thread time blamed on units of {}:",
+            scenarios[0].label()
+        )?;
+        writeln!(
+            out,
+            "{}",
+            Table::new(dice_box::blame(
+                dependency_queue,
+                scenarios[0].schedule(),
+                opts.num_threads,
+                limit,
+            ))
+        )?;
+    }
+    if let Some(thread_sweep) = thread_sweep {
+        writeln!(
+            out,
+            "{}",
+            Table::new(thread_sweep.iter().flatten().cloned().collect::<Vec<_>>())
+        )?;
+        writeln!(
+            out,
+            "{}",
+            Table::new(
+                thread_sweep
+                    .iter()
+                    .map(|points| dice_box::fit_scalability(points))
+            )
+        )?;
+    }
+    if let Some(core_sizing) = core_sizing {
+        writeln!(
+            out,
+            "Fewest threads within {}% of the makespan with unlimited threads:",
+            opts.min_cores.unwrap_or_default()
+        )?;
+        writeln!(out, "{}", Table::new(core_sizing))?;
+    }
+    if let Some(cost_estimates) = cost_estimates {
+        writeln!(out, "{}", Table::new(cost_estimates))?;
+    }
+    if let Some(horizon) = opts.horizon {
+        let scores = dice_box::rank_on_horizon(
+            achievable(&scenarios).map(|index| {
+                (
+                    scenarios[index].label().to_owned(),
+                    scenarios[index].schedule(),
+                )
+            }),
+            (horizon * 1000.) as u64,
+        );
+        writeln!(out, "Progress after {horizon}s:")?;
+        writeln!(out, "{}", Table::new(scores))?;
+    }
+    if let Some(dispatch_overheads) = dispatch_overheads {
+        writeln!(out, "{}", Table::new(dispatch_overheads))?;
+    }
+    if let Some(io_contention) = io_contention {
+        writeln!(out, "{}", Table::new(io_contention))?;
+    }
+    if let Some(exclusivity_costs) = exclusivity_costs {
+        writeln!(out, "{}", Table::new(exclusivity_costs))?;
+    }
+    if let Some(memory_pressure) = memory_pressure {
+        writeln!(out, "{}", Table::new(memory_pressure))?;
+    }
+    if opts.aging.is_some() {
+        writeln!(
+            out,
+            "{}",
+            Table::new(scenarios.iter().map(dice_box::Runner::max_wait))
+        )?;
+    }
+    if opts.packages {
+        for runner in scenarios.iter() {
+            writeln!(out, "Packages in \"{}\":", runner.label())?;
+            writeln!(
+                out,
+                "{}",
+                Table::new(dice_box::group_by_package(runner.schedule()))
+            )?;
+        }
+    }
+    if opts.inversions.is_some() {
+        for runner in scenarios.iter_mut() {
+            writeln!(out, "Priority inversions in \"{}\":", runner.label())?;
+            let inversions = runner.priority_inversions();
+            if inversions.is_empty() {
+                writeln!(out, "  none")?;
+            }
+            for inversion in inversions {
+                writeln!(out, "  {}", inversion)?;
+            }
+        }
+    }
+    let dev_loop_timings = dev_loop
+        .map(|dev_loop| {
+            simulate_phases(opts, registry, &mut *out, |scheduler| {
+                dev_loop.run(
+                    |deps, timings| scheduler.create(deps, timings),
+                    opts.num_threads,
+                )
+            })
+        })
+        .transpose()?;
+    let pipeline_timings = pipeline
+        .map(|pipeline| {
+            simulate_phases(opts, registry, &mut *out, |scheduler| {
+                pipeline.run(
+                    |deps, timings| scheduler.create(deps, timings),
+                    opts.num_threads,
+                )
+            })
+        })
+        .transpose()?;
     if opts.timings {
-        let all_but_optimal = timings.len() - 1;
-        timings
+        let report_limits = report_limits(opts);
+        for (index, timing) in dev_loop_timings.into_iter().flatten().enumerate() {
+            timing
+                .summarized(&report_limits)
+                .report_html(format!("dev-loop-{index}"))
+                .ok();
+        }
+        for (index, timing) in pipeline_timings.into_iter().flatten().enumerate() {
+            timing
+                .summarized(&report_limits)
+                .report_html(format!("pipeline-{index}"))
+                .ok();
+        }
+        reports
             .into_iter()
-            .take(all_but_optimal)
+            .zip(&scenarios)
+            .filter(|(_, runner)| !runner.is_optimal())
+            .map(|(timing, _)| timing)
             .enumerate()
             .for_each(|(index, timing)| {
-                timing.report_html(index.to_string()).ok();
+                timing
+                    .summarized(&report_limits)
+                    .report_html(index.to_string())
+                    .ok();
             });
     }
+    Ok(())
+}
+
+/// Simulates a build in two phases, such as the dev loop or a build followed by tests, with each compared scheduler.
+/// Writes a table of the results to `out` and returns the combined timeline of each simulation.
+fn simulate_phases<T: tabled::Tabled>(
+    opts: &Cli,
+    registry: &dice_box::Registry,
+    out: &mut dyn Write,
+    run: impl Fn(
+        &dyn dice_box::HintProviderFactory,
+    ) -> Result<(T, dice_box::Timings), dice_box::InputError>,
+) -> Result<Vec<dice_box::Timings>> {
+    let (results, timings): (Vec<_>, Vec<_>) = compared_schedulers(opts, registry)
+        .iter()
+        .map(|name| run(registry.get(name).unwrap()))
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .unzip();
+    writeln!(out, "{}", Table::new(results))?;
+    Ok(timings)
+}
+
+/// Reports an error to the user and exits, for places where it can't be returned from [run].
+fn exit_with(err: anyhow::Error) -> ! {
+    eprintln!("error: {err:#}");
+    std::process::exit(1)
+}
+
+fn read(path: &Path) -> Result<String> {
+    std::fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))
+}
+
+fn create(path: &Path) -> Result<File> {
+    File::create(path).with_context(|| format!("failed to create {}", path.display()))
+}
+
+/// Parses `contents` of the input file at `path`, naming the file if they're not valid.
+fn parse_input<T, E>(
+    path: &Path,
+    contents: &str,
+    parse: impl FnOnce(&str) -> Result<T, E>,
+) -> Result<T>
+where
+    E: std::error::Error + Send + Sync + 'static,
+{
+    parse(contents).with_context(|| path.display().to_string())
+}
+
+/// Reads and parses the input file at `path`.
+fn load<T, E>(path: &Path, parse: impl FnOnce(&str) -> Result<T, E>) -> Result<T>
+where
+    E: std::error::Error + Send + Sync + 'static,
+{
+    parse_input(path, &read(path)?, parse)
+}
+
+/// Scenario with a given index, as passed on the command line.
+fn scenario(scenarios: &[Runner], index: usize) -> Result<&Runner> {
+    scenarios.get(index).with_context(|| {
+        format!(
+            "there is no scenario #{index}, only {} of them (counting from 0)",
+            scenarios.len()
+        )
+    })
+}
+
+/// Runs `f`, returning its result along with the wall-clock time it took.
+fn timed<T>(f: impl FnOnce() -> T) -> (T, std::time::Duration) {
+    let start = std::time::Instant::now();
+    let ret = f();
+    (ret, start.elapsed())
 }
@@ -0,0 +1,1281 @@
+//! End-to-end tests running every subcommand of dice_box against the toy workspace in `fixtures/toy`. Makespans
+//! asserted here are the regression baseline: if a change moves them, it changes how builds are simulated.
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/toy");
+
+/// Fresh directory for a test to run dice_box in, since some subcommands write files to the current directory.
+fn scratch(name: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Runs dice_box on the toy workspace with 4 threads (as in the captured build) and extra `args`, returning its
+/// standard output.
+fn dice_box(dir: &Path, args: &[&str]) -> String {
+    dice_box_on(dir, 4, args)
+}
+
+/// Like [dice_box], but with `threads` threads. The toy workspace is bound by its critical path at 4 threads, where
+/// schedulers tend to tie; with fewer of them, the order units start in matters.
+fn dice_box_on(dir: &Path, threads: usize, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_dice_box"))
+        .current_dir(dir)
+        .arg(Path::new(FIXTURE).join("timings.json"))
+        .arg(Path::new(FIXTURE).join("unit-graph.json"))
+        .args(["--num-threads", &threads.to_string()])
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "dice_box {args:?} failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Cells of rows of a table with a given label.
+fn rows<'a>(stdout: &'a str, label: &str) -> Vec<Vec<&'a str>> {
+    stdout
+        .lines()
+        .map(|line| line.split('|').map(str::trim).collect::<Vec<_>>())
+        .filter(|cells| cells.get(1) == Some(&label))
+        .collect()
+}
+
+/// Makespan of the first scenario with a given label in a results table.
+fn makespan<'a>(stdout: &'a str, label: &str) -> &'a str {
+    rows(stdout, label)
+        .first()
+        .unwrap_or_else(|| panic!("no scenario labeled {label} in:\n{stdout}"))[3]
+}
+
+#[test]
+fn simulates_all_schedulers() {
+    let dir = scratch("simulates_all_schedulers");
+    let stdout = dice_box(
+        &dir,
+        &[
+            "--environment",
+            &format!("{FIXTURE}/environment.json"),
+            "--verify-determinism",
+        ],
+    );
+    assert!(stdout.starts_with("Parallel and serial runs of 7 scenarios are identical"));
+    assert!(stdout.contains("Environment: rustc 1.97.0-nightly"));
+    // The captured build took 19.12s.
+    assert_eq!(makespan(&stdout, "Cargo Hints"), "19.058s");
+    assert_eq!(makespan(&stdout, "N-Hints"), "19.959s");
+    assert_eq!(makespan(&stdout, "FIFO"), "20.43s");
+    assert_eq!(makespan(&stdout, "Critical Path"), "19.009s");
+    assert_eq!(makespan(&stdout, "Shortest Job First"), "20.539s");
+    assert_eq!(makespan(&stdout, "Longest Processing Time"), "19.999s");
+    assert_eq!(
+        makespan(&stdout, "Optimal build schedule (current Cargo algo)"),
+        "18.5s"
+    );
+}
+
+#[test]
+fn optional_schedulers() {
+    let dir = scratch("optional_schedulers");
+    let stdout = dice_box(
+        &dir,
+        &[
+            "--rollout",
+            "3",
+            "--reservation",
+            "0.5",
+            "--weight",
+            "critical-path=1",
+            "--worst-case",
+        ],
+    );
+    assert_eq!(makespan(&stdout, "Rollout (top 3)"), "18.719s");
+    assert_eq!(makespan(&stdout, "Reservation (up to 500ms)"), "18.929s");
+    assert_eq!(
+        makespan(
+            &stdout,
+            "Weighted (duration=0,transitive-dependants=0,critical-path=1,unlocked=0)"
+        ),
+        "19.009s"
+    );
+    assert_eq!(makespan(&stdout, "Worst Case"), "20.507s");
+    // On 2 threads, looking ahead beats following the critical path alone.
+    let stdout = dice_box_on(&dir, 2, &["--rollout", "3"]);
+    assert_eq!(makespan(&stdout, "Critical Path"), "28.952s");
+    assert_eq!(makespan(&stdout, "Rollout (top 3)"), "28.655s");
+}
+
+#[test]
+fn sweeps_n_hints() {
+    let dir = scratch("sweeps_n_hints");
+    let stdout = dice_box(&dir, &["--n-hints", "10", "--n-hints-sweep", "1,75"]);
+    assert_eq!(makespan(&stdout, "N-Hints (N=10)"), "20.289s");
+    assert_eq!(rows(&stdout, "1")[0][2], "20.729s");
+    assert_eq!(rows(&stdout, "75")[0][2], "19.959s");
+}
+
+#[test]
+fn caches_scenarios() {
+    let dir = scratch("caches_scenarios");
+    let cached = |dir: &Path| std::fs::read_dir(dir.join("cache")).unwrap().count();
+    let first = dice_box(&dir, &["--cache-dir", "cache"]);
+    let scenarios = cached(&dir);
+    let second = dice_box(&dir, &["--cache-dir", "cache", "--rollout", "3"]);
+    assert_eq!(cached(&dir), scenarios + 1);
+    for label in ["Cargo Hints", "N-Hints", "Critical Path"] {
+        assert_eq!(makespan(&first, label), makespan(&second, label));
+    }
+    assert_eq!(makespan(&second, "Rollout (top 3)"), "18.719s");
+}
+
+#[test]
+fn logs_decisions() {
+    let dir = scratch("logs_decisions");
+    dice_box(&dir, &["--decision-log", "decisions.jsonl"]);
+    let log = std::fs::read_to_string(dir.join("decisions.jsonl")).unwrap();
+    let decisions: Vec<serde_json::Value> = log
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .filter(|decision: &serde_json::Value| decision["scenario"] == "Cargo Hints")
+        .collect();
+    let first = &decisions[0];
+    assert_eq!(first["candidates"].as_array().unwrap().len(), 9);
+    assert_eq!(
+        first["choice"]["package_id"],
+        "path+file:///toy/crates/text#0.1.0"
+    );
+    assert!(first["candidates"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|candidate| candidate["artifact"] == first["choice"] && candidate["score"] == 180.0));
+    assert!(decisions
+        .iter()
+        .any(|decision| decision["reason"] == "codegen"));
+}
+
+#[test]
+fn models_jobserver_tokens() {
+    let dir = scratch("models_jobserver_tokens");
+    let stdout = dice_box(&dir, &["--jobserver-tokens", "2"]);
+    assert_eq!(makespan(&stdout, "Cargo Hints"), "29.685s");
+    let stdout = dice_box(&dir, &["--jobserver-tokens", "4", "--token-latency", "50"]);
+    assert_eq!(makespan(&stdout, "Cargo Hints"), "19.389s");
+}
+
+#[test]
+fn models_dispatch_latency() {
+    let dir = scratch("models_dispatch_latency");
+    let stdout = dice_box(&dir, &["--dispatch-latency-ms", "20"]);
+    assert_eq!(makespan(&stdout, "Cargo Hints"), "19.178s");
+    // Overhead of the latency, next to the makespan without it.
+    assert_eq!(
+        rows(&stdout, "Cargo Hints")[1][2..6],
+        ["19.178s", "19.058s", "120ms", "0.6%"]
+    );
+}
+
+#[test]
+fn audits_decisions() {
+    let dir = scratch("audits_decisions");
+    let stdout = dice_box(&dir, &["audit", "--scenario", "2", "--top", "3"]);
+    let audits = rows(&stdout, "FIFO");
+    assert_eq!(audits.len(), 3);
+    // The worst decision comes first, along with the pick that would have saved the most.
+    assert_eq!(
+        audits[0][2..9],
+        [
+            "629ms",
+            "0",
+            "net 0.1.0 (BuildScriptBuild)",
+            "text 0.1.0 (Metadata)",
+            "19.908s",
+            "19.369s",
+            "539ms"
+        ]
+    );
+}
+
+#[test]
+fn models_coordinator_cost() {
+    let dir = scratch("models_coordinator_cost");
+    let stdout = dice_box(
+        &dir,
+        &[
+            "--machine",
+            "4=0.05",
+            "--machine",
+            "4=0.05,1000",
+            "--machine",
+            "16=0.05",
+            "--machine",
+            "16=0.05,1000",
+        ],
+    );
+    let estimates = rows(&stdout, "Cargo Hints");
+    let makespans: Vec<_> = estimates[1..].iter().map(|row| row[3]).collect();
+    // Bookkeeping is charged per running unit, so it costs more on a machine that runs more of them at once.
+    assert_eq!(makespans, ["19.058s", "19.094s", "18.5s", "18.538s"]);
+    // Critical Path is the fastest on 2 cores, until bookkeeping is slow enough for Cargo Hints to overtake it.
+    let stdout = dice_box(&dir, &["--machine", "2=0.05", "--machine", "2=0.05,100000"]);
+    let makespans =
+        |label| -> Vec<_> { rows(&stdout, label)[1..].iter().map(|row| row[3]).collect() };
+    assert_eq!(makespans("Critical Path"), ["28.952s", "32.646s"]);
+    assert_eq!(makespans("Cargo Hints"), ["29.685s", "32.065s"]);
+}
+
+#[test]
+fn models_spawn_overhead() {
+    let dir = scratch("models_spawn_overhead");
+    let stdout = dice_box(&dir, &["--spawn-overhead", "windows"]);
+    // Critical path first is ahead of Cargo without the overhead, but not with it.
+    assert_eq!(makespan(&stdout, "Cargo Hints"), "19.349s");
+    assert_eq!(makespan(&stdout, "Critical Path"), "19.458s");
+    let stdout = dice_box(&dir, &["--spawn-overhead", "5"]);
+    assert_eq!(makespan(&stdout, "Cargo Hints"), "19.108s");
+    let output = Command::new(env!("CARGO_BIN_EXE_dice_box"))
+        .current_dir(&dir)
+        .arg(Path::new(FIXTURE).join("timings.json"))
+        .arg(Path::new(FIXTURE).join("unit-graph.json"))
+        .args(["--spawn-overhead", &u64::MAX.to_string()])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("overhead must be at most"));
+}
+
+#[test]
+fn models_heterogeneous_cores() {
+    let dir = scratch("models_heterogeneous_cores");
+    let stdout = dice_box(
+        &dir,
+        &[
+            "--cores",
+            "2x1.0",
+            "--cores",
+            "2x0.5",
+            "--scheduler",
+            "heft",
+        ],
+    );
+    assert_eq!(makespan(&stdout, "Cargo Hints"), "31.86s");
+    assert_eq!(makespan(&stdout, "Critical Path"), "29.338s");
+    // HEFT holds long units for the fast cores rather than starting them on a slow one.
+    assert_eq!(makespan(&stdout, "HEFT"), "23.036s");
+    // Cores beyond the listed ones run at the fastest speed, so the optimal schedule is still a bound.
+    assert_eq!(
+        makespan(&stdout, "Optimal build schedule (current Cargo algo)"),
+        "18.5s"
+    );
+}
+
+#[test]
+fn models_parallel_frontend() {
+    let dir = scratch("models_parallel_frontend");
+    let stdout = dice_box(&dir, &["--rustc-threads", "4"]);
+    assert_eq!(makespan(&stdout, "Cargo Hints"), "18.633s");
+    assert_eq!(makespan(&stdout, "Critical Path"), "18.584s");
+    let stdout = dice_box(
+        &dir,
+        &["--rustc-threads", "4", "--parallel-fraction", "0.9"],
+    );
+    assert_eq!(makespan(&stdout, "Critical Path"), "17.51s");
+}
+
+#[test]
+fn models_parallel_codegen() {
+    let dir = scratch("models_parallel_codegen");
+    // Codegen spread over the idle threads leaves fewer of them to crates that become ready in the meantime.
+    let stdout = dice_box(&dir, &["--parallel-codegen"]);
+    assert_eq!(makespan(&stdout, "Cargo Hints"), "19.152s");
+    let stdout = dice_box(&dir, &["--parallel-codegen", "--codegen-units", "dev=2"]);
+    assert_eq!(makespan(&stdout, "Cargo Hints"), "19.098s");
+}
+
+#[test]
+fn reports_pool_limits_as_bottleneck() {
+    let dir = scratch("reports_pool_limits_as_bottleneck");
+    // Link units of the toy workspace never overlap, so a single linker does not hold anything up.
+    let stdout = dice_box(&dir, &["--max-concurrent-links", "1"]);
+    assert_eq!(makespan(&stdout, "Cargo Hints"), "19.058s");
+    assert_eq!(rows(&stdout, "Cargo Hints")[0][12], "0ns");
+    let stdout = dice_box(&dir, &["--pool", "compile=2"]);
+    assert_eq!(rows(&stdout, "Cargo Hints")[0][12], "24.346s");
+}
+
+#[test]
+fn models_io_bandwidth() {
+    let dir = scratch("models_io_bandwidth");
+    let stdout = dice_box(&dir, &["--io-capacity", "1"]);
+    assert_eq!(makespan(&stdout, "Cargo Hints"), "32.889s");
+    // Time lost to IO contention, next to the makespan without the limit.
+    assert_eq!(
+        rows(&stdout, "Cargo Hints")[1][2..6],
+        ["32.889s", "19.058s", "13.831s", "42.1%"]
+    );
+    let stdout = dice_box(&dir, &["--io-capacity", "2"]);
+    assert_eq!(rows(&stdout, "Cargo Hints")[1][4], "0ns");
+}
+
+#[test]
+fn models_thermal_throttling() {
+    let dir = scratch("models_thermal_throttling");
+    let args = ["--throttling", "2:1,4:0.6", "--throttling-window", "5"];
+    let stdout = dice_box(&dir, &args);
+    assert_eq!(makespan(&stdout, "Cargo Hints"), "21.83s");
+    // A long window keeps the machine from heating up within the 19 seconds the toy build takes.
+    let stdout = dice_box(&dir, &[&args[..3], &["300"]].concat());
+    assert_eq!(makespan(&stdout, "Cargo Hints"), "19.058s");
+}
+
+#[test]
+fn simulates_two_phase_builds_with_each_scheduler() {
+    let dir = scratch("simulates_two_phase_builds_with_each_scheduler");
+    // HEFT is not one of the default scenarios, so its row comes from the dev loop.
+    let stdout = dice_box(&dir, &["--dev-loop"]);
+    assert_eq!(rows(&stdout, "HEFT").len(), 1);
+    // Picked schedulers are the only ones the pipeline is simulated with.
+    let stdout = dice_box(
+        &dir,
+        &[
+            "--test-timings",
+            &format!("{FIXTURE}/timings.json"),
+            "--test-unit-graph",
+            &format!("{FIXTURE}/unit-graph.json"),
+            "--scheduler",
+            "heft",
+        ],
+    );
+    assert_eq!(rows(&stdout, "HEFT").len(), 2);
+    assert_eq!(rows(&stdout, "FIFO").len(), 1);
+}
+
+#[test]
+fn follows_core_timeline() {
+    let dir = scratch("follows_core_timeline");
+    // Two cores throughout are the same as two threads.
+    let stdout = dice_box(&dir, &["--core-timeline", "0:2"]);
+    assert_eq!(makespan(&stdout, "Cargo Hints"), "29.685s");
+    let stdout = dice_box(&dir, &["--core-timeline", "0:1,5:4"]);
+    assert_eq!(makespan(&stdout, "Cargo Hints"), "23.147s");
+    // Taking all cores away for good leaves the rest of the build stuck.
+    let output = Command::new(env!("CARGO_BIN_EXE_dice_box"))
+        .current_dir(&dir)
+        .arg(Path::new(FIXTURE).join("timings.json"))
+        .arg(Path::new(FIXTURE).join("unit-graph.json"))
+        .args(["--num-threads", "4", "--core-timeline", "0:4,1:0"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("so the build can never finish"), "{stderr}");
+}
+
+#[test]
+fn simulates_incremental_rebuilds() {
+    let dir = scratch("simulates_incremental_rebuilds");
+    // Only the binary depends on the server, so little has to be rebuilt.
+    let stdout = dice_box(&dir, &["--dirty", "server"]);
+    assert_eq!(makespan(&stdout, "Cargo Hints"), "1.73s");
+    let stdout = dice_box(&dir, &["--dirty", "server", "--dirty", "ui"]);
+    assert_eq!(makespan(&stdout, "Cargo Hints"), "7.17s");
+    let stdout = dice_box(&dir, &["--dirty", "hashing"]);
+    assert_eq!(makespan(&stdout, "Cargo Hints"), "18.49s");
+}
+
+#[test]
+fn reports_the_tail() {
+    let dir = scratch("reports_the_tail");
+    let stdout = dice_box(&dir, &["--tail"]);
+    let tail = &rows(&stdout, "Cargo Hints")[1];
+    assert_eq!((tail[2], tail[3]), ("17.153s", "2.52"));
+    assert!(tail[4].starts_with("server 0.1.0 (Metadata) 1.63s"));
+}
+
+#[test]
+fn reports_the_spread() {
+    let dir = scratch("reports_the_spread");
+    let stdout = dice_box(&dir, &["--worst-case", "--random-runs", "5"]);
+    // The adversarial scheduler isn't the worst one when the critical path bounds the build anyway.
+    let spread = &rows(&stdout, "Critical Path")[1];
+    assert_eq!(
+        (spread[2], spread[3], spread[4]),
+        ("19.009s", "Shortest Job First", "20.539s")
+    );
+    let random = &rows(&stdout, "Random")[0];
+    assert_eq!(
+        (random[3], random[4], random[5]),
+        ("19.688s", "20.11s", "20.679s")
+    );
+    let stdout = dice_box_on(&dir, 2, &["--worst-case", "--random-runs", "5"]);
+    let spread = &rows(&stdout, "Critical Path")[1];
+    assert_eq!(
+        (spread[2], spread[3], spread[4]),
+        ("28.952s", "Worst Case", "33.065s")
+    );
+    let random = &rows(&stdout, "Random")[0];
+    assert_eq!(
+        (random[3], random[4], random[5]),
+        ("29.327s", "31.226s", "32.395s")
+    );
+}
+
+#[test]
+fn reports_serial_stretches() {
+    let dir = scratch("reports_serial_stretches");
+    let stdout = dice_box(&dir, &["--serial-stretches"]);
+    let stretches = &rows(&stdout, "Cargo Hints")[1];
+    assert_eq!(
+        (stretches[2], stretches[3], stretches[4]),
+        ("2", "1.77s", "5.31s")
+    );
+    assert!(stretches[5].starts_with("server 0.1.0 (Metadata) 0.94s, api 0.1.0 (Metadata) 0.73s"));
+}
+
+#[test]
+fn blames_units_for_idle_threads() {
+    let dir = scratch("blames_units_for_idle_threads");
+    let stdout = dice_box(&dir, &["--blame", "3"]);
+    assert_eq!(
+        rows(&stdout, "db 0.1.0 (Metadata)"),
+        [["", "db 0.1.0 (Metadata)", "5.64s", "4.262s", ""]]
+    );
+    // The server builds quickly, but most threads sit idle while the binary waits for it.
+    assert_eq!(rows(&stdout, "server 0.1.0 (Metadata)")[0][3], "3.486s");
+}
+
+#[test]
+fn finds_minimum_cores() {
+    let dir = scratch("finds_minimum_cores");
+    let stdout = dice_box(&dir, &["--min-cores", "5"]);
+    let sizing = &rows(&stdout, "Cargo Hints")[1];
+    assert_eq!((sizing[2], sizing[3], sizing[4]), ("18.5s", "4", "19.058s"));
+    let sizing = &rows(&stdout, "Shortest Job First")[1];
+    assert_eq!((sizing[3], sizing[4]), ("6", "19.079s"));
+}
+
+#[test]
+fn fits_scalability_laws() {
+    let dir = scratch("fits_scalability_laws");
+    let stdout = dice_box(&dir, &["--thread-sweep", "2,4,8,16"]);
+    let points: Vec<(&str, &str)> = rows(&stdout, "Cargo Hints")[1..6]
+        .iter()
+        .map(|cells| (cells[2], cells[3]))
+        .collect();
+    assert_eq!(
+        points,
+        [
+            ("1", "57.23s"),
+            ("2", "29.685s"),
+            ("4", "19.058s"),
+            ("8", "18.5s"),
+            ("16", "18.5s")
+        ]
+    );
+    let fit = &rows(&stdout, "Cargo Hints")[6];
+    assert_eq!(
+        (fit[2], fit[3], fit[4], fit[5]),
+        ("0.2631", "0.1329", "0.0092", "10")
+    );
+}
+
+#[test]
+fn compares_to_baseline() {
+    let dir = scratch("compares_to_baseline");
+    let stdout = dice_box(&dir, &[]);
+    assert_eq!(
+        &rows(&stdout, "Cargo Hints")[0][13..15],
+        ["+0.000s", "+0.0%"]
+    );
+    assert_eq!(&rows(&stdout, "FIFO")[0][13..15], ["+1.372s", "+7.2%"]);
+    let stdout = dice_box(&dir, &["--baseline", "3"]);
+    assert_eq!(
+        &rows(&stdout, "Cargo Hints")[0][13..15],
+        ["+0.049s", "+0.3%"]
+    );
+}
+
+#[test]
+fn reports_utilization() {
+    let dir = scratch("reports_utilization");
+    let stdout = dice_box(&dir, &[]);
+    assert_eq!(
+        &rows(&stdout, "Cargo Hints")[0][15..18],
+        ["3", "75.1%", "19.002s"]
+    );
+}
+
+#[test]
+fn writes_json_results() {
+    let dir = scratch("writes_json_results");
+    let stdout = dice_box(&dir, &["--output", "json"]);
+    let results: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(results["baseline"], "Cargo Hints");
+    let cargo = &results["scenarios"][0];
+    assert_eq!(cargo["label"], "Cargo Hints");
+    assert_eq!(cargo["num-threads"], 4);
+    assert_eq!(cargo["makespan"], 19.058);
+    assert_eq!(cargo["critical-path"], 18.5);
+    assert_eq!(cargo["idle-core-time"], 19.002);
+    let stdout = dice_box(&dir, &["--output", "json", "--output-file", "results.json"]);
+    assert!(stdout.is_empty(), "{stdout}");
+    let written: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(dir.join("results.json")).unwrap()).unwrap();
+    // Setup times are measured, so only makespans are the same in both runs.
+    let makespans = |results: &serde_json::Value| {
+        results["scenarios"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|scenario| scenario["makespan"].clone())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(makespans(&written), makespans(&results));
+    // Reports that are not part of the results don't end up in the JSON on stdout.
+    let stdout = dice_box(
+        &dir,
+        &["--output", "json", "--tail", "0.1", "--min-cores", "10"],
+    );
+    let results: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(results["core-sizing"][0]["min-threads"], 4);
+}
+
+#[test]
+fn balances_overlapping_core_events() {
+    let dir = scratch("balances_overlapping_core_events");
+    std::fs::write(
+        dir.join("events.json"),
+        r#"[
+            { "label": "other job", "at": 0.0, "cores": -8, "duration": 5.0 },
+            { "label": "scale up", "at": 0.0, "cores": 4, "duration": 5.0 }
+        ]"#,
+    )
+    .unwrap();
+    // No cores are left until both events end, after which the build runs as usual.
+    let stdout = dice_box(&dir, &["--events", "events.json"]);
+    assert_eq!(makespan(&stdout, "Cargo Hints"), "24.058s");
+}
+
+#[test]
+fn clamps_durations() {
+    let dir = scratch("clamps_durations");
+    let stdout = dice_box(&dir, &["--min-duration", "0.5", "--max-duration", "1"]);
+    assert_eq!(makespan(&stdout, "Cargo Hints"), "11.77s");
+}
+
+#[test]
+fn replays_recorded_decisions() {
+    let dir = scratch("replays_recorded_decisions");
+    let recorded = dice_box(&dir, &["--reservation", "0.5", "--record", "trace.json"]);
+    let replayed = dice_box(&dir, &["--reservation", "0.5", "--replay", "trace.json"]);
+    for label in ["Cargo Hints", "FIFO", "Reservation (up to 500ms)"] {
+        assert_eq!(makespan(&recorded, label), makespan(&replayed, label));
+    }
+}
+
+#[test]
+fn rejects_mismatched_replays() {
+    let dir = scratch("rejects_mismatched_replays");
+    dice_box(&dir, &["--record", "trace.json"]);
+    for args in [
+        ["--dirty", "api"],
+        ["--io-capacity", "1"],
+        ["--spawn-overhead", "100"],
+    ] {
+        let output = Command::new(env!("CARGO_BIN_EXE_dice_box"))
+            .current_dir(&dir)
+            .args([
+                Path::new(FIXTURE).join("timings.json"),
+                Path::new(FIXTURE).join("unit-graph.json"),
+            ])
+            .args(["--num-threads", "4", "--replay", "trace.json"])
+            .args(args)
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(1), "{args:?}");
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.starts_with(
+                "error: trace.json: Cargo Hints was recorded with different inputs or options"
+            ),
+            "{stderr}"
+        );
+    }
+}
+
+#[test]
+fn follows_hints_files() {
+    let dir = scratch("follows_hints_files");
+    let emitted = dice_box(
+        &dir,
+        &[
+            "--rollout",
+            "3",
+            "--emit-hints",
+            "hints.json",
+            "--jitter-trials",
+            "2",
+        ],
+    );
+    let hints: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(dir.join("hints.json")).unwrap()).unwrap();
+    assert_eq!(hints["format-version"], 1);
+    assert_eq!(hints["scenario"], "Rollout (top 3)");
+    assert_eq!(hints["hints"][0]["artifact"], "metadata");
+    // Jitter of 100% or more could make durations negative.
+    let output = Command::new(env!("CARGO_BIN_EXE_dice_box"))
+        .current_dir(&dir)
+        .arg(Path::new(FIXTURE).join("timings.json"))
+        .arg(Path::new(FIXTURE).join("unit-graph.json"))
+        .args(["--emit-hints", "hints.json", "--jitter", "1"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("jitter must be at least 0 and below 1")
+    );
+    let followed = dice_box(&dir, &["--hints-file", "hints.json"]);
+    assert_eq!(
+        makespan(&followed, "Hints file hints.json"),
+        makespan(&emitted, "Rollout (top 3)")
+    );
+    std::fs::write(
+        dir.join("order.txt"),
+        "# Slowest crate first\ndb:metadata\n",
+    )
+    .unwrap();
+    let followed = dice_box(&dir, &["--hints-file", "order.txt"]);
+    assert_eq!(makespan(&followed, "Hints file order.txt"), "19.058s");
+}
+
+#[test]
+fn follows_the_real_build_order() {
+    let dir = scratch("follows_the_real_build_order");
+    let timings = std::fs::read_to_string(Path::new(FIXTURE).join("timings.json")).unwrap();
+    // Units start in the order Cargo reported them in.
+    let started = timings
+        .lines()
+        .enumerate()
+        .map(|(index, line)| match line.strip_suffix('}') {
+            Some(line) if line.starts_with('{') => format!("{line},\"start\":{index}}}\n"),
+            _ => format!("{line}\n"),
+        })
+        .collect::<String>();
+    std::fs::write(dir.join("started.json"), started).unwrap();
+    let run = |timings: &str| {
+        Command::new(env!("CARGO_BIN_EXE_dice_box"))
+            .current_dir(&dir)
+            .arg(timings)
+            .arg(Path::new(FIXTURE).join("unit-graph.json"))
+            .args(["--num-threads", "4", "--real-build-order"])
+            .output()
+            .unwrap()
+    };
+    let output = run("started.json");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(makespan(&stdout, "Real build order"), "19.058s");
+    // Start times are made up, so the recorded build looks much slower than any simulated one.
+    assert_eq!(rows(&stdout, "Observed build")[0][6], "0.53");
+    assert_eq!(rows(&stdout, "Cargo Hints")[0][11], "0.97");
+    let output = run(&format!("{FIXTURE}/timings.json"));
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("has no start times"));
+}
+
+#[test]
+fn calibrates_against_the_recorded_build() {
+    let dir = scratch("calibrates_against_the_recorded_build");
+    let timings = std::fs::read_to_string(Path::new(FIXTURE).join("timings.json")).unwrap();
+    // Units start in the order Cargo reported them in.
+    let started = timings
+        .lines()
+        .enumerate()
+        .map(|(index, line)| match line.strip_suffix('}') {
+            Some(line) if line.starts_with('{') => format!("{line},\"start\":{index}}}\n"),
+            _ => format!("{line}\n"),
+        })
+        .collect::<String>();
+    std::fs::write(dir.join("started.json"), started).unwrap();
+    let calibrate = |threads: &str| {
+        let output = Command::new(env!("CARGO_BIN_EXE_dice_box"))
+            .current_dir(&dir)
+            .arg("started.json")
+            .arg(Path::new(FIXTURE).join("unit-graph.json"))
+            .args(["--num-threads", threads, "--calibrate"])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let stdout = calibrate("4");
+    // Calibration summary, with how much longer the recorded build took.
+    assert_eq!(rows(&stdout, "Cargo Hints")[1][4], "+15.592s");
+    assert_eq!(
+        rows(&stdout, "config 0.1.0 (BuildScriptBuild)")[0][5],
+        "+11.981s"
+    );
+    // Units of the recorded build start one by one, which a simulation on fewer threads comes closer to. There, the
+    // build script waits for a thread and starts later than it did for real.
+    let stdout = calibrate("2");
+    assert_eq!(rows(&stdout, "Cargo Hints")[1][4], "+4.965s");
+    assert_eq!(
+        rows(&stdout, "config 0.1.0 (BuildScriptBuild)")[0][5],
+        "-5.676s"
+    );
+}
+
+#[test]
+fn reports_input_errors() {
+    let dir = scratch("reports_input_errors");
+    let timings = std::fs::read_to_string(Path::new(FIXTURE).join("timings.json")).unwrap();
+    let truncated = &timings[..timings.find('\n').unwrap() + 20];
+    std::fs::write(dir.join("truncated.json"), truncated).unwrap();
+    for (timings, message) in [
+        ("missing.json", "error: failed to read missing.json"),
+        ("truncated.json", "error: truncated.json: line 2, column 19"),
+    ] {
+        let output = Command::new(env!("CARGO_BIN_EXE_dice_box"))
+            .current_dir(&dir)
+            .arg(timings)
+            .arg(Path::new(FIXTURE).join("unit-graph.json"))
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(1));
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.starts_with(message), "{stderr}");
+    }
+}
+
+#[test]
+fn rejects_invalid_configurations() {
+    let dir = scratch("rejects_invalid_configurations");
+    std::fs::write(dir.join("constraints.json"), r#"{ "pinned": { "db": 4 } }"#).unwrap();
+    // `api` depends on `db`, so it can't be built before it.
+    std::fs::write(dir.join("cycle.json"), r#"{ "precedes": [["api", "db"]] }"#).unwrap();
+    let toy = |file: &str| Path::new(FIXTURE).join(file).display().to_string();
+    let (timings, unit_graph) = (toy("timings.json"), toy("unit-graph.json"));
+    for (args, message) in [
+        (
+            vec!["--num-threads", "0"],
+            "error: --num-threads must be at least 1",
+        ),
+        (
+            vec!["--num-threads", "4", "--max-concurrent-links", "0"],
+            "error: --max-concurrent-links must be at least 1",
+        ),
+        (
+            vec!["--num-threads", "4", "--random-runs", "0"],
+            "error: --random-runs must be at least 1",
+        ),
+        (
+            vec!["--num-threads", "4", "--constraints", "constraints.json"],
+            "error: constraints.json: db is pinned to thread 4, but the build only has 4 threads",
+        ),
+        (
+            vec!["--num-threads", "4", "--constraints", "cycle.json"],
+            "error: cycle.json: ordering api before db makes them depend on each other",
+        ),
+        (
+            vec![
+                "--num-threads",
+                "4",
+                "colocate",
+                "--job",
+                &timings,
+                &unit_graph,
+                "--job",
+                &timings,
+                &unit_graph,
+                "--job",
+                &timings,
+                &unit_graph,
+                "--job",
+                &timings,
+                &unit_graph,
+            ],
+            "error: each of the 5 jobs needs at least one core, but there are only 4",
+        ),
+        (
+            vec!["--num-threads", "4", "--baseline", "99"],
+            "error: there is no scenario #99",
+        ),
+    ] {
+        let output = Command::new(env!("CARGO_BIN_EXE_dice_box"))
+            .current_dir(&dir)
+            .args([&timings, &unit_graph])
+            .args(&args)
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(1), "{args:?}");
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.starts_with(message), "{stderr}");
+    }
+    // Values out of range are rejected by clap, as usage errors.
+    for (args, message) in [
+        // More than all of the frontend can't parallelize.
+        (
+            vec!["--rustc-threads", "4", "--parallel-fraction", "1.5"],
+            "fraction must be between 0 and 1",
+        ),
+        // Warm caches can't make codegen free, let alone slower.
+        (
+            vec!["--affinity", "1"],
+            "affinity must be at least 0 and below 1",
+        ),
+        (
+            vec!["--affinity=-0.5"],
+            "affinity must be at least 0 and below 1",
+        ),
+        // Codegen split into no units at all would never run.
+        (
+            vec!["--parallel-codegen", "--codegen-units", "dev=0"],
+            "count must be at least 1",
+        ),
+        // Units can't hand IO bandwidth back to the others.
+        (
+            vec!["--io-capacity", "1", "--io-consumption=codegen=-0.5"],
+            "consumption must be a finite, non-negative number",
+        ),
+        (
+            vec!["--io-capacity", "1", "--io-consumption", "codegen=NaN"],
+            "consumption must be a finite, non-negative number",
+        ),
+    ] {
+        let output = Command::new(env!("CARGO_BIN_EXE_dice_box"))
+            .current_dir(&dir)
+            .args([&timings, &unit_graph])
+            .args(&args)
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(2), "{args:?}");
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains(message), "{stderr}");
+    }
+}
+
+#[test]
+fn evaluates_external_schedules() {
+    let dir = scratch("evaluates_external_schedules");
+    let schedule = format!("{FIXTURE}/external-schedule.json");
+    let stdout = dice_box(&dir, &["--external-schedule", &schedule]);
+    assert_eq!(
+        makespan(&stdout, "External schedule from a critical path scheduler"),
+        makespan(&stdout, "Critical Path")
+    );
+    let contents = std::fs::read_to_string(&schedule).unwrap();
+    let incomplete = contents.replace(r#""unit": "db:metadata""#, r#""unit": "db:link""#);
+    std::fs::write(dir.join("incomplete.json"), incomplete).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_dice_box"))
+        .current_dir(&dir)
+        .arg(Path::new(FIXTURE).join("timings.json"))
+        .arg(Path::new(FIXTURE).join("unit-graph.json"))
+        .args(["--external-schedule", "incomplete.json"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("db:link: no Link unit of `db` in the build"));
+    assert!(stderr.contains("db 0.1.0 (Metadata) is not scheduled"));
+}
+
+#[test]
+fn writes_reports() {
+    let dir = scratch("writes_reports");
+    dice_box(
+        &dir,
+        &[
+            "--timings",
+            "--waterfall",
+            "--animate",
+            "0",
+            "--da-vinci",
+            "0",
+        ],
+    );
+    for report in [
+        "waterfall.csv",
+        "waterfall.svg",
+        "animation.svg",
+        "da-vinci.html",
+    ] {
+        assert!(dir.join(report).exists(), "{report} was not written");
+    }
+    assert!(std::fs::read_dir(&dir).unwrap().any(|entry| entry
+        .unwrap()
+        .file_name()
+        .to_string_lossy()
+        .starts_with("cargo-timing-")));
+    dice_box(&dir, &["--waterfall", "makespan"]);
+    assert!(dir.join("makespan.csv").exists() && dir.join("makespan.svg").exists());
+}
+
+#[test]
+fn explain() {
+    let dir = scratch("explain");
+    let stdout = dice_box(&dir, &["explain", "db"]);
+    let explanation: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(explanation[0]["label"], "Cargo Hints");
+    assert_eq!(explanation[0]["units"][0]["start"], 8.198);
+}
+
+#[test]
+fn deps() {
+    let dir = scratch("deps");
+    let stdout = dice_box(&dir, &["deps", "db"]);
+    assert!(stdout.starts_with("Dependencies of db (10):"));
+    let stdout = dice_box(&dir, &["deps", "ids", "--chain-to", "server"]);
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        [
+            "server 0.1.0 (Metadata)",
+            "api 0.1.0 (Metadata)",
+            "net 0.1.0 (Metadata)",
+            "errors 0.1.0 (Metadata)",
+            "ids 0.1.0 (Metadata)"
+        ]
+    );
+}
+
+#[test]
+fn diff() {
+    let dir = scratch("diff");
+    let mut graph: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(Path::new(FIXTURE).join("unit-graph.json")).unwrap(),
+    )
+    .unwrap();
+    // Unit 3 is client, which no longer depends on api (unit 0).
+    graph["units"][3]["dependencies"]
+        .as_array_mut()
+        .unwrap()
+        .retain(|dependency| dependency["index"] != 0);
+    std::fs::write(dir.join("unit-graph.json"), graph.to_string()).unwrap();
+    let stdout = dice_box(&dir, &["diff", "unit-graph.json"]);
+    assert_eq!(
+        rows(&stdout, "removed"),
+        [[
+            "",
+            "removed",
+            "client 0.1.0 (Metadata)",
+            "api 0.1.0 (Metadata)",
+            ""
+        ]]
+    );
+    assert_eq!(rows(&stdout, "cargo")[0][4], "-0.070s");
+}
+
+#[test]
+fn what_if_remove_dep() {
+    let dir = scratch("what_if_remove_dep");
+    let stdout = dice_box(&dir, &["what-if", "remove-dep", "api", "auth"]);
+    assert_eq!(
+        rows(&stdout, "removed"),
+        [[
+            "",
+            "removed",
+            "api 0.1.0 (Metadata)",
+            "auth 0.1.0 (Metadata)",
+            ""
+        ]]
+    );
+    assert_eq!(rows(&stdout, "cargo")[0][4], "-1.020s");
+}
+
+#[test]
+fn what_if_split() {
+    let dir = scratch("what_if_split");
+    let stdout = dice_box(&dir, &["what-if", "split", "db", "--parts", "4"]);
+    // Metadata and codegen of each part, dependencies of each part on both of db's dependencies and on its own
+    // metadata, and dependencies of db's three dependants on each part.
+    assert_eq!(rows(&stdout, "added").len(), 8 + 4 * (2 + 1) + 3 * 4);
+    assert_eq!(rows(&stdout, "cargo")[0][4], "-1.810s");
+    let stdout = dice_box(&dir, &["what-if", "split", "api", "--ratio", "3:1"]);
+    assert_eq!(rows(&stdout, "cargo")[0][4], "-0.325s");
+}
+
+#[test]
+fn what_if_merge() {
+    let dir = scratch("what_if_merge");
+    // The merged crate has to wait for the dependencies of both db and storage before it can start.
+    let stdout = dice_box(&dir, &["what-if", "merge", "db", "storage"]);
+    assert_eq!(rows(&stdout, "cargo")[0][4], "+3.491s");
+}
+
+#[test]
+fn rejects_cyclic_merges() {
+    let dir = scratch("rejects_cyclic_merges");
+    std::fs::write(
+        dir.join("transforms.json"),
+        r#"[{ "rewrite": "merge", "package": "server", "into": "db" }]"#,
+    )
+    .unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_dice_box"))
+        .current_dir(&dir)
+        .arg(Path::new(FIXTURE).join("timings.json"))
+        .arg(Path::new(FIXTURE).join("unit-graph.json"))
+        .args(["--transforms", "transforms.json"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.starts_with("error: transforms.json: merging server into db"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn rejects_invalid_split_ratios() {
+    let dir = scratch("rejects_invalid_split_ratios");
+    for (ratio, message) in [
+        ("[]", "splitting db needs at least two shares, got 0"),
+        ("[0, 0]", "shares of db must be positive, got 0"),
+    ] {
+        std::fs::write(
+            dir.join("transforms.json"),
+            format!(r#"[{{ "rewrite": "split-into", "package": "db", "ratio": {ratio} }}]"#),
+        )
+        .unwrap();
+        let output = Command::new(env!("CARGO_BIN_EXE_dice_box"))
+            .current_dir(&dir)
+            .arg(Path::new(FIXTURE).join("timings.json"))
+            .arg(Path::new(FIXTURE).join("unit-graph.json"))
+            .args(["--transforms", "transforms.json"])
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(1), "{ratio}");
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.starts_with(&format!("error: transforms.json: {message}")),
+            "{stderr}"
+        );
+    }
+}
+
+#[test]
+fn what_if_speedup() {
+    let dir = scratch("what_if_speedup");
+    let stdout = dice_box(&dir, &["what-if", "speedup", "db", "2"]);
+    assert!(rows(&stdout, "added").is_empty());
+    assert_eq!(rows(&stdout, "cargo")[0][4], "-2.099s");
+}
+
+#[test]
+fn critical_path() {
+    let dir = scratch("critical_path");
+    let stdout = dice_box(&dir, &["critical-path"]);
+    assert_eq!(
+        rows(&stdout, "db 0.1.0 (Metadata)"),
+        [["", "db 0.1.0 (Metadata)", "7.64s", "5.64s", "29.6%", ""]]
+    );
+    assert!(stdout.contains("Critical path: 18.5s (97.1% of the 19.058s makespan"));
+}
+
+#[test]
+fn optimize() {
+    let dir = scratch("optimize");
+    let stdout = dice_box(&dir, &["optimize", "--limit", "3"]);
+    let units: Vec<&str> = stdout
+        .lines()
+        .skip(1)
+        .map(|line| line.split('|').map(str::trim).collect::<Vec<_>>())
+        .filter_map(|cells| cells.get(1).copied())
+        .skip(1)
+        .collect();
+    // codec takes longer than api, but other units keep the threads busy while it's building.
+    assert_eq!(
+        units,
+        [
+            "db 0.1.0 (Metadata)",
+            "api 0.1.0 (Metadata)",
+            "auth 0.1.0 (Metadata)"
+        ]
+    );
+    assert_eq!(rows(&stdout, "db 0.1.0 (Metadata)")[0][4], "2.099s");
+}
+
+#[test]
+fn compare() {
+    let dir = scratch("compare");
+    let stdout = dice_box(&dir, &["compare", "server", "--left", "0", "--right", "3"]);
+    assert!(stdout.starts_with("Cargo Hints vs Critical Path"));
+    assert!(stdout.contains("<- first divergence"));
+}
+
+#[test]
+fn attribute() {
+    let dir = scratch("attribute");
+    let stdout = dice_box(&dir, &["attribute", "--left", "3", "--right", "6"]);
+    let attribution = &rows(&stdout, "Critical Path")[0];
+    assert_eq!(
+        attribution[2],
+        "Optimal build schedule (current Cargo algo)"
+    );
+    assert_eq!(
+        attribution[3..7],
+        ["-0.509s", "+0.374s", "-0.883s", "+0.000s"]
+    );
+}
+
+#[test]
+fn advise() {
+    let dir = scratch("advise");
+    let stdout = dice_box(&dir, &["advise", "--limit", "3"]);
+    let split = &rows(&stdout, "Split crate db 0.1.0")[0];
+    assert_eq!((split[2], split[3]), ("16.959s", "2.099s"));
+}
+
+#[test]
+fn search() {
+    let dir = scratch("search");
+    let stdout = dice_box(
+        &dir,
+        &["search", "--population", "10", "--generations", "5"],
+    );
+    assert!(stdout.contains("Best schedule found: 19.009s"));
+    assert!(dir.join("search.json").exists());
+    // The trace holds the found schedule alone, which is then the best one to visualize.
+    dice_box(
+        &dir,
+        &["--replay", "search.json", "--waterfall", "waterfall"],
+    );
+    assert!(dir.join("waterfall.svg").exists());
+    // On 2 threads, the search finds a better order than the default schedulers (Critical Path takes 28.952s).
+    let stdout = dice_box_on(
+        &dir,
+        2,
+        &["search", "--population", "10", "--generations", "5"],
+    );
+    assert!(stdout.contains("Best schedule found: 28.723s"));
+}
+
+#[test]
+fn anneal() {
+    let dir = scratch("anneal");
+    let stdout = dice_box(&dir, &["anneal", "--iterations", "50"]);
+    assert!(stdout.contains("Best schedule found: 19.009s"));
+    assert!(dir.join("anneal.json").exists());
+}
+
+#[test]
+fn tune_weights() {
+    let dir = scratch("tune_weights");
+    let stdout = dice_box(&dir, &["tune-weights", "--steps", "1", "--limit", "2"]);
+    assert!(stdout.contains(
+        r#"Best weights: {"duration":1.0,"transitive-dependants":0.0,"critical-path":1.0,"unlocked":0.0}"#
+    ));
+}
+
+#[test]
+fn tune_n_hints() {
+    let dir = scratch("tune_n_hints");
+    let stdout = dice_box(&dir, &["tune-n-hints", "--n", "25,75", "--limit", "1"]);
+    assert!(stdout.contains(
+        "Best configuration: N=25, tie-break=longest, separate codegen (--n-hints 25 --n-hints-tie-break longest --n-hints-separate-codegen)"
+    ));
+    let stdout = dice_box(
+        &dir,
+        &[
+            "--n-hints",
+            "25",
+            "--n-hints-tie-break",
+            "longest",
+            "--n-hints-separate-codegen",
+        ],
+    );
+    assert_eq!(
+        makespan(
+            &stdout,
+            "N-Hints (N=25, tie-break=longest, separate codegen)"
+        ),
+        "19.88s"
+    );
+}
+
+#[test]
+fn colocate() {
+    let dir = scratch("colocate");
+    let stdout = dice_box(
+        &dir,
+        &[
+            "colocate",
+            "--job",
+            &format!("{FIXTURE}/timings.json"),
+            &format!("{FIXTURE}/unit-graph.json"),
+            "--iterations",
+            "20",
+        ],
+    );
+    // Two copies of the same build are best off splitting the machine in half.
+    let jobs = rows(&stdout, &format!("{FIXTURE}/timings.json"));
+    assert_eq!(jobs.len(), 2);
+    assert!(jobs.iter().all(|job| job[2] == "2"));
+}
+
+#[test]
+fn anonymize() {
+    let dir = scratch("anonymize");
+    let stdout = dice_box(&dir, &["anonymize", "--symbolic", "--output", "anonymized"]);
+    let cargo = &rows(&stdout, "Cargo Hints")[0];
+    assert_eq!((cargo[2], cargo[3]), ("19.058s", "24s"));
+    let timings = std::fs::read_to_string(dir.join("anonymized/timings.json")).unwrap();
+    assert!(!timings.contains("toy"));
+    assert!(dir.join("anonymized/unit-graph.json").exists());
+    // Rounding up to coarse buckets on few threads strays further from the original than the bound promises.
+    let stdout = dice_box_on(
+        &dir,
+        2,
+        &["anonymize", "--bucket", "5", "--output", "coarse"],
+    );
+    let cargo = &rows(&stdout, "Cargo Hints")[0];
+    assert_eq!(
+        (cargo[2], cargo[3], cargo[5], cargo[6]),
+        ("29.685s", "95s", "60s", "false")
+    );
+    // Durations can't be rounded up to empty buckets.
+    let output = Command::new(env!("CARGO_BIN_EXE_dice_box"))
+        .current_dir(&dir)
+        .arg(Path::new(FIXTURE).join("timings.json"))
+        .arg(Path::new(FIXTURE).join("unit-graph.json"))
+        .args(["anonymize", "--bucket", "0"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("bucket size must be positive"));
+}
+
+/// Builds the toy workspace for real, which takes a while and needs a nightly toolchain.
+#[test]
+#[ignore]
+fn verify() {
+    let dir = scratch("verify");
+    let target_dir = dir.join("target");
+    let target_dir = target_dir.to_str().unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_dice_box"))
+        .current_dir(FIXTURE)
+        .arg(Path::new(FIXTURE).join("timings.json"))
+        .arg(Path::new(FIXTURE).join("unit-graph.json"))
+        .args([
+            "--num-threads",
+            "4",
+            "verify",
+            "--",
+            "--target-dir",
+            target_dir,
+        ])
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}